@@ -0,0 +1,663 @@
+#ifndef WINPTYRS_H
+#define WINPTYRS_H
+
+/* This file is generated by cbindgen from src/ffi.rs, do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Amount of time (in ms) that [`PTYImpl::kill`] waits for a process to exit
+ * after closing its console, before terminating it forcefully.
+ */
+#define KILL_GRACE_PERIOD 5000
+
+/**
+ * Version of the format of the cassettes.
+ */
+#define CASSETTE_VERSION 1
+
+/**
+ * Default maximum length (in characters) of the payload of an OSC sequence.
+ */
+#define DEFAULT_MAX_OSC_PAYLOAD 4096
+
+/**
+ * Maximum length (in characters) of a line, beyond which it is wrapped.
+ */
+#define MAX_LINE_LENGTH 65536
+
+/**
+ * Maximum number of finished commands kept in the timeline.
+ */
+#define MAX_COMMANDS 1000
+
+/**
+ * Version of the protocol, sent in the [`Frame::Hello`] of each end.
+ */
+#define PROTOCOL_VERSION 3
+
+/**
+ * Maximum length of a frame, larger frames are rejected.
+ */
+#define MAX_FRAME_LENGTH ((16 * 1024) * 1024)
+
+#define HELLO 1
+
+#define CREATE 2
+
+#define ATTACH 3
+
+#define SPAWN 4
+
+#define INPUT 5
+
+#define RESIZE 6
+
+#define SIGNAL 7
+
+#define CLOSE 8
+
+#define LIST 9
+
+#define CREATED 64
+
+#define SPAWNED 65
+
+#define WRITTEN 66
+
+#define ACK 67
+
+#define CLOSED 68
+
+#define ERROR 69
+
+#define ATTACHED 70
+
+#define LISTED 71
+
+#define OUTPUT 128
+
+#define EXIT 129
+
+#define RESIZED 130
+
+/**
+ * Default amount of output (in bytes) retained by each session for the clients that attach to it.
+ */
+#define DEFAULT_SCROLLBACK (1024 * 1024)
+
+/**
+ * Maximum length of the content of a message.
+ */
+#define MAX_CONTENT_LENGTH ((64 * 1024) * 1024)
+
+#define PARSE_ERROR -32700
+
+#define INVALID_REQUEST -32600
+
+#define METHOD_NOT_FOUND -32601
+
+#define INVALID_PARAMS -32602
+
+/**
+ * Error reported by the PTY of a session, or by a session that does not exist.
+ */
+#define PTY_ERROR -32000
+
+/**
+ * Select the backend automatically, see [`PTYBackend::Auto`].
+ */
+#define WINPTYRS_BACKEND_AUTO 2
+
+/**
+ * Use the ConPTY backend, see [`PTYBackend::ConPTY`].
+ */
+#define WINPTYRS_BACKEND_CONPTY 0
+
+/**
+ * Use the winpty backend, see [`PTYBackend::WinPTY`].
+ */
+#define WINPTYRS_BACKEND_WINPTY 1
+
+/**
+ * Replay the session recorded in the `cassette` argument, see [`PTYBackend::Replay`].
+ */
+#define WINPTYRS_BACKEND_REPLAY 4
+
+/**
+ * See [`ShutdownPolicy::CloseConsole`].
+ */
+#define WINPTYRS_SHUTDOWN_CLOSE_CONSOLE 0
+
+/**
+ * See [`ShutdownPolicy::Terminate`].
+ */
+#define WINPTYRS_SHUTDOWN_TERMINATE 1
+
+/**
+ * See [`ShutdownPolicy::TerminateTree`].
+ */
+#define WINPTYRS_SHUTDOWN_TERMINATE_TREE 2
+
+/**
+ * See [`ShutdownPolicy::WaitWithTimeout`], which waits for `shutdown_timeout` ms.
+ */
+#define WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT 3
+
+/**
+ * Default amount of output (in bytes) kept by a trigger to match across reads.
+ */
+#define DEFAULT_LOOKBACK 4096
+
+/**
+ * Width of a cell, in pixels.
+ */
+#define CELL_WIDTH 6
+
+/**
+ * Height of a cell, in pixels.
+ */
+#define CELL_HEIGHT 12
+
+/**
+ * Row of a cell where underlines are drawn.
+ */
+#define UNDERLINE_ROW 10
+
+/**
+ * Opaque handle of a PTY, created by [`winptyrs_pty_new`] and released by [`winptyrs_pty_free`].
+ */
+typedef struct WinptyrsPty WinptyrsPty;
+
+typedef struct winpty_error_s {
+  uint8_t _unused[0];
+} winpty_error_s;
+
+/**
+ * An error object.
+ */
+typedef struct winpty_error_s winpty_error_t;
+
+typedef winpty_error_t *winpty_error_ptr_t;
+
+typedef struct winpty_config_s {
+  uint8_t _unused[0];
+} winpty_config_s;
+
+/**
+ * Agent configuration object (not thread-safe).
+ */
+typedef struct winpty_config_s winpty_config_t;
+
+typedef struct winpty_s {
+  uint8_t _unused[0];
+} winpty_s;
+
+/**
+ * Agent object (thread-safe)
+ */
+typedef struct winpty_s winpty_t;
+
+typedef struct winpty_spawn_config_s {
+  uint8_t _unused[0];
+} winpty_spawn_config_s;
+
+/**
+ * Configuration object (not thread-safe)
+ */
+typedef struct winpty_spawn_config_s winpty_spawn_config_t;
+
+/**
+ * Result of the functions of the C API.
+ */
+typedef int32_t WinptyrsStatus;
+
+/**
+ * Arguments used to create a PTY, see [`PTYArgs`]. Use [`winptyrs_args_default`] to
+ * initialize them.
+ */
+typedef struct WinptyrsArgs {
+  /**
+   * Number of character columns to display.
+   */
+  int32_t cols;
+  /**
+   * Number of line rows to display.
+   */
+  int32_t rows;
+  /**
+   * Mouse capture settings for the winpty backend: 0 (none), 1 (auto) or 2 (force), see [`MouseMode`].
+   */
+  int32_t mouse_mode;
+  /**
+   * Amount of time (in ms) to wait for the winpty agent.
+   */
+  uint32_t timeout;
+  /**
+   * Configuration flags for the winpty backend, see [`AgentConfig`].
+   */
+  uint64_t agent_config;
+  /**
+   * Place the spawned process in a job object with all of its descendants.
+   */
+  bool contain_tree;
+  /**
+   * What to do with the process when the PTY is freed, one of the `WINPTYRS_SHUTDOWN_*` values.
+   */
+  uint32_t shutdown_policy;
+  /**
+   * Amount of time (in ms) used by `WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT`.
+   */
+  uint32_t shutdown_timeout;
+  /**
+   * Maximum amount of time (in ms) to wait for the remaining output when the process exits.
+   */
+  uint32_t drain_timeout;
+  /**
+   * Maximum length (in characters) of the payload of the tracked OSC sequences.
+   */
+  size_t osc_max_payload;
+  /**
+   * Read the output through the I/O reactor shared by all the PTYs (ConPTY only).
+   */
+  bool shared_reactor;
+  /**
+   * Path of the cassette where the session is recorded, or that is replayed by
+   * `WINPTYRS_BACKEND_REPLAY`. Null to disable recording.
+   */
+  const char *cassette;
+  /**
+   * Fail the replay when the input or the resizes diverge from the cassette.
+   */
+  bool strict_replay;
+} WinptyrsArgs;
+
+/**
+ * The function succeeded.
+ */
+#define WINPTYRS_OK 0
+
+/**
+ * A required pointer argument was null.
+ */
+#define WINPTYRS_ERROR_NULL_ARGUMENT 1
+
+/**
+ * A string argument was not valid UTF-8.
+ */
+#define WINPTYRS_ERROR_INVALID_UTF8 2
+
+/**
+ * An argument had an invalid value.
+ */
+#define WINPTYRS_ERROR_INVALID_ARGUMENT 3
+
+/**
+ * The PTY reported an error.
+ */
+#define WINPTYRS_ERROR_PTY 4
+
+/**
+ * The process exited and all of its output was read.
+ */
+#define WINPTYRS_ERROR_EOF 5
+
+/**
+ * The library panicked. The PTY involved should not be used anymore, other than to free it.
+ */
+#define WINPTYRS_ERROR_PANIC 6
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Gets the error code from the error object.
+ * Returns a textual representation of the error.  The string is freed when
+ * the error is freed.
+ */
+extern const uint16_t *winpty_error_msg(winpty_error_ptr_t err);
+
+/**
+ * Free the error object.  Every error returned from the winpty API must be
+ * freed.
+ */
+extern void winpty_error_free(winpty_error_ptr_t err);
+
+/**
+ * Allocate a winpty_config_t value.  Returns NULL on error.  There are no
+ * required settings -- the object may immediately be used.  agentFlags is a
+ * set of zero or more WINPTY_FLAG_xxx values.  An unrecognized flag results
+ * in an assertion failure.
+ */
+extern winpty_config_t *winpty_config_new(uint64_t flags, winpty_error_ptr_t *err);
+
+/**
+ * Free the cfg object after passing it to winpty_open.
+ */
+extern void winpty_config_free(winpty_config_t *cfg);
+
+/**
+ * Set the agent config size.
+ */
+extern void winpty_config_set_initial_size(winpty_config_t *cfg, int32_t cols, int32_t rows);
+
+/**
+ * Set the mouse mode to one of the [`super::MouseMode`] constants.
+ */
+extern void winpty_config_set_mouse_mode(winpty_config_t *cfg, int32_t mouse_mode);
+
+/**
+ * Amount of time to wait for the agent to startup and to wait for any given
+ * agent RPC request.  Must be greater than 0.  Can be INFINITE.
+ */
+extern void winpty_config_set_agent_timeout(winpty_config_t *cfg, uint32_t timeout);
+
+/**
+ *  Starts the agent.  Returns NULL on error.  This process will connect to the
+ * agent over a control pipe, and the agent will open data pipes (e.g. CONIN
+ * and CONOUT).
+ */
+extern winpty_t *winpty_open(const winpty_config_t *cfg, winpty_error_ptr_t *err);
+
+/**
+ * Returns the names of named pipes used for terminal I/O.  Each input or
+ * output direction uses a different half-duplex pipe.  The agent creates
+ * these pipes, and the client can connect to them using ordinary I/O methods.
+ * The strings are freed when the winpty_t object is freed.
+ * `winpty_conerr_name` returns NULL unless `WINPTY_FLAG_CONERR` is specified.
+ */
+extern const uint16_t *winpty_conin_name(winpty_t *wp);
+
+extern const uint16_t *winpty_conout_name(winpty_t *wp);
+
+/**
+ * winpty_spawn_config strings do not need to live as long as the config
+ * object.  They are copied.  Returns NULL on error.  spawnFlags is a set of
+ * zero or more WINPTY_SPAWN_FLAG_xxx values.  An unrecognized flag results in
+ * an assertion failure.
+ *
+ * env is a a pointer to an environment block like that passed to
+ * CreateProcess--a contiguous array of NUL-terminated "VAR=VAL" strings
+ * followed by a final NUL terminator.
+ *
+ * N.B.: If you want to gather all of the child's output, you may want the
+ * WINPTY_SPAWN_FLAG_AUTO_SHUTDOWN flag.
+ */
+extern winpty_spawn_config_t *winpty_spawn_config_new(uint64_t spawn_flags,
+                                                      const uint16_t *appname,
+                                                      const uint16_t *cmdline,
+                                                      const uint16_t *cwd,
+                                                      const uint16_t *env,
+                                                      winpty_error_ptr_t *err);
+
+/**
+ * Free the cfg object after passing it to winpty_spawn.
+ */
+extern void winpty_spawn_config_free(winpty_spawn_config_t *cfg);
+
+/**
+ * Spawns the new process.
+ *
+ * The function initializes all output parameters to zero or NULL.
+ *
+ * On success, the function returns TRUE.  For each of process_handle and
+ * thread_handle that is non-NULL, the HANDLE returned from CreateProcess is
+ * duplicated from the agent and returned to the winpty client.  The client is
+ * responsible for closing these HANDLES.
+ *
+ * On failure, the function returns FALSE, and if err is non-NULL, then *err
+ * is set to an error object.
+ *
+ * If the agent's CreateProcess call failed, then *create_process_error is set
+ * to GetLastError(), and the WINPTY_ERROR_SPAWN_CREATE_PROCESS_FAILED error
+ * is returned.
+ *
+ * winpty_spawn can only be called once per winpty_t object.  If it is called
+ * before the output data pipe(s) is/are connected, then collected output is
+ * buffered until the pipes are connected, rather than being discarded.
+ *
+ * N.B.: GetProcessId works even if the process has exited.  The PID is not
+ * recycled until the NT process object is freed.
+ * (https://blogs.msdn.microsoft.com/oldnewthing/20110107-00/?p=11803)
+ */
+extern bool winpty_spawn(winpty_t *wp,
+                         const winpty_spawn_config_t *cfg,
+                         HANDLE *process_handle,
+                         HANDLE *thread_handle,
+                         uint32_t *create_process_error,
+                         winpty_error_ptr_t *err);
+
+/**
+ * Change the size of the Windows console window.
+ */
+extern bool winpty_set_size(winpty_t *wp, int32_t cols, int32_t rows, winpty_error_ptr_t *err);
+
+/**
+ * Frees the winpty_t object and the OS resources contained in it.  This
+ * call breaks the connection with the agent, which should then close its
+ * console, terminating the processes attached to it.
+ *
+ * This function must not be called if any other threads are using the
+ * winpty_t object.  Undefined behavior results.
+ */
+extern void winpty_free(winpty_t *wp);
+
+/**
+ * Creates a "Pseudo-console" (conpty) with dimensions (in characters)
+ *      provided by the `size` parameter. The caller should provide two handles:
+ * - `hInput` is used for writing input to the pty, encoded as UTF-8 and VT sequences.
+ * - `hOutput` is used for reading the output of the pty, encoded as UTF-8 and VT sequences.
+ * Once the call completes, `phPty` will receive a token value to identify this
+ *      conpty object. This value should be used in conjunction with the other
+ *      Pseudoconsole API's.
+ * `dwFlags` is used to specify optional behavior to the created pseudoconsole.
+ * The flags can be combinations of the following values:
+ *  INHERIT_CURSOR: This will cause the created conpty to attempt to inherit the
+ *      cursor position of the parent terminal application. This can be useful
+ *      for applications like `ssh`, where ssh (currently running in a terminal)
+ *      might want to create a pseudoterminal session for an child application
+ *      and the child inherit the cursor position of ssh.
+ *      The created conpty will immediately emit a "Device Status Request" VT
+ *      sequence to hOutput, that should be replied to on hInput in the format
+ *      "\x1b[<r>;<c>R", where `<r>` is the row and `<c>` is the column of the
+ *      cursor position.
+ *      This requires a cooperating terminal application - if a caller does not
+ *      reply to this message, the conpty will not process any input until it
+ *      does. Most *nix terminals and the Windows Console (after Windows 10
+ *      Anniversary Update) will be able to handle such a message.
+ */
+extern int32_t ConptyCreatePseudoConsole(COORD size,
+                                         HANDLE hInput,
+                                         HANDLE hOutput,
+                                         uint32_t dwFlags,
+                                         void *hPC);
+
+/**
+ * Resizes the given conpty to the specified size, in characters.
+ */
+extern int32_t ConptyResizePseudoConsole(void *hPC, COORD size);
+
+/**
+ * - Clear the contents of the conpty buffer, leaving the cursor row at the top
+ *   of the viewport.
+ * - This is used exclusively by ConPTY to support GH#1193, GH#1882. This allows
+ *   a terminal to clear the contents of the ConPTY buffer, which is important
+ *   if the user would like to be able to clear the terminal-side buffer.
+ */
+extern int32_t ConptyClearPseudoConsole(void *hPC);
+
+/**
+ * - Tell the ConPTY about the state of the hosting window. This should be used
+ *   to keep ConPTY's internal HWND state in sync with the state of whatever the
+ *   hosting window is.
+ * - For more information, refer to GH#12515.
+ */
+extern int32_t ConptyShowHidePseudoConsole(void *hPC, bool show);
+
+/**
+ * - Sends a message to the pseudoconsole informing it that it should use the
+ *   given window handle as the owner for the conpty's pseudo window. This
+ *   allows the response given to GetConsoleWindow() to be a HWND that's owned
+ *   by the actual hosting terminal's HWND.
+ * - Used to support GH#2988
+ */
+extern int32_t ConptyReparentPseudoConsole(void *hPC, void *newParent);
+
+/**
+ * The \Reference handle ensures that conhost keeps running by keeping the ConDrv server pipe open.
+ * After you've finished setting up your PTY via PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE, this method may be called
+ * to release that handle, allowing conhost to shut down automatically once the last client has disconnected.
+ * You'll know when this happens, because a ReadFile() on the output pipe will return ERROR_BROKEN_PIPE.
+ */
+extern int32_t ConptyReleasePseudoConsole(void *hPC);
+
+/**
+ * Closes the conpty and all associated state.
+ * Client applications attached to the conpty will also behave as though the
+ *      console window they were running in was closed.
+ * This can fail if the conhost hosting the pseudoconsole failed to be
+ *      terminated, or if the pseudoconsole was already terminated.
+ * Waits for conhost/OpenConsole to exit first.
+ */
+extern int32_t ConptyClosePseudoConsole(void *hPC);
+
+extern int32_t ConptyPackPseudoConsole(HANDLE hServerProcess,
+                                       HANDLE hRef,
+                                       HANDLE hSignal,
+                                       void *phPC);
+
+/**
+ * Retrieve the description of the last error that happened on the calling thread.
+ *
+ * # Returns
+ * A UTF-8 string that remains valid until the next failure on the same thread, or null
+ * if no function failed on this thread.
+ */
+const char *winptyrs_last_error(void);
+
+/**
+ * Initialize `args` with the default arguments of a PTY.
+ *
+ * # Safety
+ * `args` must be null or point to a writable `WinptyrsArgs`.
+ */
+WinptyrsStatus winptyrs_args_default(struct WinptyrsArgs *args);
+
+/**
+ * Create a PTY, see [`PTY::new_with_backend`].
+ *
+ * # Arguments
+ * * `args` - Arguments of the PTY, or null to use the default ones.
+ * * `backend` - One of the `WINPTYRS_BACKEND_*` values.
+ * * `pty` - Receives the handle of the PTY, which must be released with `winptyrs_pty_free`.
+ *
+ * # Safety
+ * `args` must be null or point to a valid `WinptyrsArgs`, whose `cassette` is null or a
+ * valid C string, and `pty` must be null or writable.
+ */
+WinptyrsStatus winptyrs_pty_new(const struct WinptyrsArgs *args,
+                                uint32_t backend,
+                                struct WinptyrsPty **pty);
+
+/**
+ * Spawn a process inside a PTY, see [`PTY::spawn`].
+ *
+ * # Arguments
+ * * `appname` - Path or name of the program to run.
+ * * `cmdline` - Arguments of the program, or null.
+ * * `cwd` - Working directory of the program, or null to use the current one.
+ * * `env` - Null-terminated array of `NAME=value` strings that make up the whole
+ *   environment of the program, or null to inherit the current one.
+ *
+ * # Safety
+ * `pty` must be a handle returned by `winptyrs_pty_new` that is not used concurrently, and
+ * the strings must be null or NUL-terminated.
+ */
+WinptyrsStatus winptyrs_pty_spawn(struct WinptyrsPty *pty,
+                                  const char *appname,
+                                  const char *cmdline,
+                                  const char *cwd,
+                                  const char *const *env);
+
+/**
+ * Read the output of the process as UTF-8, waiting at most `timeout` ms for it to be
+ * available. Output that does not fit in `buf` is returned by the next reads, so a
+ * multi-byte character may be split across two reads.
+ *
+ * # Returns
+ * `WINPTYRS_ERROR_EOF` once the process exited and all of its output was read. Otherwise,
+ * `read` receives the number of bytes written into `buf`, which is 0 if the timeout elapsed.
+ *
+ * # Safety
+ * `pty` must be a valid handle, `buf` must be writable for `len` bytes and `read` must be writable.
+ */
+WinptyrsStatus winptyrs_pty_read(const struct WinptyrsPty *pty,
+                                 uint8_t *buf,
+                                 size_t len,
+                                 uint32_t timeout,
+                                 size_t *read);
+
+/**
+ * Write `len` bytes of UTF-8 `data` into the standard input of the process, see [`PTY::write`].
+ *
+ * # Arguments
+ * * `written` - Receives the number of characters written, or null.
+ *
+ * # Safety
+ * `pty` must be a valid handle, `data` must be readable for `len` bytes and `written`
+ * must be null or writable.
+ */
+WinptyrsStatus winptyrs_pty_write(const struct WinptyrsPty *pty,
+                                  const uint8_t *data,
+                                  size_t len,
+                                  uint32_t *written);
+
+/**
+ * Change the size of the PTY, see [`PTY::set_size`].
+ *
+ * # Safety
+ * `pty` must be a valid handle.
+ */
+WinptyrsStatus winptyrs_pty_resize(const struct WinptyrsPty *pty, int32_t cols, int32_t rows);
+
+/**
+ * Retrieve the process ID of the spawned process, see [`PTY::get_pid`].
+ *
+ * # Safety
+ * `pty` must be a valid handle, and `pid` must be writable.
+ */
+WinptyrsStatus winptyrs_pty_pid(const struct WinptyrsPty *pty, uint32_t *pid);
+
+/**
+ * Retrieve the exit status of the process, see [`PTY::get_exitstatus`].
+ *
+ * # Arguments
+ * * `exited` - Receives whether the process exited.
+ * * `code` - Receives the exit code of the process, if it exited. May be null.
+ *
+ * # Safety
+ * `pty` must be a valid handle, `exited` must be writable and `code` must be null or writable.
+ */
+WinptyrsStatus winptyrs_pty_exit_status(const struct WinptyrsPty *pty,
+                                        bool *exited,
+                                        uint32_t *code);
+
+/**
+ * Release a PTY, which applies its shutdown policy to the process. Null handles are ignored.
+ *
+ * # Safety
+ * `pty` must be null or a handle returned by `winptyrs_pty_new` that was not freed yet,
+ * and that is not used afterwards.
+ */
+void winptyrs_pty_free(struct WinptyrsPty *pty);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WINPTYRS_H */
@@ -0,0 +1,337 @@
+//! Parallel execution of many commands, each one inside its own PTY.
+//!
+//! Many Windows programs only produce colors or progress bars when they are attached to a
+//! console. A [`BatchRunner`] runs a list of [`BatchCommand`]s inside PTYs, with a limited
+//! number of them running at the same time, and collects the transcript and exit status of
+//! each one of them into a [`BatchReport`].
+
+use std::ffi::OsString;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::pty::{PTYArgs, PTYBackend, PTY};
+use crate::resolver::environment_block;
+
+/// Maximum amount of time that a command waits for output before checking its deadline.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Command run by a [`BatchRunner`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchCommand {
+    /// Program to spawn, resolved as in [`PTY::spawn`].
+    pub appname: OsString,
+    /// Optional space-delimited arguments of the program.
+    pub cmdline: Option<OsString>,
+    /// Optional path from where the program should be spawned.
+    pub cwd: Option<OsString>,
+    /// Environment variables of the program, on top of the ones of the current process.
+    pub env: Vec<(OsString, OsString)>,
+    /// Maximum amount of time that the program may run, instead of [`BatchOptions::timeout`].
+    pub timeout: Option<Duration>,
+}
+
+impl BatchCommand {
+    /// Create a command that runs `appname` with the given arguments.
+    pub fn new(appname: &str, cmdline: Option<&str>) -> BatchCommand {
+        BatchCommand {
+            appname: OsString::from(appname),
+            cmdline: cmdline.map(OsString::from),
+            ..BatchCommand::default()
+        }
+    }
+}
+
+impl fmt::Display for BatchCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.appname.to_string_lossy())?;
+        if let Some(cmdline) = &self.cmdline {
+            write!(f, " {}", cmdline.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// Options of a [`BatchRunner`].
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    /// Arguments used to create the PTY of every command. [`PTYArgs::contain_tree`] is always
+    /// set, so that the commands that time out or get cancelled are killed with their descendants.
+    pub args: PTYArgs,
+    /// Backend used to create the PTY of every command, see [`PTY::new_with_backend`].
+    pub backend: PTYBackend,
+    /// Maximum number of commands that run at the same time. Defaults to the number of
+    /// processors.
+    pub concurrency: usize,
+    /// Maximum amount of time that each command may run, unless the command sets its own.
+    pub timeout: Option<Duration>,
+    /// If true, the commands that are running are killed and the ones that did not start are
+    /// skipped as soon as a command fails.
+    pub fail_fast: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            args: PTYArgs::default(),
+            backend: PTYBackend::Auto,
+            concurrency: thread::available_parallelism().map_or(1, |n| n.get()),
+            timeout: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// Outcome of a command run by a [`BatchRunner`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchStatus {
+    /// The command exited with the given exit code.
+    Exited(u32),
+    /// The command was killed after running for longer than its timeout.
+    TimedOut,
+    /// The command was killed because another command failed, see [`BatchOptions::fail_fast`].
+    Cancelled,
+    /// The command did not start because another command failed.
+    Skipped,
+    /// The PTY of the command could not be created, or the command could not be spawned.
+    Failed(OsString),
+}
+
+impl fmt::Display for BatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchStatus::Exited(0) => write!(f, "ok"),
+            BatchStatus::Exited(code) => write!(f, "exit {}", code),
+            BatchStatus::TimedOut => write!(f, "timed out"),
+            BatchStatus::Cancelled => write!(f, "cancelled"),
+            BatchStatus::Skipped => write!(f, "skipped"),
+            BatchStatus::Failed(err) => write!(f, "failed: {}", err.to_string_lossy()),
+        }
+    }
+}
+
+/// Result of a command run by a [`BatchRunner`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchResult {
+    /// Position of the command in the batch.
+    pub index: usize,
+    pub command: BatchCommand,
+    pub status: BatchStatus,
+    /// Everything that the command wrote into its PTY, including escape sequences.
+    pub transcript: OsString,
+    /// Amount of time that the command ran for.
+    pub duration: Duration,
+}
+
+impl BatchResult {
+    /// Check if the command exited with code 0.
+    pub fn success(&self) -> bool {
+        self.status == BatchStatus::Exited(0)
+    }
+}
+
+/// Results of all the commands of a batch, in the order in which they were given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchReport {
+    pub results: Vec<BatchResult>,
+    /// Amount of time that the whole batch took.
+    pub duration: Duration,
+}
+
+impl BatchReport {
+    /// Check if every command exited with code 0.
+    pub fn success(&self) -> bool {
+        self.results.iter().all(BatchResult::success)
+    }
+
+    /// Retrieve the results of the commands that did not exit with code 0.
+    pub fn failures(&self) -> impl Iterator<Item = &BatchResult> {
+        self.results.iter().filter(|result| !result.success())
+    }
+}
+
+/// Summary of the batch, with a line per command.
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            writeln!(f, "[{}] {} ({:.1}s)", result.status, result.command, result.duration.as_secs_f64())?;
+        }
+        let failed = self.failures().count();
+        write!(
+            f,
+            "{} commands, {} succeeded, {} failed in {:.1}s",
+            self.results.len(),
+            self.results.len() - failed,
+            failed,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Progress of a batch, reported through [`BatchRunner::on_progress`].
+#[derive(Debug)]
+pub enum BatchProgress<'a> {
+    /// A command was spawned.
+    Started(usize, &'a BatchCommand),
+    /// A command produced output.
+    Output(usize, &'a OsString),
+    /// A command finished, or was skipped.
+    Finished(&'a BatchResult),
+}
+
+type ProgressCallback = Box<dyn Fn(&BatchProgress) + Send + Sync>;
+
+/// Runner of many commands in parallel, each one inside its own PTY.
+///
+/// # Examples
+/// ```no_run
+/// use winptyrs::{BatchCommand, BatchOptions, BatchRunner};
+///
+/// let options = BatchOptions { concurrency: 4, fail_fast: true, ..BatchOptions::default() };
+/// let runner = BatchRunner::new(options);
+/// let report = runner.run(vec![
+///     BatchCommand::new("cargo", Some("build --color always")),
+///     BatchCommand::new("npm", Some("run lint")),
+/// ]);
+/// println!("{}", report);
+/// ```
+pub struct BatchRunner {
+    options: BatchOptions,
+    progress: Option<ProgressCallback>,
+}
+
+/// State shared by the workers of a batch.
+struct Batch<'a> {
+    commands: &'a [BatchCommand],
+    next: AtomicUsize,
+    cancelled: AtomicBool,
+    results: Mutex<Vec<Option<BatchResult>>>,
+}
+
+impl BatchRunner {
+    /// Create a runner with the given options.
+    pub fn new(options: BatchOptions) -> BatchRunner {
+        BatchRunner { options, progress: None }
+    }
+
+    /// Set a function that is called with the progress of the batch. It is called from the
+    /// threads that run the commands, so it must not block for long.
+    pub fn on_progress<F: Fn(&BatchProgress) + Send + Sync + 'static>(mut self, callback: F) -> BatchRunner {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Run all the commands and wait for them to finish.
+    pub fn run(&self, commands: Vec<BatchCommand>) -> BatchReport {
+        let start = Instant::now();
+        let batch = Batch {
+            commands: &commands,
+            next: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            results: Mutex::new(vec![None; commands.len()]),
+        };
+        let workers = self.options.concurrency.max(1).min(commands.len());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| self.work(&batch));
+            }
+        });
+
+        let results = batch.results.into_inner().unwrap_or_else(|err| err.into_inner());
+        BatchReport { results: results.into_iter().flatten().collect(), duration: start.elapsed() }
+    }
+
+    fn report(&self, progress: BatchProgress) {
+        if let Some(callback) = &self.progress {
+            callback(&progress);
+        }
+    }
+
+    /// Run the commands of the batch until there are none left.
+    fn work(&self, batch: &Batch) {
+        loop {
+            let index = batch.next.fetch_add(1, Ordering::SeqCst);
+            let Some(command) = batch.commands.get(index) else {
+                return;
+            };
+            let start = Instant::now();
+            let mut transcript = OsString::new();
+            let status = match batch.cancelled.load(Ordering::SeqCst) {
+                true => BatchStatus::Skipped,
+                false => self
+                    .execute(index, command, &batch.cancelled, &mut transcript)
+                    .unwrap_or_else(BatchStatus::Failed),
+            };
+            let result = BatchResult { index, command: command.clone(), status, transcript, duration: start.elapsed() };
+
+            let failed = !matches!(result.status, BatchStatus::Exited(0) | BatchStatus::Cancelled | BatchStatus::Skipped);
+            if failed && self.options.fail_fast {
+                batch.cancelled.store(true, Ordering::SeqCst);
+            }
+            self.report(BatchProgress::Finished(&result));
+            batch.results.lock().unwrap_or_else(|err| err.into_inner())[index] = Some(result);
+        }
+    }
+
+    /// Run a command until it exits, times out or the batch is cancelled.
+    fn execute(&self, index: usize, command: &BatchCommand, cancelled: &AtomicBool, transcript: &mut OsString) -> Result<BatchStatus, OsString> {
+        let args = PTYArgs { contain_tree: true, ..self.options.args.clone() };
+        let mut pty = PTY::new_with_backend(&args, self.options.backend)?;
+        let env = (!command.env.is_empty()).then(|| environment_block(&command.env));
+        pty.spawn(command.appname.clone(), command.cmdline.clone(), command.cwd.clone(), env)?;
+        self.report(BatchProgress::Started(index, command));
+
+        let deadline = command.timeout.or(self.options.timeout).map(|timeout| Instant::now() + timeout);
+        let mut eof = false;
+        let status = loop {
+            if eof {
+                // The output reached EOF, the process exits shortly after.
+                match pty.get_exitstatus()? {
+                    Some(code) => break BatchStatus::Exited(code),
+                    None if !pty.is_alive()? => {
+                        return Err(OsString::from("The exit code of the process is not available"));
+                    }
+                    None => thread::sleep(POLL_INTERVAL),
+                }
+            } else {
+                match pty.read_timeout(POLL_INTERVAL) {
+                    Ok(output) if !output.is_empty() => {
+                        self.report(BatchProgress::Output(index, &output));
+                        transcript.push(output);
+                    }
+                    Ok(_) => (),
+                    Err(_) if pty.is_eof()? => eof = true,
+                    Err(err) => {
+                        stop(&pty);
+                        return Err(err);
+                    }
+                }
+            }
+            if cancelled.load(Ordering::SeqCst) {
+                stop(&pty);
+                break BatchStatus::Cancelled;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                stop(&pty);
+                break BatchStatus::TimedOut;
+            }
+        };
+
+        if let Ok(Some(exit)) = pty.close() {
+            if !exit.output.is_empty() {
+                self.report(BatchProgress::Output(index, &exit.output));
+                transcript.push(exit.output);
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// Kill the process and its descendants, or at least the process if its tree cannot be killed.
+fn stop(pty: &PTY) {
+    if pty.kill_tree().is_err() {
+        let _ = pty.kill();
+    }
+}
@@ -0,0 +1,51 @@
+//! Minimal parser of the options of a subcommand.
+
+use std::ffi::OsString;
+use std::str::FromStr;
+
+/// Options of a subcommand, given as `--option value` or as `--flag`, optionally followed
+/// by `--` and positional arguments.
+pub struct Args {
+    args: std::iter::Peekable<std::vec::IntoIter<OsString>>,
+}
+
+impl Args {
+    pub fn new(args: Vec<OsString>) -> Args {
+        Args { args: args.into_iter().peekable() }
+    }
+
+    /// Next option, or `None` once all of them were consumed.
+    pub fn next_option(&mut self) -> Result<Option<String>, String> {
+        match self.args.next() {
+            None => Ok(None),
+            Some(arg) => match arg.to_str() {
+                Some(option) if option.starts_with('-') => Ok(Some(option.to_owned())),
+                _ => Err(format!("Unexpected argument {}", arg.to_string_lossy())),
+            },
+        }
+    }
+
+    /// Next argument, if it is positional rather than an option.
+    pub fn next_positional(&mut self) -> Option<OsString> {
+        self.args.next_if(|arg| !arg.to_string_lossy().starts_with('-'))
+    }
+
+    /// Value of `option`, i.e., the argument that follows it.
+    pub fn value(&mut self, option: &str) -> Result<OsString, String> {
+        self.args.next().ok_or_else(|| format!("Missing value of {}", option))
+    }
+
+    /// Value of `option`, parsed into `T`.
+    pub fn parse<T: FromStr>(&mut self, option: &str) -> Result<T, String> {
+        let value = self.value(option)?;
+        value
+            .to_str()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Invalid value of {}: {}", option, value.to_string_lossy()))
+    }
+
+    /// Arguments that were not consumed yet, e.g., the ones that follow `--`.
+    pub fn rest(self) -> Vec<OsString> {
+        self.args.collect()
+    }
+}
@@ -0,0 +1,197 @@
+//! Control of the console that `winpty-rs run` is attached to.
+
+use std::io::{self, Read};
+
+#[cfg(windows)]
+pub use self::windows_impl::{size, Input, RawMode};
+#[cfg(not(windows))]
+pub use self::fallback_impl::{size, Input, RawMode};
+
+/// Decoder of UTF-8 text that arrives in chunks, which may split multi-byte characters.
+#[derive(Default)]
+struct Utf8Decoder {
+    incomplete: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Decode a chunk, retaining the trailing bytes of an incomplete character. Invalid
+    /// sequences are replaced by U+FFFD.
+    fn decode(&mut self, chunk: &[u8]) -> String {
+        let mut bytes = std::mem::take(&mut self.incomplete);
+        bytes.extend_from_slice(chunk);
+
+        let mut text = String::new();
+        let mut rest = &bytes[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    return text;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap());
+                    match err.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.incomplete = invalid.to_vec();
+                            return text;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Read a chunk of the standard input when it is not a console.
+fn read_stdin(decoder: &mut Utf8Decoder) -> io::Result<Option<String>> {
+    let mut buf = [0u8; 4096];
+    match io::stdin().lock().read(&mut buf)? {
+        0 => Ok(None),
+        count => Ok(Some(decoder.decode(&buf[..count]))),
+    }
+}
+
+#[cfg(windows)]
+mod windows_impl {
+    use core::ffi::c_void;
+
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Console::{
+        GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle, ReadConsoleW, SetConsoleMode, CONSOLE_MODE,
+        CONSOLE_SCREEN_BUFFER_INFO, DISABLE_NEWLINE_AUTO_RETURN, ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT,
+        ENABLE_PROCESSED_INPUT, ENABLE_VIRTUAL_TERMINAL_INPUT, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_HANDLE,
+        STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    use super::*;
+
+    /// Handle of a standard stream, if it is attached to a console.
+    fn console(stream: STD_HANDLE) -> Option<(HANDLE, CONSOLE_MODE)> {
+        unsafe {
+            let handle = GetStdHandle(stream).ok()?;
+            let mut mode = CONSOLE_MODE::default();
+            GetConsoleMode(handle, &mut mode).ok()?;
+            Some((handle, mode))
+        }
+    }
+
+    /// Console modes in which keys are delivered as VT sequences without being processed,
+    /// and the output is interpreted as VT sequences. The previous modes are restored on drop.
+    pub struct RawMode {
+        saved: Vec<(HANDLE, CONSOLE_MODE)>,
+    }
+
+    impl RawMode {
+        /// Switch the streams that are attached to a console into raw mode.
+        pub fn enable() -> RawMode {
+            let mut raw = RawMode { saved: Vec::new() };
+            raw.set(STD_INPUT_HANDLE, |mode| {
+                (mode & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT)) | ENABLE_VIRTUAL_TERMINAL_INPUT
+            });
+            raw.set(STD_OUTPUT_HANDLE, |mode| mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING | DISABLE_NEWLINE_AUTO_RETURN);
+            raw
+        }
+
+        fn set<F: FnOnce(CONSOLE_MODE) -> CONSOLE_MODE>(&mut self, stream: STD_HANDLE, raw: F) {
+            if let Some((handle, mode)) = console(stream) {
+                if unsafe { SetConsoleMode(handle, raw(mode)) }.is_ok() {
+                    self.saved.push((handle, mode));
+                }
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            for (handle, mode) in self.saved.drain(..) {
+                let _ = unsafe { SetConsoleMode(handle, mode) };
+            }
+        }
+    }
+
+    /// Number of columns and rows of the console window.
+    pub fn size() -> Option<(i32, i32)> {
+        let (handle, _) = console(STD_OUTPUT_HANDLE)?;
+        let mut info = CONSOLE_SCREEN_BUFFER_INFO::default();
+        unsafe { GetConsoleScreenBufferInfo(handle, &mut info) }.ok()?;
+        let window = info.srWindow;
+        Some(((window.Right - window.Left + 1) as i32, (window.Bottom - window.Top + 1) as i32))
+    }
+
+    /// Reader of the standard input, which reads UTF-16 from consoles and UTF-8 otherwise.
+    pub struct Input {
+        console: Option<HANDLE>,
+        surrogate: Option<u16>,
+        decoder: Utf8Decoder,
+    }
+
+    impl Input {
+        pub fn new() -> Input {
+            Input {
+                console: console(STD_INPUT_HANDLE).map(|(handle, _)| handle),
+                surrogate: None,
+                decoder: Utf8Decoder::default(),
+            }
+        }
+
+        /// Read the next chunk of input, or `None` at EOF.
+        pub fn read(&mut self) -> io::Result<Option<String>> {
+            let handle = match self.console {
+                Some(handle) => handle,
+                None => return read_stdin(&mut self.decoder),
+            };
+
+            let mut buf = [0u16; 1024];
+            let mut read = 0;
+            unsafe { ReadConsoleW(handle, buf.as_mut_ptr() as *mut c_void, buf.len() as u32, &mut read, None) }
+                .map_err(|err| io::Error::other(err.to_string()))?;
+
+            let mut units: Vec<u16> = self.surrogate.take().into_iter().collect();
+            units.extend_from_slice(&buf[..read as usize]);
+            // A high surrogate at the end is completed by the next read.
+            if let Some(&last) = units.last().filter(|&&last| (0xd800..0xdc00).contains(&last)) {
+                self.surrogate = Some(last);
+                units.pop();
+            }
+            Ok(Some(String::from_utf16_lossy(&units)))
+        }
+    }
+}
+
+/// Consoles are only controlled on Windows, where the PTYs live. Elsewhere, the standard
+/// streams are used as they are.
+#[cfg(not(windows))]
+mod fallback_impl {
+    use super::*;
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> RawMode {
+            RawMode
+        }
+    }
+
+    pub fn size() -> Option<(i32, i32)> {
+        None
+    }
+
+    pub struct Input {
+        decoder: Utf8Decoder,
+    }
+
+    impl Input {
+        pub fn new() -> Input {
+            Input { decoder: Utf8Decoder::default() }
+        }
+
+        pub fn read(&mut self) -> io::Result<Option<String>> {
+            read_stdin(&mut self.decoder)
+        }
+    }
+}
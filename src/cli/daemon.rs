@@ -0,0 +1,98 @@
+//! `winpty-rs daemon`: host process that keeps PTY sessions alive while their clients detach.
+
+use std::ffi::OsString;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use winptyrs::remote::DEFAULT_SCROLLBACK;
+use winptyrs::{PtyServer, RemotePTY};
+
+use crate::args::Args;
+use crate::DEFAULT_NAME;
+
+const DAEMON_HELP: &str = "Usage: winpty-rs daemon [options]
+
+Host PTY sessions, which clients create, detach from and attach to by name.
+Sessions whose process exited are closed once no client is attached to them, and
+the daemon exits once it hosts no sessions and no client is connected to it.
+
+Options:
+    --name NAME          Name of the pipe to listen on [default: winpty-rs]
+    --scrollback BYTES   Amount of output retained by each session [default: 1048576]
+    --persist            Keep running when there are no sessions nor clients";
+
+const LIST_HELP: &str = "Usage: winpty-rs list [options]
+
+List the sessions hosted by a daemon.
+
+Options:
+    --name NAME   Name of the pipe that the daemon listens on [default: winpty-rs]";
+
+/// Interval on which the daemon checks its sessions.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Amount of time without sessions nor clients after which the daemon exits, which gives
+/// the client that launched it time to connect.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn run(args: Vec<OsString>) -> Result<(), String> {
+    let mut name = OsString::from(DEFAULT_NAME);
+    let mut scrollback = DEFAULT_SCROLLBACK;
+    let mut persist = false;
+    let mut args = Args::new(args);
+    while let Some(option) = args.next_option()? {
+        match option.as_str() {
+            "--name" => name = args.value(&option)?,
+            "--scrollback" => scrollback = args.parse(&option)?,
+            "--persist" => persist = true,
+            "-h" | "--help" => {
+                println!("{}", DAEMON_HELP);
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown option {}\n\n{}", option, DAEMON_HELP)),
+        }
+    }
+
+    let server = PtyServer::bind_with_scrollback(&name, scrollback).map_err(|err| err.to_string_lossy().into_owned())?;
+    let mut idle_since = Instant::now();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        for info in server.sessions().iter().filter(|info| info.exit.is_some() && info.clients == 0) {
+            let _ = server.close(info.session);
+        }
+        if !server.sessions().is_empty() || server.connections() > 0 {
+            idle_since = Instant::now();
+        } else if !persist && idle_since.elapsed() >= IDLE_TIMEOUT {
+            return Ok(());
+        }
+    }
+}
+
+pub fn list(args: Vec<OsString>) -> Result<(), String> {
+    let mut name = OsString::from(DEFAULT_NAME);
+    let mut args = Args::new(args);
+    while let Some(option) = args.next_option()? {
+        match option.as_str() {
+            "--name" => name = args.value(&option)?,
+            "-h" | "--help" => {
+                println!("{}", LIST_HELP);
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown option {}\n\n{}", option, LIST_HELP)),
+        }
+    }
+
+    let sessions = RemotePTY::list(&name).map_err(|err| err.to_string_lossy().into_owned())?;
+    println!("{:<8} {:<20} {:<8} {:<9} {:<8} STATUS", "ID", "NAME", "PID", "SIZE", "CLIENTS");
+    for info in sessions {
+        let status = match info.exit {
+            None if info.pid == 0 => String::from("created"),
+            None => String::from("running"),
+            Some(Some(code)) => format!("exited ({})", code),
+            Some(None) => String::from("exited"),
+        };
+        let size = format!("{}x{}", info.cols, info.rows);
+        println!("{:<8} {:<20} {:<8} {:<9} {:<8} {}", info.session, info.name, info.pid, size, info.clients, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! `winpty-rs host`: JSON-RPC host of PTY sessions, meant to be driven by editors.
+
+use std::ffi::OsString;
+use std::io;
+
+use winptyrs::JsonRpcHost;
+
+use crate::args::Args;
+
+const HOST_HELP: &str = "Usage: winpty-rs host --stdio
+
+Serve PTY sessions through JSON-RPC 2.0 messages, framed with Content-Length headers
+as done by the Language Server Protocol. The host exits once its input is closed,
+after killing the processes of its sessions.
+
+Methods:
+    spawn    {command, args?, cwd?, env?, cols?, rows?, backend?, encoding?} -> {id, pid}
+    write    {id, data, encoding?} -> {written}
+    resize   {id, cols, rows}
+    signal   {id, signal}
+    kill     {id}
+
+Notifications:
+    output   {id, data, encoding}
+    title    {id, title}
+    exit     {id, exitCode}
+
+Options:
+    --stdio   Read requests from the standard input and write to the standard output";
+
+pub fn run(args: Vec<OsString>) -> Result<(), String> {
+    let mut stdio = false;
+    let mut args = Args::new(args);
+    while let Some(option) = args.next_option()? {
+        match option.as_str() {
+            "--stdio" => stdio = true,
+            "-h" | "--help" => {
+                println!("{}", HOST_HELP);
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown option {}\n\n{}", option, HOST_HELP)),
+        }
+    }
+    if !stdio {
+        return Err(format!("The host only supports --stdio\n\n{}", HOST_HELP));
+    }
+
+    let host = JsonRpcHost::new(io::stdout());
+    host.serve(io::stdin().lock()).map_err(|err| err.to_string_lossy().into_owned())
+}
@@ -0,0 +1,57 @@
+//! Command line interface of winpty-rs.
+
+extern crate winptyrs;
+
+mod args;
+mod console;
+mod daemon;
+mod host;
+mod run;
+mod script;
+
+use std::process::ExitCode;
+
+/// Name of the pipe used by the daemon, unless another one is given.
+pub const DEFAULT_NAME: &str = "winpty-rs";
+
+const HELP: &str = "Usage: winpty-rs <command> [options]
+
+Commands:
+    run      Run a command inside a PTY attached to the current console
+    script   Run a send/expect script that drives an interactive program
+    daemon   Host PTY sessions that survive their clients
+    list     List the sessions hosted by a daemon
+    host     Serve PTY sessions through JSON-RPC over the standard streams
+
+Run `winpty-rs <command> --help` to get the options of a command.";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1);
+    let command = args.next();
+    let args = args.collect();
+    let result = match command.as_ref().and_then(|command| command.to_str()) {
+        Some("run") => match run::run(args) {
+            // The exit code of the command may not fit in an `ExitCode`.
+            Ok(code) => std::process::exit(code as i32),
+            Err(err) => Err(err),
+        },
+        Some("script") => script::run(args),
+        Some("daemon") => daemon::run(args),
+        Some("list") => daemon::list(args),
+        Some("host") => host::run(args),
+        Some("-h") | Some("--help") => {
+            println!("{}", HELP);
+            Ok(())
+        }
+        Some(command) => Err(format!("Unknown command {}\n\n{}", command, HELP)),
+        None => Err(String::from(HELP)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
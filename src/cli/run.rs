@@ -0,0 +1,117 @@
+//! `winpty-rs run`: interactive passthrough between the current console and a command
+//! running inside a PTY.
+
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use winptyrs::resolver::environment_block;
+use winptyrs::{PTYArgs, PTYBackend, QuotingStyle, PTY};
+
+use crate::args::Args;
+use crate::console::{self, Input, RawMode};
+
+const RUN_HELP: &str = "Usage: winpty-rs run [options] -- <command> [arguments...]
+
+Run a command inside a PTY attached to the current console, and exit with its exit code.
+The console is switched into raw mode, so every key is forwarded to the command, and
+changes of its size are propagated to the PTY unless the size is given.
+
+Options:
+    --backend BACKEND   PTY backend: conpty, winpty or auto [default: auto]
+    --cols COLS         Number of columns [default: width of the console]
+    --rows ROWS         Number of rows [default: height of the console]
+    --cwd DIR           Working directory of the command
+    --env NAME=VALUE    Set an environment variable of the command, may be repeated";
+
+/// Interval on which the size of the console is checked.
+const RESIZE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run the command, returning its exit code.
+pub fn run(args: Vec<OsString>) -> Result<u32, String> {
+    let mut backend = PTYBackend::Auto;
+    let mut cols: Option<i32> = None;
+    let mut rows: Option<i32> = None;
+    let mut cwd = None;
+    let mut vars = Vec::new();
+    let mut args = Args::new(args);
+    while let Some(option) = args.next_option()? {
+        match option.as_str() {
+            "--backend" => {
+                backend = match args.value(&option)?.to_str() {
+                    Some("conpty") => PTYBackend::ConPTY,
+                    Some("winpty") => PTYBackend::WinPTY,
+                    Some("auto") => PTYBackend::Auto,
+                    _ => return Err(format!("Invalid value of {}\n\n{}", option, RUN_HELP)),
+                }
+            }
+            "--cols" => cols = Some(args.parse(&option)?),
+            "--rows" => rows = Some(args.parse(&option)?),
+            "--cwd" => cwd = Some(args.value(&option)?),
+            "--env" => {
+                let var = args.value(&option)?;
+                match var.to_str().and_then(|var| var.split_once('=')) {
+                    Some((name, value)) if !name.is_empty() => vars.push((name.into(), value.into())),
+                    _ => return Err(format!("Invalid value of {}: {}", option, var.to_string_lossy())),
+                }
+            }
+            "--" => break,
+            "-h" | "--help" => {
+                println!("{}", RUN_HELP);
+                return Ok(0);
+            }
+            _ => return Err(format!("Unknown option {}\n\n{}", option, RUN_HELP)),
+        }
+    }
+
+    let mut command = args.rest().into_iter();
+    let program = command.next().ok_or_else(|| format!("Missing command\n\n{}", RUN_HELP))?;
+    let arguments: Vec<String> = command.map(|arg| QuotingStyle::Msvcrt.quote(&arg.to_string_lossy())).collect();
+    let cmdline = (!arguments.is_empty()).then(|| OsString::from(arguments.join(" ")));
+    let env = (!vars.is_empty()).then(|| environment_block(&vars));
+
+    let (console_cols, console_rows) = console::size().unwrap_or((80, 24));
+    let pty_args = PTYArgs::new(cols.unwrap_or(console_cols), rows.unwrap_or(console_rows));
+    if pty_args.cols <= 0 || pty_args.rows <= 0 {
+        return Err(format!("Invalid size {}x{}", pty_args.cols, pty_args.rows));
+    }
+    let follow_console = cols.is_none() && rows.is_none();
+
+    let to_string = |err: OsString| err.to_string_lossy().into_owned();
+    let mut pty = PTY::new_with_backend(&pty_args, backend).map_err(to_string)?;
+    pty.spawn(program, cmdline, cwd, env).map_err(to_string)?;
+    let pty = Arc::new(pty);
+
+    // The console leaves raw mode once the process exits.
+    let _raw_mode = RawMode::enable();
+    {
+        // The input thread stays blocked on the console until the process exits.
+        let pty = pty.clone();
+        thread::spawn(move || {
+            let mut input = Input::new();
+            while let Ok(Some(data)) = input.read() {
+                if pty.write(OsString::from(data)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut size = (pty_args.cols, pty_args.rows);
+    while let Ok(output) = pty.read_timeout(RESIZE_INTERVAL) {
+        if !output.is_empty() {
+            let _ = stdout.write_all(output.to_string_lossy().as_bytes());
+            let _ = stdout.flush();
+        }
+        if let Some(current) = console::size().filter(|&current| follow_console && current != size) {
+            let _ = pty.set_size(current.0, current.1);
+            size = current;
+        }
+    }
+
+    let _ = pty.wait_for_exit();
+    Ok(pty.get_exitstatus().ok().flatten().unwrap_or(1))
+}
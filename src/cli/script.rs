@@ -0,0 +1,73 @@
+//! `winpty-rs script`: runner of send/expect scripts, see [`winptyrs::script`].
+
+use std::ffi::OsString;
+
+use winptyrs::{PTYArgs, PTYBackend, Script};
+
+use crate::args::Args;
+
+const SCRIPT_HELP: &str = "Usage: winpty-rs script [options] <file>
+
+Run a script that drives an interactive program inside a PTY. Each line of the script
+holds a command, blank lines and lines that start with # are ignored:
+
+    spawn PROGRAM [ARGUMENTS...]              Start the program, must be the first command
+    send \"TEXT\"                               Write a text
+    sendline \"TEXT\"                           Write a text followed by a new line
+    expect /PATTERN/ [timeout=DURATION]       Wait for the output to match a pattern
+    expect \"TEXT\" [timeout=DURATION]          Wait for the output to contain a text
+    sleep DURATION                            Wait, e.g., 500ms, 2s or 1m
+    resize COLS ROWS                          Change the size of the PTY
+    key NAME...                               Press keys, e.g., enter, up, f5 or ctrl-c
+    assert-exit CODE [timeout=DURATION]       Wait for the program to exit with a code
+
+Named groups of patterns, such as (?<name>\\d+), are captured as variables, which are
+used in texts as ${name}. Expectations and exits are waited for 10s by default.
+
+Options:
+    --backend BACKEND   PTY backend: conpty, winpty or auto [default: auto]
+    --cols COLS         Number of columns [default: 80]
+    --rows ROWS         Number of rows [default: 24]";
+
+pub fn run(args: Vec<OsString>) -> Result<(), String> {
+    let mut backend = PTYBackend::Auto;
+    let mut pty_args = PTYArgs::default();
+    let mut file = None;
+    let mut args = Args::new(args);
+    loop {
+        if file.is_none() {
+            file = args.next_positional();
+            if file.is_some() {
+                continue;
+            }
+        }
+        let option = match args.next_option()? {
+            Some(option) => option,
+            None => break,
+        };
+        match option.as_str() {
+            "--backend" => {
+                backend = match args.value(&option)?.to_str() {
+                    Some("conpty") => PTYBackend::ConPTY,
+                    Some("winpty") => PTYBackend::WinPTY,
+                    Some("auto") => PTYBackend::Auto,
+                    _ => return Err(format!("Invalid value of {}\n\n{}", option, SCRIPT_HELP)),
+                }
+            }
+            "--cols" => pty_args.cols = args.parse(&option)?,
+            "--rows" => pty_args.rows = args.parse(&option)?,
+            "-h" | "--help" => {
+                println!("{}", SCRIPT_HELP);
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown option {}\n\n{}", option, SCRIPT_HELP)),
+        }
+    }
+
+    let file = file.ok_or_else(|| format!("Missing script\n\n{}", SCRIPT_HELP))?;
+    let name = file.to_string_lossy().into_owned();
+    let source = std::fs::read_to_string(&file).map_err(|err| format!("Cannot read {}: {}", name, err))?;
+    let script = Script::parse(&source).map_err(|err| format!("{}: {}", name, err))?;
+    script.run(&pty_args, backend).map_err(|err| format!("{}: {}", name, err))?;
+    Ok(())
+}
@@ -3,13 +3,10 @@ use std::ffi::OsString;
 use winptyrs::{PTY, PTYArgs, PTYBackend, AgentConfig, MouseMode};
 
 fn main() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     match PTY::new_with_backend(&pty_args, PTYBackend::ConPTY) {
         Ok(mut pty) => {
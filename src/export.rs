@@ -0,0 +1,397 @@
+//! Export of terminal output into self-contained HTML and SVG documents, e.g., to attach
+//! colored output to test reports, and into PNG screenshots.
+//!
+//! The exporters render the lines of a [`Screen`]: its scrollback followed by its rows. To
+//! export a whole transcript, feed it into a screen with an unbounded scrollback and set
+//! [`ExportOptions::trim`]. Colors are resolved through a configurable [`Palette`], and all
+//! the text and hyperlinks are escaped, hence the output of a process cannot inject markup.
+//!
+//! Screenshots only cover the rows of the screen, and are drawn with an embedded bitmap font
+//! rather than the fonts of the system, hence they are identical on every machine.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::vt::{Cell, Color, Screen, Style};
+
+mod font;
+mod raster;
+
+pub use font::{CELL_HEIGHT, CELL_WIDTH};
+pub use raster::{png, screenshot, Image, PngOptions};
+
+/// 24-bit color, as `(red, green, blue)`.
+pub type Rgb = (u8, u8, u8);
+
+/// Colors used to resolve the colors of the cells.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// The 16 ANSI colors: black, red, green, yellow, blue, magenta, cyan and white,
+    /// followed by their bright variants.
+    pub ansi: [Rgb; 16],
+    pub foreground: Rgb,
+    pub background: Rgb,
+    pub cursor: Rgb,
+}
+
+/// The Campbell palette of Windows Terminal.
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            ansi: [
+                (0x0c, 0x0c, 0x0c),
+                (0xc5, 0x0f, 0x1f),
+                (0x13, 0xa1, 0x0e),
+                (0xc1, 0x9c, 0x00),
+                (0x00, 0x37, 0xda),
+                (0x88, 0x17, 0x98),
+                (0x3a, 0x96, 0xdd),
+                (0xcc, 0xcc, 0xcc),
+                (0x76, 0x76, 0x76),
+                (0xe7, 0x48, 0x56),
+                (0x16, 0xc6, 0x0c),
+                (0xf9, 0xf1, 0xa5),
+                (0x3b, 0x78, 0xff),
+                (0xb4, 0x00, 0x9e),
+                (0x61, 0xd6, 0xd6),
+                (0xf2, 0xf2, 0xf2),
+            ],
+            foreground: (0xcc, 0xcc, 0xcc),
+            background: (0x0c, 0x0c, 0x0c),
+            cursor: (0xff, 0xff, 0xff),
+        }
+    }
+}
+
+impl Palette {
+    /// Resolve a color of the 256-color palette.
+    pub fn indexed(&self, index: u8) -> Rgb {
+        match index {
+            0..=15 => self.ansi[index as usize],
+            16..=231 => {
+                let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+                let n = index - 16;
+                (level(n / 36), level(n / 6 % 6), level(n % 6))
+            }
+            _ => {
+                let gray = 8 + 10 * (index - 232);
+                (gray, gray, gray)
+            }
+        }
+    }
+
+    /// Resolve the foreground and background colors of a cell.
+    pub fn colors(&self, style: &Style) -> (Rgb, Rgb) {
+        let resolve = |color: Color, default: Rgb| match color {
+            Color::Default => default,
+            Color::Indexed(index) => self.indexed(index),
+            Color::Rgb(r, g, b) => (r, g, b),
+        };
+        let fg = resolve(style.fg, self.foreground);
+        let bg = resolve(style.bg, self.background);
+        match style.inverse {
+            true => (bg, fg),
+            false => (fg, bg),
+        }
+    }
+}
+
+/// Options of the exporters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportOptions {
+    pub palette: Palette,
+    /// CSS font family of the text.
+    pub font_family: String,
+    /// Size of the font, in pixels.
+    pub font_size: u32,
+    /// Height of a line, relative to the font size.
+    pub line_height: f32,
+    /// Title of the HTML document, or accessible title of the SVG image.
+    pub title: Option<String>,
+    /// Remove the blank lines at the bottom, e.g., when exporting a transcript.
+    pub trim: bool,
+    /// Draw the cursor of the screen, unless it is hidden.
+    pub cursor: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            palette: Palette::default(),
+            font_family: String::from("'Cascadia Mono', Consolas, 'Courier New', monospace"),
+            font_size: 14,
+            line_height: 1.2,
+            title: None,
+            trim: false,
+            cursor: false,
+        }
+    }
+}
+
+/// Sequence of cells of a line that share the same style and hyperlink.
+struct Run {
+    /// Column of the first cell.
+    col: usize,
+    /// Number of columns taken by the cells.
+    width: usize,
+    text: String,
+    style: Style,
+    link: Option<Arc<str>>,
+    /// Set if the run is the cell under the cursor.
+    cursor: bool,
+}
+
+impl Run {
+    /// Resolve the foreground and background colors of the run.
+    fn colors(&self, palette: &Palette) -> (Rgb, Rgb) {
+        match self.cursor {
+            true => (palette.background, palette.cursor),
+            false => palette.colors(&self.style),
+        }
+    }
+}
+
+/// Lines to export, as the runs of each one of them.
+fn lines(screen: &Screen, options: &ExportOptions) -> Vec<Vec<Run>> {
+    let scrollback = screen.scrollback().count();
+    let cursor = (options.cursor && screen.cursor_visible()).then(|| {
+        let (row, col) = screen.cursor();
+        (scrollback + row, col)
+    });
+    let mut lines: Vec<Vec<Run>> = screen
+        .lines()
+        .enumerate()
+        .map(|(index, line)| runs(line, cursor.filter(|&(row, _)| row == index).map(|(_, col)| col)))
+        .collect();
+    if options.trim {
+        while lines.last().is_some_and(Vec::is_empty) {
+            lines.pop();
+        }
+    }
+    lines
+}
+
+/// Split a line into runs, omitting the trailing blank cells. The cell at `cursor` gets a
+/// run of its own.
+fn runs(line: &[Cell], cursor: Option<usize>) -> Vec<Run> {
+    let blank = |cell: &Cell| cell.c == ' ' && cell.style == Style::default() && cell.link.is_none();
+    let end = line.iter().rposition(|cell| !blank(cell)).map_or(0, |i| i + 1);
+    let end = cursor.map_or(end, |col| end.max(col + 1).min(line.len()));
+
+    let mut runs: Vec<Run> = Vec::new();
+    for (col, cell) in line[..end].iter().enumerate() {
+        if cell.is_continuation() {
+            if let Some(run) = runs.last_mut() {
+                run.width += 1;
+            }
+            continue;
+        }
+        let at_cursor = cursor == Some(col);
+        match runs.last_mut() {
+            Some(run) if run.style == cell.style && run.link == cell.link && !run.cursor && !at_cursor => {
+                run.text.push(cell.c);
+                run.width += 1;
+            }
+            _ => runs.push(Run {
+                col,
+                width: 1,
+                text: cell.c.to_string(),
+                style: cell.style,
+                link: cell.link.clone(),
+                cursor: at_cursor,
+            }),
+        }
+    }
+    runs
+}
+
+/// Check if a hyperlink can be exported, i.e., if its scheme cannot run scripts.
+fn safe_link(link: &str) -> bool {
+    let scheme = link.split_once(':').map_or("", |(scheme, _)| scheme).to_ascii_lowercase();
+    matches!(scheme.as_str(), "http" | "https" | "ftp" | "file" | "mailto")
+}
+
+/// Escape the characters of `text` that are special in HTML and XML, including quotes so
+/// that the result can be used in attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            // Control characters are not allowed in XML.
+            c if c.is_control() && c != '\t' => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Font family that cannot escape its CSS declaration.
+fn font_family(options: &ExportOptions) -> String {
+    options.font_family.chars().filter(|c| !matches!(c, '<' | '>' | '{' | '}' | ';' | '"' | '\\')).collect()
+}
+
+/// Format a coordinate with at most two decimals.
+fn number(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Render the lines of `screen` into a self-contained HTML document.
+///
+/// # Examples
+/// ```
+/// use winptyrs::export::{html, ExportOptions};
+/// use winptyrs::vt::Screen;
+///
+/// let mut screen = Screen::new(80, 24).with_scrollback(usize::MAX);
+/// screen.feed("\x1b[1;31merror\x1b[0m: <missing>\r\n");
+/// let options = ExportOptions { trim: true, ..ExportOptions::default() };
+/// let document = html(&screen, &options);
+/// assert!(document.contains("font-weight:bold\">error</span>: &lt;missing&gt;\n</pre>"));
+/// ```
+pub fn html(screen: &Screen, options: &ExportOptions) -> String {
+    let palette = &options.palette;
+    let mut document = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    if let Some(title) = &options.title {
+        let _ = writeln!(document, "<title>{}</title>", escape(title));
+    }
+    let _ = write!(
+        document,
+        "<style>\npre.terminal {{ margin: 0; padding: 8px; color: {}; background-color: {}; font-family: {}; font-size: {}px; line-height: {}; }}\npre.terminal a {{ color: inherit; }}\n</style>\n</head>\n<body>\n<pre class=\"terminal\">",
+        hex(palette.foreground),
+        hex(palette.background),
+        font_family(options),
+        options.font_size,
+        options.line_height
+    );
+
+    for line in lines(screen, options) {
+        for run in line {
+            let link = run.link.as_deref().filter(|link| safe_link(link));
+            if let Some(link) = link {
+                let _ = write!(document, "<a href=\"{}\">", escape(link));
+            }
+            let text = escape(&run.text);
+            let mut css = Vec::new();
+            let (fg, bg) = run.colors(palette);
+            if fg != palette.foreground {
+                css.push(format!("color:{}", hex(fg)));
+            }
+            if bg != palette.background {
+                css.push(format!("background-color:{}", hex(bg)));
+            }
+            if run.style.bold {
+                css.push(String::from("font-weight:bold"));
+            }
+            if run.style.italic {
+                css.push(String::from("font-style:italic"));
+            }
+            if run.style.underline {
+                css.push(String::from("text-decoration:underline"));
+            }
+            match css.is_empty() {
+                true => document.push_str(&text),
+                false => {
+                    let _ = write!(document, "<span style=\"{}\">{}</span>", css.join(";"), text);
+                }
+            }
+            if link.is_some() {
+                document.push_str("</a>");
+            }
+        }
+        document.push('\n');
+    }
+    document.push_str("</pre>\n</body>\n</html>\n");
+    document
+}
+
+/// Render the lines of `screen` into a self-contained SVG image, where every cell takes
+/// 0.6 times the font size horizontally.
+pub fn svg(screen: &Screen, options: &ExportOptions) -> String {
+    let palette = &options.palette;
+    let lines = lines(screen, options);
+    let cell_width = options.font_size as f64 * 0.6;
+    let line_height = options.font_size as f64 * options.line_height as f64;
+    let padding = 8.0;
+    let width = number(screen.cols() as f64 * cell_width + 2.0 * padding);
+    let height = number(lines.len() as f64 * line_height + 2.0 * padding);
+
+    let mut image = String::new();
+    let _ = writeln!(
+        image,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"{}\" font-size=\"{}\">",
+        escape(&font_family(options)),
+        options.font_size,
+        w = width,
+        h = height
+    );
+    if let Some(title) = &options.title {
+        let _ = writeln!(image, "<title>{}</title>", escape(title));
+    }
+    let _ = writeln!(image, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex(palette.background));
+
+    for (index, line) in lines.iter().enumerate() {
+        let y = padding + index as f64 * line_height;
+        for run in line {
+            let (_, bg) = run.colors(palette);
+            if bg != palette.background {
+                let _ = writeln!(
+                    image,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    number(padding + run.col as f64 * cell_width),
+                    number(y),
+                    number(run.width as f64 * cell_width),
+                    number(line_height),
+                    hex(bg)
+                );
+            }
+        }
+
+        let visible: Vec<&Run> = line.iter().filter(|run| !run.text.trim().is_empty() || run.style.underline).collect();
+        if visible.is_empty() {
+            continue;
+        }
+        // The baseline is placed so that the text is vertically centered in the line.
+        let baseline = y + (line_height + options.font_size as f64 * 0.7) / 2.0;
+        let _ = write!(image, "<text y=\"{}\" xml:space=\"preserve\">", number(baseline));
+        for run in visible {
+            let link = run.link.as_deref().filter(|link| safe_link(link));
+            if let Some(link) = link {
+                let _ = write!(image, "<a href=\"{}\">", escape(link));
+            }
+            let (fg, _) = run.colors(palette);
+            let _ = write!(
+                image,
+                "<tspan x=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" fill=\"{}\"",
+                number(padding + run.col as f64 * cell_width),
+                number(run.width as f64 * cell_width),
+                hex(fg)
+            );
+            if run.style.bold {
+                image.push_str(" font-weight=\"bold\"");
+            }
+            if run.style.italic {
+                image.push_str(" font-style=\"italic\"");
+            }
+            if run.style.underline {
+                image.push_str(" text-decoration=\"underline\"");
+            }
+            let _ = write!(image, ">{}</tspan>", escape(&run.text));
+            if link.is_some() {
+                image.push_str("</a>");
+            }
+        }
+        image.push_str("</text>\n");
+    }
+    image.push_str("</svg>\n");
+    image
+}
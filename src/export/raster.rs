@@ -0,0 +1,163 @@
+//! Rasterization of screens into images, and encoding of the images into PNG.
+//!
+//! Screens are drawn with the embedded bitmap font (see [`CELL_WIDTH`] and [`CELL_HEIGHT`]),
+//! hence the result only depends on the screen and on the options, which makes it suitable
+//! for visual regression tests in headless environments.
+
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+use super::font::{self, CELL_HEIGHT, CELL_WIDTH, UNDERLINE_ROW};
+use super::{Palette, Rgb};
+use crate::vt::Screen;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Options of [`screenshot`] and [`png`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PngOptions {
+    pub palette: Palette,
+    /// Size of the pixels of the embedded font, e.g., 2 for cells of 12 × 24 pixels.
+    pub scale: usize,
+    /// Draw the cursor of the screen as a block, unless it is hidden.
+    pub cursor: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions { palette: Palette::default(), scale: 2, cursor: false }
+    }
+}
+
+/// RGB image, stored row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    /// Create an image filled with `color`.
+    pub fn new(width: usize, height: usize, color: Rgb) -> Image {
+        Image { width, height, pixels: vec![color; width * height] }
+    }
+
+    /// Color of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    /// If the pixel is outside of the image.
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        assert!(x < self.width && y < self.height, "The pixel ({}, {}) is outside of the image", x, y);
+        self.pixels[y * self.width + x]
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for row in y..(y + height).min(self.height) {
+            let start = row * self.width;
+            self.pixels[start + x.min(self.width)..start + (x + width).min(self.width)].fill(color);
+        }
+    }
+
+    /// Encode the image into PNG.
+    pub fn to_png(&self) -> Vec<u8> {
+        // Every row uses the Sub filter, i.e., stores the difference with the pixel to its
+        // left, which turns runs of the same color into runs of zeros.
+        let mut raw = Vec::with_capacity((3 * self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(1);
+            let mut left = (0, 0, 0);
+            for &(r, g, b) in row {
+                raw.extend([r.wrapping_sub(left.0), g.wrapping_sub(left.1), b.wrapping_sub(left.2)]);
+                left = (r, g, b);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering and no interlacing.
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Draw the rows of `screen` into an image of `CELL_WIDTH × scale` by `CELL_HEIGHT × scale`
+/// pixels per cell.
+///
+/// # Examples
+/// ```
+/// use winptyrs::export::{screenshot, PngOptions, CELL_HEIGHT, CELL_WIDTH};
+/// use winptyrs::vt::Screen;
+///
+/// let mut screen = Screen::new(80, 24);
+/// screen.feed("\x1b[1;32mOK\x1b[0m");
+/// let image = screenshot(&screen, &PngOptions::default());
+/// assert_eq!((image.width, image.height), (80 * CELL_WIDTH * 2, 24 * CELL_HEIGHT * 2));
+/// assert!(image.to_png().starts_with(b"\x89PNG"));
+/// ```
+pub fn screenshot(screen: &Screen, options: &PngOptions) -> Image {
+    let palette = &options.palette;
+    let scale = options.scale.max(1);
+    let (cell_width, cell_height) = (CELL_WIDTH * scale, CELL_HEIGHT * scale);
+    let mut image = Image::new(screen.cols() * cell_width, screen.rows() * cell_height, palette.background);
+    let cursor = (options.cursor && screen.cursor_visible()).then(|| screen.cursor());
+
+    for row in 0..screen.rows() {
+        for (col, cell) in screen.row(row).iter().enumerate() {
+            if cell.is_continuation() {
+                continue;
+            }
+            let cols = cell.width().clamp(1, screen.cols() - col);
+            let (fg, bg) = match cursor == Some((row, col)) {
+                true => (palette.background, palette.cursor),
+                false => palette.colors(&cell.style),
+            };
+            let (left, top) = (col * cell_width, row * cell_height);
+            image.fill(left, top, cols * cell_width, cell_height, bg);
+
+            let mut set = |x: usize, y: usize| {
+                if x < cols * CELL_WIDTH && y < CELL_HEIGHT {
+                    image.fill(left + x * scale, top + y * scale, scale, scale, fg);
+                }
+            };
+            font::draw(cell.c, cols, cell.style.bold, &mut set);
+            if cell.style.underline {
+                for x in 0..cols * CELL_WIDTH {
+                    set(x, UNDERLINE_ROW);
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Draw the rows of `screen` into a PNG image, see [`screenshot`].
+pub fn png(screen: &Screen, options: &PngOptions) -> Vec<u8> {
+    screenshot(screen, options).to_png()
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let mut crc = Crc::new();
+    crc.update(&png[start..]);
+    png.extend(crc.sum().to_be_bytes());
+}
+
+/// Compress `data` into a zlib stream.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len() / 4), Compression::default());
+    // Writing into a vector cannot fail.
+    encoder.write_all(data).expect("Cannot compress the image");
+    encoder.finish().expect("Cannot compress the image")
+}
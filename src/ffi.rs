@@ -0,0 +1,452 @@
+//! C ABI over [`PTY`], exported by the `cdylib` build of the crate.
+//!
+//! PTYs are handled through opaque [`WinptyrsPty`] pointers. Every function returns a
+//! [`WinptyrsStatus`], which is [`WINPTYRS_OK`] on success. On failure, a description of the
+//! error is available through [`winptyrs_last_error`] on the same thread. Strings are UTF-8.
+//!
+//! The C header `include/winptyrs.h` is generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/winptyrs.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString, OsString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::pty::{AgentConfig, MouseMode, PTYArgs, PTYBackend, ShutdownPolicy, PTY};
+
+/// Result of the functions of the C API.
+pub type WinptyrsStatus = i32;
+
+/// The function succeeded.
+pub const WINPTYRS_OK: WinptyrsStatus = 0;
+/// A required pointer argument was null.
+pub const WINPTYRS_ERROR_NULL_ARGUMENT: WinptyrsStatus = 1;
+/// A string argument was not valid UTF-8.
+pub const WINPTYRS_ERROR_INVALID_UTF8: WinptyrsStatus = 2;
+/// An argument had an invalid value.
+pub const WINPTYRS_ERROR_INVALID_ARGUMENT: WinptyrsStatus = 3;
+/// The PTY reported an error.
+pub const WINPTYRS_ERROR_PTY: WinptyrsStatus = 4;
+/// The process exited and all of its output was read.
+pub const WINPTYRS_ERROR_EOF: WinptyrsStatus = 5;
+/// The library panicked. The PTY involved should not be used anymore, other than to free it.
+pub const WINPTYRS_ERROR_PANIC: WinptyrsStatus = 6;
+
+/// Select the backend automatically, see [`PTYBackend::Auto`].
+pub const WINPTYRS_BACKEND_AUTO: u32 = 2;
+/// Use the ConPTY backend, see [`PTYBackend::ConPTY`].
+pub const WINPTYRS_BACKEND_CONPTY: u32 = 0;
+/// Use the winpty backend, see [`PTYBackend::WinPTY`].
+pub const WINPTYRS_BACKEND_WINPTY: u32 = 1;
+/// Replay the session recorded in the `cassette` argument, see [`PTYBackend::Replay`].
+pub const WINPTYRS_BACKEND_REPLAY: u32 = 4;
+
+/// See [`ShutdownPolicy::CloseConsole`].
+pub const WINPTYRS_SHUTDOWN_CLOSE_CONSOLE: u32 = 0;
+/// See [`ShutdownPolicy::Terminate`].
+pub const WINPTYRS_SHUTDOWN_TERMINATE: u32 = 1;
+/// See [`ShutdownPolicy::TerminateTree`].
+pub const WINPTYRS_SHUTDOWN_TERMINATE_TREE: u32 = 2;
+/// See [`ShutdownPolicy::WaitWithTimeout`], which waits for `shutdown_timeout` ms.
+pub const WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT: u32 = 3;
+
+/// Arguments used to create a PTY, see [`PTYArgs`]. Use [`winptyrs_args_default`] to
+/// initialize them.
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct WinptyrsArgs {
+    /// Number of character columns to display.
+    pub cols: i32,
+    /// Number of line rows to display.
+    pub rows: i32,
+    /// Mouse capture settings for the winpty backend: 0 (none), 1 (auto) or 2 (force), see [`MouseMode`].
+    pub mouse_mode: i32,
+    /// Amount of time (in ms) to wait for the winpty agent.
+    pub timeout: u32,
+    /// Configuration flags for the winpty backend, see [`AgentConfig`].
+    pub agent_config: u64,
+    /// Place the spawned process in a job object with all of its descendants.
+    pub contain_tree: bool,
+    /// What to do with the process when the PTY is freed, one of the `WINPTYRS_SHUTDOWN_*` values.
+    pub shutdown_policy: u32,
+    /// Amount of time (in ms) used by `WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT`.
+    pub shutdown_timeout: u32,
+    /// Maximum amount of time (in ms) to wait for the remaining output when the process exits.
+    pub drain_timeout: u32,
+    /// Maximum length (in characters) of the payload of the tracked OSC sequences.
+    pub osc_max_payload: usize,
+    /// Read the output through the I/O reactor shared by all the PTYs (ConPTY only).
+    pub shared_reactor: bool,
+    /// Path of the cassette where the session is recorded, or that is replayed by
+    /// `WINPTYRS_BACKEND_REPLAY`. Null to disable recording.
+    pub cassette: *const c_char,
+    /// Fail the replay when the input or the resizes diverge from the cassette.
+    pub strict_replay: bool,
+}
+
+impl WinptyrsArgs {
+    fn new(args: &PTYArgs) -> WinptyrsArgs {
+        let (shutdown_policy, shutdown_timeout) = match args.shutdown_policy {
+            ShutdownPolicy::CloseConsole => (WINPTYRS_SHUTDOWN_CLOSE_CONSOLE, 0),
+            ShutdownPolicy::Terminate => (WINPTYRS_SHUTDOWN_TERMINATE, 0),
+            ShutdownPolicy::TerminateTree => (WINPTYRS_SHUTDOWN_TERMINATE_TREE, 0),
+            ShutdownPolicy::WaitWithTimeout(timeout) => {
+                (WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT, timeout.as_millis().min(u32::MAX as u128) as u32)
+            }
+        };
+        WinptyrsArgs {
+            cols: args.cols,
+            rows: args.rows,
+            mouse_mode: args.mouse_mode.to_i32().unwrap_or_default(),
+            timeout: args.timeout,
+            agent_config: args.agent_config.bits(),
+            contain_tree: args.contain_tree,
+            shutdown_policy,
+            shutdown_timeout,
+            drain_timeout: args.drain_timeout,
+            osc_max_payload: args.osc_max_payload,
+            shared_reactor: args.shared_reactor,
+            // The path is owned by the caller, so a default one cannot be given.
+            cassette: ptr::null(),
+            strict_replay: args.strict_replay,
+        }
+    }
+
+    /// Convert the arguments, whose `cassette` must be null or a valid C string.
+    unsafe fn to_pty_args(&self) -> Result<PTYArgs, Error> {
+        let mouse_mode = MouseMode::from_i32(self.mouse_mode)
+            .ok_or_else(|| Error::invalid(format!("Invalid mouse mode {}", self.mouse_mode)))?;
+        let shutdown_policy = match self.shutdown_policy {
+            WINPTYRS_SHUTDOWN_CLOSE_CONSOLE => ShutdownPolicy::CloseConsole,
+            WINPTYRS_SHUTDOWN_TERMINATE => ShutdownPolicy::Terminate,
+            WINPTYRS_SHUTDOWN_TERMINATE_TREE => ShutdownPolicy::TerminateTree,
+            WINPTYRS_SHUTDOWN_WAIT_WITH_TIMEOUT => {
+                ShutdownPolicy::WaitWithTimeout(Duration::from_millis(self.shutdown_timeout as u64))
+            }
+            value => return Err(Error::invalid(format!("Invalid shutdown policy {}", value))),
+        };
+        Ok(PTYArgs {
+            cols: self.cols,
+            rows: self.rows,
+            mouse_mode,
+            timeout: self.timeout,
+            agent_config: AgentConfig::from_bits_truncate(self.agent_config),
+            contain_tree: self.contain_tree,
+            shutdown_policy,
+            drain_timeout: self.drain_timeout,
+            osc_max_payload: self.osc_max_payload,
+            shared_reactor: self.shared_reactor,
+            cassette: optional_str(self.cassette, "cassette")?.map(OsString::from),
+            strict_replay: self.strict_replay,
+        })
+    }
+}
+
+/// Opaque handle of a PTY, created by [`winptyrs_pty_new`] and released by [`winptyrs_pty_free`].
+pub struct WinptyrsPty {
+    pty: PTY,
+    /// Output that was read from the PTY but did not fit in the buffer of the caller.
+    pending: Mutex<Vec<u8>>,
+}
+
+/// Failure of a function of the C API.
+struct Error {
+    status: WinptyrsStatus,
+    message: String,
+}
+
+impl Error {
+    fn new(status: WinptyrsStatus, message: String) -> Error {
+        Error { status, message }
+    }
+
+    fn null(name: &str) -> Error {
+        Error::new(WINPTYRS_ERROR_NULL_ARGUMENT, format!("{} must not be null", name))
+    }
+
+    fn invalid(message: String) -> Error {
+        Error::new(WINPTYRS_ERROR_INVALID_ARGUMENT, message)
+    }
+
+    fn pty(err: OsString) -> Error {
+        Error::new(WINPTYRS_ERROR_PTY, err.to_string_lossy().into_owned())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Run the body of a function of the C API, recording its error and catching its panics.
+fn guard<F: FnOnce() -> Result<(), Error>>(f: F) -> WinptyrsStatus {
+    let err = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return WINPTYRS_OK,
+        Ok(Err(err)) => err,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Error::new(WINPTYRS_ERROR_PANIC, format!("Panic inside winptyrs: {}", message))
+        }
+    };
+    let message = CString::new(err.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    err.status
+}
+
+/// Read an optional C string.
+unsafe fn optional_str(value: *const c_char, name: &str) -> Result<Option<String>, Error> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(|value| Some(value.to_owned()))
+        .map_err(|_| Error::new(WINPTYRS_ERROR_INVALID_UTF8, format!("{} is not valid UTF-8", name)))
+}
+
+unsafe fn handle<'a>(pty: *const WinptyrsPty) -> Result<&'a WinptyrsPty, Error> {
+    pty.as_ref().ok_or_else(|| Error::null("pty"))
+}
+
+unsafe fn output<'a, T>(value: *mut T, name: &str) -> Result<&'a mut T, Error> {
+    value.as_mut().ok_or_else(|| Error::null(name))
+}
+
+/// Retrieve the description of the last error that happened on the calling thread.
+///
+/// # Returns
+/// A UTF-8 string that remains valid until the next failure on the same thread, or null
+/// if no function failed on this thread.
+#[no_mangle]
+pub extern "C" fn winptyrs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Initialize `args` with the default arguments of a PTY.
+///
+/// # Safety
+/// `args` must be null or point to a writable `WinptyrsArgs`.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_args_default(args: *mut WinptyrsArgs) -> WinptyrsStatus {
+    guard(|| {
+        let args = output(args, "args")?;
+        *args = WinptyrsArgs::new(&PTYArgs::default());
+        Ok(())
+    })
+}
+
+/// Create a PTY, see [`PTY::new_with_backend`].
+///
+/// # Arguments
+/// * `args` - Arguments of the PTY, or null to use the default ones.
+/// * `backend` - One of the `WINPTYRS_BACKEND_*` values.
+/// * `pty` - Receives the handle of the PTY, which must be released with `winptyrs_pty_free`.
+///
+/// # Safety
+/// `args` must be null or point to a valid `WinptyrsArgs`, whose `cassette` is null or a
+/// valid C string, and `pty` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_new(args: *const WinptyrsArgs, backend: u32, pty: *mut *mut WinptyrsPty) -> WinptyrsStatus {
+    guard(|| {
+        let pty = output(pty, "pty")?;
+        *pty = ptr::null_mut();
+        let args = match args.as_ref() {
+            Some(args) => args.to_pty_args()?,
+            None => PTYArgs::default(),
+        };
+        let backend = match PTYBackend::from_u32(backend) {
+            Some(backend @ (PTYBackend::ConPTY | PTYBackend::WinPTY | PTYBackend::Auto | PTYBackend::Replay)) => backend,
+            _ => return Err(Error::invalid(format!("Invalid backend {}", backend))),
+        };
+
+        let created = PTY::new_with_backend(&args, backend).map_err(Error::pty)?;
+        *pty = Box::into_raw(Box::new(WinptyrsPty { pty: created, pending: Mutex::new(Vec::new()) }));
+        Ok(())
+    })
+}
+
+/// Spawn a process inside a PTY, see [`PTY::spawn`].
+///
+/// # Arguments
+/// * `appname` - Path or name of the program to run.
+/// * `cmdline` - Arguments of the program, or null.
+/// * `cwd` - Working directory of the program, or null to use the current one.
+/// * `env` - Null-terminated array of `NAME=value` strings that make up the whole
+///   environment of the program, or null to inherit the current one.
+///
+/// # Safety
+/// `pty` must be a handle returned by `winptyrs_pty_new` that is not used concurrently, and
+/// the strings must be null or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_spawn(
+    pty: *mut WinptyrsPty,
+    appname: *const c_char,
+    cmdline: *const c_char,
+    cwd: *const c_char,
+    env: *const *const c_char,
+) -> WinptyrsStatus {
+    guard(|| {
+        let handle = pty.as_mut().ok_or_else(|| Error::null("pty"))?;
+        let appname = optional_str(appname, "appname")?.ok_or_else(|| Error::null("appname"))?;
+        let cmdline = optional_str(cmdline, "cmdline")?.map(OsString::from);
+        let cwd = optional_str(cwd, "cwd")?.map(OsString::from);
+
+        let env = match env.is_null() {
+            true => None,
+            false => {
+                let mut block = OsString::new();
+                let mut var = env;
+                while !(*var).is_null() {
+                    let value = optional_str(*var, "env")?.unwrap_or_default();
+                    if !value.contains('=') {
+                        return Err(Error::invalid(format!("Invalid environment variable {:?}", value)));
+                    }
+                    block.push(value);
+                    block.push("\0");
+                    var = var.add(1);
+                }
+                Some(block)
+            }
+        };
+
+        handle.pty.spawn(OsString::from(appname), cmdline, cwd, env).map_err(Error::pty)?;
+        Ok(())
+    })
+}
+
+/// Read the output of the process as UTF-8, waiting at most `timeout` ms for it to be
+/// available. Output that does not fit in `buf` is returned by the next reads, so a
+/// multi-byte character may be split across two reads.
+///
+/// # Returns
+/// `WINPTYRS_ERROR_EOF` once the process exited and all of its output was read. Otherwise,
+/// `read` receives the number of bytes written into `buf`, which is 0 if the timeout elapsed.
+///
+/// # Safety
+/// `pty` must be a valid handle, `buf` must be writable for `len` bytes and `read` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_read(pty: *const WinptyrsPty, buf: *mut u8, len: usize, timeout: u32, read: *mut usize) -> WinptyrsStatus {
+    guard(|| {
+        let handle = handle(pty)?;
+        let read = output(read, "read")?;
+        *read = 0;
+        if buf.is_null() && len > 0 {
+            return Err(Error::null("buf"));
+        }
+
+        let mut pending = handle.pending.lock().unwrap();
+        if pending.is_empty() {
+            match handle.pty.read_timeout(Duration::from_millis(timeout as u64)) {
+                Ok(data) => pending.extend_from_slice(data.to_string_lossy().as_bytes()),
+                Err(_) if handle.pty.is_eof().unwrap_or(false) => {
+                    return Err(Error::new(WINPTYRS_ERROR_EOF, String::from("The process exited")))
+                }
+                Err(err) => return Err(Error::pty(err)),
+            }
+        }
+
+        let count = pending.len().min(len);
+        if count > 0 {
+            ptr::copy_nonoverlapping(pending.as_ptr(), buf, count);
+        }
+        pending.drain(..count);
+        *read = count;
+        Ok(())
+    })
+}
+
+/// Write `len` bytes of UTF-8 `data` into the standard input of the process, see [`PTY::write`].
+///
+/// # Arguments
+/// * `written` - Receives the number of characters written, or null.
+///
+/// # Safety
+/// `pty` must be a valid handle, `data` must be readable for `len` bytes and `written`
+/// must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_write(pty: *const WinptyrsPty, data: *const u8, len: usize, written: *mut u32) -> WinptyrsStatus {
+    guard(|| {
+        let handle = handle(pty)?;
+        let data = match len {
+            0 => &[][..],
+            _ if data.is_null() => return Err(Error::null("data")),
+            _ => std::slice::from_raw_parts(data, len),
+        };
+        let data = std::str::from_utf8(data)
+            .map_err(|_| Error::new(WINPTYRS_ERROR_INVALID_UTF8, String::from("data is not valid UTF-8")))?;
+        let count = handle.pty.write(OsString::from(data)).map_err(Error::pty)?;
+        if let Some(written) = written.as_mut() {
+            *written = count;
+        }
+        Ok(())
+    })
+}
+
+/// Change the size of the PTY, see [`PTY::set_size`].
+///
+/// # Safety
+/// `pty` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_resize(pty: *const WinptyrsPty, cols: i32, rows: i32) -> WinptyrsStatus {
+    guard(|| {
+        let handle = handle(pty)?;
+        if cols <= 0 || rows <= 0 {
+            return Err(Error::invalid(format!("Invalid size {}x{}", cols, rows)));
+        }
+        handle.pty.set_size(cols, rows).map_err(Error::pty)
+    })
+}
+
+/// Retrieve the process ID of the spawned process, see [`PTY::get_pid`].
+///
+/// # Safety
+/// `pty` must be a valid handle, and `pid` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_pid(pty: *const WinptyrsPty, pid: *mut u32) -> WinptyrsStatus {
+    guard(|| {
+        let handle = handle(pty)?;
+        *output(pid, "pid")? = handle.pty.get_pid();
+        Ok(())
+    })
+}
+
+/// Retrieve the exit status of the process, see [`PTY::get_exitstatus`].
+///
+/// # Arguments
+/// * `exited` - Receives whether the process exited.
+/// * `code` - Receives the exit code of the process, if it exited. May be null.
+///
+/// # Safety
+/// `pty` must be a valid handle, `exited` must be writable and `code` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_exit_status(pty: *const WinptyrsPty, exited: *mut bool, code: *mut u32) -> WinptyrsStatus {
+    guard(|| {
+        let handle = handle(pty)?;
+        let exited = output(exited, "exited")?;
+        let status = handle.pty.get_exitstatus().map_err(Error::pty)?;
+        *exited = status.is_some();
+        if let (Some(code), Some(status)) = (code.as_mut(), status) {
+            *code = status;
+        }
+        Ok(())
+    })
+}
+
+/// Release a PTY, which applies its shutdown policy to the process. Null handles are ignored.
+///
+/// # Safety
+/// `pty` must be null or a handle returned by `winptyrs_pty_new` that was not freed yet,
+/// and that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn winptyrs_pty_free(pty: *mut WinptyrsPty) {
+    if !pty.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(pty))));
+    }
+}
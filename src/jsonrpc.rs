@@ -0,0 +1,12 @@
+//! Control of PTY sessions from other processes through JSON-RPC 2.0.
+//!
+//! A [`JsonRpcHost`] reads requests framed with `Content-Length` headers, as done by the
+//! Language Server Protocol, and answers them with responses and notifications framed in
+//! the same way. `winpty-rs host --stdio` serves it over its standard input and output.
+
+mod framing;
+mod host;
+
+pub use serde_json::Value;
+pub use framing::{read_message, write_message, MAX_CONTENT_LENGTH};
+pub use host::{JsonRpcHost, PARSE_ERROR, INVALID_REQUEST, METHOD_NOT_FOUND, INVALID_PARAMS, PTY_ERROR};
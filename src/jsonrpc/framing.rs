@@ -0,0 +1,64 @@
+//! Framing of JSON-RPC messages with `Content-Length` headers, as done by the Language
+//! Server Protocol.
+
+use std::ffi::OsString;
+use std::io::{BufRead, Write};
+
+/// Maximum length of the content of a message.
+pub const MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+fn io_error(err: std::io::Error) -> OsString {
+    OsString::from(err.to_string())
+}
+
+/// Read the content of the next message from `reader`.
+///
+/// # Returns
+/// `None` if `reader` reached EOF before the start of a message.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<String>, OsString> {
+    let mut length = None;
+    let mut started = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 {
+            return match started {
+                true => Err(OsString::from("Unexpected EOF inside the headers of a message")),
+                false => Ok(None),
+            };
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            // Tolerate blank lines between messages.
+            match started {
+                true => break,
+                false => continue,
+            }
+        }
+        started = true;
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| OsString::from(format!("Invalid header {:?}", line)))?;
+        if name.trim().eq_ignore_ascii_case("Content-Length") {
+            let value: usize = value
+                .trim()
+                .parse()
+                .map_err(|_| OsString::from(format!("Invalid Content-Length {:?}", value.trim())))?;
+            if value > MAX_CONTENT_LENGTH {
+                return Err(OsString::from(format!("Message of {} bytes exceeds the maximum length", value)));
+            }
+            length = Some(value);
+        }
+    }
+
+    let length = length.ok_or_else(|| OsString::from("Missing Content-Length header"))?;
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content).map_err(io_error)?;
+    String::from_utf8(content).map(Some).map_err(|_| OsString::from("The content of a message is not valid UTF-8"))
+}
+
+/// Write a message with the given `content` into `writer`, and flush it.
+pub fn write_message<W: Write + ?Sized>(writer: &mut W, content: &str) -> Result<(), OsString> {
+    write!(writer, "Content-Length: {}\r\n\r\n{}", content.len(), content).map_err(io_error)?;
+    writer.flush().map_err(io_error)
+}
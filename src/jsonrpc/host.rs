@@ -0,0 +1,434 @@
+//! JSON-RPC 2.0 host of PTY sessions.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use serde_json::{json, Map, Value};
+
+use crate::jsonrpc::framing::{read_message, write_message};
+use crate::manager::{PtyEvent, PtyManager, SessionId};
+use crate::pty::{PTYArgs, PTYBackend, Signal};
+use crate::shells::QuotingStyle;
+
+/// Maximum amount of time that the dispatcher waits for an event before checking if the host was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Error reported by the PTY of a session, or by a session that does not exist.
+pub const PTY_ERROR: i64 = -32000;
+
+/// Encoding of the `data` of the `write` requests and of the `output` notifications.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Base64,
+}
+
+impl Encoding {
+    fn parse(value: Option<&Value>) -> Result<Encoding, RpcError> {
+        match value.map(|value| value.as_str()) {
+            None | Some(Some("utf8")) | Some(Some("utf-8")) => Ok(Encoding::Utf8),
+            Some(Some("base64")) => Ok(Encoding::Base64),
+            _ => Err(RpcError::invalid_params("encoding must be \"utf8\" or \"base64\"")),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf8",
+            Encoding::Base64 => "base64",
+        }
+    }
+
+    fn encode(&self, data: &OsString) -> String {
+        let data = data.to_string_lossy();
+        match self {
+            Encoding::Utf8 => data.into_owned(),
+            Encoding::Base64 => BASE64.encode(data.as_bytes()),
+        }
+    }
+
+    fn decode(&self, data: &str) -> Result<OsString, RpcError> {
+        match self {
+            Encoding::Utf8 => Ok(OsString::from(data)),
+            Encoding::Base64 => {
+                let bytes = BASE64.decode(data).map_err(|err| RpcError::invalid_params(&err.to_string()))?;
+                String::from_utf8(bytes)
+                    .map(OsString::from)
+                    .map_err(|_| RpcError::invalid_params("data is not valid UTF-8"))
+            }
+        }
+    }
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> RpcError {
+        RpcError { code, message: message.to_owned() }
+    }
+
+    fn invalid_params(message: &str) -> RpcError {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    fn pty(err: OsString) -> RpcError {
+        RpcError::new(PTY_ERROR, &err.to_string_lossy())
+    }
+
+    fn into_response(self, id: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "error": {"code": self.code, "message": self.message}})
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+/// Named parameter of a request. Null values are treated as missing.
+fn param<'a>(params: &'a Value, name: &str) -> Option<&'a Value> {
+    params.get(name).filter(|value| !value.is_null())
+}
+
+fn required<'a>(params: &'a Value, name: &str) -> Result<&'a Value, RpcError> {
+    param(params, name).ok_or_else(|| RpcError::invalid_params(&format!("Missing parameter {}", name)))
+}
+
+fn string_param(params: &Value, name: &str) -> Result<Option<String>, RpcError> {
+    param(params, name)
+        .map(|value| value.as_str().map(str::to_owned).ok_or_else(|| RpcError::invalid_params(&format!("{} must be a string", name))))
+        .transpose()
+}
+
+fn size_param(params: &Value, name: &str) -> Result<Option<i32>, RpcError> {
+    param(params, name)
+        .map(|value| {
+            value
+                .as_i64()
+                .filter(|value| (1..=i16::MAX as i64).contains(value))
+                .map(|value| value as i32)
+                .ok_or_else(|| RpcError::invalid_params(&format!("{} must be a positive integer", name)))
+        })
+        .transpose()
+}
+
+fn session_param(params: &Value) -> Result<SessionId, RpcError> {
+    required(params, "id")?.as_u64().ok_or_else(|| RpcError::invalid_params("id must be a session id"))
+}
+
+/// Command line built from the `args` of a `spawn` request, which are either given as a
+/// single string or as an array of arguments that get quoted.
+fn command_line(args: Option<&Value>) -> Result<Option<OsString>, RpcError> {
+    let invalid = || RpcError::invalid_params("args must be a string or an array of strings");
+    match args {
+        None => Ok(None),
+        Some(Value::String(args)) => Ok(Some(OsString::from(args))),
+        Some(Value::Array(args)) => {
+            let args = args
+                .iter()
+                .map(|arg| arg.as_str().map(|arg| QuotingStyle::Msvcrt.quote(arg)).ok_or_else(invalid))
+                .collect::<Result<Vec<String>, RpcError>>()?;
+            Ok(Some(OsString::from(args.join(" "))))
+        }
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Environment block of a `spawn` request: the variables of the host, updated with the
+/// given ones. Variables set to null are removed.
+fn environment(env: Option<&Value>) -> Result<Option<OsString>, RpcError> {
+    let env = match env {
+        None => return Ok(None),
+        Some(env) => env.as_object().ok_or_else(|| RpcError::invalid_params("env must be an object"))?,
+    };
+
+    let mut vars: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+    for (name, value) in env {
+        if name.is_empty() || name.contains(['=', '\0']) {
+            return Err(RpcError::invalid_params(&format!("Invalid environment variable name {:?}", name)));
+        }
+        // The names of the environment variables are case insensitive in Windows.
+        vars.retain(|(var, _)| !var.to_string_lossy().eq_ignore_ascii_case(name));
+        match value {
+            Value::Null => (),
+            Value::String(value) if !value.contains('\0') => vars.push((OsString::from(name), OsString::from(value))),
+            _ => return Err(RpcError::invalid_params(&format!("The value of {} must be a string or null", name))),
+        }
+    }
+
+    let mut block = OsString::new();
+    for (name, value) in vars {
+        block.push(name);
+        block.push("=");
+        block.push(value);
+        block.push("\0");
+    }
+    Ok(Some(block))
+}
+
+struct Shared<W> {
+    manager: PtyManager,
+    output: Mutex<W>,
+    encodings: Mutex<HashMap<SessionId, Encoding>>,
+}
+
+impl<W: Write> Shared<W> {
+    fn send(output: &mut W, message: &Value) -> Result<(), OsString> {
+        write_message(output, &message.to_string())
+    }
+
+    fn encoding(&self, id: SessionId) -> Encoding {
+        self.encodings.lock().unwrap().get(&id).copied().unwrap_or(Encoding::Utf8)
+    }
+
+    fn output(&self, output: &mut W, id: SessionId, data: &OsString) {
+        let encoding = self.encoding(id);
+        let params = json!({"id": id, "data": encoding.encode(data), "encoding": encoding.name()});
+        let _ = Self::send(output, &notification("output", params));
+    }
+
+    /// Forward an event of the manager to the client.
+    fn dispatch(&self, event: PtyEvent) {
+        match event {
+            PtyEvent::Output(id, data) => self.output(&mut self.output.lock().unwrap(), id, &data),
+            PtyEvent::TitleChanged(id, title) => {
+                let params = json!({"id": id, "title": title});
+                let _ = Self::send(&mut self.output.lock().unwrap(), &notification("title", params));
+            }
+            PtyEvent::Exit(id, code) => {
+                // Sessions are closed as soon as their process exits, which delivers the output
+                // that the pump did not read yet.
+                let rest = self.manager.close(id).ok().flatten().map(|status| status.output);
+                let mut output = self.output.lock().unwrap();
+                if let Some(rest) = rest.filter(|rest| !rest.is_empty()) {
+                    self.output(&mut output, id, &rest);
+                }
+                self.encodings.lock().unwrap().remove(&id);
+                let params = json!({"id": id, "exitCode": code});
+                let _ = Self::send(&mut output, &notification("exit", params));
+            }
+        }
+    }
+
+    fn spawn(&self, params: &Value) -> Result<Value, RpcError> {
+        let command = string_param(params, "command")?.ok_or_else(|| RpcError::invalid_params("Missing parameter command"))?;
+        let cmdline = command_line(param(params, "args"))?;
+        let cwd = string_param(params, "cwd")?.map(OsString::from);
+        let env = environment(param(params, "env"))?;
+        let encoding = Encoding::parse(param(params, "encoding"))?;
+        let backend = match string_param(params, "backend")?.as_deref() {
+            None | Some("auto") => PTYBackend::Auto,
+            Some("conpty") => PTYBackend::ConPTY,
+            Some("winpty") => PTYBackend::WinPTY,
+            Some(_) => return Err(RpcError::invalid_params("backend must be \"conpty\", \"winpty\" or \"auto\"")),
+        };
+
+        let mut args = PTYArgs::default();
+        args.cols = size_param(params, "cols")?.unwrap_or(args.cols);
+        args.rows = size_param(params, "rows")?.unwrap_or(args.rows);
+
+        let id = self.manager.create(&args, backend).map_err(RpcError::pty)?;
+        self.encodings.lock().unwrap().insert(id, encoding);
+        let pid = self
+            .manager
+            .spawn(id, OsString::from(command), cmdline, cwd, env)
+            .and_then(|_| self.manager.with_session(id, |pty| Ok(pty.get_pid())));
+        match pid {
+            Ok(pid) => Ok(json!({"id": id, "pid": pid})),
+            Err(err) => {
+                self.encodings.lock().unwrap().remove(&id);
+                let _ = self.manager.close(id);
+                Err(RpcError::pty(err))
+            }
+        }
+    }
+
+    fn write(&self, params: &Value) -> Result<Value, RpcError> {
+        let id = session_param(params)?;
+        let data = required(params, "data")?.as_str().ok_or_else(|| RpcError::invalid_params("data must be a string"))?;
+        let data = Encoding::parse(param(params, "encoding"))?.decode(data)?;
+        let written = self.manager.write(id, data).map_err(RpcError::pty)?;
+        Ok(json!({"written": written}))
+    }
+
+    fn resize(&self, params: &Value) -> Result<Value, RpcError> {
+        let id = session_param(params)?;
+        let cols = size_param(params, "cols")?.ok_or_else(|| RpcError::invalid_params("Missing parameter cols"))?;
+        let rows = size_param(params, "rows")?.ok_or_else(|| RpcError::invalid_params("Missing parameter rows"))?;
+        self.manager.resize(id, cols, rows).map_err(RpcError::pty)?;
+        Ok(Value::Null)
+    }
+
+    fn signal(&self, params: &Value) -> Result<Value, RpcError> {
+        let id = session_param(params)?;
+        let signal = match required(params, "signal")?.as_str() {
+            Some("interrupt") => Signal::Interrupt,
+            Some("break") => Signal::Break,
+            Some("terminate") => Signal::Terminate,
+            Some("kill") => Signal::Kill,
+            _ => return Err(RpcError::invalid_params("signal must be \"interrupt\", \"break\", \"terminate\" or \"kill\"")),
+        };
+        self.manager.with_session(id, |pty| pty.signal(signal)).map_err(RpcError::pty)?;
+        Ok(Value::Null)
+    }
+
+    fn kill(&self, params: &Value) -> Result<Value, RpcError> {
+        let id = session_param(params)?;
+        self.manager.with_session(id, |pty| pty.kill()).map_err(RpcError::pty)?;
+        Ok(Value::Null)
+    }
+
+    /// Handle a single request, and build its response, if any.
+    fn handle(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let response_id = match &id {
+            Some(id @ (Value::Null | Value::Number(_) | Value::String(_))) => id.clone(),
+            _ => Value::Null,
+        };
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) if request.get("jsonrpc").and_then(Value::as_str) == Some("2.0") => method,
+            _ => return Some(RpcError::new(INVALID_REQUEST, "Invalid request").into_response(response_id)),
+        };
+        if matches!(id, Some(Value::Bool(_) | Value::Array(_) | Value::Object(_))) {
+            return Some(RpcError::new(INVALID_REQUEST, "Invalid request id").into_response(Value::Null));
+        }
+
+        let params = request.get("params").cloned().unwrap_or_else(|| Value::Object(Map::new()));
+        let result = match params {
+            Value::Object(_) => match method {
+                "spawn" => self.spawn(&params),
+                "write" => self.write(&params),
+                "resize" => self.resize(&params),
+                "signal" => self.signal(&params),
+                "kill" => self.kill(&params),
+                _ => Err(RpcError::new(METHOD_NOT_FOUND, &format!("Unknown method {}", method))),
+            },
+            _ => Err(RpcError::invalid_params("params must be an object")),
+        };
+
+        // Notifications never get a response, even if they fail.
+        id.as_ref()?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": response_id, "result": result}),
+            Err(err) => err.into_response(response_id),
+        })
+    }
+
+    /// Handle the content of a message, and build its response, if any.
+    fn handle_message(&self, content: &str) -> Option<Value> {
+        match serde_json::from_str(content) {
+            Err(err) => Some(RpcError::new(PARSE_ERROR, &err.to_string()).into_response(Value::Null)),
+            Ok(Value::Array(requests)) if requests.is_empty() => {
+                Some(RpcError::new(INVALID_REQUEST, "Empty batch").into_response(Value::Null))
+            }
+            Ok(Value::Array(requests)) => {
+                let responses: Vec<Value> = requests.iter().filter_map(|request| self.handle(request)).collect();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            Ok(request) => self.handle(&request),
+        }
+    }
+}
+
+fn dispatch<W: Write>(shared: Arc<Shared<W>>, events: Receiver<PtyEvent>, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::SeqCst) {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => shared.dispatch(event),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Host of PTY sessions driven through JSON-RPC 2.0 messages, framed with `Content-Length`
+/// headers as done by the Language Server Protocol.
+///
+/// # Methods
+/// * `spawn` - Spawn a process in a new session. Takes the `command` to run, and optionally
+///   its `args` (a command line or an array of arguments), `cwd`, `env` (variables that
+///   update the ones of the host, removed if null), `cols`, `rows`, `backend` (`"conpty"`,
+///   `"winpty"` or `"auto"`) and the `encoding` of its output (`"utf8"` or `"base64"`).
+///   Returns the `id` of the session and the `pid` of the process.
+/// * `write` - Write `data`, encoded as given by `encoding`, into the session `id`.
+///   Returns the number of characters `written`.
+/// * `resize` - Change the size of the session `id` to `cols` and `rows`.
+/// * `signal` - Send a `signal` (`"interrupt"`, `"break"`, `"terminate"` or `"kill"`)
+///   to the process of the session `id`.
+/// * `kill` - Kill the process of the session `id`.
+///
+/// # Notifications
+/// * `output` - Output `data` of the session `id`, in the `encoding` chosen on spawn.
+/// * `title` - The session `id` changed its `title`.
+/// * `exit` - The process of the session `id` exited with `exitCode`, after which the
+///   session is closed.
+///
+/// Every notification of a session is sent after the response to its `spawn` request.
+pub struct JsonRpcHost<W: Write + Send + 'static> {
+    shared: Arc<Shared<W>>,
+    stop: Arc<AtomicBool>,
+    dispatcher: Option<JoinHandle<()>>,
+}
+
+impl<W: Write + Send + 'static> JsonRpcHost<W> {
+    /// Create a host that sends its responses and notifications through `output`.
+    pub fn new(output: W) -> JsonRpcHost<W> {
+        let manager = PtyManager::new();
+        let events = manager.events();
+        let shared = Arc::new(Shared {
+            manager,
+            output: Mutex::new(output),
+            encodings: Mutex::new(HashMap::new()),
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let dispatcher = {
+            let shared = shared.clone();
+            let stop = stop.clone();
+            thread::spawn(move || dispatch(shared, events, stop))
+        };
+        JsonRpcHost { shared, stop, dispatcher: Some(dispatcher) }
+    }
+
+    /// Handle the requests read from `input` until it reaches EOF.
+    ///
+    /// # Returns
+    /// An error if `input` does not contain valid messages, or if the responses cannot be sent.
+    pub fn serve<R: BufRead>(&self, mut input: R) -> Result<(), OsString> {
+        while let Some(content) = read_message(&mut input)? {
+            // The output stays locked while the request is handled, so that the notifications
+            // of a new session cannot precede the response to its spawn request.
+            let mut output = self.shared.output.lock().unwrap();
+            if let Some(response) = self.shared.handle_message(&content) {
+                Shared::<W>::send(&mut output, &response)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send + 'static> Drop for JsonRpcHost<W> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(dispatcher) = self.dispatcher.take() {
+            let _ = dispatcher.join();
+        }
+        for id in self.shared.manager.ids() {
+            let _ = self.shared.manager.with_session(id, |pty| pty.kill());
+            let _ = self.shared.manager.close(id);
+        }
+    }
+}
@@ -15,14 +15,40 @@ extern crate enum_primitive_derive;
 extern crate num_traits;
 
 pub mod pty;
+pub mod vt;
+pub mod osc;
+pub mod shell_integration;
+pub mod session;
+pub mod shells;
+pub mod resolver;
+pub mod manager;
+pub mod remote;
+pub mod jsonrpc;
+pub mod ffi;
+pub mod script;
+pub mod batch;
+pub mod triggers;
+pub mod export;
 // mod pty_spawn;
-pub use pty::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig};
+pub use pty::{
+    PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig, Signal, ShutdownPolicy, ExitStatus,
+    ResourceUsage, ProcessInfo, Cassette, CassetteEntry, CassetteEvent
+};
+pub use osc::OscEvent;
+pub use shell_integration::{CommandRecord, ShellKind};
+pub use session::{ShellSession, CommandOutput, CancelHandle};
+pub use shells::{ShellProfile, SpawnRequest, LaunchMode, QuotingStyle, discover_shells, default_shell};
+pub use manager::{PtyManager, PtyEvent, SessionId};
+pub use remote::{PtyServer, RemotePTY, SessionInfo};
+pub use jsonrpc::JsonRpcHost;
+pub use script::{Script, ScriptError};
+pub use batch::{BatchCommand, BatchOptions, BatchProgress, BatchReport, BatchResult, BatchRunner, BatchStatus};
+pub use triggers::{Trigger, TriggerId, TriggerMatch};
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::time::Instant;
-    use std::thread::sleep;
     use std::time::Duration;
     use std::ffi::OsString;
 
@@ -40,7 +66,7 @@ mod tests {
         pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
 
         // Wait for process to start
-        sleep(Duration::from_millis(1000));
+        pty.wait_idle(Duration::from_millis(200), Duration::from_secs(5)).unwrap();
 
         // Test data
         let test_data = OsString::from("echo test\r\n");
@@ -77,13 +103,13 @@ mod tests {
         pty.spawn(cmd, Some("/c echo test".into()), None, None).unwrap();
         pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
 
-        // Wait for process to start
-        sleep(Duration::from_millis(1000));
+        // Wait for the process to produce its output
+        let output = pty.wait_idle(Duration::from_millis(200), Duration::from_secs(5)).unwrap();
 
         // Test parameters
         let iterations = 100;
         let mut total_time = Duration::from_secs(0);
-        let mut total_bytes = 0;
+        let mut total_bytes = output.len();
         let mut successful_reads = 0;
 
         // Perform read performance test
@@ -140,11 +166,11 @@ mod tests {
 
         pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
 
-        // Wait for process to start
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        // Wait for the process to produce its output
+        let output = pty.wait_idle(Duration::from_millis(100), Duration::from_secs(5)).unwrap();
 
         let start = Instant::now();
-        let mut total_bytes = 0;
+        let mut total_bytes = output.len();
         let mut read_count = 0;
         let mut empty_reads = 0;
 
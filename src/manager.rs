@@ -0,0 +1,237 @@
+//! Management of many PTY sessions through a single event stream.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::osc::OscEvent;
+use crate::pty::{ExitStatus, PTYArgs, PTYBackend, PTY};
+
+/// Identifier of a session owned by a [`PtyManager`].
+pub type SessionId = u64;
+
+/// Maximum amount of time that a session waits for output before checking if it was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Event produced by one of the sessions of a [`PtyManager`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PtyEvent {
+    /// Output produced by the process of a session.
+    Output(SessionId, OsString),
+    /// The process of a session exited, with the given exit code (if available).
+    Exit(SessionId, Option<u32>),
+    /// The process of a session changed the window title.
+    TitleChanged(SessionId, String),
+}
+
+struct Session {
+    /// PTY of the session, taken once the session is closed.
+    pty: Option<Arc<PTY>>,
+    stop: Arc<AtomicBool>,
+    pump: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(pump) = self.pump.take() {
+            let _ = pump.join();
+        }
+    }
+}
+
+/// Session shared between the map of sessions and the calls that use it. Every session has
+/// its own lock, so that a slow operation (e.g., spawning a process) only blocks the calls
+/// made on the same session.
+type SharedSession = Arc<Mutex<Session>>;
+
+/// Owner of many PTY sessions, identified by a [`SessionId`].
+///
+/// The output, exit and title changes of every session are delivered through a single
+/// channel, see [`PtyManager::events`].
+pub struct PtyManager {
+    sessions: Mutex<HashMap<SessionId, SharedSession>>,
+    next_id: AtomicU64,
+    events_tx: Sender<PtyEvent>,
+    events_rx: Receiver<PtyEvent>,
+}
+
+impl Default for PtyManager {
+    fn default() -> Self {
+        PtyManager::new()
+    }
+}
+
+impl PtyManager {
+    /// Create a manager without sessions.
+    pub fn new() -> PtyManager {
+        let (events_tx, events_rx) = unbounded();
+        PtyManager {
+            sessions: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            events_tx,
+            events_rx,
+        }
+    }
+
+    /// Retrieve the receiver of the events produced by all the sessions.
+    ///
+    /// # Notes
+    /// Every clone of the receiver competes for the same events, i.e., each event is
+    /// delivered to a single receiver.
+    pub fn events(&self) -> Receiver<PtyEvent> {
+        self.events_rx.clone()
+    }
+
+    /// Retrieve the identifiers of the open sessions.
+    pub fn ids(&self) -> Vec<SessionId> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut ids: Vec<SessionId> = sessions.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Create a new session, without spawning any process on it.
+    ///
+    /// # Arguments
+    /// * `args` - Arguments used to create the PTY.
+    /// * `backend` - Backend used to create the PTY, see [`PTY::new_with_backend`].
+    pub fn create(&self, args: &PTYArgs, backend: PTYBackend) -> Result<SessionId, OsString> {
+        let pty = PTY::new_with_backend(args, backend)?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let session = Session {
+            pty: Some(Arc::new(pty)),
+            stop: Arc::new(AtomicBool::new(false)),
+            pump: None,
+        };
+        self.sessions.lock().unwrap().insert(id, Arc::new(Mutex::new(session)));
+        Ok(id)
+    }
+
+    fn session(&self, id: SessionId) -> Result<SharedSession, OsString> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&id).cloned().ok_or_else(|| unknown_session(id))
+    }
+
+    /// Spawn a process in a session, see [`PTY::spawn`]. Its output starts to be delivered
+    /// through [`PtyManager::events`] right away.
+    pub fn spawn(
+        &self,
+        id: SessionId,
+        appname: OsString,
+        cmdline: Option<OsString>,
+        cwd: Option<OsString>,
+        env: Option<OsString>,
+    ) -> Result<bool, OsString> {
+        // Only this session is locked while the process spawns.
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        let Session { pty, stop, pump: session_pump } = &mut *session;
+        let pty = pty.as_mut().ok_or_else(|| unknown_session(id))?;
+        match Arc::get_mut(pty) {
+            Some(pty) if session_pump.is_none() => pty.spawn(appname, cmdline, cwd, env)?,
+            _ => return Err(OsString::from(format!("A process was already spawned in session {}", id))),
+        };
+
+        let pty = pty.clone();
+        let stop = stop.clone();
+        let events_tx = self.events_tx.clone();
+        *session_pump = Some(thread::spawn(move || pump(id, pty, stop, events_tx)));
+        Ok(true)
+    }
+
+    /// Write into the standard input of the process of a session, see [`PTY::write`].
+    pub fn write(&self, id: SessionId, buf: OsString) -> Result<u32, OsString> {
+        self.with_session(id, |pty| pty.write(buf))
+    }
+
+    /// Change the size of a session, see [`PTY::set_size`].
+    pub fn resize(&self, id: SessionId, cols: i32, rows: i32) -> Result<(), OsString> {
+        self.with_session(id, |pty| pty.set_size(cols, rows))
+    }
+
+    /// Run a function over the PTY of a session.
+    pub fn with_session<T, F: FnOnce(&PTY) -> Result<T, OsString>>(&self, id: SessionId, f: F) -> Result<T, OsString> {
+        let pty = self.session(id)?.lock().unwrap().pty.clone().ok_or_else(|| unknown_session(id))?;
+        f(&pty)
+    }
+
+    /// Close a session, see [`PTY::close`]. The output that was not delivered yet
+    /// is returned as part of the [`ExitStatus`].
+    ///
+    /// # Returns
+    /// An error if the session does not exist, or if it is still in use by a call to
+    /// [`PtyManager::with_session`], in which case the session is left open.
+    pub fn close(&self, id: SessionId) -> Result<Option<ExitStatus>, OsString> {
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        // The PTY is only shared with the pump, and with the calls to `with_session`, which
+        // clone it while the session is locked.
+        let users = 1 + session.pump.is_some() as usize;
+        match &session.pty {
+            None => return Err(unknown_session(id)),
+            Some(pty) if Arc::strong_count(pty) > users => {
+                return Err(OsString::from(format!("Session {} is still in use", id)))
+            }
+            Some(_) => (),
+        }
+
+        self.sessions.lock().unwrap().remove(&id);
+        session.stop();
+        match session.pty.take().map(Arc::try_unwrap) {
+            Some(Ok(pty)) => pty.close(),
+            _ => Err(OsString::from(format!("Session {} is still in use", id))),
+        }
+    }
+}
+
+impl Drop for PtyManager {
+    fn drop(&mut self) {
+        let sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        for session in sessions.values() {
+            session.lock().unwrap_or_else(|err| err.into_inner()).stop();
+        }
+    }
+}
+
+fn unknown_session(id: SessionId) -> OsString {
+    OsString::from(format!("Session {} does not exist", id))
+}
+
+/// Deliver the output, title changes and exit of a session until it is closed.
+fn pump(id: SessionId, pty: Arc<PTY>, stop: Arc<AtomicBool>, events_tx: Sender<PtyEvent>) {
+    let osc_events = pty.osc_events();
+    while !stop.load(Ordering::SeqCst) {
+        let result = pty.read_timeout(POLL_INTERVAL);
+        if let Ok(output) = &result {
+            if !output.is_empty() {
+                let _ = events_tx.send(PtyEvent::Output(id, output.clone()));
+            }
+        }
+
+        for event in osc_events.try_iter() {
+            if let OscEvent::TitleChanged(title) = event {
+                let _ = events_tx.send(PtyEvent::TitleChanged(id, title));
+            }
+        }
+
+        if result.is_err() {
+            // The output reached EOF, the process exits unless it only closed its output,
+            // hence the wait is interrupted once the session is closed.
+            while pty.is_alive().unwrap_or(false) {
+                if stop.load(Ordering::SeqCst) {
+                    return;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            let code = pty.get_exitstatus().ok().flatten();
+            let _ = events_tx.send(PtyEvent::Exit(id, code));
+            break;
+        }
+    }
+}
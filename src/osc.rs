@@ -0,0 +1,151 @@
+//! Tracking of the metadata that programs report through OSC sequences.
+//!
+//! The following sequences are recognized:
+//! * `OSC 0 ; <title>` and `OSC 2 ; <title>` - Set the window title.
+//! * `OSC 7 ; file://<host>/<path>` - Report the current working directory.
+//! * `OSC 9 ; 9 ; <path>` - Report the current working directory (ConEmu).
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::vt::{Token, Tokenizer};
+
+/// Change reported by an [`OscTracker`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OscEvent {
+    /// The window title was changed.
+    TitleChanged(String),
+    /// The current working directory was changed.
+    CwdChanged(String),
+}
+
+/// Tracks the window title and the current working directory reported by a program.
+#[derive(Debug)]
+pub struct OscTracker {
+    tokenizer: Tokenizer,
+    title: Option<String>,
+    cwd: Option<String>,
+    subscribers: Vec<Sender<OscEvent>>,
+}
+
+impl Default for OscTracker {
+    fn default() -> Self {
+        OscTracker::new(crate::vt::DEFAULT_MAX_OSC_PAYLOAD)
+    }
+}
+
+impl OscTracker {
+    /// Create a new tracker.
+    ///
+    /// # Arguments
+    /// * `max_osc_payload` - Maximum length (in characters) of the payload of an OSC
+    ///   sequence. Longer sequences are ignored.
+    pub fn new(max_osc_payload: usize) -> OscTracker {
+        OscTracker {
+            tokenizer: Tokenizer::new(max_osc_payload),
+            title: None,
+            cwd: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Last window title set by the program, if any.
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    /// Last working directory reported by the program, if any.
+    pub fn cwd(&self) -> Option<String> {
+        self.cwd.clone()
+    }
+
+    /// Create a new channel that receives every subsequent [`OscEvent`].
+    pub fn subscribe(&mut self) -> Receiver<OscEvent> {
+        let (tx, rx) = unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Process a chunk of the program output.
+    pub fn feed(&mut self, output: &str) {
+        for token in self.tokenizer.feed(output) {
+            if let Token::Osc(payload) = token {
+                self.process(&payload);
+            }
+        }
+    }
+
+    fn process(&mut self, payload: &str) {
+        let (code, value) = match payload.split_once(';') {
+            Some(parts) => parts,
+            None => return,
+        };
+
+        let event = match code {
+            "0" | "2" => {
+                if self.title.as_deref() == Some(value) {
+                    return;
+                }
+                self.title = Some(value.to_owned());
+                OscEvent::TitleChanged(value.to_owned())
+            },
+            "7" | "9" => {
+                let cwd = match code {
+                    "7" => parse_file_url(value),
+                    _ => value.strip_prefix("9;").map(unquote),
+                };
+                match cwd {
+                    Some(cwd) if self.cwd.as_ref() != Some(&cwd) => {
+                        self.cwd = Some(cwd.clone());
+                        OscEvent::CwdChanged(cwd)
+                    },
+                    _ => return,
+                }
+            },
+            _ => return,
+        };
+
+        // Subscribers whose receiver was dropped are removed.
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// Remove the surrounding quotes of a path, if present.
+fn unquote(path: &str) -> String {
+    let path = path.trim();
+    path.strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path)
+        .to_owned()
+}
+
+/// Convert a `file://<host>/<path>` URL into a path, e.g., `file://host/C:/Users`
+/// into `C:\Users`.
+fn parse_file_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let path = percent_decode(path)?;
+
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        Some(path[1..].replace('/', "\\"))
+    } else {
+        Some(path)
+    }
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -12,15 +12,35 @@
 mod winpty;
 mod conpty;
 mod base;
+mod job;
+mod process_info;
+mod reactor;
+mod cassette;
+mod replay;
 
 use std::ffi::OsString;
 use std::default::Default;
+use std::time::Duration;
+use std::sync::Mutex;
+use crossbeam_channel::Receiver;
 
 // Local imports
 use self::winpty::WinPTY;
 pub use self::winpty::{MouseMode, AgentConfig};
 use self::conpty::ConPTY;
-pub use base::{PTYImpl, PTYProcess};
+use self::replay::Replay;
+use self::cassette::Recorder;
+pub use self::cassette::{Cassette, CassetteEntry, CassetteEvent, CASSETTE_VERSION};
+pub use base::{
+	PTYImpl, PTYProcess, Signal, ShutdownPolicy, ExitStatus, ResourceUsage, KILL_GRACE_PERIOD
+};
+pub use job::JobObject;
+pub use process_info::ProcessInfo;
+use crate::osc::{OscEvent, OscTracker};
+use crate::shell_integration::{CommandRecord, ShellIntegration, ShellKind};
+use crate::shells::SpawnRequest;
+use crate::triggers::{Trigger, TriggerId, Triggers};
+use crate::vt::DEFAULT_MAX_OSC_PAYLOAD;
 
 /// Available backends to create pseudoterminals.
 #[derive(Primitive)]
@@ -34,10 +54,23 @@ pub enum PTYBackend {
 	Auto = 2,
 	/// Placeholder value used to declare that a PTY was created with no backend.
 	NoBackend = 3,
+	/// Serve back a session recorded into the cassette given in [`PTYArgs::cassette`].
+	Replay = 4,
 }
 
 /// Data struct that represents the possible arguments used to create a pseudoterminal
+///
+/// The struct is non-exhaustive, so that new arguments can be added without breaking compatibility.
+/// Create it with [`PTYArgs::new`] or [`PTYArgs::default`], then chain the setters or assign the fields:
+///
+/// ```
+/// use winptyrs::{PTYArgs, ShutdownPolicy};
+///
+/// let pty_args = PTYArgs::new(120, 40).contain_tree(true).shutdown_policy(ShutdownPolicy::TerminateTree);
+/// assert_eq!((pty_args.cols, pty_args.rows), (120, 40));
+/// ```
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct PTYArgs {
 	// Common arguments
 	/// Number of character columns to display.
@@ -51,7 +84,28 @@ pub struct PTYArgs {
     /// agent RPC request.
 	pub timeout: u32,
 	/// General configuration settings for the winpty backend.
-	pub agent_config: AgentConfig
+	pub agent_config: AgentConfig,
+	/// Place the spawned process in a job object, so that it and all its descendants
+	/// can be enumerated and terminated together. The whole process tree is killed
+	/// when the PTY is dropped, unless `shutdown_policy` is [`ShutdownPolicy::CloseConsole`].
+	pub contain_tree: bool,
+	/// What to do with the spawned process when the PTY is closed or dropped.
+	pub shutdown_policy: ShutdownPolicy,
+	/// Maximum amount of time (in ms) to wait for the remaining output to be drained
+	/// after the process exits or the PTY is closed.
+	pub drain_timeout: u32,
+	/// Maximum length (in characters) of the payload of the OSC sequences tracked
+	/// by [`PTY::title`] and [`PTY::cwd`]. Longer sequences are ignored.
+	pub osc_max_payload: usize,
+	/// Read the output using the I/O reactor shared by all the PTYs of the process, instead
+	/// of dedicated threads for each PTY. Only supported by the ConPTY backend.
+	pub shared_reactor: bool,
+	/// Path of a [`Cassette`]. The session is recorded into it, unless the backend is
+	/// [`PTYBackend::Replay`], which serves back the session recorded in it.
+	pub cassette: Option<OsString>,
+	/// Fail the replay when the input or the resizes diverge from the cassette, instead of
+	/// ignoring them. Only supported by the Replay backend.
+	pub strict_replay: bool
 }
 
 impl Default for PTYArgs {
@@ -61,11 +115,94 @@ impl Default for PTYArgs {
 			rows: 24,
 			mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
 			timeout: 10000,
-			agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
+			agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
+			contain_tree: false,
+			shutdown_policy: ShutdownPolicy::CloseConsole,
+			drain_timeout: 5000,
+			osc_max_payload: DEFAULT_MAX_OSC_PAYLOAD,
+			shared_reactor: false,
+			cassette: None,
+			strict_replay: false
 		}
 	}
 }
 
+impl PTYArgs {
+	/// Create the arguments of a pseudoterminal of `cols` columns by `rows` rows,
+	/// whose other arguments are set to their defaults.
+	pub fn new(cols: i32, rows: i32) -> PTYArgs {
+		PTYArgs { cols, rows, ..PTYArgs::default() }
+	}
+
+	/// Set [`PTYArgs::mouse_mode`].
+	pub fn mouse_mode(mut self, mouse_mode: MouseMode) -> PTYArgs {
+		self.mouse_mode = mouse_mode;
+		self
+	}
+
+	/// Set [`PTYArgs::timeout`], in ms.
+	pub fn timeout(mut self, timeout: u32) -> PTYArgs {
+		self.timeout = timeout;
+		self
+	}
+
+	/// Set [`PTYArgs::agent_config`].
+	pub fn agent_config(mut self, agent_config: AgentConfig) -> PTYArgs {
+		self.agent_config = agent_config;
+		self
+	}
+
+	/// Set [`PTYArgs::contain_tree`].
+	pub fn contain_tree(mut self, contain_tree: bool) -> PTYArgs {
+		self.contain_tree = contain_tree;
+		self
+	}
+
+	/// Set [`PTYArgs::shutdown_policy`].
+	pub fn shutdown_policy(mut self, shutdown_policy: ShutdownPolicy) -> PTYArgs {
+		self.shutdown_policy = shutdown_policy;
+		self
+	}
+
+	/// Set [`PTYArgs::drain_timeout`], in ms.
+	pub fn drain_timeout(mut self, drain_timeout: u32) -> PTYArgs {
+		self.drain_timeout = drain_timeout;
+		self
+	}
+
+	/// Set [`PTYArgs::osc_max_payload`].
+	pub fn osc_max_payload(mut self, osc_max_payload: usize) -> PTYArgs {
+		self.osc_max_payload = osc_max_payload;
+		self
+	}
+
+	/// Set [`PTYArgs::shared_reactor`].
+	pub fn shared_reactor(mut self, shared_reactor: bool) -> PTYArgs {
+		self.shared_reactor = shared_reactor;
+		self
+	}
+
+	/// Set the path of the [`PTYArgs::cassette`].
+	pub fn cassette<P: Into<OsString>>(mut self, path: P) -> PTYArgs {
+		self.cassette = Some(path.into());
+		self
+	}
+
+	/// Set [`PTYArgs::strict_replay`].
+	pub fn strict_replay(mut self, strict_replay: bool) -> PTYArgs {
+		self.strict_replay = strict_replay;
+		self
+	}
+}
+
+/// Wrap a backend so that its session is recorded into [`PTYArgs::cassette`], if given.
+fn record(args: &PTYArgs, pty: Box<dyn PTYImpl>) -> Result<Box<dyn PTYImpl>, OsString> {
+	match &args.cassette {
+		Some(path) => Recorder::new(pty, args, path),
+		None => Ok(pty)
+	}
+}
+
 /// Pseudoterminal struct that communicates with a spawned process.
 ///
 /// This struct spawns a terminal given a set of arguments, as well as a backend,
@@ -80,13 +217,10 @@ impl Default for PTYArgs {
 /// use winptyrs::{PTY, PTYArgs, MouseMode, AgentConfig};
 ///
 /// let cmd = OsString::from("c:\\windows\\system32\\cmd.exe");
-/// let pty_args = PTYArgs {
-///     cols: 80,
-///     rows: 25,
-///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-///     timeout: 10000,
-///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-/// };
+/// let pty_args = PTYArgs::new(80, 25)
+///     .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+///     .timeout(10000)
+///     .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 ///
 /// // Initialize a pseudoterminal.
 /// let mut pty = PTY::new(&pty_args).unwrap();
@@ -118,13 +252,10 @@ impl Default for PTYArgs {
 ///
 /// let cmd = OsString::from("c:\\windows\\system32\\cmd.exe");
 ///
-/// let pty_args = PTYArgs {
-///     cols: 80,
-///     rows: 25,
-///     mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-///     timeout: 10000,
-///     agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-/// };
+/// let pty_args = PTYArgs::new(80, 25)
+///     .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+///     .timeout(10000)
+///     .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 ///
 /// // Initialize a winpty and a conpty pseudoterminal.
 /// let mut conpty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -145,10 +276,27 @@ pub struct PTY {
 	 /// If the value is [`self::PTYBackend::NoBackend`], then no operations will be available.
 	 backend: PTYBackend,
 	 /// Reference to the PTY handler which depends on the value of `backend`.
-	 pty: Box<dyn PTYImpl>
+	 pty: Box<dyn PTYImpl>,
+	 /// Tracker of the title and working directory reported by the spawned process.
+	 osc: Mutex<OscTracker>,
+	 /// Timeline of the commands reported through shell integration marks.
+	 shell: Mutex<ShellIntegration>,
+	 /// Triggers evaluated over the output of the spawned process.
+	 triggers: Mutex<Triggers>
 }
 
 impl PTY {
+	/// Wrap a backend instance together with the trackers that observe its output.
+	fn wrap(backend: PTYBackend, pty: Box<dyn PTYImpl>, args: &PTYArgs) -> PTY {
+		PTY {
+			backend,
+			pty,
+			osc: Mutex::new(OscTracker::new(args.osc_max_payload)),
+			shell: Mutex::new(ShellIntegration::new()),
+			triggers: Mutex::new(Triggers::new(args.osc_max_payload))
+		}
+	}
+
 	/// Create a new pseudoterminal setting the backend automatically.
 	pub fn new(args: &PTYArgs) -> Result<PTY, OsString> {
 		let mut errors: OsString = OsString::from("There were some errors trying to instantiate a PTY:");
@@ -157,10 +305,7 @@ impl PTY {
 	 	let pty: Option<PTY> =
 			match conpty_instance {
 				Ok(conpty) => {
-					let pty_instance = PTY {
-						backend: PTYBackend::ConPTY,
-						pty: conpty
-					};
+					let pty_instance = PTY::wrap(PTYBackend::ConPTY, record(args, conpty)?, args);
 					Some(pty_instance)
 				},
 				Err(err) => {
@@ -176,10 +321,7 @@ impl PTY {
 				let winpty_instance: Result<Box<dyn PTYImpl>, OsString> = WinPTY::new(args);
 				match winpty_instance {
 					Ok(winpty) => {
-						let pty_instance = PTY {
-							backend: PTYBackend::WinPTY,
-							pty: winpty
-						};
+						let pty_instance = PTY::wrap(PTYBackend::WinPTY, record(args, winpty)?, args);
 						Ok(pty_instance)
 					},
 					Err(err) => {
@@ -197,10 +339,7 @@ impl PTY {
 			PTYBackend::ConPTY => {
 				match ConPTY::new(args) {
 					Ok(conpty) => {
-						let pty = PTY {
-							backend,
-							pty: conpty
-						};
+						let pty = PTY::wrap(backend, record(args, conpty)?, args);
 						Ok(pty)
 					},
 					Err(err) => Err(err)
@@ -209,10 +348,16 @@ impl PTY {
 			PTYBackend::WinPTY => {
 				match WinPTY::new(args) {
 					Ok(winpty) => {
-						let pty = PTY {
-							backend,
-							pty: winpty
-						};
+						let pty = PTY::wrap(backend, record(args, winpty)?, args);
+						Ok(pty)
+					},
+					Err(err) => Err(err)
+				}
+			},
+			PTYBackend::Replay => {
+				match Replay::new(args) {
+					Ok(replay) => {
+						let pty = PTY::wrap(backend, replay, args);
 						Ok(pty)
 					},
 					Err(err) => Err(err)
@@ -226,18 +371,30 @@ impl PTY {
 	/// Spawn a process inside the PTY.
 	///
 	/// # Arguments
-	/// * `appname` - Full path to the executable binary to spawn.
+	/// * `appname` - Full path to the executable binary to spawn. Names and relative paths
+	/// are resolved against `PATH` and `PATHEXT`, see [`resolve_executable`](crate::resolver::resolve_executable).
+	/// Batch files are run by the command interpreter, see [`launch_command`](crate::resolver::launch_command).
 	/// * `cmdline` - Optional space-delimited arguments to provide to the executable.
 	/// * `cwd` - Optional path from where the executable should be spawned.
 	/// * `env` - Optional environment variables to provide to the process. Each
 	/// variable should be declared as `VAR=VALUE` and be separated by a NUL (0) character.
 	///
 	/// # Returns
-	/// `true` if the call was successful, else an error will be returned.
+	/// `true` if the call was successful, else an error will be returned. If the executable
+	/// cannot be found, the error lists every path that was checked.
 	pub fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString> {
 		self.pty.spawn(appname, cmdline, cwd, env)
 	}
 
+	/// Spawn a process inside the PTY given a [`SpawnRequest`], e.g., one built from a
+	/// [`ShellProfile`](crate::shells::ShellProfile).
+	///
+	/// # Returns
+	/// `true` if the call was successful, else an error will be returned.
+	pub fn spawn_request(&mut self, request: SpawnRequest) -> Result<bool, OsString> {
+		self.pty.spawn(request.appname, request.cmdline, request.cwd, request.env)
+	}
+
 	/// Change the PTY size.
     ///
     /// # Arguments
@@ -265,9 +422,151 @@ impl PTY {
     /// * The actual read operation happens in a background thread
     /// * The returned data is represented using a [`OsString`] since Windows operates over `u16` strings
     pub fn read(&self, blocking: bool) -> Result<OsString, OsString> {
-        self.pty.read(blocking)
+        let output = self.pty.read(blocking)?;
+        self.track_output(&output);
+        Ok(output)
     }
 
+	/// Read all available characters from the standard output of a process, waiting
+	/// at most `timeout` for data to be available.
+    ///
+    /// # Returns
+    /// * `Ok(OsString)` - The data read from the process output, empty if the timeout elapsed
+    /// * `Err(OsString)` - If EOF is reached or an error occurs
+	pub fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+		let output = self.pty.read_timeout(timeout)?;
+		self.track_output(&output);
+		Ok(output)
+	}
+
+	/// Wait until the output of the process settles, i.e., until no output is received
+	/// for `quiet`, the process exits or `max` elapses, whichever happens first.
+	///
+	/// This is meant to replace sleeping for an arbitrary amount of time after spawning a
+	/// process or writing to it, before reading its output.
+	///
+	/// # Arguments
+	/// * `quiet` - Amount of time without output after which the output is considered settled.
+	/// * `max` - Maximum amount of time to wait.
+	///
+	/// # Returns
+	/// * `Ok(OsString)` - The output received while waiting, possibly empty
+	/// * `Err(OsString)` - If EOF was already reached or an error occurs
+	///
+	/// # Notes
+	/// The quiet period is measured from the instant on which the reader thread received the
+	/// last output, so output that was received but not read yet does not extend the wait.
+	/// When an error occurs, the output received until then is returned by the next read.
+	///
+	/// # Examples
+	/// ```no_run
+	/// # use std::ffi::OsString;
+	/// # use std::time::Duration;
+	/// # use winptyrs::{PTY, PTYArgs};
+	/// let mut pty = PTY::new(&PTYArgs::default()).unwrap();
+	/// pty.spawn(OsString::from("cmd.exe"), None, None, None).unwrap();
+	/// let banner = pty.wait_idle(Duration::from_millis(200), Duration::from_secs(5)).unwrap();
+	/// ```
+	pub fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+		let output = self.pty.wait_idle(quiet, max)?;
+		self.track_output(&output);
+		Ok(output)
+	}
+
+	fn track_output(&self, output: &OsString) {
+		if output.is_empty() {
+			return;
+		}
+		let text = output.to_string_lossy();
+		if let Ok(mut osc) = self.osc.lock() {
+			osc.feed(&text);
+		}
+		if let Ok(mut shell) = self.shell.lock() {
+			shell.feed(&text);
+		}
+
+		// The triggers are released before reacting, so that callbacks can use the PTY.
+		let firings = match self.triggers.lock() {
+			Ok(mut triggers) if !triggers.is_empty() => triggers.feed(&text),
+			_ => Vec::new(),
+		};
+		for firing in firings {
+			if let Some(callback) = &firing.callback {
+				callback(&firing.matched);
+			}
+			if let Some(response) = firing.response {
+				let _ = self.pty.write(OsString::from(response));
+			}
+		}
+	}
+
+	/// Register a trigger over the output of the spawned process, see [`Trigger`].
+	///
+	/// # Notes
+	/// Triggers are evaluated over the output consumed through [`PTY::read`],
+	/// [`PTY::read_timeout`] and [`PTY::wait_idle`], and their responses are written as
+	/// soon as the output that matched is read.
+	///
+	/// # Examples
+	/// ```no_run
+	/// # use std::ffi::OsString;
+	/// # use winptyrs::{PTY, PTYArgs, Trigger};
+	/// let mut pty = PTY::new(&PTYArgs::default()).unwrap();
+	/// pty.add_trigger(Trigger::new("Press any key to continue").unwrap().respond("\r"));
+	/// pty.spawn(OsString::from("cmd.exe"), Some(OsString::from("/c pause")), None, None).unwrap();
+	/// while pty.read(true).is_ok() {}
+	/// ```
+	pub fn add_trigger(&self, trigger: Trigger) -> TriggerId {
+		self.triggers.lock().unwrap_or_else(|err| err.into_inner()).add(trigger)
+	}
+
+	/// Remove a trigger, returning `false` if it does not exist, e.g., if it only fired once.
+	pub fn remove_trigger(&self, id: TriggerId) -> bool {
+		self.triggers.lock().unwrap_or_else(|err| err.into_inner()).remove(id)
+	}
+
+	/// Retrieve the last window title set by the spawned process (`OSC 0` or `OSC 2`).
+	///
+	/// # Notes
+	/// Only the output that was consumed through [`PTY::read`] is taken into account.
+	pub fn title(&self) -> Option<String> {
+		self.osc.lock().ok().and_then(|osc| osc.title())
+	}
+
+	/// Retrieve the last working directory reported by the spawned process
+	/// (`OSC 7` or `OSC 9;9`).
+	///
+	/// # Notes
+	/// Only the output that was consumed through [`PTY::read`] is taken into account.
+	pub fn cwd(&self) -> Option<String> {
+		self.osc.lock().ok().and_then(|osc| osc.cwd())
+	}
+
+	/// Enable the shell integration marks (`OSC 133`) in the shell running inside the PTY,
+	/// by writing the snippet given by [`ShellKind::enable_command`] into its standard input.
+	///
+	/// # Arguments
+	/// * `kind` - Kind of the spawned shell, see [`ShellKind::from_program`].
+	pub fn enable_shell_integration(&self, kind: ShellKind) -> Result<u32, OsString> {
+		self.write(OsString::from(kind.enable_command()))
+	}
+
+	/// Retrieve the commands that finished in the shell running inside the PTY, oldest first.
+	///
+	/// # Notes
+	/// The shell must emit shell integration marks, see [`PTY::enable_shell_integration`].
+	/// Only the output that was consumed through [`PTY::read`] is taken into account.
+	pub fn commands(&self) -> Vec<CommandRecord> {
+		self.shell.lock().map(|shell| shell.commands()).unwrap_or_default()
+	}
+
+	/// Create a channel that receives an [`OscEvent`] every time the title or the
+	/// working directory reported by the spawned process change.
+	pub fn osc_events(&self) -> Receiver<OscEvent> {
+		let mut osc = self.osc.lock().unwrap_or_else(|err| err.into_inner());
+		osc.subscribe()
+	}
+
 	/// Write a (possibly) UTF-16 string into the standard input of a process.
     ///
     /// # Arguments
@@ -321,4 +620,86 @@ impl PTY {
 	pub fn cancel_io(&self) -> Result<bool, OsString> {
 		self.pty.cancel_io()
 	}
+
+	/// Send a signal to the spawned process.
+	///
+	/// # Arguments
+	/// * `signal` - [`Signal`] to deliver. [`Signal::Interrupt`] and [`Signal::Break`] are
+	/// delivered as if the keys were pressed on the console, [`Signal::Terminate`] closes the
+	/// console attached to the process (ConPTY) and [`Signal::Kill`] terminates it forcefully.
+	///
+	/// # Returns
+	/// An error if the backend cannot deliver `signal`, see [`Signal`] for the supported ones.
+	pub fn signal(&self, signal: Signal) -> Result<(), OsString> {
+		self.pty.signal(signal)
+	}
+
+	/// Close the console attached to the spawned process and terminate it.
+	///
+	/// The process is given [`KILL_GRACE_PERIOD`] milliseconds to exit by itself,
+	/// after which it is terminated forcefully.
+	pub fn kill(&self) -> Result<(), OsString> {
+		self.pty.kill()
+	}
+
+	/// Terminate the spawned process and all of its descendants.
+	///
+	/// # Notes
+	/// The PTY must be created with [`PTYArgs::contain_tree`] set to `true`, else an error is returned.
+	pub fn kill_tree(&self) -> Result<(), OsString> {
+		self.pty.kill_tree()
+	}
+
+	/// Retrieve the process IDs of the spawned process and all of its descendants.
+	///
+	/// # Notes
+	/// The PTY must be created with [`PTYArgs::contain_tree`] set to `true`, else an error is returned.
+	pub fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+		self.pty.get_tree_pids()
+	}
+
+	/// Retrieve the CPU time, wall time, peak memory and I/O counters of the spawned process.
+	///
+	/// # Notes
+	/// This call is available both while the process runs and after it exits. Only the
+	/// resources used by the spawned process itself are reported, not by its descendants.
+	pub fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+		self.pty.resource_usage()
+	}
+
+	/// Retrieve the spawned process and all of its descendants, in breadth-first order.
+	///
+	/// # Notes
+	/// The command line and working directory of a process are `None` when it cannot be
+	/// inspected, e.g., because it runs with higher privileges or a different architecture.
+	pub fn process_tree(&self) -> Result<Vec<ProcessInfo>, OsString> {
+		match self.pty.get_pid() {
+			0 => Err(OsString::from("The process has not been spawned yet")),
+			pid => process_info::process_tree(pid)
+		}
+	}
+
+	/// Retrieve the process that runs in the foreground of the PTY, i.e., the most recently
+	/// created descendant of the spawned process that has not spawned other processes.
+	///
+	/// # Returns
+	/// The spawned process itself if it has no descendants (e.g., an idle shell).
+	pub fn foreground_process(&self) -> Result<Option<ProcessInfo>, OsString> {
+		match self.pty.get_pid() {
+			0 => Err(OsString::from("The process has not been spawned yet")),
+			pid => process_info::foreground_process(pid)
+		}
+	}
+
+	/// Close the PTY, applying the [`ShutdownPolicy`] declared in [`PTYArgs::shutdown_policy`]
+	/// to the spawned process.
+	///
+	/// The output that was not read yet is drained for at most [`PTYArgs::drain_timeout`] ms
+	/// and returned as part of the [`ExitStatus`].
+	///
+	/// # Returns
+	/// `None` if the process is still running (i.e., it was detached), else its [`ExitStatus`].
+	pub fn close(mut self) -> Result<Option<ExitStatus>, OsString> {
+		self.pty.close()
+	}
 }
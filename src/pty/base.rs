@@ -1,7 +1,7 @@
 use windows::core::{Error, HRESULT, PCSTR};
 /// Base struct used to generalize some of the PTY I/O operations.
 use windows::Win32::Foundation::{
-    CloseHandle, ERROR_IO_PENDING, HANDLE, STATUS_PENDING, S_OK, WAIT_FAILED, WAIT_OBJECT_0,
+    CloseHandle, ERROR_IO_PENDING, FILETIME, HANDLE, STATUS_PENDING, S_OK, WAIT_FAILED, WAIT_OBJECT_0,
     WAIT_TIMEOUT,
 };
 use windows::Win32::Globalization::{
@@ -10,20 +10,25 @@ use windows::Win32::Globalization::{
 use windows::Win32::Storage::FileSystem::{GetFileSizeEx, ReadFile, WriteFile};
 use windows::Win32::System::Pipes::PeekNamedPipe;
 use windows::Win32::System::Threading::{
-    CreateEventExW, SetEvent, WaitForSingleObjectEx, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS,
-    INFINITE,
+    CreateEventExW, SetEvent, WaitForMultipleObjects, WaitForSingleObjectEx,
+    CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS, INFINITE,
+};
+use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+use windows::Win32::System::Threading::{
+    GetExitCodeProcess, GetProcessId, GetProcessIoCounters, GetProcessTimes, TerminateProcess,
+    WaitForSingleObject, IO_COUNTERS,
 };
-use windows::Win32::System::Threading::{GetExitCodeProcess, GetProcessId, WaitForSingleObject};
 use windows::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
 
 use core::ffi::c_void;
 use std::ffi::OsString;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
@@ -32,8 +37,10 @@ use std::os::windows::prelude::*;
 #[cfg(unix)]
 use std::vec::IntoIter;
 
-use crossbeam_channel::{unbounded, Sender, Receiver};
+use crossbeam_channel::{unbounded, Sender, Receiver, RecvTimeoutError, TryRecvError};
 
+use super::job::JobObject;
+use super::reactor::{reactor, Registration};
 use super::PTYArgs;
 
 #[cfg(unix)]
@@ -77,6 +84,91 @@ impl From<LocalHandle> for HANDLE {
     }
 }
 
+/// Signals that can be delivered to a process running inside a PTY.
+///
+/// Each backend maps these values to its native mechanism, see [`PTYImpl::signal`].
+/// Signals that a backend cannot deliver return an error instead of being emulated:
+///
+/// | Signal | ConPTY | WinPTY |
+/// |--------|--------|--------|
+/// | [`Signal::Interrupt`] | `^C` written to the input | `^C` written to the input |
+/// | [`Signal::Break`] | win32-input-mode key sequence | unsupported |
+/// | [`Signal::Terminate`] | console closed | unsupported |
+/// | [`Signal::Kill`] | `TerminateProcess` | `TerminateProcess` |
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Interrupt the process, equivalent to pressing Ctrl+C.
+    Interrupt,
+    /// Break the process, equivalent to pressing Ctrl+Break.
+    ///
+    /// ConPTY only translates the key sequence into a `CTRL_BREAK_EVENT` when its input
+    /// parser understands win32-input-mode (Windows 10 21H2 and later); older versions
+    /// forward it to the process as text.
+    Break,
+    /// Ask the process to terminate by closing its console, which gives it a chance to
+    /// clean up. Only supported by ConPTY.
+    Terminate,
+    /// Forcefully terminate the process.
+    Kill,
+}
+
+/// Amount of time (in ms) that [`PTYImpl::kill`] waits for a process to exit
+/// after closing its console, before terminating it forcefully.
+pub const KILL_GRACE_PERIOD: u32 = 5000;
+
+/// Determines what happens to the process running inside a PTY when the PTY
+/// is closed or dropped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Close the console attached to the process and release the resources held by the PTY,
+    /// without terminating the process.
+    ///
+    /// Closing the console delivers a `CTRL_CLOSE_EVENT` to the process, which exits unless
+    /// it handles the event. A process that outlives the PTY keeps running without a console.
+    CloseConsole,
+    /// Close the console attached to the process and terminate it if it is still alive.
+    Terminate,
+    /// Terminate the process and all of its descendants. This policy implies
+    /// [`PTYArgs::contain_tree`].
+    TerminateTree,
+    /// Wait for the process to exit by itself for at most the given duration,
+    /// after which it is terminated as in [`ShutdownPolicy::Terminate`].
+    WaitWithTimeout(Duration),
+}
+
+/// Resources consumed by a process running inside a PTY.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Amount of time that the process has executed in user mode.
+    pub user_time: Duration,
+    /// Amount of time that the process has executed in kernel mode.
+    pub kernel_time: Duration,
+    /// Time elapsed since the process was spawned, or until it exited.
+    pub wall_time: Duration,
+    /// Peak working set size of the process, in bytes.
+    pub peak_memory: u64,
+    /// Number of read operations performed by the process.
+    pub read_operations: u64,
+    /// Number of write operations performed by the process.
+    pub write_operations: u64,
+    /// Number of bytes read by the process.
+    pub read_bytes: u64,
+    /// Number of bytes written by the process.
+    pub write_bytes: u64,
+}
+
+/// Information reported by [`PTYImpl::close`] about a process that has exited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExitStatus {
+    /// Exit code of the process.
+    pub code: u32,
+    /// `true` if the process was terminated due to the [`ShutdownPolicy`],
+    /// `false` if it exited by itself.
+    pub terminated: bool,
+    /// Output that was drained from the PTY while closing it.
+    pub output: OsString,
+}
+
 /// This trait should be implemented by any backend that wants to provide a PTY implementation.
 pub trait PTYImpl: Sync + Send {
     /// Create a new instance of the PTY backend.
@@ -131,6 +223,21 @@ pub trait PTYImpl: Sync + Send {
     /// * The returned data is represented using a [`OsString`] since Windows operates over `u16` strings
     fn read(&self, blocking: bool) -> Result<OsString, OsString>;
 
+    /// Read from the process standard output, waiting at most `timeout` for data to be available.
+    ///
+    /// # Returns
+    /// * `Ok(OsString)` - The data read from the process output, empty if the timeout elapsed
+    /// * `Err(OsString)` - If EOF is reached or an error occurs
+    fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString>;
+
+    /// Wait until the process stops producing output for `quiet`, its output reaches EOF
+    /// or `max` elapses.
+    ///
+    /// # Returns
+    /// * `Ok(OsString)` - The output read while waiting, possibly empty
+    /// * `Err(OsString)` - If EOF was already reached or an error occurs
+    fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString>;
+
     /// Write a (possibly) UTF-16 string into the standard input of a process.
     ///
     /// # Arguments
@@ -168,6 +275,39 @@ pub trait PTYImpl: Sync + Send {
 
     /// Cancel all pending I/O read operations.
     fn cancel_io(&self) -> Result<bool, OsString>;
+
+    /// Send a signal to the process running inside the PTY.
+    ///
+    /// # Arguments
+    /// * `signal` - [`Signal`] to deliver to the process.
+    ///
+    /// # Returns
+    /// An [`OsString`] containing a human-readable error if the signal could not be delivered
+    /// or if the backend does not support it.
+    fn signal(&self, signal: Signal) -> Result<(), OsString>;
+
+    /// Close the console attached to the process and wait up to [`KILL_GRACE_PERIOD`] ms
+    /// for it to exit, after which the process is terminated forcefully.
+    fn kill(&self) -> Result<(), OsString>;
+
+    /// Terminate the process and all of its descendants. Requires the PTY to be
+    /// created with [`PTYArgs::contain_tree`] enabled.
+    fn kill_tree(&self) -> Result<(), OsString>;
+
+    /// Retrieve the identifiers of the process and all of its descendants. Requires
+    /// the PTY to be created with [`PTYArgs::contain_tree`] enabled.
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString>;
+
+    /// Retrieve the resources consumed by the process. It can be called while the
+    /// process is running, as well as after it exits.
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString>;
+
+    /// Apply the [`ShutdownPolicy`] given in [`PTYArgs::shutdown_policy`] to the process and
+    /// drain its remaining output for at most [`PTYArgs::drain_timeout`] ms.
+    ///
+    /// # Returns
+    /// `None` if the process is still alive (i.e., it was detached), else its [`ExitStatus`].
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString>;
 }
 
 fn read(
@@ -329,9 +469,9 @@ fn is_alive(process: HANDLE) -> Result<bool, OsString> {
     }
 }
 
-fn wait_for_exit(process: HANDLE) -> Result<bool, OsString> {
+fn wait_for_exit(process: HANDLE, timeout: u32) -> Result<bool, OsString> {
     unsafe {
-        let wait_status = WaitForSingleObject(process, INFINITE);
+        let wait_status = WaitForSingleObject(process, timeout);
         let succ = wait_status != WAIT_FAILED;
         if succ {
             let dead = wait_status == WAIT_OBJECT_0;
@@ -370,6 +510,66 @@ fn get_exitstatus(process: HANDLE) -> Result<Option<u32>, OsString> {
     }
 }
 
+/// Convert a [`FILETIME`] (expressed in 100-nanosecond intervals) into a [`Duration`].
+fn filetime_to_duration(time: FILETIME) -> Duration {
+    let intervals = ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+    Duration::from_nanos(intervals.saturating_mul(100))
+}
+
+fn resource_usage(process: HANDLE) -> Result<(ResourceUsage, Option<Duration>), OsString> {
+    let mut creation_time = FILETIME::default();
+    let mut exit_time = FILETIME::default();
+    let mut kernel_time = FILETIME::default();
+    let mut user_time = FILETIME::default();
+    let mut memory = PROCESS_MEMORY_COUNTERS::default();
+    let mut io = IO_COUNTERS::default();
+
+    unsafe {
+        let succ = GetProcessTimes(
+            process,
+            &mut creation_time,
+            &mut exit_time,
+            &mut kernel_time,
+            &mut user_time,
+        )
+        .is_ok()
+            && GetProcessMemoryInfo(
+                process,
+                &mut memory,
+                mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+            )
+            .is_ok()
+            && GetProcessIoCounters(process, &mut io).is_ok();
+
+        if !succ {
+            let err: HRESULT = Error::from_thread().into();
+            let result_msg = err.message();
+            let string = OsString::from(result_msg);
+            return Err(string);
+        }
+    }
+
+    let usage = ResourceUsage {
+        user_time: filetime_to_duration(user_time),
+        kernel_time: filetime_to_duration(kernel_time),
+        wall_time: Duration::ZERO,
+        peak_memory: memory.PeakWorkingSetSize as u64,
+        read_operations: io.ReadOperationCount,
+        write_operations: io.WriteOperationCount,
+        read_bytes: io.ReadTransferCount,
+        write_bytes: io.WriteTransferCount,
+    };
+
+    // The exit time is undefined while the process is still running.
+    let lifetime = match get_exitstatus(process)? {
+        Some(_) => Some(
+            filetime_to_duration(exit_time).saturating_sub(filetime_to_duration(creation_time)),
+        ),
+        None => None,
+    };
+    Ok((usage, lifetime))
+}
+
 fn is_eof(process: HANDLE, stream: HANDLE) -> Result<bool, OsString> {
     let mut bytes = MaybeUninit::<u32>::uninit();
     unsafe {
@@ -392,6 +592,27 @@ fn is_eof(process: HANDLE, stream: HANDLE) -> Result<bool, OsString> {
     }
 }
 
+/// Instant on which the output of a process was last received, updated by the thread
+/// (or the reactor) that reads it before the output is delivered.
+#[derive(Clone)]
+pub struct OutputClock(Arc<Mutex<Instant>>);
+
+impl OutputClock {
+    pub fn new() -> OutputClock {
+        OutputClock(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Record that output was received right now.
+    pub fn touch(&self) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = Instant::now();
+    }
+
+    /// Retrieve the instant on which output was last received.
+    pub fn last(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
 /// This struct handles the I/O operations to the standard streams, as well
 /// the lifetime of a process running inside a PTY.
 pub struct PTYProcess {
@@ -416,18 +637,36 @@ pub struct PTYProcess {
     /// Manual-reset event signaled by the reader thread on exit; lets teardown wait
     /// event-driven instead of busy-looping on `reader_atomic`.
     reader_exit_event: LocalHandle,
+    /// Manual-reset event signaled on teardown to release the thread that waits
+    /// for the process to exit.
+    shutdown_event: LocalHandle,
+    /// Maximum amount of time (in ms) to wait for the reader thread to drain the
+    /// remaining output once the process exits or the PTY is dropped.
+    drain_timeout: u32,
     /// Channel used to send the process handle to the reading thread.
     reader_process_out: Sender<Option<LocalHandle>>,
     /// Atomic flag to signal that the reading process has the process handle.
     reader_ready: Arc<AtomicBool>,
     /// Channel used to receive a response from the reading thread.
     reader_out_rx: Receiver<Option<Result<OsString, OsString>>>,
+    /// Instant on which the reading thread last received output.
+    output_clock: OutputClock,
+    /// Set once the end of the output was consumed by a read.
+    eof_reached: AtomicBool,
+    /// Output collected by [`PTYProcess::wait_idle`] before it failed, returned by the next read.
+    unread: Mutex<OsString>,
     /// PTY process is async
     async_: bool,
     /// Writing OVERLAPPED struct for async operation
     write_overlapped: Option<OVERLAPPED>,
     /// Write mutex for concurrent access under async IO
     write_mutex: Arc<Mutex<bool>>,
+    /// Job object that contains the process and all its descendants, if enabled.
+    job: Option<JobObject>,
+    /// Instant on which the process was set.
+    spawned_at: Option<Instant>,
+    /// Registration in the shared I/O reactor, if it reads the output instead of a dedicated thread.
+    reactor: Option<Registration>,
 }
 
 impl PTYProcess {
@@ -438,6 +677,8 @@ impl PTYProcess {
     /// * `conout` - Handle to the process standard output stream
     /// * `using_pipes` - `true` if the streams are Windows named pipes, `false` if they are files.
     /// * `async_` - `true` if the streams are async, `false` if they are sync.
+    /// * `cleanup_tx` - Optional channel notified once the process exits and its output is drained.
+    /// * `drain_timeout` - Maximum amount of time (in ms) to wait for the output to be drained.
     ///
     /// # Returns
     /// * `pty` - A new [`PTYProcess`] instance.
@@ -447,6 +688,7 @@ impl PTYProcess {
         using_pipes: bool,
         async_: bool,
         cleanup_tx: Option<mpsc::Sender<bool>>,
+        drain_timeout: u32,
     ) -> PTYProcess {
         let thread_arc = Arc::new(AtomicBool::new(true));
         let reader_arc = Arc::new(AtomicBool::new(false));
@@ -471,6 +713,8 @@ impl PTYProcess {
             let spinlock_clone = Arc::clone(&thread_arc);
             let reader_ready = Arc::clone(&reader_arc);
             let reader_exit_for_thread = reader_exit_event;
+            let output_clock = OutputClock::new();
+            let reader_clock = output_clock.clone();
 
             let reader_thread = thread::spawn(move || {
                 let process_result = reader_process_rx.recv();
@@ -484,17 +728,20 @@ impl PTYProcess {
                         if !is_eof(process.into(), conout.into()).unwrap() {
                             match read(true, conout.into(), using_pipes, None) {
                                 Ok((result, _)) => {
-                                    reader_out_tx.send(Some(Ok(result))).unwrap();
+                                    if !result.is_empty() {
+                                        reader_clock.touch();
+                                    }
+                                    let _ = reader_out_tx.send(Some(Ok(result)));
                                 }
                                 Err(err) => {
-                                    reader_out_tx.send(Some(Err(err))).unwrap();
+                                    let _ = reader_out_tx.send(Some(Err(err)));
                                 }
                             }
                             alive = reader_alive_rx
                         .try_recv()
                         .unwrap_or(true);
                         } else {
-                            reader_out_tx.send(None).unwrap();
+                            let _ = reader_out_tx.send(None);
                             alive = false;
                         }
                     }
@@ -521,12 +768,20 @@ impl PTYProcess {
                 reader_alive: reader_alive_tx,
                 reader_atomic: thread_arc,
                 reader_exit_event,
+                shutdown_event: LocalHandle(std::ptr::null_mut()),
+                drain_timeout,
                 reader_process_out: reader_process_tx,
                 reader_ready: reader_arc,
                 reader_out_rx,
+                output_clock,
+                eof_reached: AtomicBool::new(false),
+                unread: Mutex::new(OsString::new()),
                 async_,
                 write_overlapped: None,
                 write_mutex: Arc::new(Mutex::new(false)),
+                job: None,
+                spawned_at: None,
+                reactor: None,
             }
         } else {
             let mut write_overlapped = OVERLAPPED::default();
@@ -550,6 +805,17 @@ impl PTYProcess {
             let reader_exit_for_thread = reader_exit_event;
             let reader_exit_for_alive = reader_exit_event;
 
+            let shutdown_handle = unsafe {
+                match CreateEventExW(None, None, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS.0) {
+                    Ok(evt) => evt,
+                    Err(_) => HANDLE::default(),
+                }
+            };
+            let shutdown_event = LocalHandle::from(shutdown_handle);
+            let shutdown_for_alive = shutdown_event;
+            let output_clock = OutputClock::new();
+            let reader_clock = output_clock.clone();
+
             let reader_thread = thread::spawn(move || {
                 let mut read_overlapped = OVERLAPPED::default();
                 unsafe {
@@ -571,11 +837,14 @@ impl PTYProcess {
                     while alive {
                         match read(true, conout.into(), using_pipes, Some(&mut read_overlapped)) {
                             Ok((result, alive_status)) => {
-                                reader_out_tx.send(Some(Ok(result))).unwrap();
+                                if !result.is_empty() {
+                                    reader_clock.touch();
+                                }
+                                let _ = reader_out_tx.send(Some(Ok(result)));
                                 alive = alive_status;
                             }
                             Err(err) => {
-                                reader_out_tx.send(Some(Err(err))).unwrap();
+                                let _ = reader_out_tx.send(Some(Err(err)));
                                 alive = false;
                             }
                         }
@@ -599,29 +868,40 @@ impl PTYProcess {
 
             let alive_thread = thread::spawn(move || {
                 if let Ok(handle) = reader_process_2_rx.recv() {
-                    let _ = wait_for_exit(handle.into());
                     unsafe {
-                        // Child has exited. Let modern ConPTY auto-close the output
-                        // pipe — reader's pending ReadFile then returns 0 bytes
-                        // (natural EOF) and the reader exits, signaling
-                        // `reader_exit_event`. If that doesn't happen in time
-                        // (older ConPTY / non-ConPTY consumer), fall back to
-                        // CancelIoEx to unstick the reader.
-                        let exit_handle = Into::<HANDLE>::into(reader_exit_for_alive);
-                        if WaitForSingleObject(exit_handle, 5000) != WAIT_OBJECT_0 {
-                            let _ = CancelIoEx(Into::<HANDLE>::into(conout), None);
-                            loop {
-                                if WaitForSingleObject(exit_handle, 50) == WAIT_OBJECT_0 {
-                                    break;
-                                }
+                        let shutdown_handle = Into::<HANDLE>::into(shutdown_for_alive);
+                        let process_handles = [Into::<HANDLE>::into(handle), shutdown_handle];
+                        let process_exited = if shutdown_for_alive.is_invalid() {
+                            wait_for_exit(handle.into(), INFINITE).unwrap_or(true)
+                        } else {
+                            WaitForMultipleObjects(&process_handles, false, INFINITE)
+                                == WAIT_OBJECT_0
+                        };
+
+                        if process_exited {
+                            // Child has exited. Let modern ConPTY auto-close the output
+                            // pipe — reader's pending ReadFile then returns 0 bytes
+                            // (natural EOF) and the reader exits, signaling
+                            // `reader_exit_event`. If that doesn't happen in time
+                            // (older ConPTY / non-ConPTY consumer), fall back to
+                            // CancelIoEx to unstick the reader until it exits or the
+                            // PTY is torn down.
+                            let exit_handle = Into::<HANDLE>::into(reader_exit_for_alive);
+                            if WaitForSingleObject(exit_handle, drain_timeout) != WAIT_OBJECT_0 {
                                 let _ = CancelIoEx(Into::<HANDLE>::into(conout), None);
+                                let exit_handles = [exit_handle, shutdown_handle];
+                                while WaitForMultipleObjects(&exit_handles, false, 50)
+                                    == WAIT_TIMEOUT
+                                {
+                                    let _ = CancelIoEx(Into::<HANDLE>::into(conout), None);
+                                }
+                            }
+                            // Reader has drained and exited. Signal cleanup so the
+                            // consumer (e.g. ConPTY's cleanup_thread) can release
+                            // resources via ClosePseudoConsole.
+                            if let Some(tx) = cleanup_tx {
+                                let _ = tx.send(true).unwrap_or(());
                             }
-                        }
-                        // Reader has drained and exited. Signal cleanup so the
-                        // consumer (e.g. ConPTY's cleanup_thread) can release
-                        // resources via ClosePseudoConsole.
-                        if let Some(tx) = cleanup_tx {
-                            let _ = tx.send(true).unwrap_or(());
                         }
                     }
                 }
@@ -639,14 +919,99 @@ impl PTYProcess {
                 reader_alive: reader_alive_tx,
                 reader_atomic: thread_arc,
                 reader_exit_event,
+                shutdown_event,
+                drain_timeout,
                 reader_process_out: reader_process_tx,
                 reader_ready: reader_arc,
                 reader_out_rx,
+                output_clock,
+                eof_reached: AtomicBool::new(false),
+                unread: Mutex::new(OsString::new()),
                 async_,
                 write_overlapped: Some(write_overlapped),
                 write_mutex: Arc::new(Mutex::new(false)),
+                job: None,
+                spawned_at: None,
+                reactor: None,
+            }
+        }
+    }
+
+    /// Create a new [`PTYProcess`] instance whose output is read by the I/O reactor shared
+    /// by all the PTYs of the process, instead of dedicated threads.
+    ///
+    /// # Arguments
+    /// * `conin` - Handle to the process standard input stream
+    /// * `conout` - Handle to the process standard output stream, an async named pipe.
+    /// * `on_drained` - Optional callback invoked once the process exits and its output is drained.
+    /// * `drain_timeout` - Maximum amount of time (in ms) to wait for the output to be drained.
+    ///
+    /// # Returns
+    /// * `pty` - A new [`PTYProcess`] instance, or an error if the reactor could not read from `conout`.
+    pub fn new_shared(
+        conin: LocalHandle,
+        conout: LocalHandle,
+        on_drained: Option<Box<dyn FnOnce() + Send>>,
+        drain_timeout: u32,
+    ) -> Result<PTYProcess, OsString> {
+        let mut write_overlapped = OVERLAPPED::default();
+        unsafe {
+            if let Ok(evt) = CreateEventExW(None, None, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS.0) {
+                // Setting the low-order bit of the event prevents the completion of the
+                // writes from being queued to the completion port of the reactor.
+                write_overlapped.hEvent = HANDLE((evt.0 as usize | 1) as *mut c_void);
             }
         }
+
+        let reader_exit_handle = unsafe {
+            match CreateEventExW(None, None, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS.0) {
+                Ok(evt) => evt,
+                Err(_) => HANDLE::default(),
+            }
+        };
+        let reader_exit_event = LocalHandle::from(reader_exit_handle);
+
+        let thread_arc = Arc::new(AtomicBool::new(true));
+        let (reader_out_tx, reader_out_rx) = unbounded::<Option<Result<OsString, OsString>>>();
+        let (reader_alive_tx, _) = unbounded::<bool>();
+        let (reader_process_tx, _) = unbounded::<Option<LocalHandle>>();
+        let output_clock = OutputClock::new();
+        let registration = reactor()?.register(
+            conout,
+            reader_out_tx,
+            output_clock.clone(),
+            Arc::clone(&thread_arc),
+            reader_exit_event,
+            drain_timeout,
+            on_drained,
+        )?;
+
+        Ok(PTYProcess {
+            process: LocalHandle(std::ptr::null_mut()),
+            conin,
+            conout,
+            pid: 0,
+            close_process: true,
+            reading_thread: None,
+            alive_thread: None,
+            reader_alive: reader_alive_tx,
+            reader_atomic: thread_arc,
+            reader_exit_event,
+            shutdown_event: LocalHandle(std::ptr::null_mut()),
+            drain_timeout,
+            reader_process_out: reader_process_tx,
+            reader_ready: Arc::new(AtomicBool::new(true)),
+            reader_out_rx,
+            output_clock,
+            eof_reached: AtomicBool::new(false),
+            unread: Mutex::new(OsString::new()),
+            async_: true,
+            write_overlapped: Some(write_overlapped),
+            write_mutex: Arc::new(Mutex::new(false)),
+            job: None,
+            spawned_at: None,
+            reactor: Some(registration),
+        })
     }
 
     /// Read from the process standard output.
@@ -662,21 +1027,99 @@ impl PTYProcess {
     /// * The actual read operation happens in a background thread with a fixed buffer size
     /// * The returned data is represented using a [`OsString`] since Windows operates over `u16` strings
     pub fn read(&self, blocking: bool) -> Result<OsString, OsString> {
+        if let Some(output) = self.take_unread() {
+            return Ok(output);
+        }
         // Get data directly from reading thread
         match blocking {
             true => match self.reader_out_rx.recv() {
-                Ok(None) => Err(OsString::from("Standard out reached EOF")),
+                Ok(None) => self.reached_eof(),
                 Ok(Some(bytes)) => bytes,
                 Err(_) => Ok(OsString::new()),
             },
             false => match self.reader_out_rx.try_recv() {
-                Ok(None) => Err(OsString::from("Standard out reached EOF")),
+                Ok(None) => self.reached_eof(),
                 Ok(Some(bytes)) => bytes,
                 Err(_) => Ok(OsString::new()),
             },
         }
     }
 
+    /// Read from the process standard output, waiting at most `timeout` for data to be available.
+    ///
+    /// # Returns
+    /// * `Ok(OsString)` - The data read from the process output, empty if the timeout elapsed
+    /// * `Err(OsString)` - If EOF is reached or an error occurs
+    pub fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        if let Some(output) = self.take_unread() {
+            return Ok(output);
+        }
+        match self.reader_out_rx.recv_timeout(timeout) {
+            Ok(None) => self.reached_eof(),
+            Ok(Some(bytes)) => bytes,
+            Err(_) => Ok(OsString::new()),
+        }
+    }
+
+    /// Remember that the end of the output was consumed by a read, for [`PTYProcess::is_eof`].
+    fn reached_eof(&self) -> Result<OsString, OsString> {
+        self.eof_reached.store(true, Ordering::Release);
+        Err(OsString::from("Standard out reached EOF"))
+    }
+
+    /// Take the output left by a failed [`PTYProcess::wait_idle`], if any.
+    fn take_unread(&self) -> Option<OsString> {
+        let mut unread = self.unread.lock().unwrap_or_else(|err| err.into_inner());
+        (!unread.is_empty()).then(|| mem::take(&mut *unread))
+    }
+
+    /// Wait until no output is received for `quiet`, the output reaches EOF or `max` elapses.
+    ///
+    /// # Returns
+    /// * `Ok(OsString)` - The output read while waiting, possibly empty
+    /// * `Err(OsString)` - If EOF was already reached or a read fails
+    ///
+    /// # Notes
+    /// The quiet period is measured from the instant on which the reader received the last
+    /// output, so output that was already buffered does not extend the wait. When a read
+    /// fails, the output read until then is returned by the next read.
+    pub fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+        let mut output = self.take_unread().unwrap_or_default();
+        if output.is_empty() && self.eof_reached.load(Ordering::Acquire) {
+            return Err(OsString::from("Standard out reached EOF"));
+        }
+        let deadline = Instant::now() + max;
+        loop {
+            let now = Instant::now();
+            let wake = (self.output_clock.last() + quiet).min(deadline);
+            let message = match self.reader_out_rx.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) if now >= wake => return Ok(output),
+                Err(TryRecvError::Empty) => match self.reader_out_rx.recv_timeout(wake - now) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => None,
+                },
+                Err(TryRecvError::Disconnected) => None,
+            };
+            match message {
+                Some(Ok(bytes)) => output.push(bytes),
+                Some(Err(err)) => {
+                    self.unread.lock().unwrap_or_else(|err| err.into_inner()).push(output);
+                    return Err(err);
+                }
+                // The reader stops once the output reaches EOF.
+                None => {
+                    self.eof_reached.store(true, Ordering::Release);
+                    return Ok(output);
+                }
+            }
+            if now >= deadline {
+                return Ok(output);
+            }
+        }
+    }
+
     /// Write an (possibly) UTF-16 string into the standard input of a process.
     ///
     /// # Arguments
@@ -797,6 +1240,9 @@ impl PTYProcess {
     /// `true` if the process reached EOL, false otherwise. If an error occurs, then a [`OsString`]
     /// containing a human-readable error is raised.
     pub fn is_eof(&self) -> Result<bool, OsString> {
+        if self.eof_reached.load(Ordering::Acquire) {
+            return Ok(true);
+        }
         // let mut available_bytes: Box<u32> = Box::new_uninit();
         // let bytes_ptr: *mut u32 = &mut *available_bytes;
         // let bytes_ptr: *mut u32 = ptr::null_mut();
@@ -871,12 +1317,131 @@ impl PTYProcess {
         //     res.unwrap();
         // }
 
-        self.reader_process_out.send(Some(process.into())).unwrap();
+        match &self.reactor {
+            // The process can outlive the PTY if the wait cannot be registered, in which
+            // case the pseudoconsole is released when the PTY is dropped.
+            Some(registration) => {
+                let _ = registration.watch(process.into());
+            }
+            None => self.reader_process_out.send(Some(process.into())).unwrap(),
+        }
+        self.spawned_at = Some(Instant::now());
+        self.output_clock.touch();
         unsafe {
             self.pid = GetProcessId(Into::<HANDLE>::into(self.process));
         }
     }
 
+    /// Set the job object that will contain the process and its descendants.
+    /// It must be called before [`PTYProcess::set_process`].
+    pub fn set_job(&mut self, job: JobObject) {
+        self.job = Some(job);
+    }
+
+    /// Assign the running process to the job object, if any was set.
+    pub fn assign_to_job(&self) -> Result<(), OsString> {
+        match &self.job {
+            Some(job) => job.assign(self.process.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Terminate the process and all of its descendants.
+    pub fn kill_tree(&self) -> Result<(), OsString> {
+        match &self.job {
+            Some(job) => job.terminate(1),
+            None => Err(OsString::from(
+                "Process tree containment is not enabled, see PTYArgs::contain_tree",
+            )),
+        }
+    }
+
+    /// Retrieve the identifiers of the process and all of its descendants.
+    pub fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        match &self.job {
+            Some(job) => job.pids(),
+            None => Err(OsString::from(
+                "Process tree containment is not enabled, see PTYArgs::contain_tree",
+            )),
+        }
+    }
+
+    /// Apply a [`ShutdownPolicy`] to the running process.
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to apply.
+    /// * `close_console` - Function that closes the console attached to the process, if the
+    /// backend supports it.
+    ///
+    /// # Returns
+    /// `true` if the process was alive and had to be terminated, `false` otherwise.
+    pub fn shutdown<F: FnOnce()>(
+        &self,
+        policy: ShutdownPolicy,
+        close_console: F,
+    ) -> Result<bool, OsString> {
+        if self.pid == 0 || !self.is_alive()? {
+            return Ok(false);
+        }
+
+        let terminated = match policy {
+            ShutdownPolicy::CloseConsole => false,
+            ShutdownPolicy::Terminate => {
+                close_console();
+                self.terminate(1)?;
+                true
+            }
+            ShutdownPolicy::TerminateTree => {
+                self.kill_tree()?;
+                close_console();
+                true
+            }
+            ShutdownPolicy::WaitWithTimeout(timeout) => {
+                let timeout_ms = timeout.as_millis().min(u32::MAX as u128 - 1) as u32;
+                if self.wait_for_exit_timeout(timeout_ms)? {
+                    false
+                } else {
+                    close_console();
+                    self.terminate(1)?;
+                    true
+                }
+            }
+        };
+
+        if terminated {
+            // Process termination is asynchronous, wait for the exit code to be available.
+            self.wait_for_exit_timeout(self.drain_timeout)?;
+        }
+        Ok(terminated)
+    }
+
+    /// Retrieve the resources consumed by the process, either while it runs or after it exits.
+    pub fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        let spawned_at = match self.spawned_at {
+            Some(spawned_at) if self.pid != 0 => spawned_at,
+            _ => return Err(OsString::from("No process has been spawned")),
+        };
+
+        let (mut usage, lifetime) = resource_usage(self.process.into())?;
+        usage.wall_time = lifetime.unwrap_or_else(|| spawned_at.elapsed());
+        Ok(usage)
+    }
+
+    /// Collect the output that is still pending to be read, until the standard
+    /// output reaches EOF or the drain timeout elapses.
+    pub fn drain(&self) -> OsString {
+        let mut output = OsString::new();
+        let deadline = Instant::now() + Duration::from_millis(self.drain_timeout as u64);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.reader_out_rx.recv_timeout(remaining) {
+                Ok(Some(Ok(chunk))) => output.push(chunk),
+                _ => break,
+            }
+        }
+        output
+    }
+
     /// Retrieve the Process ID associated to the current process.
     pub fn get_pid(&self) -> u32 {
         self.pid
@@ -889,7 +1454,43 @@ impl PTYProcess {
 
     /// Wait for the process to exit
     pub fn wait_for_exit(&self) -> Result<bool, OsString> {
-        wait_for_exit(self.process.into())
+        wait_for_exit(self.process.into(), INFINITE)
+    }
+
+    /// Wait for the process to exit for at most `timeout` milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the process exited before the timeout elapsed, `false` otherwise.
+    pub fn wait_for_exit_timeout(&self, timeout: u32) -> Result<bool, OsString> {
+        if self.pid == 0 {
+            return Err(OsString::from("No process has been spawned"));
+        }
+        wait_for_exit(self.process.into(), timeout)
+    }
+
+    /// Forcefully terminate the process.
+    ///
+    /// # Arguments
+    /// * `exit_code` - Exit code that the process will report after being terminated.
+    pub fn terminate(&self, exit_code: u32) -> Result<(), OsString> {
+        if self.pid == 0 {
+            return Err(OsString::from("No process has been spawned"));
+        }
+
+        if !self.is_alive()? {
+            return Ok(());
+        }
+
+        unsafe {
+            if TerminateProcess(Into::<HANDLE>::into(self.process), exit_code).is_ok() {
+                Ok(())
+            } else {
+                let result: HRESULT = Error::from_thread().into();
+                let result_msg = result.message();
+                let string = OsString::from(result_msg);
+                Err(string)
+            }
+        }
     }
 
     /// Cancel all pending I/O operations
@@ -907,6 +1508,19 @@ impl PTYProcess {
     }
 }
 
+/// Close the standard streams of a process, `conout` being only closed if `close_conout`.
+fn close_streams(conin: LocalHandle, conout: LocalHandle, close_conout: bool) {
+    unsafe {
+        if !conin.is_invalid() {
+            let _ = CloseHandle(Into::<HANDLE>::into(conin));
+        }
+
+        if !conout.is_invalid() && close_conout {
+            let _ = CloseHandle(Into::<HANDLE>::into(conout));
+        }
+    }
+}
+
 impl Drop for PTYProcess {
     fn drop(&mut self) {
         unsafe {
@@ -915,48 +1529,95 @@ impl Drop for PTYProcess {
                 if self.reader_process_out.send(None).is_ok() {}
             }
 
+            // Release the thread that waits for the process to exit, the process
+            // may outlive the PTY depending on the shutdown policy.
+            if !self.shutdown_event.is_invalid() {
+                let _ = SetEvent(Into::<HANDLE>::into(self.shutdown_event));
+            }
+
             // Signal the sync reader (which polls the channel) that it should exit,
             // and cancel any pending I/O so a blocked ReadFile returns. Then wait
             // event-driven for the reader to actually exit. Retry on timeout to
             // handle the race where the reader issues a new read between cancel
-            // and wait.
+            // and wait, giving up once the drain timeout elapses.
             let _ = self.reader_alive.send(false);
             let _ = CancelIoEx(Into::<HANDLE>::into(self.conout), None);
 
             let exit_handle = Into::<HANDLE>::into(self.reader_exit_event);
-            if !self.reader_exit_event.is_invalid() {
-                loop {
-                    if WaitForSingleObject(exit_handle, 50) == WAIT_OBJECT_0 {
-                        break;
-                    }
+            let mut reader_exited = self.reader_exit_event.is_invalid();
+            let start = Instant::now();
+            while !reader_exited {
+                if WaitForSingleObject(exit_handle, 50) == WAIT_OBJECT_0 {
+                    reader_exited = true;
+                } else if start.elapsed() >= Duration::from_millis(self.drain_timeout as u64) {
+                    break;
+                } else {
                     let _ = self.reader_alive.send(false);
                     let _ = CancelIoEx(Into::<HANDLE>::into(self.conout), None);
                 }
             }
 
-            // Wait for the thread to be down
-            if let Some(thread_handle) = self.reading_thread.take() {
-                thread_handle.join().unwrap();
+            // Stop reading the output and waiting for the process before their handles get
+            // closed. Once the registration is dropped the reactor no longer uses the pipe.
+            if let Some(registration) = self.reactor.take() {
+                drop(registration);
+                reader_exited = true;
             }
 
-            if !self.conin.is_invalid() {
-                let _ = CloseHandle(Into::<HANDLE>::into(self.conin));
+            // Wait for the thread to be down. If it did not exit in time, the streams and
+            // the process handle are only closed once it does, so that it never uses a
+            // closed handle whose value may have been reused.
+            let reading_thread = self.reading_thread.take();
+            let mut close_process = self.close_process && !self.process.is_invalid();
+            if reader_exited {
+                if let Some(thread_handle) = reading_thread {
+                    thread_handle.join().unwrap();
+                }
+                close_streams(self.conin, self.conout, !self.async_);
+                if !self.reader_exit_event.is_invalid() {
+                    let _ = CloseHandle(exit_handle);
+                }
+            } else {
+                let (conin, conout, close_conout) = (self.conin, self.conout, !self.async_);
+                let (process, reader_exit_event) = (self.process, self.reader_exit_event);
+                let process_owned = close_process;
+                close_process = false;
+                let reader_alive = self.reader_alive.clone();
+                let drain_timeout = Duration::from_millis(self.drain_timeout as u64);
+                thread::spawn(move || {
+                    // Keep cancelling the reads for another drain timeout. Past that, the reader
+                    // is blocked until the pipe breaks, so block on its exit instead of spinning.
+                    let exit_handle = Into::<HANDLE>::into(reader_exit_event);
+                    let start = Instant::now();
+                    while WaitForSingleObject(exit_handle, 50) != WAIT_OBJECT_0 {
+                        if start.elapsed() >= drain_timeout {
+                            WaitForSingleObject(exit_handle, INFINITE);
+                            break;
+                        }
+                        let _ = reader_alive.send(false);
+                        let _ = CancelIoEx(Into::<HANDLE>::into(conout), None);
+                    }
+                    if let Some(thread_handle) = reading_thread {
+                        thread_handle.join().unwrap_or(());
+                    }
+                    close_streams(conin, conout, close_conout);
+                    let _ = CloseHandle(exit_handle);
+                    if process_owned {
+                        let _ = CloseHandle(Into::<HANDLE>::into(process));
+                    }
+                });
             }
 
-            if !self.conout.is_invalid() && !self.async_ {
-                let _ = CloseHandle(Into::<HANDLE>::into(self.conout));
+            if let Some(thread_handle) = self.alive_thread.take() {
+                thread_handle.join().unwrap_or(());
             }
 
-            if self.close_process && !self.process.is_invalid() {
+            if close_process {
                 let _ = CloseHandle(Into::<HANDLE>::into(self.process));
             }
 
-            if let Some(thread_handle) = self.alive_thread.take() {
-                thread_handle.join().unwrap_or(());
-            }
-
-            if !self.reader_exit_event.is_invalid() {
-                let _ = CloseHandle(exit_handle);
+            if !self.shutdown_event.is_invalid() {
+                let _ = CloseHandle(Into::<HANDLE>::into(self.shutdown_event));
             }
         }
     }
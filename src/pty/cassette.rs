@@ -0,0 +1,351 @@
+//! Cassettes: recordings of the inputs, outputs, resizes and exit code of a PTY session,
+//! which are served back by the [`super::replay::Replay`] backend.
+//!
+//! A cassette is stored as JSON lines. The first line is a header that declares the
+//! version of the format and the initial size of the PTY, and each of the following
+//! lines is an event, stamped with the milliseconds elapsed since the process was spawned:
+//!
+//! ```text
+//! {"cassette":1,"cols":80,"rows":24}
+//! {"time":0,"type":"spawn","appname":"cmd.exe","cmdline":null}
+//! {"time":85,"type":"output","data":"Microsoft Windows..."}
+//! {"time":1203,"type":"input","data":"exit\r\n"}
+//! {"time":1250,"type":"resize","cols":120,"rows":40}
+//! {"time":1310,"type":"exit","code":0}
+//! ```
+
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use super::{ExitStatus, PTYArgs, PTYImpl, ResourceUsage, Signal};
+
+/// Version of the format of the cassettes.
+pub const CASSETTE_VERSION: u64 = 1;
+
+/// Event of a recorded session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CassetteEvent {
+    /// The process was spawned.
+    Spawn { appname: String, cmdline: Option<String> },
+    /// Text written into the standard input of the process.
+    Input(String),
+    /// Text read from the output of the process.
+    Output(String),
+    /// The PTY was resized.
+    Resize { cols: i32, rows: i32 },
+    /// The process exited with the given exit code.
+    Exit(u32),
+}
+
+/// Event of a recorded session, alongside the time elapsed since the process was spawned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CassetteEntry {
+    pub time: Duration,
+    pub event: CassetteEvent,
+}
+
+/// Recorded session of a PTY.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cassette {
+    /// Number of columns of the PTY when it was created.
+    pub cols: i32,
+    /// Number of rows of the PTY when it was created.
+    pub rows: i32,
+    /// Events of the session, in the order in which they happened.
+    pub entries: Vec<CassetteEntry>,
+}
+
+fn header(cols: i32, rows: i32) -> Value {
+    json!({"cassette": CASSETTE_VERSION, "cols": cols, "rows": rows})
+}
+
+impl CassetteEntry {
+    fn to_value(&self) -> Value {
+        let time = self.time.as_millis().min(u64::MAX as u128) as u64;
+        match &self.event {
+            CassetteEvent::Spawn { appname, cmdline } => {
+                json!({"time": time, "type": "spawn", "appname": appname, "cmdline": cmdline})
+            }
+            CassetteEvent::Input(data) => json!({"time": time, "type": "input", "data": data}),
+            CassetteEvent::Output(data) => json!({"time": time, "type": "output", "data": data}),
+            CassetteEvent::Resize { cols, rows } => json!({"time": time, "type": "resize", "cols": cols, "rows": rows}),
+            CassetteEvent::Exit(code) => json!({"time": time, "type": "exit", "code": code}),
+        }
+    }
+
+    fn from_value(value: &Value) -> Result<CassetteEntry, String> {
+        let time = value.get("time").and_then(Value::as_u64).ok_or("missing time")?;
+        let string = |name: &str| {
+            value.get(name).and_then(Value::as_str).map(str::to_owned).ok_or(format!("missing {}", name))
+        };
+        let int = |name: &str| {
+            value
+                .get(name)
+                .and_then(Value::as_i64)
+                .and_then(|value| i32::try_from(value).ok())
+                .ok_or(format!("missing {}", name))
+        };
+        let event = match value.get("type").and_then(Value::as_str) {
+            Some("spawn") => CassetteEvent::Spawn {
+                appname: string("appname")?,
+                cmdline: value.get("cmdline").and_then(Value::as_str).map(str::to_owned),
+            },
+            Some("input") => CassetteEvent::Input(string("data")?),
+            Some("output") => CassetteEvent::Output(string("data")?),
+            Some("resize") => CassetteEvent::Resize { cols: int("cols")?, rows: int("rows")? },
+            Some("exit") => CassetteEvent::Exit(
+                value.get("code").and_then(Value::as_u64).and_then(|code| u32::try_from(code).ok()).ok_or("missing code")?,
+            ),
+            Some(other) => return Err(format!("unknown event {}", other)),
+            None => return Err(String::from("missing type")),
+        };
+        Ok(CassetteEntry { time: Duration::from_millis(time), event })
+    }
+}
+
+impl Cassette {
+    /// Parse the contents of a cassette.
+    pub fn parse(text: &str) -> Result<Cassette, OsString> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let invalid = |number: usize, message: &str| {
+            OsString::from(format!("Invalid cassette at line {}: {}", number + 1, message))
+        };
+
+        let (number, line) = lines.next().ok_or_else(|| OsString::from("The cassette is empty"))?;
+        let header: Value = serde_json::from_str(line).map_err(|err| invalid(number, &err.to_string()))?;
+        match header.get("cassette").and_then(Value::as_u64) {
+            Some(CASSETTE_VERSION) => (),
+            Some(version) => return Err(OsString::from(format!("Unsupported cassette version {}", version))),
+            None => return Err(invalid(number, "missing header")),
+        }
+        let size = |name: &str| header.get(name).and_then(Value::as_i64).and_then(|value| i32::try_from(value).ok());
+        let (cols, rows) = match (size("cols"), size("rows")) {
+            (Some(cols), Some(rows)) => (cols, rows),
+            _ => return Err(invalid(number, "missing size")),
+        };
+
+        let mut entries = Vec::new();
+        for (number, line) in lines {
+            let value: Value = serde_json::from_str(line).map_err(|err| invalid(number, &err.to_string()))?;
+            entries.push(CassetteEntry::from_value(&value).map_err(|err| invalid(number, &err))?);
+        }
+        Ok(Cassette { cols, rows, entries })
+    }
+
+    /// Load a cassette from a file.
+    pub fn load(path: &OsStr) -> Result<Cassette, OsString> {
+        let text = std::fs::read_to_string(path).map_err(|err| {
+            OsString::from(format!("Cannot read the cassette {}: {}", path.to_string_lossy(), err))
+        })?;
+        Cassette::parse(&text)
+    }
+
+    /// Save the cassette into a file.
+    pub fn save(&self, path: &OsStr) -> Result<(), OsString> {
+        std::fs::write(path, self.to_string()).map_err(|err| {
+            OsString::from(format!("Cannot write the cassette {}: {}", path.to_string_lossy(), err))
+        })
+    }
+}
+
+impl fmt::Display for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", header(self.cols, self.rows))?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.to_value())?;
+        }
+        Ok(())
+    }
+}
+
+/// Cassette that is being written.
+struct Log {
+    file: BufWriter<File>,
+    /// Time when the process was spawned.
+    start: Option<Instant>,
+    exited: bool,
+    /// First error raised while writing the cassette, after which nothing else is recorded.
+    /// It is reported by [`Recorder::close`], so that the session itself is not disrupted.
+    error: Option<OsString>,
+}
+
+impl Log {
+    fn record(&mut self, event: CassetteEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let time = self.start.map(|start| start.elapsed()).unwrap_or_default();
+        let entry = CassetteEntry { time, event };
+        if let Err(err) = writeln!(self.file, "{}", entry.to_value()).and_then(|_| self.file.flush()) {
+            self.error = Some(OsString::from(format!("Cannot record the cassette: {}", err)));
+        }
+    }
+
+    fn record_exit(&mut self, code: Option<u32>) {
+        if let (Some(code), false) = (code, self.exited) {
+            self.exited = true;
+            self.record(CassetteEvent::Exit(code));
+        }
+    }
+}
+
+/// Backend that records the session of another backend into a cassette.
+///
+/// Failing to write the cassette does not disrupt the session, the failure is reported
+/// when the PTY is closed.
+pub struct Recorder {
+    inner: Box<dyn PTYImpl>,
+    log: Mutex<Log>,
+}
+
+impl Recorder {
+    /// Wrap `inner`, writing its session into the cassette at `path`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(inner: Box<dyn PTYImpl>, args: &PTYArgs, path: &OsStr) -> Result<Box<dyn PTYImpl>, OsString> {
+        let file = File::create(path).map_err(|err| {
+            OsString::from(format!("Cannot create the cassette {}: {}", path.to_string_lossy(), err))
+        })?;
+        let mut file = BufWriter::new(file);
+        writeln!(file, "{}", header(args.cols, args.rows))
+            .and_then(|_| file.flush())
+            .map_err(|err| OsString::from(format!("Cannot record the cassette: {}", err)))?;
+        let log = Log { file, start: None, exited: false, error: None };
+        Ok(Box::new(Recorder { inner, log: Mutex::new(log) }))
+    }
+
+    fn log(&self) -> std::sync::MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn record_output(&self, output: Result<OsString, OsString>) -> Result<OsString, OsString> {
+        let output = output?;
+        if !output.is_empty() {
+            self.log().record(CassetteEvent::Output(output.to_string_lossy().into_owned()));
+        }
+        Ok(output)
+    }
+}
+
+impl PTYImpl for Recorder {
+    fn new(_args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString> {
+        Err(OsString::from("The recorder wraps another backend, see PTYArgs::cassette"))
+    }
+
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString> {
+        let event = CassetteEvent::Spawn {
+            appname: appname.to_string_lossy().into_owned(),
+            cmdline: cmdline.as_ref().map(|cmdline| cmdline.to_string_lossy().into_owned()),
+        };
+        let spawned = self.inner.spawn(appname, cmdline, cwd, env)?;
+        let mut log = self.log();
+        log.start = Some(Instant::now());
+        log.record(event);
+        Ok(spawned)
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
+        self.inner.set_size(cols, rows)?;
+        self.log().record(CassetteEvent::Resize { cols, rows });
+        Ok(())
+    }
+
+    fn read(&self, blocking: bool) -> Result<OsString, OsString> {
+        self.record_output(self.inner.read(blocking))
+    }
+
+    fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        self.record_output(self.inner.read_timeout(timeout))
+    }
+
+    fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+        self.record_output(self.inner.wait_idle(quiet, max))
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, OsString> {
+        let data = buf.to_string_lossy().into_owned();
+        let written = self.inner.write(buf)?;
+        self.log().record(CassetteEvent::Input(data));
+        Ok(written)
+    }
+
+    fn is_eof(&self) -> Result<bool, OsString> {
+        self.inner.is_eof()
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+        let status = self.inner.get_exitstatus()?;
+        self.log().record_exit(status);
+        Ok(status)
+    }
+
+    fn is_alive(&self) -> Result<bool, OsString> {
+        self.inner.is_alive()
+    }
+
+    fn get_pid(&self) -> u32 {
+        self.inner.get_pid()
+    }
+
+    fn get_fd(&self) -> isize {
+        self.inner.get_fd()
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, OsString> {
+        let exited = self.inner.wait_for_exit()?;
+        self.get_exitstatus()?;
+        Ok(exited)
+    }
+
+    fn cancel_io(&self) -> Result<bool, OsString> {
+        self.inner.cancel_io()
+    }
+
+    fn signal(&self, signal: Signal) -> Result<(), OsString> {
+        self.inner.signal(signal)
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        self.inner.kill()
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        self.inner.kill_tree()
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        self.inner.get_tree_pids()
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        self.inner.resource_usage()
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        let status = self.inner.close()?;
+        let mut log = self.log();
+        if let Some(status) = &status {
+            if !status.output.is_empty() {
+                log.record(CassetteEvent::Output(status.output.to_string_lossy().into_owned()));
+            }
+            log.record_exit(Some(status.code));
+        }
+        match log.error.take() {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Record the exit code of a process that exited without being asked about it.
+        if let Ok(status) = self.inner.get_exitstatus() {
+            self.log().record_exit(status);
+        }
+    }
+}
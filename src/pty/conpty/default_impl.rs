@@ -1,7 +1,8 @@
 use std::ffi::OsString;
+use std::time::Duration;
 
 // Default implementation if winpty is not available
-use crate::pty::{PTYArgs, PTYImpl};
+use crate::pty::{ExitStatus, PTYArgs, PTYImpl, ResourceUsage, Signal};
 
 pub struct ConPTY {}
 
@@ -22,6 +23,14 @@ impl PTYImpl for ConPTY {
         Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
     }
 
+    fn read_timeout(&self, _timeout: Duration) -> Result<OsString, OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn wait_idle(&self, _quiet: Duration, _max: Duration) -> Result<OsString, OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
     fn write(&self, _buf: OsString) -> Result<u32, OsString> {
         Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
     }
@@ -53,4 +62,28 @@ impl PTYImpl for ConPTY {
     fn cancel_io(&self) -> Result<bool, OsString> {
         Err(OsString::from("winpty_rs was compiled without ConPTY enabled"))
     }
+
+    fn signal(&self, _signal: Signal) -> Result<(), OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        Err(OsString::from("pty_rs was compiled without ConPTY enabled"))
+    }
 }
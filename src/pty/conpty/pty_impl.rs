@@ -24,9 +24,9 @@ use windows::Win32::System::Console::{
 use windows::Win32::System::Pipes::CreatePipe;
 use windows::Win32::System::Threading::{
     CreateProcessW, DeleteProcThreadAttributeList, GetCurrentProcess,
-    InitializeProcThreadAttributeList, UpdateProcThreadAttribute, CREATE_UNICODE_ENVIRONMENT,
-    EXTENDED_STARTUPINFO_PRESENT, LPPROC_THREAD_ATTRIBUTE_LIST, PROCESS_INFORMATION,
-    STARTUPINFOEXW, STARTUPINFOW,
+    InitializeProcThreadAttributeList, ResumeThread, TerminateProcess, UpdateProcThreadAttribute,
+    CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT,
+    LPPROC_THREAD_ATTRIBUTE_LIST, PROCESS_INFORMATION, STARTUPINFOEXW, STARTUPINFOW,
 };
 use windows::Win32::System::WindowsProgramming::RtlInitUnicodeString;
 use windows::Win32::System::IO::IO_STATUS_BLOCK;
@@ -41,10 +41,19 @@ use std::os::windows::ffi::OsStrExt;
 use std::sync::{mpsc, Arc, Condvar, Mutex};
 use std::thread::JoinHandle;
 use std::{mem, ptr, thread};
+use std::time::Duration;
 
 use super::calls::{ClosePseudoConsole, CreatePseudoConsole, ResizePseudoConsole, ShowHidePseudoConsole};
+use crate::resolver::{launch_command, resolve_executable};
 use crate::pty::PTYArgs;
-use crate::pty::{PTYImpl, PTYProcess};
+use crate::pty::{
+    ExitStatus, JobObject, ResourceUsage, PTYImpl, PTYProcess, ShutdownPolicy, Signal, KILL_GRACE_PERIOD,
+};
+
+/// Ctrl+Break key press and release, encoded using the win32-input-mode
+/// sequence (`ESC [ Vk ; Sc ; Uc ; Kd ; Cs ; Rc _`) understood by ConPTY.
+/// ConPTY translates it into a `CTRL_BREAK_EVENT` for the attached processes.
+const CTRL_BREAK_SEQUENCE: &str = "\x1b[3;70;0;1;8;1_\x1b[3;70;0;0;8;1_";
 
 /// Struct that contains the required information to spawn a console
 /// using the Windows API `CreatePseudoConsole` call.
@@ -54,8 +63,10 @@ pub struct ConPTY {
     startup_info: STARTUPINFOEXW,
     process: PTYProcess,
     console_allocated: bool,
+    contain_tree: bool,
+    shutdown_policy: ShutdownPolicy,
     release_info_tx: mpsc::Sender<(isize, isize, isize, isize, bool)>,
-    cleanup_thread: JoinHandle<()>,
+    cleanup_thread: Option<JoinHandle<()>>,
     cleanup_tx: mpsc::Sender<bool>
 }
 
@@ -74,6 +85,20 @@ fn cleanup(
     }
 }
 
+impl ConPTY {
+    /// Close the pseudoconsole, which sends a `CTRL_CLOSE_EVENT` to all the
+    /// processes attached to it.
+    fn close_console(&self) {
+        unsafe {
+            let mut guard = self.handle.lock().unwrap();
+            if guard.1 {
+                let _ = ClosePseudoConsole(guard.0);
+                *guard = (guard.0, false);
+            }
+        }
+    }
+}
+
 unsafe impl Send for ConPTY {}
 unsafe impl Sync for ConPTY {}
 
@@ -419,18 +444,44 @@ impl PTYImpl for ConPTY {
             let (release_info_tx, release_info_rx) =
                 mpsc::channel::<(isize, isize, isize, isize, bool)>();
 
-            let pty_process = PTYProcess::new(
-                server_pipe.into(),
-                server_pipe.into(),
-                true,
-                true,
-                Some(cleanup_tx.clone()),
-            );
-
             let hpcon_mutex = Arc::new(Mutex::new((pty_handle, true)));
             let hpcon_clone = Arc::clone(&hpcon_mutex);
 
-            let cleanup_thread = thread::spawn(move || {
+            let mut pty_process = if args.shared_reactor {
+                // The pseudoconsole is released by the reactor once the output is drained.
+                let hpcon_drained = Arc::clone(&hpcon_mutex);
+                let on_drained = move || {
+                    let mut hpcon_guard = hpcon_drained.lock().unwrap();
+                    if hpcon_guard.1 {
+                        cleanup(hpcon_guard.0.0, console_allocated);
+                        *hpcon_guard = (hpcon_guard.0, false);
+                    }
+                };
+                PTYProcess::new_shared(
+                    server_pipe.into(),
+                    server_pipe.into(),
+                    Some(Box::new(on_drained)),
+                    args.drain_timeout,
+                )?
+            } else {
+                PTYProcess::new(
+                    server_pipe.into(),
+                    server_pipe.into(),
+                    true,
+                    true,
+                    Some(cleanup_tx.clone()),
+                    args.drain_timeout,
+                )
+            };
+
+            let contain_tree =
+                args.contain_tree || args.shutdown_policy == ShutdownPolicy::TerminateTree;
+            if contain_tree {
+                let kill_on_close = args.shutdown_policy != ShutdownPolicy::CloseConsole;
+                pty_process.set_job(JobObject::new(kill_on_close)?);
+            }
+
+            let cleanup_thread = (!args.shared_reactor).then(|| thread::spawn(move || {
                 match release_info_rx.recv() {
                     Ok((_hthread_ptr, _hprocess_ptr, _startup_ptr, hpcon_ptr, console_allocated))=>{
                         if let Ok(clean) = cleanup_rx.recv(){
@@ -453,7 +504,7 @@ impl PTYImpl for ConPTY {
                 }
                 drop(cleanup_rx);
                 drop(release_info_rx);
-            });
+            }));
 
             Ok(Box::new(ConPTY {
                 handle: hpcon_mutex,
@@ -461,6 +512,8 @@ impl PTYImpl for ConPTY {
                 startup_info: STARTUPINFOEXW::default(),
                 process: pty_process,
                 console_allocated,
+                contain_tree,
+                shutdown_policy: args.shutdown_policy,
                 release_info_tx,
                 cleanup_thread,
                 cleanup_tx
@@ -482,9 +535,18 @@ impl PTYImpl for ConPTY {
         let mut cwd_buf: Vec<u16>;
         let cmd_buf: Vec<u16>;
 
-        let mut cmdline_oss = OsString::new();
-        cmdline_oss.clone_from(&appname);
-        let mut cmdline_oss_buf: Vec<u16> = cmdline_oss.encode_wide().collect();
+        // Both backends resolve the executable the same way, instead of relying on the
+        // search performed by CreateProcessW.
+        let program = resolve_executable(&appname, cwd.as_deref(), env.as_deref())?;
+        let (appname, cmdline) = launch_command(&program, cmdline, env.as_deref());
+        let mut app_buf: Vec<u16> = appname.encode_wide().collect();
+        app_buf.push(0);
+
+        let mut cmdline_oss_buf: Vec<u16> = appname.encode_wide().collect();
+        if cmdline_oss_buf.contains(&0x0020) {
+            cmdline_oss_buf.insert(0, 0x0022);
+            cmdline_oss_buf.push(0x0022);
+        }
 
         if let Some(env_opt) = env {
             env_buf = env_opt.encode_wide().collect();
@@ -573,13 +635,20 @@ impl PTYImpl for ConPTY {
             let si_ptr_addr = si_ptr as usize;
             let si_w_ptr = si_ptr_addr as *const STARTUPINFOW;
 
+            let mut creation_flags = EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT;
+            if self.contain_tree {
+                // Keep the process suspended until it is assigned to the job,
+                // so that none of its descendants can escape from it.
+                creation_flags |= CREATE_SUSPENDED;
+            }
+
             let succ = CreateProcessW(
-                PCWSTR(ptr::null_mut()),
+                PCWSTR(app_buf.as_ptr()),
                 Some(PWSTR(cmd)),
                 None,
                 None,
                 false,
-                EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+                creation_flags,
                 Some(environ as _),
                 PCWSTR(working_dir),
                 si_w_ptr.as_ref().unwrap(),
@@ -594,16 +663,26 @@ impl PTYImpl for ConPTY {
                 return Err(string);
             }
 
-            self.process.set_process(self.process_info.hProcess, false);
-            self.release_info_tx
+            // The process handle is owned by the PTYProcess, which closes it only
+            // after the thread that waits on it exits.
+            self.process.set_process(self.process_info.hProcess, true);
+            if self.contain_tree {
+                if let Err(err) = self.process.assign_to_job() {
+                    let _ = TerminateProcess(self.process_info.hProcess, 1);
+                    return Err(err);
+                }
+                ResumeThread(self.process_info.hThread);
+            }
+
+            // There is no cleanup thread to notify when the output is read by the reactor.
+            let _ = self.release_info_tx
                 .send((
                     self.process_info.hProcess.0 as isize,
                     self.process_info.hThread.0 as isize,
                     self.startup_info.lpAttributeList.0 as isize,
                     handle.0.0,
                     self.console_allocated,
-                ))
-                .unwrap();
+                ));
             Ok(true)
         }
     }
@@ -638,6 +717,14 @@ impl PTYImpl for ConPTY {
         self.process.read(blocking)
     }
 
+    fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        self.process.read_timeout(timeout)
+    }
+
+    fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+        self.process.wait_idle(quiet, max)
+    }
+
     fn write(&self, buf: OsString) -> Result<u32, OsString> {
         self.process.write(buf)
     }
@@ -669,10 +756,63 @@ impl PTYImpl for ConPTY {
     fn cancel_io(&self) -> Result<bool, OsString> {
         self.process.cancel_io()
     }
+
+    fn signal(&self, signal: Signal) -> Result<(), OsString> {
+        match signal {
+            Signal::Interrupt => self.process.write(OsString::from("\x03")).map(|_| ()),
+            Signal::Break => self.process.write(OsString::from(CTRL_BREAK_SEQUENCE)).map(|_| ()),
+            Signal::Terminate => {
+                if self.process.get_pid() == 0 {
+                    return Err(OsString::from("No process has been spawned"));
+                }
+                self.close_console();
+                Ok(())
+            }
+            Signal::Kill => self.process.terminate(1),
+        }
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        self.signal(Signal::Terminate)?;
+        if !self.process.wait_for_exit_timeout(KILL_GRACE_PERIOD)? {
+            self.process.terminate(1)?;
+        }
+        Ok(())
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        self.process.kill_tree()
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        self.process.get_tree_pids()
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        self.process.resource_usage()
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        let terminated = self
+            .process
+            .shutdown(self.shutdown_policy, || self.close_console())?;
+        match self.process.get_exitstatus()? {
+            Some(code) => Ok(Some(ExitStatus {
+                code,
+                terminated,
+                output: self.process.drain(),
+            })),
+            None => Ok(None),
+        }
+    }
 }
 
 impl Drop for ConPTY {
     fn drop(&mut self) {
+        let _ = self
+            .process
+            .shutdown(self.shutdown_policy, || self.close_console());
+
         unsafe {
             self.cleanup_tx.send(false).unwrap_or(());
 
@@ -680,10 +820,6 @@ impl Drop for ConPTY {
                 let _ = CloseHandle(self.process_info.hThread);
             }
 
-            if !self.process_info.hProcess.is_invalid() {
-                let _ = CloseHandle(self.process_info.hProcess);
-            }
-
             DeleteProcThreadAttributeList(self.startup_info.lpAttributeList);
             let mut guard =  self.handle.lock().unwrap();
             if guard.1 {
@@ -0,0 +1,125 @@
+/// Job objects used to contain a process running inside a PTY together with all its descendants.
+
+use windows::core::{Error, HRESULT, PCWSTR};
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicProcessIdList,
+    JobObjectExtendedLimitInformation, QueryInformationJobObject, SetInformationJobObject,
+    TerminateJobObject, JOBOBJECT_BASIC_PROCESS_ID_LIST, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
+
+use core::ffi::c_void;
+use std::ffi::OsString;
+use std::{mem, ptr, slice};
+
+use super::base::LocalHandle;
+
+/// Maximum number of process identifiers retrieved from a job at once.
+const MAX_JOB_PIDS: usize = 4096;
+
+fn last_error() -> OsString {
+    let result: HRESULT = Error::from_thread().into();
+    OsString::from(result.message())
+}
+
+/// Wrapper around a Windows job object. Every process assigned to the job,
+/// as well as all the processes that they spawn afterwards, belong to it.
+pub struct JobObject {
+    handle: LocalHandle,
+}
+
+impl JobObject {
+    /// Create a new anonymous job object.
+    ///
+    /// # Arguments
+    /// * `kill_on_close` - If `true`, all the processes in the job are terminated
+    /// once the last handle to the job is closed.
+    pub fn new(kill_on_close: bool) -> Result<JobObject, OsString> {
+        unsafe {
+            let handle = match CreateJobObjectW(None, PCWSTR(ptr::null())) {
+                Ok(handle) => handle,
+                Err(err) => return Err(OsString::from(err.message())),
+            };
+            let job = JobObject { handle: handle.into() };
+
+            if kill_on_close {
+                let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                if SetInformationJobObject(
+                    handle,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const c_void,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                )
+                .is_err()
+                {
+                    return Err(last_error());
+                }
+            }
+
+            Ok(job)
+        }
+    }
+
+    /// Assign a process (and its future descendants) to the job.
+    pub fn assign(&self, process: HANDLE) -> Result<(), OsString> {
+        unsafe {
+            if AssignProcessToJobObject(self.handle.into(), process).is_ok() {
+                Ok(())
+            } else {
+                Err(last_error())
+            }
+        }
+    }
+
+    /// Terminate all the processes that belong to the job.
+    pub fn terminate(&self, exit_code: u32) -> Result<(), OsString> {
+        unsafe {
+            if TerminateJobObject(self.handle.into(), exit_code).is_ok() {
+                Ok(())
+            } else {
+                Err(last_error())
+            }
+        }
+    }
+
+    /// Retrieve the identifiers of all the processes that belong to the job.
+    pub fn pids(&self) -> Result<Vec<u32>, OsString> {
+        // The list header is followed by `MAX_JOB_PIDS` pointer-sized entries.
+        let header = mem::size_of::<JOBOBJECT_BASIC_PROCESS_ID_LIST>();
+        let mut buf: Vec<usize> = vec![0; header / mem::size_of::<usize>() + MAX_JOB_PIDS];
+        let buf_bytes = buf.len() * mem::size_of::<usize>();
+
+        unsafe {
+            if QueryInformationJobObject(
+                Some(self.handle.into()),
+                JobObjectBasicProcessIdList,
+                buf.as_mut_ptr() as *mut c_void,
+                buf_bytes as u32,
+                None,
+            )
+            .is_err()
+            {
+                return Err(last_error());
+            }
+
+            let list = &*(buf.as_ptr() as *const JOBOBJECT_BASIC_PROCESS_ID_LIST);
+            let pids = slice::from_raw_parts(
+                list.ProcessIdList.as_ptr(),
+                list.NumberOfProcessIdsInList as usize,
+            );
+            Ok(pids.iter().map(|pid| *pid as u32).collect())
+        }
+    }
+}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        if !self.handle.is_invalid() {
+            unsafe {
+                let _ = CloseHandle(Into::<HANDLE>::into(self.handle));
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+/// Introspection of the processes that run inside a PTY.
+
+use windows::core::{Error, HRESULT};
+use windows::Wdk::System::Threading::{
+    NtQueryInformationProcess, ProcessBasicInformation, ProcessCommandLineInformation,
+};
+use windows::Win32::Foundation::{CloseHandle, FILETIME, HANDLE, UNICODE_STRING};
+use windows::Win32::System::Diagnostics::Debug::ReadProcessMemory;
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+};
+use windows::Win32::System::Threading::{
+    GetProcessTimes, OpenProcess, PEB, PROCESS_BASIC_INFORMATION,
+    PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_VM_READ,
+};
+
+use core::ffi::c_void;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
+use std::mem;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStringExt;
+
+/// Offset of `CurrentDirectory.DosPath` inside `RTL_USER_PROCESS_PARAMETERS`.
+#[cfg(target_pointer_width = "64")]
+const CURRENT_DIRECTORY_OFFSET: usize = 0x38;
+#[cfg(target_pointer_width = "32")]
+const CURRENT_DIRECTORY_OFFSET: usize = 0x24;
+
+/// Information about a process running inside a PTY.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessInfo {
+    /// Identifier of the process.
+    pub pid: u32,
+    /// Identifier of the parent process.
+    pub parent_pid: u32,
+    /// Name of the executable file of the process.
+    pub name: OsString,
+    /// Command line used to start the process, if it could be retrieved.
+    pub cmdline: Option<OsString>,
+    /// Current working directory of the process, if it could be retrieved.
+    pub cwd: Option<OsString>,
+    /// Creation time of the process, expressed in 100-nanosecond intervals since January 1, 1601 (UTC),
+    /// if it could be retrieved.
+    creation_time: Option<u64>,
+}
+
+/// Process handle that is closed when dropped.
+struct ProcessHandle(HANDLE);
+
+impl ProcessHandle {
+    fn open(pid: u32) -> Option<ProcessHandle> {
+        unsafe {
+            // Elevated and protected processes deny reading their memory, but their
+            // creation time can still be queried.
+            OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ, false, pid)
+                .or_else(|_| OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid))
+                .ok()
+                .map(ProcessHandle)
+        }
+    }
+
+    fn creation_time(&self) -> Option<u64> {
+        let mut creation_time = FILETIME::default();
+        let mut exit_time = FILETIME::default();
+        let mut kernel_time = FILETIME::default();
+        let mut user_time = FILETIME::default();
+        unsafe {
+            GetProcessTimes(
+                self.0,
+                &mut creation_time,
+                &mut exit_time,
+                &mut kernel_time,
+                &mut user_time,
+            )
+            .ok()?;
+        }
+        Some(((creation_time.dwHighDateTime as u64) << 32) | creation_time.dwLowDateTime as u64)
+    }
+
+    fn cmdline(&self) -> Option<OsString> {
+        let mut required = 0u32;
+        unsafe {
+            // The first call fails, but it reports the size of the buffer (Windows 8.1+).
+            let _ = NtQueryInformationProcess(
+                self.0,
+                ProcessCommandLineInformation,
+                std::ptr::null_mut(),
+                0,
+                &mut required,
+            );
+            if required == 0 {
+                return None;
+            }
+
+            let mut buf: Vec<usize> = vec![0; (required as usize).div_ceil(mem::size_of::<usize>())];
+            let status = NtQueryInformationProcess(
+                self.0,
+                ProcessCommandLineInformation,
+                buf.as_mut_ptr() as *mut c_void,
+                required,
+                &mut required,
+            );
+            if status.is_err() {
+                return None;
+            }
+
+            let cmdline = &*(buf.as_ptr() as *const UNICODE_STRING);
+            let chars = std::slice::from_raw_parts(
+                cmdline.Buffer.0,
+                cmdline.Length as usize / mem::size_of::<u16>(),
+            );
+            Some(OsString::from_wide(chars))
+        }
+    }
+
+    fn read<T: Default>(&self, address: usize) -> Option<T> {
+        let mut value = T::default();
+        unsafe {
+            ReadProcessMemory(
+                self.0,
+                address as *const c_void,
+                &mut value as *mut T as *mut c_void,
+                mem::size_of::<T>(),
+                None,
+            )
+            .ok()?;
+        }
+        Some(value)
+    }
+
+    fn cwd(&self) -> Option<OsString> {
+        let mut info = PROCESS_BASIC_INFORMATION::default();
+        let mut length = 0u32;
+        unsafe {
+            let status = NtQueryInformationProcess(
+                self.0,
+                ProcessBasicInformation,
+                &mut info as *mut _ as *mut c_void,
+                mem::size_of::<PROCESS_BASIC_INFORMATION>() as u32,
+                &mut length,
+            );
+            if status.is_err() || info.PebBaseAddress.is_null() {
+                return None;
+            }
+        }
+
+        // Both processes must share the same architecture for the offsets to be valid.
+        let peb: PEB = self.read(info.PebBaseAddress as usize)?;
+        let params = peb.ProcessParameters as usize;
+        if params == 0 {
+            return None;
+        }
+
+        let dos_path: UNICODE_STRING = self.read(params + CURRENT_DIRECTORY_OFFSET)?;
+        let length = dos_path.Length as usize / mem::size_of::<u16>();
+        let mut chars: Vec<u16> = vec![0; length];
+        unsafe {
+            ReadProcessMemory(
+                self.0,
+                dos_path.Buffer.0 as *const c_void,
+                chars.as_mut_ptr() as *mut c_void,
+                dos_path.Length as usize,
+                None,
+            )
+            .ok()?;
+        }
+        Some(OsString::from_wide(&chars))
+    }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// Take a snapshot of all the processes in the system, given as `(pid, parent pid, name)` tuples.
+fn snapshot() -> Result<Vec<(u32, u32, OsString)>, OsString> {
+    let mut processes = Vec::new();
+    unsafe {
+        let snapshot = match CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) {
+            Ok(handle) => handle,
+            Err(err) => return Err(OsString::from(err.message())),
+        };
+
+        let mut entry = PROCESSENTRY32W {
+            dwSize: mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        let mut next = Process32FirstW(snapshot, &mut entry).is_ok();
+        while next {
+            let name_len = entry
+                .szExeFile
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            processes.push((
+                entry.th32ProcessID,
+                entry.th32ParentProcessID,
+                OsString::from_wide(&entry.szExeFile[..name_len]),
+            ));
+            next = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+
+        let _ = CloseHandle(snapshot);
+    }
+
+    if processes.is_empty() {
+        let err: HRESULT = Error::from_thread().into();
+        return Err(OsString::from(err.message()));
+    }
+    Ok(processes)
+}
+
+/// Retrieve a process and all of its descendants, in breadth-first order.
+///
+/// # Arguments
+/// * `root_pid` - Identifier of the process at the root of the tree.
+pub fn process_tree(root_pid: u32) -> Result<Vec<ProcessInfo>, OsString> {
+    let processes = snapshot()?;
+    let mut children: HashMap<u32, Vec<(u32, OsString)>> = HashMap::new();
+    let mut root = None;
+    for (pid, parent_pid, name) in processes {
+        if pid == root_pid {
+            root = Some((parent_pid, name));
+        } else if pid != 0 {
+            children.entry(parent_pid).or_default().push((pid, name));
+        }
+    }
+
+    let (root_parent_pid, root_name) = match root {
+        Some(root) => root,
+        None => return Err(OsString::from(format!("Process {} is not running", root_pid))),
+    };
+
+    let mut tree = Vec::new();
+    let mut pending = VecDeque::from([(root_pid, root_parent_pid, root_name)]);
+    while let Some((pid, parent_pid, name)) = pending.pop_front() {
+        let handle = ProcessHandle::open(pid);
+        let creation_time = handle.as_ref().and_then(|h| h.creation_time());
+
+        // Parent identifiers are not updated when a parent exits, a child created
+        // before its (reused) parent identifier cannot be one of its descendants.
+        // The check is skipped when either creation time is unknown.
+        let parent = tree.iter().find(|p: &&ProcessInfo| p.pid == parent_pid);
+        if let (Some(time), Some(parent_time)) = (creation_time, parent.and_then(|p| p.creation_time)) {
+            if time < parent_time {
+                continue;
+            }
+        }
+
+        tree.push(ProcessInfo {
+            pid,
+            parent_pid,
+            name,
+            cmdline: handle.as_ref().and_then(|h| h.cmdline()),
+            cwd: handle.as_ref().and_then(|h| h.cwd()),
+            creation_time,
+        });
+
+        if let Some(descendants) = children.remove(&pid) {
+            for (child_pid, child_name) in descendants {
+                pending.push_back((child_pid, pid, child_name));
+            }
+        }
+    }
+    Ok(tree)
+}
+
+/// Retrieve the process that is currently in the foreground of a tree, i.e., the
+/// most recently created process that has not spawned other processes. It is the
+/// root process itself if it has no descendants.
+///
+/// # Arguments
+/// * `root_pid` - Identifier of the process at the root of the tree.
+pub fn foreground_process(root_pid: u32) -> Result<Option<ProcessInfo>, OsString> {
+    let tree = process_tree(root_pid)?;
+    let foreground = tree
+        .iter()
+        .filter(|p| !tree.iter().any(|c| c.parent_pid == p.pid && c.pid != p.pid))
+        .max_by_key(|p| p.creation_time)
+        .cloned();
+    Ok(foreground)
+}
@@ -0,0 +1,402 @@
+/// Process-wide I/O reactor that reads the output of many PTYs from a single thread.
+///
+/// The output pipes are associated with a single I/O completion port and read using
+/// overlapped operations, whose completions are dispatched by the reactor thread. The exit
+/// of each process is awaited by the wait threads of the system thread pool, which wait on
+/// up to 63 handles each, instead of a dedicated thread per PTY.
+///
+/// Only the async named pipes used by the ConPTY backend can be read by the reactor.
+
+use windows::Win32::Foundation::{ERROR_BROKEN_PIPE, ERROR_IO_PENDING, HANDLE, INVALID_HANDLE_VALUE};
+use windows::Win32::Storage::FileSystem::ReadFile;
+use windows::Win32::System::Threading::{
+    RegisterWaitForSingleObject, SetEvent, UnregisterWaitEx, INFINITE, WT_EXECUTEONLYONCE,
+};
+use windows::Win32::System::IO::{
+    CancelIoEx, CreateIoCompletionPort, GetQueuedCompletionStatus, PostQueuedCompletionStatus,
+    OVERLAPPED,
+};
+
+use core::ffi::c_void;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+
+use super::base::{LocalHandle, OutputClock};
+
+/// Size of the buffer of each overlapped read.
+const BUFFER_SIZE: usize = 32768;
+
+/// Interval between the cancellation attempts of a read that did not complete once
+/// the drain timeout elapsed.
+const CANCEL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Channel used to deliver the output of a process, with the same semantics as the
+/// one used by the reader threads of [`PTYProcess`](super::PTYProcess).
+pub type OutputSender = Sender<Option<Result<OsString, OsString>>>;
+
+static REACTOR: OnceLock<Result<Reactor, OsString>> = OnceLock::new();
+
+/// Overlapped read in flight. The `OVERLAPPED` struct must be the first field, since its
+/// address identifies the operation when it is dequeued from the completion port.
+#[repr(C)]
+struct ReadOp {
+    overlapped: OVERLAPPED,
+    buf: [u8; BUFFER_SIZE],
+}
+
+/// State of a pipe registered in the reactor.
+struct Entry {
+    conout: LocalHandle,
+    op: Box<ReadOp>,
+    /// Incomplete UTF-8 sequence found at the end of the last read.
+    pending: Vec<u8>,
+    output: Option<OutputSender>,
+    /// Updated every time output is received.
+    clock: OutputClock,
+    /// Set to `false` once the output reaches EOF.
+    reading: Arc<AtomicBool>,
+    /// Manual-reset event signaled once the output reaches EOF.
+    exit_event: LocalHandle,
+    /// Thread pool wait registered on the process handle.
+    wait: LocalHandle,
+    in_flight: bool,
+    exited: bool,
+    drain_timeout: Duration,
+    /// Instant on which the read in flight gets cancelled, set once the process exits.
+    deadline: Option<Instant>,
+    on_drained: Option<Box<dyn FnOnce() + Send>>,
+    /// The registration was dropped while a read was in flight. The entry is removed
+    /// once the read completes, since its buffer cannot be freed before.
+    orphaned: bool,
+}
+
+unsafe impl Send for Entry {}
+
+impl Entry {
+    fn start_read(&mut self) -> windows::core::Result<()> {
+        self.op.overlapped = OVERLAPPED::default();
+        unsafe {
+            // The completion is queued to the port even if the read finishes right away.
+            match ReadFile(
+                self.conout.into(),
+                Some(&mut self.op.buf[..]),
+                None,
+                Some(&mut self.op.overlapped),
+            ) {
+                Ok(_) => (),
+                Err(err) if err.code() == ERROR_IO_PENDING.into() => (),
+                Err(err) => return Err(err),
+            }
+        }
+        self.in_flight = true;
+        Ok(())
+    }
+
+    fn cancel(&mut self) {
+        unsafe {
+            let _ = CancelIoEx(self.conout.into(), Some(&self.op.overlapped));
+        }
+    }
+
+    /// Convert the bytes read into a string, keeping any incomplete UTF-8 sequence for the next read.
+    fn decode(&mut self, bytes: usize) -> OsString {
+        self.pending.extend_from_slice(&self.op.buf[..bytes]);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        OsString::from(text)
+    }
+
+    /// Deliver the last message of the output, after which the channel is closed.
+    /// `None` means that the output reached EOF.
+    fn finish(&mut self, last: Option<Result<OsString, OsString>>) {
+        if let Some(output) = self.output.take() {
+            if !self.pending.is_empty() {
+                let text = String::from_utf8_lossy(&self.pending).into_owned();
+                self.clock.touch();
+                let _ = output.send(Some(Ok(OsString::from(text))));
+                self.pending.clear();
+            }
+            let _ = output.send(last);
+        }
+        self.reading.store(false, Ordering::Release);
+        unsafe {
+            let _ = SetEvent(self.exit_event.into());
+        }
+        self.check_drained();
+    }
+
+    /// Finish the output after a read failed, a broken pipe being its EOF.
+    fn fail(&mut self, err: windows::core::Error) {
+        match err.code() == ERROR_BROKEN_PIPE.into() {
+            true => self.finish(None),
+            false => self.finish(Some(Err(OsString::from(err.message())))),
+        }
+    }
+
+    /// Run the drain callback once the process exited and its output reached EOF. It
+    /// runs on its own thread, since it may block (e.g., `ClosePseudoConsole`).
+    fn check_drained(&mut self) {
+        if self.exited && !self.in_flight {
+            if let Some(on_drained) = self.on_drained.take() {
+                thread::spawn(on_drained);
+            }
+        }
+    }
+}
+
+/// Handle to a pipe registered in the reactor. Its output stops being delivered once it is dropped,
+/// after which the pipe is no longer used by the reactor and can be closed.
+pub struct Registration {
+    key: usize,
+}
+
+impl Registration {
+    /// Wait for a process to exit, after which its output is drained for at most the drain
+    /// timeout given on [`Reactor::register`] before the read in flight is cancelled.
+    pub fn watch(&self, process: LocalHandle) -> Result<(), OsString> {
+        let reactor = reactor()?;
+        let mut entries = reactor.entries.lock().unwrap();
+        let entry = match entries.get_mut(&self.key) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+
+        let mut wait = HANDLE::default();
+        unsafe {
+            RegisterWaitForSingleObject(
+                &mut wait,
+                process.into(),
+                Some(on_process_exit),
+                Some(self.key as *const c_void),
+                INFINITE,
+                WT_EXECUTEONLYONCE,
+            )
+            .map_err(|err| OsString::from(err.message()))?;
+        }
+        entry.wait = wait.into();
+        Ok(())
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let reactor = match reactor() {
+            Ok(reactor) => reactor,
+            Err(_) => return,
+        };
+
+        let wait = reactor.entries.lock().unwrap().get(&self.key).map(|e| e.wait);
+        if let Some(wait) = wait.filter(|w| !w.is_invalid()) {
+            // Block until the exit callback finishes, if it is running. The lock must not be
+            // held, the callback does not take it but the reactor thread may need it.
+            unsafe {
+                let _ = UnregisterWaitEx(wait.into(), Some(INVALID_HANDLE_VALUE));
+            }
+        }
+
+        let mut entries = reactor.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&self.key) {
+            entry.output = None;
+            entry.on_drained = None;
+            if entry.in_flight {
+                entry.orphaned = true;
+            } else {
+                entries.remove(&self.key);
+            }
+        }
+
+        // Block until the read in flight completes, so that the pipe does not get closed
+        // while the reactor may still use it.
+        while let Some(entry) = entries.get_mut(&self.key) {
+            entry.cancel();
+            entries = reactor.retired.wait_timeout(entries, CANCEL_INTERVAL).unwrap().0;
+        }
+    }
+}
+
+unsafe extern "system" fn on_process_exit(context: *mut c_void, _timed_out: bool) {
+    if let Ok(reactor) = reactor() {
+        let _ = PostQueuedCompletionStatus(reactor.port.into(), 0, context as usize, None);
+    }
+}
+
+/// Retrieve the reactor of the current process, starting it if it is not running yet.
+pub fn reactor() -> Result<&'static Reactor, OsString> {
+    REACTOR.get_or_init(Reactor::start).as_ref().map_err(Clone::clone)
+}
+
+/// Single thread that reads the output of all the registered pipes.
+pub struct Reactor {
+    port: LocalHandle,
+    next_key: AtomicUsize,
+    entries: Mutex<HashMap<usize, Entry>>,
+    /// Notified every time an orphaned entry is removed.
+    retired: Condvar,
+}
+
+impl Reactor {
+    fn start() -> Result<Reactor, OsString> {
+        let port = unsafe {
+            CreateIoCompletionPort(INVALID_HANDLE_VALUE, None, 0, 1).map_err(|err| OsString::from(err.message()))?
+        };
+
+        thread::Builder::new()
+            .name(String::from("winpty-rs-reactor"))
+            .spawn(|| {
+                // Blocks until the reactor is initialized.
+                if let Ok(reactor) = reactor() {
+                    reactor.run();
+                }
+            })
+            .map_err(|err| OsString::from(err.to_string()))?;
+
+        Ok(Reactor {
+            port: port.into(),
+            next_key: AtomicUsize::new(1),
+            entries: Mutex::new(HashMap::new()),
+            retired: Condvar::new(),
+        })
+    }
+
+    /// Register an async pipe and start to read from it right away.
+    ///
+    /// # Arguments
+    /// * `conout` - Handle to the output pipe, opened for overlapped I/O. It gets associated
+    ///   to the completion port of the reactor until it is closed.
+    /// * `output` - Channel where the output is delivered.
+    /// * `clock` - Clock updated every time output is received.
+    /// * `reading` - Flag set to `false` once the output reaches EOF.
+    /// * `exit_event` - Manual-reset event signaled once the output reaches EOF.
+    /// * `drain_timeout` - Maximum amount of time (in ms) to wait for the output to reach EOF
+    ///   after the process exits.
+    /// * `on_drained` - Optional callback invoked once the process exits and its output reaches EOF.
+    pub fn register(
+        &self,
+        conout: LocalHandle,
+        output: OutputSender,
+        clock: OutputClock,
+        reading: Arc<AtomicBool>,
+        exit_event: LocalHandle,
+        drain_timeout: u32,
+        on_drained: Option<Box<dyn FnOnce() + Send>>,
+    ) -> Result<Registration, OsString> {
+        let key = self.next_key.fetch_add(1, Ordering::SeqCst);
+        unsafe {
+            CreateIoCompletionPort(conout.into(), Some(self.port.into()), key, 0)
+                .map_err(|err| OsString::from(err.message()))?;
+        }
+
+        let mut entry = Entry {
+            conout,
+            op: Box::new(ReadOp {
+                overlapped: OVERLAPPED::default(),
+                buf: [0; BUFFER_SIZE],
+            }),
+            pending: Vec::new(),
+            output: Some(output),
+            clock,
+            reading,
+            exit_event,
+            wait: LocalHandle(ptr::null_mut()),
+            in_flight: false,
+            exited: false,
+            drain_timeout: Duration::from_millis(drain_timeout as u64),
+            deadline: None,
+            on_drained,
+            orphaned: false,
+        };
+        entry.reading.store(true, Ordering::Release);
+
+        let mut entries = self.entries.lock().unwrap();
+        if let Err(err) = entry.start_read() {
+            entry.fail(err);
+        }
+        entries.insert(key, entry);
+        Ok(Registration { key })
+    }
+
+    fn run(&self) {
+        loop {
+            let timeout = self.next_timeout();
+            let mut bytes = 0u32;
+            let mut key = 0usize;
+            let mut overlapped: *mut OVERLAPPED = ptr::null_mut();
+            let result = unsafe {
+                GetQueuedCompletionStatus(self.port.into(), &mut bytes, &mut key, &mut overlapped, timeout)
+            };
+
+            let mut entries = self.entries.lock().unwrap();
+            if overlapped.is_null() {
+                // Either the timeout elapsed or a process exited.
+                if let (Ok(_), Some(entry)) = (&result, entries.get_mut(&key)) {
+                    if !entry.orphaned && !entry.exited {
+                        entry.exited = true;
+                        entry.deadline = Some(Instant::now() + entry.drain_timeout);
+                        entry.check_drained();
+                    }
+                }
+            } else if let Some(entry) = entries.get_mut(&key) {
+                // Other operations over the same handle (i.e., writes) are not handled here.
+                if ptr::eq(overlapped, &entry.op.overlapped) {
+                    entry.in_flight = false;
+                    if entry.orphaned {
+                        entries.remove(&key);
+                        self.retired.notify_all();
+                    } else {
+                        match result {
+                            Ok(_) if bytes > 0 => {
+                                let text = entry.decode(bytes as usize);
+                                entry.clock.touch();
+                                if let Some(output) = &entry.output {
+                                    let _ = output.send(Some(Ok(text)));
+                                }
+                                if let Err(err) = entry.start_read() {
+                                    entry.fail(err);
+                                }
+                            }
+                            Ok(_) => entry.finish(None),
+                            Err(err) => entry.fail(err),
+                        }
+                    }
+                }
+            }
+
+            // Older ConPTY versions do not close the output pipe when the process exits.
+            let now = Instant::now();
+            for entry in entries.values_mut() {
+                if let Some(deadline) = entry.deadline {
+                    if entry.in_flight && deadline <= now {
+                        entry.cancel();
+                        entry.deadline = Some(now + CANCEL_INTERVAL);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Amount of time (in ms) until the next read has to be cancelled.
+    fn next_timeout(&self) -> u32 {
+        let entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries
+            .values()
+            .filter(|entry| entry.in_flight)
+            .filter_map(|entry| entry.deadline)
+            .map(|deadline| deadline.saturating_duration_since(now).as_millis().min(u32::MAX as u128 - 1) as u32)
+            .min()
+            .unwrap_or(INFINITE)
+    }
+}
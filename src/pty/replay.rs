@@ -0,0 +1,363 @@
+//! This module provides a [`super::PTY`] backend that serves back a session recorded into a
+//! [`Cassette`], so that the clients of a PTY can be tested deterministically.
+//!
+//! The recorded output is released with its original timing, measured from the input (or
+//! resize) that preceded it, and no output that follows an input is released until the
+//! same input is written. By default, unexpected input is ignored and resizes are not
+//! waited for. When [`PTYArgs::strict_replay`] is set, every write and resize must match
+//! the next one of the cassette, otherwise the replay fails.
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::cassette::{Cassette, CassetteEntry, CassetteEvent};
+use super::{ExitStatus, PTYArgs, PTYImpl, ResourceUsage, ShutdownPolicy, Signal};
+
+/// Exit code reported when the replayed process is killed before its recorded exit.
+const KILLED_EXIT_CODE: u32 = 1;
+
+/// Match the pending `input` against the `expected` one, consuming it on success.
+///
+/// In strict mode, the input must start with the expected one. Otherwise, the input that
+/// precedes it is discarded.
+fn match_input(input: &mut String, expected: &str, strict: bool) -> Result<bool, String> {
+    if let Some(start) = input.find(expected).filter(|&start| !strict || start == 0) {
+        input.drain(..start + expected.len());
+        return Ok(true);
+    }
+    if strict {
+        if !expected.starts_with(input.as_str()) {
+            return Err(format!("The input diverged from the cassette: expected {:?}, got {:?}", expected, input));
+        }
+        return Ok(false);
+    }
+    // Keep the end of the input that may be completed by the next write.
+    let keep = (1..=input.len().min(expected.len()))
+        .rev()
+        .find(|&len| input.is_char_boundary(input.len() - len) && expected.starts_with(&input[input.len() - len..]))
+        .unwrap_or(0);
+    input.drain(..input.len() - keep);
+    Ok(false)
+}
+
+/// State of a replayed session.
+struct State {
+    entries: Vec<CassetteEntry>,
+    /// Index of the next entry to replay.
+    next: usize,
+    strict: bool,
+    spawned: bool,
+    /// Time when the last input was matched, alongside its time in the cassette.
+    gate: (Instant, Duration),
+    /// Input that was written but not matched yet.
+    input: String,
+    /// Resizes that were requested but not matched yet, only tracked in strict mode.
+    resizes: VecDeque<(i32, i32)>,
+    /// Output that was released but not read yet.
+    ready: String,
+    /// Time when the last output was released.
+    last_output: Instant,
+    exit: Option<u32>,
+    /// Divergence that made the replay fail.
+    failure: Option<OsString>,
+    /// Whether a read already reported the end of the output, after which reads return
+    /// empty output as the other backends do.
+    eof_read: bool,
+}
+
+impl State {
+    fn fail(&mut self, message: String) -> OsString {
+        let failure = OsString::from(message);
+        self.failure = Some(failure.clone());
+        failure
+    }
+
+    /// Replay the entries that are due, returning when the next one of them will be.
+    fn advance(&mut self, now: Instant) -> Result<Option<Instant>, OsString> {
+        if let Some(failure) = &self.failure {
+            return Err(failure.clone());
+        }
+        if !self.spawned {
+            return Ok(None);
+        }
+        while let Some(entry) = self.entries.get(self.next) {
+            let due = self.gate.0 + entry.time.saturating_sub(self.gate.1);
+            match &entry.event {
+                CassetteEvent::Spawn { .. } => (),
+                CassetteEvent::Output(_) | CassetteEvent::Exit(_) if due > now => return Ok(Some(due)),
+                CassetteEvent::Output(data) => {
+                    self.ready.push_str(data);
+                    self.last_output = due;
+                }
+                CassetteEvent::Exit(code) => self.exit = Some(*code),
+                CassetteEvent::Input(expected) => match match_input(&mut self.input, expected, self.strict) {
+                    Ok(true) => self.gate = (now, entry.time),
+                    Ok(false) => return Ok(None),
+                    Err(message) => return Err(self.fail(message)),
+                },
+                CassetteEvent::Resize { cols, rows } if self.strict => match self.resizes.pop_front() {
+                    None => return Ok(None),
+                    Some(size) if size == (*cols, *rows) => self.gate = (now, entry.time),
+                    Some((c, r)) => {
+                        let message = format!("The resize diverged from the cassette: expected {}x{}, got {}x{}", cols, rows, c, r);
+                        return Err(self.fail(message));
+                    }
+                },
+                CassetteEvent::Resize { .. } => (),
+            }
+            self.next += 1;
+        }
+
+        if self.strict && (!self.input.is_empty() || !self.resizes.is_empty()) {
+            let message = match self.resizes.front() {
+                Some((cols, rows)) => format!("Unexpected resize to {}x{} after the end of the cassette", cols, rows),
+                None => format!("Unexpected input {:?} after the end of the cassette", self.input),
+            };
+            return Err(self.fail(message));
+        }
+        Ok(None)
+    }
+
+    fn is_eof(&self) -> bool {
+        self.spawned && self.next >= self.entries.len() && self.ready.is_empty()
+    }
+}
+
+/// Backend that replays a [`Cassette`], selected with [`super::PTYBackend::Replay`].
+pub struct Replay {
+    state: Mutex<State>,
+    /// Notified when input arrives or when the process is killed.
+    changed: Condvar,
+    shutdown_policy: ShutdownPolicy,
+}
+
+impl Replay {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Wait until `ready` holds for the state, or until `deadline`.
+    fn wait<F: FnMut(&mut State) -> bool>(&self, deadline: Option<Instant>, mut ready: F) -> Result<MutexGuard<'_, State>, OsString> {
+        let mut state = self.state();
+        loop {
+            let now = Instant::now();
+            let next_due = state.advance(now)?;
+            if ready(&mut state) {
+                return Ok(state);
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Ok(state);
+            }
+            let wake = match (next_due, deadline) {
+                (Some(due), Some(deadline)) => Some(due.min(deadline)),
+                (due, deadline) => due.or(deadline),
+            };
+            state = match wake {
+                Some(wake) => {
+                    let timeout = wake.saturating_duration_since(now);
+                    self.changed.wait_timeout(state, timeout).unwrap_or_else(|err| err.into_inner()).0
+                }
+                None => self.changed.wait(state).unwrap_or_else(|err| err.into_inner()),
+            };
+        }
+    }
+
+    fn take_output(mut state: MutexGuard<'_, State>) -> Result<OsString, OsString> {
+        if state.is_eof() && !state.eof_read {
+            state.eof_read = true;
+            return Err(OsString::from("Standard out reached EOF"));
+        }
+        Ok(OsString::from(std::mem::take(&mut state.ready)))
+    }
+
+    fn terminate(&self) {
+        let mut state = self.state();
+        if state.exit.is_none() {
+            state.exit = Some(KILLED_EXIT_CODE);
+        }
+        state.next = state.entries.len();
+        state.ready.clear();
+        self.changed.notify_all();
+    }
+}
+
+impl PTYImpl for Replay {
+    fn new(args: &PTYArgs) -> Result<Box<dyn PTYImpl>, OsString> {
+        let path = args.cassette.as_ref().ok_or_else(|| OsString::from("The Replay backend requires PTYArgs::cassette"))?;
+        let cassette = Cassette::load(path)?;
+        let state = State {
+            entries: cassette.entries,
+            next: 0,
+            strict: args.strict_replay,
+            spawned: false,
+            gate: (Instant::now(), Duration::ZERO),
+            input: String::new(),
+            resizes: VecDeque::new(),
+            ready: String::new(),
+            last_output: Instant::now(),
+            exit: None,
+            failure: None,
+            eof_read: false,
+        };
+        Ok(Box::new(Replay { state: Mutex::new(state), changed: Condvar::new(), shutdown_policy: args.shutdown_policy }))
+    }
+
+    fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, _cwd: Option<OsString>, _env: Option<OsString>) -> Result<bool, OsString> {
+        let mut state = self.state();
+        if state.spawned {
+            return Err(OsString::from("The process was already spawned"));
+        }
+        let recorded = state.entries.iter().find_map(|entry| match &entry.event {
+            CassetteEvent::Spawn { appname, cmdline } => Some((entry.time, appname.clone(), cmdline.clone())),
+            _ => None,
+        });
+        if let Some((time, recorded_appname, recorded_cmdline)) = recorded {
+            let cmdline = cmdline.map(|cmdline| cmdline.to_string_lossy().into_owned());
+            if state.strict && (appname.to_string_lossy() != recorded_appname || cmdline != recorded_cmdline) {
+                return Err(OsString::from(format!(
+                    "The spawned command diverged from the cassette: expected {:?} {:?}, got {:?} {:?}",
+                    recorded_appname, recorded_cmdline, appname, cmdline
+                )));
+            }
+            state.gate = (Instant::now(), time);
+        }
+        state.spawned = true;
+        state.last_output = Instant::now();
+        Ok(true)
+    }
+
+    fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
+        if cols <= 0 || rows <= 0 {
+            return Err(OsString::from("PTY cols and rows must be positive and non-zero."));
+        }
+        let mut state = self.state();
+        if state.strict {
+            state.resizes.push_back((cols, rows));
+            state.advance(Instant::now())?;
+            self.changed.notify_all();
+        }
+        Ok(())
+    }
+
+    fn read(&self, blocking: bool) -> Result<OsString, OsString> {
+        let deadline = (!blocking).then(Instant::now);
+        let state = self.wait(deadline, |state| !state.ready.is_empty() || state.is_eof())?;
+        Replay::take_output(state)
+    }
+
+    fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        let state = self.wait(Some(Instant::now() + timeout), |state| !state.ready.is_empty() || state.is_eof())?;
+        Replay::take_output(state)
+    }
+
+    fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+        let deadline = Instant::now() + max;
+        let mut output = String::new();
+        let mut state = self.state();
+        if state.is_eof() {
+            return Err(OsString::from("Standard out reached EOF"));
+        }
+        loop {
+            let now = Instant::now();
+            let next_due = state.advance(now)?;
+            output.push_str(&std::mem::take(&mut state.ready));
+            let idle = state.last_output + quiet;
+            if state.is_eof() || now >= idle.min(deadline) {
+                return Ok(OsString::from(output));
+            }
+            let wake = next_due.map_or(idle, |due| due.min(idle)).min(deadline);
+            let timeout = wake.saturating_duration_since(now);
+            state = self.changed.wait_timeout(state, timeout).unwrap_or_else(|err| err.into_inner()).0;
+        }
+    }
+
+    fn write(&self, buf: OsString) -> Result<u32, OsString> {
+        let mut state = self.state();
+        if !state.spawned {
+            return Err(OsString::from("The process has not been spawned yet"));
+        }
+        if state.exit.is_some() {
+            return Err(OsString::from("The process has exited"));
+        }
+        let data = buf.to_string_lossy();
+        state.input.push_str(&data);
+        state.advance(Instant::now())?;
+        self.changed.notify_all();
+        Ok(data.len() as u32)
+    }
+
+    fn is_eof(&self) -> Result<bool, OsString> {
+        let mut state = self.state();
+        state.advance(Instant::now())?;
+        Ok(state.is_eof())
+    }
+
+    fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+        let mut state = self.state();
+        state.advance(Instant::now())?;
+        Ok(state.exit)
+    }
+
+    fn is_alive(&self) -> Result<bool, OsString> {
+        Ok(self.get_exitstatus()?.is_none())
+    }
+
+    /// Replayed sessions do not have a process.
+    fn get_pid(&self) -> u32 {
+        0
+    }
+
+    fn get_fd(&self) -> isize {
+        -1
+    }
+
+    fn wait_for_exit(&self) -> Result<bool, OsString> {
+        let state = self.wait(None, |state| state.exit.is_some() || state.is_eof())?;
+        Ok(state.exit.is_some())
+    }
+
+    fn cancel_io(&self) -> Result<bool, OsString> {
+        Ok(false)
+    }
+
+    fn signal(&self, signal: Signal) -> Result<(), OsString> {
+        match signal {
+            Signal::Terminate | Signal::Kill => self.kill(),
+            _ => Err(OsString::from(format!("{:?} is not supported by the Replay backend", signal))),
+        }
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        self.terminate();
+        Ok(())
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        self.kill()
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        Err(OsString::from("Replayed sessions do not have processes"))
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        Err(OsString::from("Replayed sessions do not have processes"))
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        let exit = self.get_exitstatus()?;
+        let terminated = match (exit, self.shutdown_policy) {
+            (Some(_), _) => false,
+            (None, ShutdownPolicy::CloseConsole) => return Ok(None),
+            (None, _) => {
+                self.terminate();
+                true
+            }
+        };
+        let mut state = self.state();
+        let output = OsString::from(std::mem::take(&mut state.ready));
+        Ok(state.exit.map(|code| ExitStatus { code, terminated, output }))
+    }
+}
@@ -1,5 +1,6 @@
 use std::ffi::OsString;
-use crate::pty::{PTYArgs, PTYImpl};
+use std::time::Duration;
+use crate::pty::{ExitStatus, PTYArgs, PTYImpl, ResourceUsage, Signal};
 
 pub struct WinPTY {}
 
@@ -20,6 +21,14 @@ impl PTYImpl for WinPTY {
         Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
     }
 
+    fn read_timeout(&self, _timeout: Duration) -> Result<OsString, OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn wait_idle(&self, _quiet: Duration, _max: Duration) -> Result<OsString, OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
     fn write(&self, _buf: OsString) -> Result<u32, OsString> {
         Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
     }
@@ -51,4 +60,28 @@ impl PTYImpl for WinPTY {
     fn cancel_io(&self) -> Result<bool, OsString> {
         Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
     }
+
+    fn signal(&self, _signal: Signal) -> Result<(), OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        Err(OsString::from("winpty_rs was compiled without WinPTY enabled"))
+    }
 }
@@ -14,10 +14,14 @@ use std::slice::from_raw_parts;
 use std::ffi::{OsString, c_void};
 use std::os::windows::prelude::*;
 use std::os::windows::ffi::OsStrExt;
+use std::time::Duration;
 
 use super::bindings::*;
-use crate::pty::{PTYProcess, PTYImpl};
+use crate::pty::{
+    ExitStatus, JobObject, ResourceUsage, PTYProcess, PTYImpl, ShutdownPolicy, Signal, KILL_GRACE_PERIOD,
+};
 use crate::pty::PTYArgs;
+use crate::resolver::{launch_command, resolve_executable};
 
 struct WinPTYPtr {
     ptr: *mut winpty_t,
@@ -152,7 +156,8 @@ unsafe fn get_error_message(err_ptr: *mut winpty_error_ptr_t) -> OsString {
 /// FFi-safe wrapper around `winpty` library calls and objects.
 pub struct WinPTY {
     ptr: WinPTYPtr,
-    process: PTYProcess
+    process: PTYProcess,
+    shutdown_policy: ShutdownPolicy
 }
 
 impl PTYImpl for WinPTY {
@@ -223,8 +228,18 @@ impl PTYImpl for WinPTY {
             let conin = conin_res.unwrap();
             let conout = conout_res.unwrap();
 
-            let process = PTYProcess::new(conin.into(), conout.into(), false, false, None);
-            Ok(Box::new(WinPTY { ptr: pty_ptr, process }) as Box<dyn PTYImpl>)
+            let mut process = PTYProcess::new(
+                conin.into(), conout.into(), false, false, None, args.drain_timeout);
+            if args.contain_tree || args.shutdown_policy == ShutdownPolicy::TerminateTree {
+                let kill_on_close = args.shutdown_policy != ShutdownPolicy::CloseConsole;
+                process.set_job(JobObject::new(kill_on_close)?);
+            }
+
+            Ok(Box::new(WinPTY {
+                ptr: pty_ptr,
+                process,
+                shutdown_policy: args.shutdown_policy
+            }) as Box<dyn PTYImpl>)
         }
     }
 
@@ -237,9 +252,9 @@ impl PTYImpl for WinPTY {
         let mut cwd_buf: Vec<u16>;
         let mut cmd_buf: Vec<u16>;
 
-        let mut app_oss = OsString::new();
-        app_oss.clone_from(&appname);
-        let mut app_oss_buf: Vec<u16> = app_oss.encode_wide().collect();
+        let program = resolve_executable(&appname, cwd.as_deref(), env.as_deref())?;
+        let (appname, cmdline) = launch_command(&program, cmdline, env.as_deref());
+        let mut app_oss_buf: Vec<u16> = appname.encode_wide().collect();
         app_oss_buf.push(0);
 
         if let Some(env_opt) = env {
@@ -264,6 +279,12 @@ impl PTYImpl for WinPTY {
         match self.ptr.spawn(app, cmd, working_dir, environ) {
             Ok(handle) => {
                 self.process.set_process(handle, true);
+                // The process is spawned by the winpty agent, therefore it can only be
+                // assigned to the job after it has started.
+                if let Err(err) = self.process.assign_to_job() {
+                    let _ = self.process.terminate(1);
+                    return Err(err);
+                }
                 Ok(true)
             },
             Err(err) => {
@@ -285,6 +306,14 @@ impl PTYImpl for WinPTY {
         self.process.read(blocking)
     }
 
+    fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        self.process.read_timeout(timeout)
+    }
+
+    fn wait_idle(&self, quiet: Duration, max: Duration) -> Result<OsString, OsString> {
+        self.process.wait_idle(quiet, max)
+    }
+
     fn write(&self, buf: OsString) -> Result<u32, OsString> {
         self.process.write(buf)
     }
@@ -316,6 +345,56 @@ impl PTYImpl for WinPTY {
     fn cancel_io(&self) -> Result<bool, OsString> {
         self.process.cancel_io()
     }
+
+    fn signal(&self, signal: Signal) -> Result<(), OsString> {
+        match signal {
+            // The winpty agent translates ^C into a CTRL_C_EVENT on its console.
+            Signal::Interrupt => self.process.write(OsString::from("\x03")).map(|_| ()),
+            Signal::Break => Err(OsString::from("Ctrl+Break is not supported by the winpty backend")),
+            // The agent console cannot be closed without freeing the winpty instance.
+            Signal::Terminate => Err(OsString::from("Terminate is not supported by the winpty backend, use Kill instead")),
+            Signal::Kill => self.process.terminate(1),
+        }
+    }
+
+    fn kill(&self) -> Result<(), OsString> {
+        self.signal(Signal::Interrupt)?;
+        if !self.process.wait_for_exit_timeout(KILL_GRACE_PERIOD)? {
+            self.process.terminate(1)?;
+        }
+        Ok(())
+    }
+
+    fn kill_tree(&self) -> Result<(), OsString> {
+        self.process.kill_tree()
+    }
+
+    fn get_tree_pids(&self) -> Result<Vec<u32>, OsString> {
+        self.process.get_tree_pids()
+    }
+
+    fn resource_usage(&self) -> Result<ResourceUsage, OsString> {
+        self.process.resource_usage()
+    }
+
+    fn close(&mut self) -> Result<Option<ExitStatus>, OsString> {
+        // The agent console is only closed once the winpty instance is freed.
+        let terminated = self.process.shutdown(self.shutdown_policy, || ())?;
+        match self.process.get_exitstatus()? {
+            Some(code) => Ok(Some(ExitStatus {
+                code,
+                terminated,
+                output: self.process.drain()
+            })),
+            None => Ok(None)
+        }
+    }
+}
+
+impl Drop for WinPTY {
+    fn drop(&mut self) {
+        let _ = self.process.shutdown(self.shutdown_policy, || ());
+    }
 }
 
 unsafe impl Send for WinPTY {}
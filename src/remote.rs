@@ -0,0 +1,16 @@
+//! Access to PTY sessions hosted by another process through a local socket.
+//!
+//! A [`PtyServer`] owns its sessions through a [`PtyManager`](crate::PtyManager) and serves
+//! them over a named pipe on Windows, or over a Unix domain socket elsewhere. Clients use a
+//! [`RemotePTY`], which offers the same operations as a [`PTY`](crate::PTY). Both ends speak
+//! the versioned, length-prefixed protocol described in [`Frame`].
+
+mod protocol;
+mod transport;
+mod server;
+mod client;
+
+pub use protocol::{Frame, SessionInfo, PROTOCOL_VERSION, MAX_FRAME_LENGTH, MAX_OSC_PAYLOAD};
+pub use transport::{LocalListener, LocalStream};
+pub use server::{PtyServer, DEFAULT_SCROLLBACK};
+pub use client::RemotePTY;
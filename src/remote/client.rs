@@ -0,0 +1,387 @@
+//! Client of a [`PtyServer`](super::PtyServer), which exposes a remote session through the same API as [`PTY`](crate::PTY).
+
+use std::ffi::{OsStr, OsString};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, TryRecvError};
+
+use super::protocol::{Frame, SessionInfo, PROTOCOL_VERSION};
+use super::transport::LocalStream;
+use crate::manager::SessionId;
+use crate::osc::{OscEvent, OscTracker};
+use crate::pty::{ExitStatus, PTYArgs, PTYBackend, Signal};
+
+/// Exit code received from the server, if the process exited.
+type ExitState = Arc<(Mutex<Option<Option<u32>>>, Condvar)>;
+
+fn disconnected() -> OsString {
+    OsString::from("The connection to the server was closed")
+}
+
+fn eof() -> OsString {
+    OsString::from("Standard out reached EOF")
+}
+
+fn unexpected(frame: Frame) -> OsString {
+    OsString::from(format!("Unexpected response {:?}", frame))
+}
+
+/// Connect to the server listening on `name` and exchange the [`Frame::Hello`] frames.
+fn handshake(name: &OsStr) -> Result<(LocalStream, LocalStream), OsString> {
+    let mut writer = LocalStream::connect(name)?;
+    let mut reader = writer.try_clone()?;
+    Frame::Hello { version: PROTOCOL_VERSION }.write_to(&mut writer)?;
+    match Frame::read_from(&mut reader)? {
+        Some(Frame::Hello { version }) if version == PROTOCOL_VERSION => Ok((writer, reader)),
+        Some(Frame::Hello { version }) => Err(OsString::from(format!(
+            "Unsupported protocol version {}, expected {}",
+            version, PROTOCOL_VERSION
+        ))),
+        _ => Err(OsString::from("Expected a Hello frame")),
+    }
+}
+
+/// Session hosted by a [`PtyServer`](super::PtyServer), which is used as a local [`PTY`](crate::PTY).
+///
+/// # Examples
+/// ```no_run
+/// use std::ffi::OsString;
+/// use winptyrs::{PTYArgs, PTYBackend, RemotePTY};
+///
+/// let mut pty = RemotePTY::connect(&OsString::from("winpty-rs"), &PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+/// pty.spawn(OsString::from("cmd.exe"), None, None, None).unwrap();
+/// pty.write(OsString::from("echo Hello\r\n")).unwrap();
+/// let output = pty.read(true).unwrap();
+/// ```
+///
+/// ## Detaching and attaching to a session by name
+/// ```no_run
+/// use std::ffi::OsString;
+/// use winptyrs::{PTYArgs, PTYBackend, RemotePTY};
+///
+/// let server = OsString::from("winpty-rs");
+/// let mut pty = RemotePTY::create(&server, "build", &PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+/// pty.spawn(OsString::from("cmd.exe"), None, None, None).unwrap();
+/// pty.detach();
+///
+/// // Later on, possibly from another process.
+/// let pty = RemotePTY::attach(&server, "build", Some((120, 40))).unwrap();
+/// let scrollback = pty.read(false).unwrap();
+/// ```
+pub struct RemotePTY {
+    session: SessionId,
+    name: String,
+    pid: AtomicU32,
+    /// Current size of the session, updated when any client resizes it.
+    size: Arc<Mutex<(i32, i32)>>,
+    /// Stream used to send requests. It is locked until the response is received.
+    writer: Mutex<LocalStream>,
+    responses: Receiver<Frame>,
+    /// Output of the process, `None` once it exits.
+    output: Receiver<Option<OsString>>,
+    exit: ExitState,
+    osc: Mutex<OscTracker>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl RemotePTY {
+    /// Create a new session in the server listening on `server`, see [`PTY::new_with_backend`](crate::PTY::new_with_backend).
+    /// The session is named after its identifier. Remote sessions do not support [`PTYArgs::cassette`].
+    pub fn connect(server: &OsStr, args: &PTYArgs, backend: PTYBackend) -> Result<RemotePTY, OsString> {
+        RemotePTY::open(server, Frame::Create { args: args.clone(), backend, name: None }, args)
+    }
+
+    /// Create a new session named `name` in the server listening on `server`, see [`RemotePTY::connect`].
+    /// Names must be unique within a server and must not be numbers.
+    pub fn create(server: &OsStr, name: &str, args: &PTYArgs, backend: PTYBackend) -> Result<RemotePTY, OsString> {
+        let request = Frame::Create { args: args.clone(), backend, name: Some(name.to_owned()) };
+        RemotePTY::open(server, request, args)
+    }
+
+    /// Attach to the session named `name` of the server listening on `server`.
+    ///
+    /// The output retained by the session is read first, followed by the output produced
+    /// after attaching. `size` is the size preferred by this client: the session takes the
+    /// smallest size preferred by its attached clients, see [`RemotePTY::size`].
+    pub fn attach(server: &OsStr, name: &str, size: Option<(i32, i32)>) -> Result<RemotePTY, OsString> {
+        RemotePTY::open(server, Frame::Attach { name: name.to_owned(), size }, &PTYArgs::default())
+    }
+
+    /// List the sessions of the server listening on `server`.
+    pub fn list(server: &OsStr) -> Result<Vec<SessionInfo>, OsString> {
+        let (mut writer, mut reader) = handshake(server)?;
+        Frame::List.write_to(&mut writer)?;
+        match Frame::read_from(&mut reader)? {
+            Some(Frame::Listed { sessions }) => Ok(sessions),
+            Some(Frame::Error { message }) => Err(OsString::from(message)),
+            Some(other) => Err(unexpected(other)),
+            None => Err(disconnected()),
+        }
+    }
+
+    fn open(server: &OsStr, request: Frame, args: &PTYArgs) -> Result<RemotePTY, OsString> {
+        if args.cassette.is_some() {
+            return Err(OsString::from("Remote sessions cannot record nor replay cassettes"));
+        }
+        let (writer, mut reader) = handshake(server)?;
+
+        let (responses_tx, responses) = unbounded();
+        let (output_tx, output) = unbounded();
+        let exit: ExitState = Arc::new((Mutex::new(None), Condvar::new()));
+        let size = Arc::new(Mutex::new((args.cols, args.rows)));
+        let reader_exit = exit.clone();
+        let reader_size = size.clone();
+        let reader = thread::spawn(move || {
+            let exited = |code| {
+                let (lock, cvar) = &*reader_exit;
+                *lock.lock().unwrap() = Some(code);
+                cvar.notify_all();
+            };
+            while let Ok(Some(frame)) = Frame::read_from(&mut reader) {
+                match frame {
+                    Frame::Output { data, .. } => {
+                        let _ = output_tx.send(Some(OsString::from(data)));
+                    }
+                    Frame::Exit { code, .. } => {
+                        exited(code);
+                        let _ = output_tx.send(None);
+                    }
+                    Frame::Resized { cols, rows, .. } => {
+                        *reader_size.lock().unwrap() = (cols, rows);
+                    }
+                    Frame::Attached { session, name, pid, cols, rows, scrollback, exit } => {
+                        // Replay the retained output before any output that follows it.
+                        *reader_size.lock().unwrap() = (cols, rows);
+                        if !scrollback.is_empty() {
+                            let _ = output_tx.send(Some(OsString::from(scrollback)));
+                        }
+                        if let Some(code) = exit {
+                            exited(code);
+                            let _ = output_tx.send(None);
+                        }
+                        let scrollback = String::new();
+                        let _ = responses_tx.send(Frame::Attached { session, name, pid, cols, rows, scrollback, exit });
+                    }
+                    response => {
+                        let _ = responses_tx.send(response);
+                    }
+                }
+            }
+            // Release the threads that wait for the process to exit.
+            reader_exit.1.notify_all();
+        });
+
+        let mut pty = RemotePTY {
+            session: 0,
+            name: String::new(),
+            pid: AtomicU32::new(0),
+            size,
+            writer: Mutex::new(writer),
+            responses,
+            output,
+            exit,
+            osc: Mutex::new(OscTracker::new(args.osc_max_payload)),
+            reader: Some(reader),
+        };
+        match pty.request(request)? {
+            Frame::Created { session, name } => {
+                pty.session = session;
+                pty.name = name;
+                Ok(pty)
+            }
+            Frame::Attached { session, name, pid, .. } => {
+                pty.session = session;
+                pty.name = name;
+                pty.pid.store(pid, Ordering::SeqCst);
+                Ok(pty)
+            }
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Send a request and wait for its response.
+    fn request(&self, frame: Frame) -> Result<Frame, OsString> {
+        let mut writer = self.writer.lock().unwrap();
+        frame.write_to(&mut *writer)?;
+        match self.responses.recv() {
+            Ok(Frame::Error { message }) => Err(OsString::from(message)),
+            Ok(response) => Ok(response),
+            Err(_) => Err(disconnected()),
+        }
+    }
+
+    /// Identifier of the session in the server.
+    pub fn session(&self) -> SessionId {
+        self.session
+    }
+
+    /// Name of the session in the server, which can be used to attach to it from other clients.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Current size of the session, as `(cols, rows)`.
+    pub fn size(&self) -> (i32, i32) {
+        *self.size.lock().unwrap()
+    }
+
+    /// Spawn a process inside the session, see [`PTY::spawn`](crate::PTY::spawn).
+    pub fn spawn(&mut self, appname: OsString, cmdline: Option<OsString>, cwd: Option<OsString>, env: Option<OsString>) -> Result<bool, OsString> {
+        let to_string = |s: OsString| s.to_string_lossy().into_owned();
+        let request = Frame::Spawn {
+            session: self.session,
+            appname: to_string(appname),
+            cmdline: cmdline.map(to_string),
+            cwd: cwd.map(to_string),
+            env: env.map(to_string),
+        };
+        match self.request(request)? {
+            Frame::Spawned { pid, .. } => {
+                self.pid.store(pid, Ordering::SeqCst);
+                Ok(true)
+            }
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Change the size preferred by this client, see [`PTY::set_size`](crate::PTY::set_size).
+    ///
+    /// The session takes the smallest size preferred by its attached clients, which is
+    /// reported by [`RemotePTY::size`] once the server applies it.
+    pub fn set_size(&self, cols: i32, rows: i32) -> Result<(), OsString> {
+        self.request(Frame::Resize { session: self.session, cols, rows }).map(|_| ())
+    }
+
+    fn received(&self, output: Option<OsString>) -> Result<OsString, OsString> {
+        match output {
+            Some(output) => {
+                if let Ok(mut osc) = self.osc.lock() {
+                    osc.feed(&output.to_string_lossy());
+                }
+                Ok(output)
+            }
+            None => Err(eof()),
+        }
+    }
+
+    /// Read the output of the process, see [`PTY::read`](crate::PTY::read).
+    pub fn read(&self, blocking: bool) -> Result<OsString, OsString> {
+        if self.is_eof()? {
+            return Err(eof());
+        }
+        let result = match blocking {
+            true => self.output.recv().map_err(|_| disconnected()),
+            false => match self.output.try_recv() {
+                Ok(output) => Ok(output),
+                Err(TryRecvError::Empty) => return Ok(OsString::new()),
+                Err(TryRecvError::Disconnected) => Err(disconnected()),
+            },
+        };
+        self.received(result?)
+    }
+
+    /// Read the output of the process, waiting at most `timeout` for data to be available,
+    /// see [`PTY::read_timeout`](crate::PTY::read_timeout).
+    pub fn read_timeout(&self, timeout: Duration) -> Result<OsString, OsString> {
+        if self.is_eof()? {
+            return Err(eof());
+        }
+        match self.output.recv_timeout(timeout) {
+            Ok(output) => self.received(output),
+            Err(RecvTimeoutError::Timeout) => Ok(OsString::new()),
+            Err(RecvTimeoutError::Disconnected) => Err(disconnected()),
+        }
+    }
+
+    /// Retrieve the last window title set by the process, see [`PTY::title`](crate::PTY::title).
+    pub fn title(&self) -> Option<String> {
+        self.osc.lock().ok().and_then(|osc| osc.title())
+    }
+
+    /// Retrieve the last working directory reported by the process, see [`PTY::cwd`](crate::PTY::cwd).
+    pub fn cwd(&self) -> Option<String> {
+        self.osc.lock().ok().and_then(|osc| osc.cwd())
+    }
+
+    /// Create a channel that receives the changes of the title and of the working directory,
+    /// see [`PTY::osc_events`](crate::PTY::osc_events).
+    pub fn osc_events(&self) -> Receiver<OscEvent> {
+        let mut osc = self.osc.lock().unwrap_or_else(|err| err.into_inner());
+        osc.subscribe()
+    }
+
+    /// Write into the standard input of the process, see [`PTY::write`](crate::PTY::write).
+    pub fn write(&self, buf: OsString) -> Result<u32, OsString> {
+        let data = buf.to_string_lossy().into_owned();
+        match self.request(Frame::Input { session: self.session, data })? {
+            Frame::Written { count, .. } => Ok(count),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Check if the process exited and all its output was read, see [`PTY::is_eof`](crate::PTY::is_eof).
+    pub fn is_eof(&self) -> Result<bool, OsString> {
+        let exited = self.exit.0.lock().unwrap().is_some();
+        Ok(exited && self.output.is_empty())
+    }
+
+    /// Retrieve the exit code of the process, `None` if it has not exited.
+    pub fn get_exitstatus(&self) -> Result<Option<u32>, OsString> {
+        Ok(self.exit.0.lock().unwrap().flatten())
+    }
+
+    /// Determine if the process is still alive.
+    pub fn is_alive(&self) -> Result<bool, OsString> {
+        Ok(self.get_pid() != 0 && self.exit.0.lock().unwrap().is_none())
+    }
+
+    /// Retrieve the process ID (PID) of the spawned program, `0` if it was not spawned yet.
+    pub fn get_pid(&self) -> u32 {
+        self.pid.load(Ordering::SeqCst)
+    }
+
+    /// Wait for the process to exit, or for the connection to the server to be closed.
+    pub fn wait_for_exit(&self) -> Result<bool, OsString> {
+        let (lock, cvar) = &*self.exit;
+        let mut exit = lock.lock().unwrap();
+        while exit.is_none() {
+            if self.reader.as_ref().is_none_or(|r| r.is_finished()) {
+                return Err(disconnected());
+            }
+            exit = cvar.wait_timeout(exit, Duration::from_millis(100)).unwrap().0;
+        }
+        Ok(true)
+    }
+
+    /// Send a signal to the process, see [`PTY::signal`](crate::PTY::signal).
+    pub fn signal(&self, signal: Signal) -> Result<(), OsString> {
+        self.request(Frame::Signal { session: self.session, signal }).map(|_| ())
+    }
+
+    /// Disconnect from the session, which keeps running in the server, so that it can be
+    /// attached again with [`RemotePTY::attach`]. Dropping the client has the same effect.
+    pub fn detach(self) {}
+
+    /// Close the session in the server, see [`PTY::close`](crate::PTY::close).
+    pub fn close(self) -> Result<Option<ExitStatus>, OsString> {
+        match self.request(Frame::Close { session: self.session })? {
+            Frame::Closed { status, .. } => Ok(status),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+impl Drop for RemotePTY {
+    fn drop(&mut self) {
+        // The session stays alive in the server, only the connection is closed.
+        if let Ok(writer) = self.writer.lock() {
+            writer.shutdown();
+        }
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! Wire protocol spoken between a [`PtyServer`](super::PtyServer) and its clients.
+//!
+//! Every frame starts with its length as a little-endian `u32`, which does not include the
+//! length itself, followed by a `u8` with the frame type and by its fields in order:
+//! * Integers are encoded in little-endian order and booleans as a `u8` that is either `0` or `1`.
+//! * Strings are encoded in UTF-8, prefixed by their length in bytes as a `u32`.
+//! * Optional values are prefixed by a `u8` that is `1` if the value is present, else `0`.
+//! * Sequences are prefixed by their number of elements as a `u32`.
+//!
+//! Both ends send a [`Frame::Hello`] as the first frame of a connection. The connection is
+//! closed if the versions do not match. Afterwards, every request sent by a client is answered
+//! by exactly one response, in order, while [`Frame::Output`], [`Frame::Exit`] and
+//! [`Frame::Resized`] are sent at any time for the sessions that the client created or
+//! attached to. The output of a session never precedes the response that attached it.
+
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::manager::SessionId;
+use crate::pty::{AgentConfig, ExitStatus, MouseMode, PTYArgs, PTYBackend, ShutdownPolicy, Signal};
+
+/// Version of the protocol, sent in the [`Frame::Hello`] of each end.
+pub const PROTOCOL_VERSION: u16 = 4;
+
+/// Maximum length of a frame, larger frames are rejected.
+pub const MAX_FRAME_LENGTH: u32 = 16 * 1024 * 1024;
+
+/// Maximum [`PTYArgs::osc_max_payload`] accepted from a client, larger values are clamped.
+pub const MAX_OSC_PAYLOAD: usize = 64 * 1024;
+
+/// Summary of a session hosted by a server, see [`Frame::Listed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Identifier of the session.
+    pub session: SessionId,
+    /// Name used to attach to the session.
+    pub name: String,
+    /// Process ID of the spawned program, `0` if it was not spawned yet.
+    pub pid: u32,
+    /// Current number of columns of the session.
+    pub cols: i32,
+    /// Current number of rows of the session.
+    pub rows: i32,
+    /// Number of clients attached to the session.
+    pub clients: u32,
+    /// `Some` with the exit code of the process (if available) once it exited.
+    pub exit: Option<Option<u32>>,
+}
+
+/// Message exchanged between a server and a client.
+#[derive(Clone, Debug)]
+pub enum Frame {
+    /// First frame sent by both ends.
+    Hello { version: u16 },
+    /// Request to create a new session, answered by [`Frame::Created`]. Sessions without a
+    /// name are named after their identifier.
+    Create { args: PTYArgs, backend: PTYBackend, name: Option<String> },
+    /// Request to receive the output of an existing session, given its name, answered by
+    /// [`Frame::Attached`]. `size` is the size preferred by the client, if any.
+    Attach { name: String, size: Option<(i32, i32)> },
+    /// Request to list the sessions of the server, answered by [`Frame::Listed`].
+    List,
+    /// Request to spawn a process in a session, answered by [`Frame::Spawned`].
+    Spawn {
+        session: SessionId,
+        appname: String,
+        cmdline: Option<String>,
+        cwd: Option<String>,
+        env: Option<String>,
+    },
+    /// Request to write into the standard input of a session, answered by [`Frame::Written`].
+    Input { session: SessionId, data: String },
+    /// Request to change the size of a session, answered by [`Frame::Ack`].
+    Resize { session: SessionId, cols: i32, rows: i32 },
+    /// Request to deliver a signal to the process of a session, answered by [`Frame::Ack`].
+    Signal { session: SessionId, signal: Signal },
+    /// Request to close a session, answered by [`Frame::Closed`].
+    Close { session: SessionId },
+    /// A session was created.
+    Created { session: SessionId, name: String },
+    /// A session was attached, with its current size, the output that it retained and, if
+    /// its process exited, the exit code. `pid` is `0` if no process was spawned in it yet.
+    Attached {
+        session: SessionId,
+        name: String,
+        pid: u32,
+        cols: i32,
+        rows: i32,
+        scrollback: String,
+        exit: Option<Option<u32>>,
+    },
+    /// Sessions hosted by the server.
+    Listed { sessions: Vec<SessionInfo> },
+    /// A process was spawned in a session.
+    Spawned { session: SessionId, pid: u32 },
+    /// Number of bytes written into the standard input of a session.
+    Written { session: SessionId, count: u32 },
+    /// A request was completed.
+    Ack { session: SessionId },
+    /// A session was closed, with the status of its process if it exited.
+    Closed { session: SessionId, status: Option<ExitStatus> },
+    /// A request failed.
+    Error { message: String },
+    /// Output produced by the process of a session.
+    Output { session: SessionId, data: String },
+    /// The process of a session exited, with the given exit code (if available).
+    Exit { session: SessionId, code: Option<u32> },
+    /// The size of a session changed.
+    Resized { session: SessionId, cols: i32, rows: i32 },
+}
+
+mod kind {
+    pub const HELLO: u8 = 0x01;
+    pub const CREATE: u8 = 0x02;
+    pub const ATTACH: u8 = 0x03;
+    pub const SPAWN: u8 = 0x04;
+    pub const INPUT: u8 = 0x05;
+    pub const RESIZE: u8 = 0x06;
+    pub const SIGNAL: u8 = 0x07;
+    pub const CLOSE: u8 = 0x08;
+    pub const LIST: u8 = 0x09;
+    pub const CREATED: u8 = 0x40;
+    pub const SPAWNED: u8 = 0x41;
+    pub const WRITTEN: u8 = 0x42;
+    pub const ACK: u8 = 0x43;
+    pub const CLOSED: u8 = 0x44;
+    pub const ERROR: u8 = 0x45;
+    pub const ATTACHED: u8 = 0x46;
+    pub const LISTED: u8 = 0x47;
+    pub const OUTPUT: u8 = 0x80;
+    pub const EXIT: u8 = 0x81;
+    pub const RESIZED: u8 = 0x82;
+}
+
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn option<T>(&mut self, value: &Option<T>, encode: impl FnOnce(&mut Self, &T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            encode(self, value);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], OsString> {
+        if self.buf.len() < len {
+            return Err(OsString::from("Truncated frame"));
+        }
+        let (value, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(value)
+    }
+
+    fn u8(&mut self) -> Result<u8, OsString> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, OsString> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(OsString::from(format!("Invalid boolean value {}", value))),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, OsString> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, OsString> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, OsString> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, OsString> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<String, OsString> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| OsString::from("Invalid UTF-8 string"))
+    }
+
+    fn option<T>(&mut self, decode: impl FnOnce(&mut Self) -> Result<T, OsString>) -> Result<Option<T>, OsString> {
+        match self.bool()? {
+            true => Ok(Some(decode(self)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+fn encode_args(e: &mut Encoder, args: &PTYArgs) {
+    e.i32(args.cols);
+    e.i32(args.rows);
+    e.i32(args.mouse_mode.to_i32().unwrap_or_default());
+    e.u32(args.timeout);
+    e.u64(args.agent_config.bits());
+    e.bool(args.contain_tree);
+    match args.shutdown_policy {
+        ShutdownPolicy::CloseConsole => e.u8(0),
+        ShutdownPolicy::Terminate => e.u8(1),
+        ShutdownPolicy::TerminateTree => e.u8(2),
+        ShutdownPolicy::WaitWithTimeout(timeout) => {
+            e.u8(3);
+            e.u64(timeout.as_millis().min(u64::MAX as u128) as u64);
+        }
+    }
+    e.u32(args.drain_timeout);
+    e.u64(args.osc_max_payload as u64);
+    e.bool(args.shared_reactor);
+}
+
+fn decode_args(d: &mut Decoder) -> Result<PTYArgs, OsString> {
+    let cols = d.i32()?;
+    let rows = d.i32()?;
+    let mouse_mode = d.i32()?;
+    let mouse_mode = MouseMode::from_i32(mouse_mode)
+        .ok_or_else(|| OsString::from(format!("Invalid mouse mode {}", mouse_mode)))?;
+    let timeout = d.u32()?;
+    let agent_config = AgentConfig::from_bits_truncate(d.u64()?);
+    let contain_tree = d.bool()?;
+    let shutdown_policy = match d.u8()? {
+        0 => ShutdownPolicy::CloseConsole,
+        1 => ShutdownPolicy::Terminate,
+        2 => ShutdownPolicy::TerminateTree,
+        3 => ShutdownPolicy::WaitWithTimeout(Duration::from_millis(d.u64()?)),
+        value => return Err(OsString::from(format!("Invalid shutdown policy {}", value))),
+    };
+    // Cassettes are not part of the protocol, a client must not choose the files that the server writes.
+    Ok(PTYArgs {
+        cols,
+        rows,
+        mouse_mode,
+        timeout,
+        agent_config,
+        contain_tree,
+        shutdown_policy,
+        drain_timeout: d.u32()?,
+        osc_max_payload: d.u64()?.min(MAX_OSC_PAYLOAD as u64) as usize,
+        shared_reactor: d.bool()?,
+        ..PTYArgs::default()
+    })
+}
+
+fn encode_exit(e: &mut Encoder, exit: &Option<Option<u32>>) {
+    e.option(exit, |e, code| e.option(code, |e, v| e.u32(*v)));
+}
+
+fn decode_exit(d: &mut Decoder) -> Result<Option<Option<u32>>, OsString> {
+    d.option(|d| d.option(Decoder::u32))
+}
+
+fn encode_signal(signal: Signal) -> u8 {
+    match signal {
+        Signal::Interrupt => 0,
+        Signal::Break => 1,
+        Signal::Terminate => 2,
+        Signal::Kill => 3,
+    }
+}
+
+fn decode_signal(value: u8) -> Result<Signal, OsString> {
+    match value {
+        0 => Ok(Signal::Interrupt),
+        1 => Ok(Signal::Break),
+        2 => Ok(Signal::Terminate),
+        3 => Ok(Signal::Kill),
+        _ => Err(OsString::from(format!("Invalid signal {}", value))),
+    }
+}
+
+impl Frame {
+    /// Encode the frame, including its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut e = Encoder(vec![0; 4]);
+        match self {
+            Frame::Hello { version } => {
+                e.u8(kind::HELLO);
+                e.u16(*version);
+            }
+            Frame::Create { args, backend, name } => {
+                e.u8(kind::CREATE);
+                encode_args(&mut e, args);
+                e.u8(backend.to_u8().unwrap_or_default());
+                e.option(name, |e, v| e.str(v));
+            }
+            Frame::Attach { name, size } => {
+                e.u8(kind::ATTACH);
+                e.str(name);
+                e.option(size, |e, (cols, rows)| {
+                    e.i32(*cols);
+                    e.i32(*rows);
+                });
+            }
+            Frame::List => e.u8(kind::LIST),
+            Frame::Spawn { session, appname, cmdline, cwd, env } => {
+                e.u8(kind::SPAWN);
+                e.u64(*session);
+                e.str(appname);
+                e.option(cmdline, |e, v| e.str(v));
+                e.option(cwd, |e, v| e.str(v));
+                e.option(env, |e, v| e.str(v));
+            }
+            Frame::Input { session, data } => {
+                e.u8(kind::INPUT);
+                e.u64(*session);
+                e.str(data);
+            }
+            Frame::Resize { session, cols, rows } => {
+                e.u8(kind::RESIZE);
+                e.u64(*session);
+                e.i32(*cols);
+                e.i32(*rows);
+            }
+            Frame::Signal { session, signal } => {
+                e.u8(kind::SIGNAL);
+                e.u64(*session);
+                e.u8(encode_signal(*signal));
+            }
+            Frame::Close { session } => {
+                e.u8(kind::CLOSE);
+                e.u64(*session);
+            }
+            Frame::Created { session, name } => {
+                e.u8(kind::CREATED);
+                e.u64(*session);
+                e.str(name);
+            }
+            Frame::Attached { session, name, pid, cols, rows, scrollback, exit } => {
+                e.u8(kind::ATTACHED);
+                e.u64(*session);
+                e.str(name);
+                e.u32(*pid);
+                e.i32(*cols);
+                e.i32(*rows);
+                e.str(scrollback);
+                encode_exit(&mut e, exit);
+            }
+            Frame::Listed { sessions } => {
+                e.u8(kind::LISTED);
+                e.u32(sessions.len() as u32);
+                for info in sessions {
+                    e.u64(info.session);
+                    e.str(&info.name);
+                    e.u32(info.pid);
+                    e.i32(info.cols);
+                    e.i32(info.rows);
+                    e.u32(info.clients);
+                    encode_exit(&mut e, &info.exit);
+                }
+            }
+            Frame::Spawned { session, pid } => {
+                e.u8(kind::SPAWNED);
+                e.u64(*session);
+                e.u32(*pid);
+            }
+            Frame::Written { session, count } => {
+                e.u8(kind::WRITTEN);
+                e.u64(*session);
+                e.u32(*count);
+            }
+            Frame::Ack { session } => {
+                e.u8(kind::ACK);
+                e.u64(*session);
+            }
+            Frame::Closed { session, status } => {
+                e.u8(kind::CLOSED);
+                e.u64(*session);
+                e.option(status, |e, status| {
+                    e.u32(status.code);
+                    e.bool(status.terminated);
+                    e.str(&status.output.to_string_lossy());
+                });
+            }
+            Frame::Error { message } => {
+                e.u8(kind::ERROR);
+                e.str(message);
+            }
+            Frame::Output { session, data } => {
+                e.u8(kind::OUTPUT);
+                e.u64(*session);
+                e.str(data);
+            }
+            Frame::Exit { session, code } => {
+                e.u8(kind::EXIT);
+                e.u64(*session);
+                e.option(code, |e, v| e.u32(*v));
+            }
+            Frame::Resized { session, cols, rows } => {
+                e.u8(kind::RESIZED);
+                e.u64(*session);
+                e.i32(*cols);
+                e.i32(*rows);
+            }
+        }
+
+        let len = (e.0.len() - 4) as u32;
+        e.0[..4].copy_from_slice(&len.to_le_bytes());
+        e.0
+    }
+
+    /// Decode the body of a frame, i.e., without its length prefix.
+    pub fn decode(body: &[u8]) -> Result<Frame, OsString> {
+        let mut d = Decoder { buf: body };
+        let frame = match d.u8()? {
+            kind::HELLO => Frame::Hello { version: d.u16()? },
+            kind::CREATE => {
+                let args = decode_args(&mut d)?;
+                let backend = d.u8()?;
+                let backend = PTYBackend::from_u8(backend)
+                    .ok_or_else(|| OsString::from(format!("Invalid backend {}", backend)))?;
+                Frame::Create { args, backend, name: d.option(Decoder::str)? }
+            }
+            kind::ATTACH => Frame::Attach {
+                name: d.str()?,
+                size: d.option(|d| Ok((d.i32()?, d.i32()?)))?,
+            },
+            kind::LIST => Frame::List,
+            kind::SPAWN => Frame::Spawn {
+                session: d.u64()?,
+                appname: d.str()?,
+                cmdline: d.option(Decoder::str)?,
+                cwd: d.option(Decoder::str)?,
+                env: d.option(Decoder::str)?,
+            },
+            kind::INPUT => Frame::Input { session: d.u64()?, data: d.str()? },
+            kind::RESIZE => Frame::Resize { session: d.u64()?, cols: d.i32()?, rows: d.i32()? },
+            kind::SIGNAL => Frame::Signal { session: d.u64()?, signal: decode_signal(d.u8()?)? },
+            kind::CLOSE => Frame::Close { session: d.u64()? },
+            kind::CREATED => Frame::Created { session: d.u64()?, name: d.str()? },
+            kind::ATTACHED => Frame::Attached {
+                session: d.u64()?,
+                name: d.str()?,
+                pid: d.u32()?,
+                cols: d.i32()?,
+                rows: d.i32()?,
+                scrollback: d.str()?,
+                exit: decode_exit(&mut d)?,
+            },
+            kind::LISTED => {
+                let count = d.u32()?;
+                let mut sessions = Vec::new();
+                for _ in 0..count {
+                    sessions.push(SessionInfo {
+                        session: d.u64()?,
+                        name: d.str()?,
+                        pid: d.u32()?,
+                        cols: d.i32()?,
+                        rows: d.i32()?,
+                        clients: d.u32()?,
+                        exit: decode_exit(&mut d)?,
+                    });
+                }
+                Frame::Listed { sessions }
+            }
+            kind::SPAWNED => Frame::Spawned { session: d.u64()?, pid: d.u32()? },
+            kind::WRITTEN => Frame::Written { session: d.u64()?, count: d.u32()? },
+            kind::ACK => Frame::Ack { session: d.u64()? },
+            kind::CLOSED => Frame::Closed {
+                session: d.u64()?,
+                status: d.option(|d| {
+                    Ok(ExitStatus {
+                        code: d.u32()?,
+                        terminated: d.bool()?,
+                        output: OsString::from(d.str()?),
+                    })
+                })?,
+            },
+            kind::ERROR => Frame::Error { message: d.str()? },
+            kind::OUTPUT => Frame::Output { session: d.u64()?, data: d.str()? },
+            kind::EXIT => Frame::Exit { session: d.u64()?, code: d.option(Decoder::u32)? },
+            kind::RESIZED => Frame::Resized { session: d.u64()?, cols: d.i32()?, rows: d.i32()? },
+            other => return Err(OsString::from(format!("Unknown frame type {:#04x}", other))),
+        };
+
+        if !d.buf.is_empty() {
+            return Err(OsString::from("Unexpected data at the end of the frame"));
+        }
+        Ok(frame)
+    }
+
+    /// Write the frame into a stream.
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<(), OsString> {
+        stream
+            .write_all(&self.encode())
+            .and_then(|_| stream.flush())
+            .map_err(|err| OsString::from(err.to_string()))
+    }
+
+    /// Read the next frame from a stream.
+    ///
+    /// # Returns
+    /// `None` if the stream was closed before the start of a frame.
+    pub fn read_from<R: Read>(stream: &mut R) -> Result<Option<Frame>, OsString> {
+        let mut len = [0u8; 4];
+        match stream.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(OsString::from(err.to_string())),
+        }
+
+        let len = u32::from_le_bytes(len);
+        if len > MAX_FRAME_LENGTH {
+            return Err(OsString::from(format!("Frame of {} bytes exceeds the maximum length", len)));
+        }
+        let mut body = vec![0u8; len as usize];
+        stream.read_exact(&mut body).map_err(|err| OsString::from(err.to_string()))?;
+        Frame::decode(&body).map(Some)
+    }
+}
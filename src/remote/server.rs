@@ -0,0 +1,536 @@
+//! Server that exposes the sessions of a [`PtyManager`] over a local socket.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{unbounded, Sender};
+
+use super::protocol::{Frame, SessionInfo, PROTOCOL_VERSION};
+use super::transport::{LocalListener, LocalStream};
+use crate::manager::{PtyEvent, PtyManager, SessionId};
+use crate::pty::ExitStatus;
+
+/// Default amount of output (in bytes) retained by each session for the clients that attach to it.
+pub const DEFAULT_SCROLLBACK: usize = 1024 * 1024;
+
+/// Maximum amount of time that [`PtyServer::launch`] waits for the host process to listen.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Output retained by a session, so that it can be replayed to the clients that attach to it.
+struct Scrollback {
+    data: String,
+    limit: usize,
+}
+
+impl Scrollback {
+    fn push(&mut self, output: &str) {
+        self.data.push_str(output);
+        // Trim in batches, so that the buffer is not shifted on every output.
+        if self.data.len() > self.limit + self.limit / 2 {
+            let start = self.start();
+            self.data.drain(..start);
+        }
+    }
+
+    /// Offset of the last `limit` bytes of the buffer, moved forward to a character boundary.
+    fn start(&self) -> usize {
+        let mut start = self.data.len().saturating_sub(self.limit);
+        while !self.data.is_char_boundary(start) {
+            start += 1;
+        }
+        start
+    }
+
+    fn contents(&self) -> String {
+        self.data[self.start()..].to_owned()
+    }
+}
+
+/// Connection that created or attached to a session.
+struct Client {
+    tx: Sender<Frame>,
+    /// Size preferred by the client, if any.
+    size: Option<(i32, i32)>,
+}
+
+/// State kept by the server for each session.
+struct Hosted {
+    name: String,
+    size: (i32, i32),
+    clients: Vec<Client>,
+    scrollback: Scrollback,
+    exit: Option<Option<u32>>,
+}
+
+impl Hosted {
+    fn broadcast(&mut self, frame: &Frame) {
+        self.clients.retain(|client| client.tx.send(frame.clone()).is_ok());
+    }
+
+    /// Record an event of the session and forward it to its clients.
+    fn dispatch(&mut self, event: PtyEvent) {
+        match event {
+            PtyEvent::Output(session, data) => {
+                let data = data.to_string_lossy().into_owned();
+                self.scrollback.push(&data);
+                self.broadcast(&Frame::Output { session, data });
+            }
+            PtyEvent::Exit(session, code) => {
+                self.exit = Some(code);
+                self.broadcast(&Frame::Exit { session, code });
+            }
+            PtyEvent::TitleChanged(..) => (),
+        }
+    }
+
+    fn resize(&mut self, session: SessionId, (cols, rows): (i32, i32), manager: &PtyManager) -> Result<(), OsString> {
+        manager.resize(session, cols, rows)?;
+        self.size = (cols, rows);
+        self.broadcast(&Frame::Resized { session, cols, rows });
+        Ok(())
+    }
+
+    /// Resize the session to the smallest size preferred by its clients, so that it fits all of them.
+    fn reconcile(&mut self, session: SessionId, manager: &PtyManager) -> Result<(), OsString> {
+        let preferred = self
+            .clients
+            .iter()
+            .filter_map(|client| client.size)
+            .reduce(|(cols, rows), (other_cols, other_rows)| (cols.min(other_cols), rows.min(other_rows)));
+        match preferred {
+            Some(size) if size != self.size => self.resize(session, size, manager),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sessions hosted by a server, indexed by their identifier.
+type Sessions = Arc<Mutex<HashMap<SessionId, Hosted>>>;
+
+/// Sessions being created, which are not hosted until the manager returns them.
+#[derive(Default)]
+struct Creating {
+    /// Names reserved by the sessions being created.
+    names: HashSet<String>,
+    count: usize,
+    /// Events of sessions that are not hosted, which may belong to a session being created.
+    events: Vec<PtyEvent>,
+}
+
+/// Streams of the open connections, used to shut them down when the server stops.
+type Connections = Arc<Mutex<HashMap<u64, LocalStream>>>;
+
+/// State shared by the threads that serve the connections.
+#[derive(Clone)]
+struct Shared {
+    manager: Arc<PtyManager>,
+    sessions: Sessions,
+    /// Locked after `sessions` when both are needed.
+    creating: Arc<Mutex<Creating>>,
+    scrollback: usize,
+}
+
+impl Shared {
+    /// Summary of the sessions, ordered by identifier.
+    fn list(&self) -> Vec<SessionInfo> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut infos: Vec<SessionInfo> = sessions
+            .iter()
+            .map(|(id, hosted)| SessionInfo {
+                session: *id,
+                name: hosted.name.clone(),
+                pid: self.manager.with_session(*id, |pty| Ok(pty.get_pid())).unwrap_or(0),
+                cols: hosted.size.0,
+                rows: hosted.size.1,
+                clients: hosted.clients.len() as u32,
+                exit: hosted.exit,
+            })
+            .collect();
+        infos.sort_by_key(|info| info.session);
+        infos
+    }
+
+    /// Close a session, letting its clients other than `closer` know that it is gone.
+    fn close(&self, session: SessionId, closer: Option<&Sender<Frame>>) -> Result<Option<ExitStatus>, OsString> {
+        let status = self.manager.close(session)?;
+        let hosted = self.sessions.lock().unwrap().remove(&session);
+        if let Some(mut hosted) = hosted {
+            if hosted.exit.is_none() {
+                if let Some(closer) = closer {
+                    hosted.clients.retain(|client| !client.tx.same_channel(closer));
+                }
+                hosted.broadcast(&Frame::Exit { session, code: status.as_ref().map(|status| status.code) });
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// Server that hosts PTY sessions and exposes them over a local socket, i.e., a named pipe
+/// on Windows and a Unix domain socket elsewhere.
+///
+/// Sessions outlive the connection that created them, so that clients can detach from them
+/// and attach to them again afterwards, by name. Each session retains its latest output,
+/// which is replayed to the clients that attach to it, and is resized to the smallest size
+/// preferred by its attached clients. Sessions are closed when a client requests it or when
+/// the server is dropped.
+///
+/// In order to keep the sessions alive after the application exits, the server can run in a
+/// separate host process, see [`PtyServer::launch`].
+pub struct PtyServer {
+    name: OsString,
+    shared: Shared,
+    stopped: Arc<AtomicBool>,
+    connections: Connections,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl PtyServer {
+    /// Start a server listening on the given name, which is the name of a named pipe on
+    /// Windows (e.g., `winpty-rs` for `\\.\pipe\winpty-rs`) or the path of the socket on Unix.
+    pub fn bind(name: &OsStr) -> Result<PtyServer, OsString> {
+        PtyServer::bind_with_scrollback(name, DEFAULT_SCROLLBACK)
+    }
+
+    /// Start a server whose sessions retain at most `scrollback` bytes of output, see [`PtyServer::bind`].
+    pub fn bind_with_scrollback(name: &OsStr, scrollback: usize) -> Result<PtyServer, OsString> {
+        let listener = LocalListener::bind(name)?;
+        let shared = Shared {
+            manager: Arc::new(PtyManager::new()),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            creating: Arc::new(Mutex::new(Creating::default())),
+            scrollback,
+        };
+        let stopped = Arc::new(AtomicBool::new(false));
+        let connections: Connections = Arc::new(Mutex::new(HashMap::new()));
+
+        // The dispatcher exits once the manager, and thus its events channel, is dropped.
+        let events = shared.manager.events();
+        let dispatch_sessions = shared.sessions.clone();
+        let dispatch_creating = shared.creating.clone();
+        thread::spawn(move || {
+            for event in events.iter() {
+                let session = match &event {
+                    PtyEvent::Output(session, _) | PtyEvent::Exit(session, _) => *session,
+                    PtyEvent::TitleChanged(..) => continue,
+                };
+                let mut sessions = dispatch_sessions.lock().unwrap();
+                match sessions.get_mut(&session) {
+                    Some(hosted) => hosted.dispatch(event),
+                    None => {
+                        // Keep the events that may be produced before a new session is hosted.
+                        let mut creating = dispatch_creating.lock().unwrap();
+                        if creating.count > 0 {
+                            creating.events.push(event);
+                        }
+                    }
+                }
+            }
+        });
+
+        let accept_shared = shared.clone();
+        let accept_stopped = stopped.clone();
+        let accept_connections = connections.clone();
+        let accept_thread = thread::spawn(move || {
+            let mut next_id = 0u64;
+            while let Ok(stream) = listener.accept() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                next_id += 1;
+                if let Ok(clone) = stream.try_clone() {
+                    accept_connections.lock().unwrap().insert(next_id, clone);
+                }
+
+                let id = next_id;
+                let shared = accept_shared.clone();
+                let connections = accept_connections.clone();
+                thread::spawn(move || {
+                    serve(stream, shared);
+                    connections.lock().unwrap().remove(&id);
+                });
+            }
+        });
+
+        Ok(PtyServer {
+            name: name.to_os_string(),
+            shared,
+            stopped,
+            connections,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Start a host process that runs a server listening on `name`, unless a server is
+    /// listening on it already, and wait until it accepts connections.
+    ///
+    /// `program` is the path of the `winpty-rs` executable, whose `daemon` subcommand runs
+    /// the server. The host process is detached from the console of the caller, so that its
+    /// sessions survive the exit of the caller.
+    pub fn launch(name: &OsStr, program: &OsStr) -> Result<(), OsString> {
+        if LocalStream::connect(name).is_ok() {
+            return Ok(());
+        }
+
+        let mut command = Command::new(program);
+        command
+            .arg("daemon")
+            .arg("--name")
+            .arg(name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            use windows::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+            command.creation_flags((DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP).0);
+        }
+        let mut child = command.spawn().map_err(|err| OsString::from(err.to_string()))?;
+
+        let start = Instant::now();
+        loop {
+            if LocalStream::connect(name).is_ok() {
+                return Ok(());
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(OsString::from(format!("The host process exited with {}", status)));
+            }
+            if start.elapsed() > LAUNCH_TIMEOUT {
+                return Err(OsString::from("Timed out waiting for the host process to listen"));
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Name on which the server listens.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Manager that owns the sessions of the server.
+    pub fn manager(&self) -> &PtyManager {
+        &self.shared.manager
+    }
+
+    /// Summary of the sessions hosted by the server, ordered by identifier.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.shared.list()
+    }
+
+    /// Number of clients connected to the server.
+    pub fn connections(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// Close a session, see [`PtyManager::close`]. Its attached clients receive a [`Frame::Exit`]
+    /// if its process did not exit before.
+    pub fn close(&self, session: SessionId) -> Result<Option<ExitStatus>, OsString> {
+        self.shared.close(session, None)
+    }
+}
+
+impl Drop for PtyServer {
+    fn drop(&mut self) {
+        // Wake up the thread that accepts connections, so that it notices the server stopped.
+        self.stopped.store(true, Ordering::SeqCst);
+        drop(LocalStream::connect(&self.name));
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+
+        for stream in self.connections.lock().unwrap().values() {
+            stream.shutdown();
+        }
+        for id in self.shared.manager.ids() {
+            let _ = self.shared.close(id, None);
+        }
+    }
+}
+
+/// Serve the requests of a client until it disconnects.
+fn serve(stream: LocalStream, shared: Shared) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut reader = stream;
+
+    // Every frame sent to the client goes through a single channel, so that responses
+    // and output are not interleaved.
+    let (tx, rx) = unbounded::<Frame>();
+    let writer_thread = thread::spawn(move || {
+        for frame in rx.iter() {
+            if frame.write_to(&mut writer).is_err() {
+                break;
+            }
+        }
+        writer.shutdown();
+    });
+
+    let _ = tx.send(Frame::Hello { version: PROTOCOL_VERSION });
+    match Frame::read_from(&mut reader) {
+        Ok(Some(Frame::Hello { version })) if version == PROTOCOL_VERSION => {
+            while let Ok(Some(frame)) = Frame::read_from(&mut reader) {
+                let response = handle(frame, &shared, &tx)
+                    .unwrap_or_else(|err| Some(Frame::Error { message: err.to_string_lossy().into_owned() }));
+                if let Some(response) = response {
+                    if tx.send(response).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(Some(Frame::Hello { version })) => {
+            let message = format!("Unsupported protocol version {}, expected {}", version, PROTOCOL_VERSION);
+            let _ = tx.send(Frame::Error { message });
+        }
+        _ => {
+            let _ = tx.send(Frame::Error { message: String::from("Expected a Hello frame") });
+        }
+    }
+
+    // Detach from every session, which closes the channel, then flush the pending frames.
+    {
+        let mut sessions = shared.sessions.lock().unwrap();
+        for (id, hosted) in sessions.iter_mut() {
+            let attached = hosted.clients.len();
+            hosted.clients.retain(|client| !client.tx.same_channel(&tx));
+            if hosted.clients.len() != attached {
+                let _ = hosted.reconcile(*id, &shared.manager);
+            }
+        }
+    }
+    drop(tx);
+    let _ = writer_thread.join();
+}
+
+fn find(sessions: &HashMap<SessionId, Hosted>, name: &str) -> Result<SessionId, OsString> {
+    sessions
+        .iter()
+        .find(|(_, hosted)| hosted.name == name)
+        .map(|(id, _)| *id)
+        .ok_or_else(|| OsString::from(format!("Session {} does not exist", name)))
+}
+
+/// Process a request, returning its response, or `None` if the response was sent already.
+fn handle(frame: Frame, shared: &Shared, tx: &Sender<Frame>) -> Result<Option<Frame>, OsString> {
+    let manager = &shared.manager;
+    let response = match frame {
+        Frame::Create { args, backend, name } => {
+            // The name is reserved while the session is created, so that the other requests
+            // are not blocked until the process starts.
+            {
+                let sessions = shared.sessions.lock().unwrap();
+                let mut creating = shared.creating.lock().unwrap();
+                if let Some(name) = &name {
+                    if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+                        return Err(OsString::from("Session names must not be empty nor numbers"));
+                    }
+                    if find(&sessions, name).is_ok() || creating.names.contains(name) {
+                        return Err(OsString::from(format!("Session {} exists already", name)));
+                    }
+                    creating.names.insert(name.clone());
+                }
+                creating.count += 1;
+            }
+
+            let created = manager.create(&args, backend);
+            let mut sessions = shared.sessions.lock().unwrap();
+            let mut creating = shared.creating.lock().unwrap();
+            if let Some(name) = &name {
+                creating.names.remove(name);
+            }
+            creating.count -= 1;
+            let events = std::mem::take(&mut creating.events);
+            let (early, others): (Vec<PtyEvent>, Vec<PtyEvent>) = events.into_iter().partition(|event| {
+                matches!(event, PtyEvent::Output(id, _) | PtyEvent::Exit(id, _) if created.as_ref() == Ok(id))
+            });
+            // The events of sessions that are not being created belong to closed sessions.
+            if creating.count > 0 {
+                creating.events = others;
+            }
+            drop(creating);
+
+            let session = created?;
+            let name = name.unwrap_or_else(|| session.to_string());
+            let mut hosted = Hosted {
+                name: name.clone(),
+                size: (args.cols, args.rows),
+                clients: vec![Client { tx: tx.clone(), size: Some((args.cols, args.rows)) }],
+                scrollback: Scrollback { data: String::new(), limit: shared.scrollback },
+                exit: None,
+            };
+            // The response goes first, the output of a session never precedes it.
+            let _ = tx.send(Frame::Created { session, name });
+            for event in early {
+                hosted.dispatch(event);
+            }
+            sessions.insert(session, hosted);
+            return Ok(None);
+        }
+        Frame::Attach { name, size } => {
+            let mut sessions = shared.sessions.lock().unwrap();
+            let session = find(&sessions, &name)?;
+            let pid = manager.with_session(session, |pty| Ok(pty.get_pid()))?;
+            let hosted = sessions.get_mut(&session).unwrap();
+            match hosted.clients.iter_mut().find(|client| client.tx.same_channel(tx)) {
+                Some(client) => client.size = size,
+                None => hosted.clients.push(Client { tx: tx.clone(), size }),
+            }
+            hosted.reconcile(session, manager)?;
+
+            // Send the response while the output of the session is blocked, so that the
+            // scrollback and the output that follows it are contiguous.
+            let _ = tx.send(Frame::Attached {
+                session,
+                name: hosted.name.clone(),
+                pid,
+                cols: hosted.size.0,
+                rows: hosted.size.1,
+                scrollback: hosted.scrollback.contents(),
+                exit: hosted.exit,
+            });
+            return Ok(None);
+        }
+        Frame::List => Frame::Listed { sessions: shared.list() },
+        Frame::Spawn { session, appname, cmdline, cwd, env } => {
+            manager.spawn(session, appname.into(), cmdline.map(Into::into), cwd.map(Into::into), env.map(Into::into))?;
+            let pid = manager.with_session(session, |pty| Ok(pty.get_pid()))?;
+            Frame::Spawned { session, pid }
+        }
+        Frame::Input { session, data } => {
+            let count = manager.write(session, data.into())?;
+            Frame::Written { session, count }
+        }
+        Frame::Resize { session, cols, rows } => {
+            let mut sessions = shared.sessions.lock().unwrap();
+            match sessions.get_mut(&session) {
+                Some(hosted) => match hosted.clients.iter_mut().find(|client| client.tx.same_channel(tx)) {
+                    Some(client) => {
+                        client.size = Some((cols, rows));
+                        hosted.reconcile(session, manager)?;
+                    }
+                    None => hosted.resize(session, (cols, rows), manager)?,
+                },
+                None => manager.resize(session, cols, rows)?,
+            }
+            Frame::Ack { session }
+        }
+        Frame::Signal { session, signal } => {
+            manager.with_session(session, |pty| pty.signal(signal))?;
+            Frame::Ack { session }
+        }
+        Frame::Close { session } => {
+            let status = shared.close(session, Some(tx))?;
+            Frame::Closed { session, status }
+        }
+        other => return Err(OsString::from(format!("Unexpected frame {:?}", other))),
+    };
+    Ok(Some(response))
+}
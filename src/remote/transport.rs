@@ -0,0 +1,352 @@
+//! Local sockets used to reach a [`PtyServer`](super::PtyServer): named pipes on Windows
+//! and Unix domain sockets elsewhere.
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+
+#[cfg(windows)]
+pub use self::windows_impl::{LocalListener, LocalStream};
+#[cfg(unix)]
+pub use self::unix_impl::{LocalListener, LocalStream};
+
+#[cfg(windows)]
+mod windows_impl {
+    use windows::core::{Error, PCWSTR};
+    use windows::Win32::Foundation::{
+        CloseHandle, ERROR_BROKEN_PIPE, ERROR_IO_PENDING, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED,
+        GENERIC_READ, GENERIC_WRITE, HANDLE, WAIT_OBJECT_0,
+    };
+    use windows::Win32::Storage::FileSystem::{
+        CreateFileW, ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED,
+        FILE_SHARE_NONE, OPEN_EXISTING, PIPE_ACCESS_DUPLEX,
+    };
+    use windows::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, WaitNamedPipeW, PIPE_READMODE_BYTE,
+        PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+    use windows::Win32::System::Threading::{
+        CreateEventExW, WaitForSingleObject, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS,
+    };
+    use windows::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+
+    use core::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Size of the input and output buffers of each pipe instance.
+    const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+
+    /// Maximum amount of time (in ms) to wait for a busy server to accept a connection.
+    const CONNECT_TIMEOUT: u32 = 5000;
+
+    /// Interval (in ms) on which a pending operation checks if the stream was shut down.
+    const SHUTDOWN_POLL: u32 = 100;
+
+    fn win_error(err: Error) -> io::Error {
+        match err.code() == ERROR_BROKEN_PIPE.into() {
+            true => io::Error::from(io::ErrorKind::BrokenPipe),
+            false => io::Error::other(err.message()),
+        }
+    }
+
+    /// Full path of the named pipe, i.e., `\\.\pipe\<name>`, terminated by NUL.
+    fn pipe_path(name: &OsStr) -> Vec<u16> {
+        let mut path = OsString::new();
+        if !name.to_string_lossy().starts_with("\\\\.\\pipe\\") {
+            path.push("\\\\.\\pipe\\");
+        }
+        path.push(name);
+        path.encode_wide().chain(Some(0)).collect()
+    }
+
+    /// Handle that is closed when dropped.
+    struct Owned(isize);
+
+    impl Owned {
+        fn new(handle: HANDLE) -> Owned {
+            Owned(handle.0 as isize)
+        }
+
+        fn handle(&self) -> HANDLE {
+            HANDLE(self.0 as *mut c_void)
+        }
+    }
+
+    impl Drop for Owned {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = CloseHandle(self.handle());
+            }
+        }
+    }
+
+    fn create_event() -> Result<Owned, OsString> {
+        unsafe {
+            CreateEventExW(None, None, CREATE_EVENT_MANUAL_RESET, EVENT_ALL_ACCESS.0)
+                .map(Owned::new)
+                .map_err(|err| OsString::from(err.message()))
+        }
+    }
+
+    struct Pipe {
+        handle: Owned,
+        closed: AtomicBool,
+    }
+
+    /// Connected named pipe, opened for overlapped I/O so that it can be read and written
+    /// concurrently through its clones.
+    pub struct LocalStream {
+        pipe: Arc<Pipe>,
+        event: Owned,
+    }
+
+    impl LocalStream {
+        fn from_handle(handle: Owned) -> Result<LocalStream, OsString> {
+            Ok(LocalStream {
+                pipe: Arc::new(Pipe { handle, closed: AtomicBool::new(false) }),
+                event: create_event()?,
+            })
+        }
+
+        /// Connect to the server listening on the named pipe `name`.
+        pub fn connect(name: &OsStr) -> Result<LocalStream, OsString> {
+            let path = pipe_path(name);
+            loop {
+                let result = unsafe {
+                    CreateFileW(
+                        PCWSTR(path.as_ptr()),
+                        (GENERIC_READ | GENERIC_WRITE).0,
+                        FILE_SHARE_NONE,
+                        None,
+                        OPEN_EXISTING,
+                        FILE_FLAG_OVERLAPPED,
+                        None,
+                    )
+                };
+                match result {
+                    Ok(handle) => return LocalStream::from_handle(Owned::new(handle)),
+                    Err(err) if err.code() == ERROR_PIPE_BUSY.into() => unsafe {
+                        if !WaitNamedPipeW(PCWSTR(path.as_ptr()), CONNECT_TIMEOUT).as_bool() {
+                            return Err(OsString::from(Error::from_thread().message()));
+                        }
+                    },
+                    Err(err) => return Err(OsString::from(err.message())),
+                }
+            }
+        }
+
+        /// Create a new handle to the same stream, used to read and write from different threads.
+        pub fn try_clone(&self) -> Result<LocalStream, OsString> {
+            Ok(LocalStream { pipe: self.pipe.clone(), event: create_event()? })
+        }
+
+        /// Shut down the stream, which makes the pending and future operations of all its clones fail.
+        pub fn shutdown(&self) {
+            self.pipe.closed.store(true, Ordering::SeqCst);
+            unsafe {
+                let _ = CancelIoEx(self.pipe.handle.handle(), None);
+            }
+        }
+
+        fn io(&self, op: impl FnOnce(HANDLE, *mut OVERLAPPED) -> windows::core::Result<()>) -> io::Result<usize> {
+            if self.pipe.closed.load(Ordering::SeqCst) {
+                return Err(io::Error::from(io::ErrorKind::NotConnected));
+            }
+
+            let handle = self.pipe.handle.handle();
+            let mut overlapped = OVERLAPPED { hEvent: self.event.handle(), ..Default::default() };
+            if let Err(err) = op(handle, &mut overlapped) {
+                if err.code() != ERROR_IO_PENDING.into() {
+                    return Err(win_error(err));
+                }
+            }
+
+            // The stream may be shut down between the check above and the start of the operation.
+            unsafe {
+                while WaitForSingleObject(self.event.handle(), SHUTDOWN_POLL) != WAIT_OBJECT_0 {
+                    if self.pipe.closed.load(Ordering::SeqCst) {
+                        let _ = CancelIoEx(handle, Some(&overlapped));
+                    }
+                }
+                let mut transferred = 0u32;
+                GetOverlappedResult(handle, &overlapped, &mut transferred, true).map_err(win_error)?;
+                Ok(transferred as usize)
+            }
+        }
+    }
+
+    impl Read for LocalStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let result = self.io(|handle, overlapped| unsafe { ReadFile(handle, Some(buf), None, Some(overlapped)) });
+            match result {
+                // The other end closed the pipe.
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+                result => result,
+            }
+        }
+    }
+
+    impl Write for LocalStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.io(|handle, overlapped| unsafe { WriteFile(handle, Some(buf), None, Some(overlapped)) })
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Server end of a named pipe, which accepts connections from many clients.
+    pub struct LocalListener {
+        path: Vec<u16>,
+        /// Pipe instance waiting for the next client.
+        pending: Mutex<Option<Owned>>,
+    }
+
+    impl LocalListener {
+        fn create_instance(path: &[u16], first: bool) -> Result<Owned, OsString> {
+            let open_mode = match first {
+                // Fail if another server already owns the name.
+                true => PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED | FILE_FLAG_FIRST_PIPE_INSTANCE,
+                false => PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
+            };
+            unsafe {
+                let handle = CreateNamedPipeW(
+                    PCWSTR(path.as_ptr()),
+                    open_mode,
+                    PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                    PIPE_UNLIMITED_INSTANCES,
+                    PIPE_BUFFER_SIZE,
+                    PIPE_BUFFER_SIZE,
+                    0,
+                    None,
+                );
+                if handle.is_invalid() {
+                    return Err(OsString::from(Error::from_thread().message()));
+                }
+                Ok(Owned::new(handle))
+            }
+        }
+
+        /// Listen for connections on the named pipe `name`.
+        pub fn bind(name: &OsStr) -> Result<LocalListener, OsString> {
+            let path = pipe_path(name);
+            let first = LocalListener::create_instance(&path, true)?;
+            Ok(LocalListener { path, pending: Mutex::new(Some(first)) })
+        }
+
+        /// Wait for the next client to connect.
+        pub fn accept(&self) -> Result<LocalStream, OsString> {
+            let pipe = match self.pending.lock().unwrap().take() {
+                Some(pipe) => pipe,
+                None => LocalListener::create_instance(&self.path, false)?,
+            };
+
+            let event = create_event()?;
+            let mut overlapped = OVERLAPPED { hEvent: event.handle(), ..Default::default() };
+            unsafe {
+                match ConnectNamedPipe(pipe.handle(), Some(&mut overlapped)) {
+                    Ok(_) => (),
+                    Err(err) if err.code() == ERROR_PIPE_CONNECTED.into() => (),
+                    Err(err) if err.code() == ERROR_IO_PENDING.into() => {
+                        let mut transferred = 0u32;
+                        GetOverlappedResult(pipe.handle(), &overlapped, &mut transferred, true)
+                            .map_err(|err| OsString::from(err.message()))?;
+                    }
+                    Err(err) => return Err(OsString::from(err.message())),
+                }
+            }
+
+            // Create the next instance right away, so that clients do not find the pipe busy.
+            *self.pending.lock().unwrap() = LocalListener::create_instance(&self.path, false).ok();
+            Ok(LocalStream { pipe: Arc::new(Pipe { handle: pipe, closed: AtomicBool::new(false) }), event })
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix_impl {
+    use std::net::Shutdown;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn io_error(err: io::Error) -> OsString {
+        OsString::from(err.to_string())
+    }
+
+    /// Connected Unix domain socket.
+    pub struct LocalStream(UnixStream);
+
+    impl LocalStream {
+        /// Connect to the server listening on the socket at path `name`.
+        pub fn connect(name: &OsStr) -> Result<LocalStream, OsString> {
+            UnixStream::connect(name).map(LocalStream).map_err(io_error)
+        }
+
+        /// Create a new handle to the same stream, used to read and write from different threads.
+        pub fn try_clone(&self) -> Result<LocalStream, OsString> {
+            self.0.try_clone().map(LocalStream).map_err(io_error)
+        }
+
+        /// Shut down the stream, which makes the pending and future operations of all its clones fail.
+        pub fn shutdown(&self) {
+            let _ = self.0.shutdown(Shutdown::Both);
+        }
+    }
+
+    impl Read for LocalStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for LocalStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    /// Unix domain socket that accepts connections from many clients. The socket file is
+    /// removed when it is dropped.
+    pub struct LocalListener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl LocalListener {
+        /// Listen for connections on the socket at path `name`.
+        pub fn bind(name: &OsStr) -> Result<LocalListener, OsString> {
+            let path = PathBuf::from(name);
+            let listener = match UnixListener::bind(&path) {
+                Ok(listener) => listener,
+                // Replace the socket left behind by a server that is not running anymore.
+                Err(err) if err.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(&path).is_err() => {
+                    std::fs::remove_file(&path).map_err(io_error)?;
+                    UnixListener::bind(&path).map_err(io_error)?
+                }
+                Err(err) => return Err(io_error(err)),
+            };
+            Ok(LocalListener { listener, path })
+        }
+
+        /// Wait for the next client to connect.
+        pub fn accept(&self) -> Result<LocalStream, OsString> {
+            self.listener.accept().map(|(stream, _)| LocalStream(stream)).map_err(io_error)
+        }
+    }
+
+    impl Drop for LocalListener {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
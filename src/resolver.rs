@@ -0,0 +1,232 @@
+//! Resolution of the executable to spawn, following the `PATH` (and `PATHEXT` on Windows)
+//! semantics of the command interpreter.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Extensions used when the `PATHEXT` variable is not defined.
+#[cfg(windows)]
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// Extensions of the files that `CreateProcess` runs directly.
+#[cfg(windows)]
+const DIRECT_EXTENSIONS: [&str; 2] = ["com", "exe"];
+
+/// Extensions of the batch files, which are run by the command interpreter.
+const BATCH_EXTENSIONS: [&str; 2] = ["bat", "cmd"];
+
+/// Command interpreter used when the `ComSpec` variable is not defined.
+const DEFAULT_COMSPEC: &str = "C:\\Windows\\System32\\cmd.exe";
+
+/// Retrieve the value of a variable from an environment block (`VAR=VALUE` entries
+/// terminated by NUL characters), or from the environment of the current process
+/// if no block is given.
+fn env_var(env: Option<&OsStr>, name: &str) -> Option<OsString> {
+    match env {
+        None => env::var_os(name),
+        Some(block) => {
+            let block = block.to_string_lossy();
+            block
+                .split('\0')
+                .filter_map(|entry| {
+                    // Entries such as `=C:=C:\` declare hidden variables, whose name starts with `=`.
+                    let idx = entry.get(1..)?.find('=')? + 1;
+                    Some((&entry[..idx], &entry[idx + 1..]))
+                })
+                .find(|(key, _)| match cfg!(windows) {
+                    true => key.eq_ignore_ascii_case(name),
+                    false => *key == name,
+                })
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+}
+
+/// Build an environment block (`VAR=VALUE` entries terminated by NUL characters) made of
+/// the variables of the current process, updated with `vars`.
+pub fn environment_block(vars: &[(OsString, OsString)]) -> OsString {
+    let mut env: Vec<(OsString, OsString)> = env::vars_os().collect();
+    for (name, value) in vars {
+        let name_lossy = name.to_string_lossy();
+        env.retain(|(var, _)| match cfg!(windows) {
+            // The names of the environment variables are case insensitive in Windows.
+            true => !var.to_string_lossy().eq_ignore_ascii_case(&name_lossy),
+            false => var != name,
+        });
+        env.push((name.clone(), value.clone()));
+    }
+
+    let mut block = OsString::new();
+    for (name, value) in env {
+        block.push(name);
+        block.push("=");
+        block.push(value);
+        block.push("\0");
+    }
+    block
+}
+
+/// Check if the files with the extension `ext` (without the leading dot) are run by
+/// [`launch_command`], either directly or by the command interpreter. The other extensions
+/// of `PATHEXT` (e.g., `.JS`) need a script host and are not tried.
+#[cfg(windows)]
+fn is_launchable(ext: &str) -> bool {
+    DIRECT_EXTENSIONS.iter().chain(&BATCH_EXTENSIONS).any(|known| known.eq_ignore_ascii_case(ext))
+}
+
+/// List the file names that are checked in every directory, i.e., the name of the
+/// program followed by each extension in `PATHEXT`. Extensions that are only opened
+/// through file associations (e.g., `.js` or `.vbs`) are skipped, since they cannot be
+/// spawned.
+#[cfg(windows)]
+fn candidate_names(path: &Path, name: OsString, explicit: bool, env: Option<&OsStr>) -> Vec<OsString> {
+    let pathext = env_var(env, "PATHEXT")
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_else(|| DEFAULT_PATHEXT.to_owned());
+    let extensions: Vec<&str> = pathext
+        .split(';')
+        .filter(|ext| ext.strip_prefix('.').is_some_and(is_launchable))
+        .collect();
+
+    let mut names = Vec::new();
+    if let Some(extension) = path.extension() {
+        let extension = format!(".{}", extension.to_string_lossy());
+        // Explicit paths are spawned as is, whatever their extension is.
+        if explicit || extensions.iter().any(|ext| ext.eq_ignore_ascii_case(&extension)) {
+            names.push(name.clone());
+        }
+    }
+    for ext in extensions {
+        let mut with_ext = name.clone();
+        with_ext.push(ext.to_lowercase());
+        names.push(with_ext);
+    }
+    names
+}
+
+/// List the file names that are checked in every directory, i.e., the name of the program.
+#[cfg(not(windows))]
+fn candidate_names(_path: &Path, name: OsString, _explicit: bool, _env: Option<&OsStr>) -> Vec<OsString> {
+    vec![name]
+}
+
+/// List the paths that are checked, in order, to find the executable of a program.
+///
+/// # Arguments
+/// * `program` - Name or path of the program to spawn.
+/// * `cwd` - Optional path from where the program is spawned, relative paths are resolved against it.
+/// * `env` - Optional environment block of the program, from where `PATH` and `PATHEXT` are
+///   read. The environment of the current process is used if it is not given.
+pub fn candidates(program: &OsStr, cwd: Option<&OsStr>, env: Option<&OsStr>) -> Vec<PathBuf> {
+    let cwd = match cwd {
+        Some(cwd) => PathBuf::from(cwd),
+        None => env::current_dir().unwrap_or_default(),
+    };
+    let path = Path::new(program);
+    let has_separator = program.to_string_lossy().contains(['/', std::path::MAIN_SEPARATOR]);
+
+    let explicit = path.is_absolute() || has_separator;
+    let dirs: Vec<PathBuf> = if explicit {
+        vec![cwd.join(path).parent().map(Path::to_path_buf).unwrap_or_default()]
+    } else {
+        let search_path = env_var(env, "PATH").unwrap_or_default();
+        let search = env::split_paths(&search_path).filter(|dir| !dir.as_os_str().is_empty());
+        // The command interpreter searches the working directory first on Windows.
+        match cfg!(windows) {
+            true => std::iter::once(cwd.clone()).chain(search).collect(),
+            false => search.collect(),
+        }
+    };
+
+    let name = match path.file_name() {
+        Some(name) => name.to_os_string(),
+        None => return Vec::new(),
+    };
+
+    let names = candidate_names(path, name, explicit, env);
+    dirs.iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(not(windows))]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Find the executable of a program, following the `PATH` and `PATHEXT` semantics.
+///
+/// # Arguments
+/// * `program` - Name or path of the program to spawn.
+/// * `cwd` - Optional path from where the program is spawned, relative paths are resolved against it.
+/// * `env` - Optional environment block of the program, from where `PATH` and `PATHEXT` are
+///   read. The environment of the current process is used if it is not given.
+///
+/// # Returns
+/// The full path to the executable, else an error that lists every path that was checked.
+pub fn resolve_executable(program: &OsStr, cwd: Option<&OsStr>, env: Option<&OsStr>) -> Result<PathBuf, OsString> {
+    let candidates = candidates(program, cwd, env);
+    if let Some(found) = candidates.iter().find(|p| is_executable(p)) {
+        return Ok(found.clone());
+    }
+
+    let mut error = OsString::from(format!("Could not find the executable {:?}, checked:", program));
+    if candidates.is_empty() {
+        error.push(" (none)");
+    }
+    for candidate in candidates {
+        error.push("\n  ");
+        error.push(candidate.as_os_str());
+    }
+    Err(error)
+}
+
+/// Build the executable and the command line that spawn a program found by
+/// [`resolve_executable`], following the rules of `CreateProcess`.
+///
+/// Batch files (`.bat` and `.cmd`) are run by the command interpreter (`%ComSpec% /d /s /c`),
+/// which also interprets the characters of `cmdline`, see
+/// [`QuotingStyle::Cmd`](crate::shells::QuotingStyle::Cmd). Any other file is spawned
+/// directly, whatever its extension is.
+///
+/// # Arguments
+/// * `program` - Path to the file to spawn.
+/// * `cmdline` - Optional space-delimited arguments to provide to the program.
+/// * `env` - Optional environment block of the program, from where `ComSpec` is read. The
+///   environment of the current process is used if it is not given.
+///
+/// # Returns
+/// The executable to spawn and its arguments.
+pub fn launch_command(
+    program: &Path,
+    cmdline: Option<OsString>,
+    env: Option<&OsStr>,
+) -> (OsString, Option<OsString>) {
+    let ext = program.extension().map(|ext| ext.to_string_lossy().into_owned()).unwrap_or_default();
+    if !BATCH_EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(&ext)) {
+        return (program.as_os_str().to_os_string(), cmdline);
+    }
+
+    let comspec = env_var(env, "ComSpec")
+        .filter(|comspec| !comspec.is_empty())
+        .unwrap_or_else(|| OsString::from(DEFAULT_COMSPEC));
+    // With `/s`, the interpreter strips the outer quotes and runs the rest as is.
+    let mut command = OsString::from("/d /s /c \"\"");
+    command.push(program.as_os_str());
+    command.push("\"");
+    if let Some(cmdline) = cmdline {
+        command.push(" ");
+        command.push(cmdline);
+    }
+    command.push("\"");
+    (comspec, Some(command))
+}
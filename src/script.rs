@@ -0,0 +1,630 @@
+//! Scripts that drive interactive console programs through a [`PTY`].
+//!
+//! A script is a text file with one command per line. Blank lines and lines that start with
+//! `#` are ignored.
+//!
+//! ```text
+//! spawn cmd.exe /q
+//! expect />$/ timeout=5s
+//! sendline "echo version=1.2"
+//! expect /version=(?<version>[\d.]+)/
+//! sendline "exit ${version}"
+//! ```
+//!
+//! The commands are:
+//! * `spawn PROGRAM [ARGUMENTS...]`: start the program in a PTY, it must be the first command.
+//! * `send TEXT` and `sendline TEXT`: write the text, followed by a new line for `sendline`.
+//! * `expect /PATTERN/ [timeout=DURATION]` or `expect TEXT [timeout=DURATION]`: wait for the
+//!   output to match a regular expression, with the syntax of the [`regex`] crate, or to contain a text. The output that precedes the end
+//!   of the match is consumed, so that the next expectation only looks at the output that
+//!   follows it. The named groups of the pattern become variables.
+//! * `sleep DURATION`: wait for the given amount of time.
+//! * `resize COLS ROWS`: change the size of the PTY.
+//! * `key NAME...`: press keys, such as `enter`, `up`, `f5`, `ctrl-c` or `alt-x`.
+//! * `assert-exit CODE [timeout=DURATION]`: wait for the program to exit with the given code.
+//!
+//! Texts are quoted with `"` and accept the escapes `\r`, `\n`, `\t`, `\e`, `\xHH`, `\\`, `\"`
+//! and `\$`, as well as variables written as `${name}`. Durations are numbers followed by
+//! `ms`, `s` or `m`, they are given in seconds when the unit is omitted.
+//!
+//! The output is matched once stripped of escape sequences, see [`crate::vt::Tokenizer`].
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::pty::{PTYArgs, PTYBackend, PTY};
+use crate::shells::QuotingStyle;
+use crate::vt::{push_text, truncate_front, Tokenizer};
+
+/// Time to wait for an expectation or an exit when no timeout is given.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum amount of time to wait for output on each read.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Number of characters of the output that are kept to be reported on failure.
+const RECENT_OUTPUT: usize = 1024;
+
+/// Number of bytes of unconsumed output after which the oldest part is discarded.
+const MAX_PENDING_OUTPUT: usize = 256 * 1024;
+
+/// Failure of a script, either while parsing or while running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// Line of the script where the failure happened, starting at 1.
+    pub line: usize,
+    /// Description of the failure.
+    pub message: String,
+    /// Expectation that was pending when the failure happened, e.g., `/ready>/`.
+    pub expectation: Option<String>,
+    /// Output of the program that preceded the failure, stripped of escape sequences.
+    pub output: String,
+}
+
+impl ScriptError {
+    fn new(line: usize, message: impl Into<String>) -> ScriptError {
+        ScriptError { line, message: message.into(), expectation: None, output: String::new() }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)?;
+        if let Some(expectation) = &self.expectation {
+            write!(f, "\nexpected: {}", expectation)?;
+        }
+        if !self.output.is_empty() {
+            write!(f, "\nrecent output:")?;
+            for line in self.output.lines() {
+                write!(f, "\n    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<ScriptError> for OsString {
+    fn from(err: ScriptError) -> OsString {
+        OsString::from(err.to_string())
+    }
+}
+
+/// Part of a text, which is either literal or the value of a variable.
+#[derive(Debug)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+/// Quoted text of a script, which may refer to variables.
+#[derive(Debug)]
+struct Text {
+    parts: Vec<Part>,
+}
+
+impl Text {
+    fn literal(text: &str) -> Text {
+        Text { parts: vec![Part::Literal(text.to_owned())] }
+    }
+
+    fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    fn expand(&self, variables: &HashMap<String, String>) -> Result<String, String> {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => text.push_str(literal),
+                Part::Variable(name) => match variables.get(name) {
+                    Some(value) => text.push_str(value),
+                    None => return Err(format!("The variable {} was not captured", name)),
+                },
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Argument of a command.
+#[derive(Debug)]
+enum Argument {
+    Word(String),
+    Text(Text),
+    Pattern(String),
+    Option(String, String),
+}
+
+/// Command of a script.
+#[derive(Debug)]
+enum Command {
+    Spawn { program: Text, arguments: Vec<Text> },
+    Send(Text),
+    Expect { pattern: Regex, description: String, timeout: Duration },
+    Sleep(Duration),
+    Resize { cols: i32, rows: i32 },
+    Key(String),
+    AssertExit { code: u32, timeout: Duration },
+}
+
+/// Parsed script, ready to be run.
+#[derive(Debug)]
+pub struct Script {
+    steps: Vec<(usize, Command)>,
+}
+
+impl Script {
+    /// Parse a script, validating its commands, patterns and variables.
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        let mut steps = Vec::new();
+        let mut variables: Vec<String> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let tokens = tokenize(rest, name == "expect").map_err(|message| ScriptError::new(number, message))?;
+            let command = parse_command(name, tokens).map_err(|message| ScriptError::new(number, message))?;
+
+            let spawned = matches!(steps.first(), Some((_, Command::Spawn { .. })));
+            match &command {
+                Command::Spawn { .. } if spawned => {
+                    return Err(ScriptError::new(number, "The program was already spawned"));
+                }
+                Command::Spawn { .. } => (),
+                _ if !spawned => return Err(ScriptError::new(number, format!("{} requires a previous spawn", name))),
+                _ => (),
+            }
+
+            let texts: Vec<&Text> = match &command {
+                Command::Spawn { program, arguments } => std::iter::once(program).chain(arguments).collect(),
+                Command::Send(text) => vec![text],
+                _ => Vec::new(),
+            };
+            for variable in texts.into_iter().flat_map(Text::variables) {
+                if !variables.iter().any(|name| name == variable) {
+                    return Err(ScriptError::new(number, format!("Undefined variable {}", variable)));
+                }
+            }
+            if let Command::Expect { pattern, .. } = &command {
+                variables.extend(pattern.capture_names().flatten().map(str::to_owned));
+            }
+            steps.push((number, command));
+        }
+        Ok(Script { steps })
+    }
+
+    /// Run the script in a new PTY.
+    ///
+    /// # Arguments
+    /// * `args` - Arguments used to create the PTY once the program is spawned.
+    /// * `backend` - Backend of the PTY.
+    ///
+    /// # Returns
+    /// The variables captured by the script, or the [`ScriptError`] that describes the
+    /// command that failed, alongside the pending expectation and the recent output.
+    pub fn run(&self, args: &PTYArgs, backend: PTYBackend) -> Result<HashMap<String, String>, ScriptError> {
+        let mut runner = Runner {
+            pty: None,
+            tokenizer: Tokenizer::new(args.osc_max_payload),
+            pending: String::new(),
+            recent: String::new(),
+            eof: false,
+            variables: HashMap::new(),
+        };
+        for (line, command) in &self.steps {
+            runner.step(command, args, backend).map_err(|(message, expectation)| ScriptError {
+                line: *line,
+                message,
+                expectation,
+                output: runner.recent_output(),
+            })?;
+        }
+        Ok(runner.variables)
+    }
+}
+
+/// Split the arguments of a command. Patterns are only recognized when `patterns` is set,
+/// since other arguments such as switches of programs may start with a slash.
+fn tokenize(line: &str, patterns: bool) -> Result<Vec<Argument>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        match c {
+            '"' => {
+                chars.next();
+                tokens.push(Argument::Text(quoted_text(&mut chars)?));
+            }
+            '/' if patterns => {
+                chars.next();
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(String::from("Unterminated pattern")),
+                        Some('/') => break,
+                        // Slashes are escaped in patterns, other escapes belong to the pattern.
+                        Some('\\') if chars.peek() == Some(&'/') => pattern.push(chars.next().unwrap()),
+                        Some('\\') => {
+                            pattern.push('\\');
+                            pattern.extend(chars.next());
+                        }
+                        Some(c) => pattern.push(c),
+                    }
+                }
+                tokens.push(Argument::Pattern(pattern));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                tokens.push(match word.split_once('=') {
+                    Some((key, value)) => Argument::Option(key.to_owned(), value.to_owned()),
+                    None => Argument::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a quoted text, whose opening quote was already consumed.
+fn quoted_text(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Text, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(String::from("Unterminated text")),
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+                Some('r') => literal.push('\r'),
+                Some('n') => literal.push('\n'),
+                Some('t') => literal.push('\t'),
+                Some('e') => literal.push('\x1b'),
+                Some('x') => {
+                    let digits: String = chars.by_ref().take(2).collect();
+                    match u8::from_str_radix(&digits, 16) {
+                        Ok(byte) if digits.len() == 2 && byte < 0x80 => literal.push(byte as char),
+                        _ => return Err(format!("Invalid escape \\x{}", digits)),
+                    }
+                }
+                Some(c @ ('\\' | '"' | '$')) => literal.push(c),
+                Some(c) => return Err(format!("Invalid escape \\{}", c)),
+                None => return Err(String::from("Unterminated text")),
+            },
+            Some('$') if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) if c.is_alphanumeric() || c == '_' => name.push(c),
+                        _ => return Err(String::from("Invalid variable reference")),
+                    }
+                }
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Variable(name));
+            }
+            Some(c) => literal.push(c),
+        }
+    }
+    if !literal.is_empty() || parts.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    Ok(Text { parts })
+}
+
+/// Parse a duration such as `500ms`, `1.5s`, `2m` or `3`, the latter given in seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, scale) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (value, 1.0)
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number >= 0.0 && (number * scale).is_finite() => Ok(Duration::from_secs_f64(number * scale)),
+        _ => Err(format!("Invalid duration {}", value)),
+    }
+}
+
+fn parse_command(name: &str, tokens: Vec<Argument>) -> Result<Command, String> {
+    let mut timeout = DEFAULT_TIMEOUT;
+    let mut arguments = Vec::new();
+    for token in tokens {
+        match token {
+            Argument::Option(key, value) if key == "timeout" && matches!(name, "expect" | "assert-exit") => {
+                timeout = parse_duration(&value)?
+            }
+            // Options are only recognized by the commands that accept them.
+            Argument::Option(key, value) if name == "spawn" => arguments.push(Argument::Word(format!("{}={}", key, value))),
+            Argument::Option(key, _) => return Err(format!("Unknown option {} of {}", key, name)),
+            token => arguments.push(token),
+        }
+    }
+
+    let mut arguments = arguments.into_iter();
+    let mut next = |what: &str| arguments.next().ok_or_else(|| format!("Missing {} of {}", what, name));
+    let command = match name {
+        "spawn" => {
+            let text = |token| match token {
+                Argument::Word(word) => Ok(Text::literal(&word)),
+                Argument::Text(text) => Ok(text),
+                _ => Err(String::from("Invalid argument of spawn")),
+            };
+            let program = text(next("program")?)?;
+            let arguments = arguments.by_ref().map(text).collect::<Result<_, _>>()?;
+            Command::Spawn { program, arguments }
+        }
+        "send" | "sendline" => match next("text")? {
+            Argument::Text(mut text) => {
+                if name == "sendline" {
+                    text.parts.push(Part::Literal(String::from("\r\n")));
+                }
+                Command::Send(text)
+            }
+            _ => return Err(format!("{} expects a quoted text", name)),
+        },
+        "expect" => {
+            let (source, description) = match next("pattern")? {
+                Argument::Pattern(source) => (source.clone(), format!("/{}/", source)),
+                Argument::Text(text) if text.variables().next().is_none() => {
+                    let text = text.expand(&HashMap::new())?;
+                    (regex::escape(&text), format!("{:?}", text))
+                }
+                _ => return Err(String::from("expect expects a pattern or a quoted text")),
+            };
+            let pattern = compile_pattern(&source)?;
+            Command::Expect { pattern, description, timeout }
+        }
+        "sleep" => match next("duration")? {
+            Argument::Word(word) => Command::Sleep(parse_duration(&word)?),
+            _ => return Err(String::from("sleep expects a duration")),
+        },
+        "resize" => {
+            let mut size = || match next("size") {
+                Ok(Argument::Word(word)) => word.parse().ok().filter(|&size: &i32| size > 0).ok_or(format!("Invalid size {}", word)),
+                Ok(_) => Err(String::from("resize expects a number of columns and rows")),
+                Err(err) => Err(err),
+            };
+            let cols = size()?;
+            let rows = size()?;
+            Command::Resize { cols, rows }
+        }
+        "key" => {
+            let mut keys = String::new();
+            for token in arguments.by_ref() {
+                match token {
+                    Argument::Word(key) => keys.push_str(&key_sequence(&key).ok_or(format!("Unknown key {}", key))?),
+                    _ => return Err(String::from("key expects key names")),
+                }
+            }
+            if keys.is_empty() {
+                return Err(String::from("Missing key of key"));
+            }
+            Command::Key(keys)
+        }
+        "assert-exit" => match next("exit code")? {
+            Argument::Word(word) => {
+                let code = word.parse().map_err(|_| format!("Invalid exit code {}", word))?;
+                Command::AssertExit { code, timeout }
+            }
+            _ => return Err(String::from("assert-exit expects an exit code")),
+        },
+        _ => return Err(format!("Unknown command {}", name)),
+    };
+    match arguments.next() {
+        Some(_) => Err(format!("Too many arguments of {}", name)),
+        None => Ok(command),
+    }
+}
+
+/// Sequence that a terminal sends when a key is pressed.
+fn key_sequence(name: &str) -> Option<String> {
+    let lower = name.to_ascii_lowercase();
+    if let Some(key) = lower.strip_prefix("ctrl-") {
+        let mut chars = key.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c @ ('a'..='z' | '@' | '[' | '\\' | ']' | '^' | '_')), None) => {
+                Some(char::from(c.to_ascii_uppercase() as u8 & 0x1f).to_string())
+            }
+            _ if key == "space" => Some(String::from("\0")),
+            _ => None,
+        };
+    }
+    if let Some(key) = lower.strip_prefix("alt-") {
+        let mut chars = name[4..].chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(format!("\x1b{}", c)),
+            _ => key_sequence(key).map(|sequence| format!("\x1b{}", sequence)),
+        };
+    }
+    let sequence = match lower.as_str() {
+        "enter" => "\r",
+        "tab" => "\t",
+        "space" => " ",
+        "esc" | "escape" => "\x1b",
+        "backspace" => "\x7f",
+        "up" => "\x1b[A",
+        "down" => "\x1b[B",
+        "right" => "\x1b[C",
+        "left" => "\x1b[D",
+        "home" => "\x1b[H",
+        "end" => "\x1b[F",
+        "insert" => "\x1b[2~",
+        "delete" => "\x1b[3~",
+        "pageup" => "\x1b[5~",
+        "pagedown" => "\x1b[6~",
+        "f1" => "\x1bOP",
+        "f2" => "\x1bOQ",
+        "f3" => "\x1bOR",
+        "f4" => "\x1bOS",
+        "f5" => "\x1b[15~",
+        "f6" => "\x1b[17~",
+        "f7" => "\x1b[18~",
+        "f8" => "\x1b[19~",
+        "f9" => "\x1b[20~",
+        "f10" => "\x1b[21~",
+        "f11" => "\x1b[23~",
+        "f12" => "\x1b[24~",
+        _ => return None,
+    };
+    Some(String::from(sequence))
+}
+
+/// State of a running script.
+struct Runner {
+    pty: Option<PTY>,
+    tokenizer: Tokenizer,
+    /// Output that was not consumed by an expectation yet.
+    pending: String,
+    /// Tail of the output, reported on failure.
+    recent: String,
+    eof: bool,
+    variables: HashMap<String, String>,
+}
+
+/// Failure of a step, alongside the expectation that was pending.
+type StepError = (String, Option<String>);
+
+impl Runner {
+    fn step(&mut self, command: &Command, args: &PTYArgs, backend: PTYBackend) -> Result<(), StepError> {
+        let error = |err: OsString| (err.to_string_lossy().into_owned(), None);
+        if let Command::Spawn { program, arguments } = command {
+            let program = program.expand(&self.variables).map_err(|err| (err, None))?;
+            let mut cmdline = Vec::new();
+            for argument in arguments {
+                let argument = argument.expand(&self.variables).map_err(|err| (err, None))?;
+                cmdline.push(QuotingStyle::Msvcrt.quote(&argument));
+            }
+            let cmdline = (!cmdline.is_empty()).then(|| OsString::from(cmdline.join(" ")));
+            let mut pty = PTY::new_with_backend(args, backend).map_err(error)?;
+            pty.spawn(OsString::from(program), cmdline, None, None).map_err(error)?;
+            self.pty = Some(pty);
+            return Ok(());
+        }
+
+        // Every other command follows a spawn, as checked when parsing.
+        let pty = self.pty.as_ref().unwrap();
+        match command {
+            Command::Spawn { .. } => unreachable!(),
+            Command::Send(text) => {
+                let text = text.expand(&self.variables).map_err(|err| (err, None))?;
+                pty.write(OsString::from(text)).map_err(error)?;
+            }
+            Command::Key(keys) => {
+                pty.write(OsString::from(keys.as_str())).map_err(error)?;
+            }
+            Command::Resize { cols, rows } => pty.set_size(*cols, *rows).map_err(error)?,
+            Command::Sleep(duration) => thread::sleep(*duration),
+            Command::Expect { pattern, description, timeout } => {
+                let deadline = Instant::now() + *timeout;
+                // The output is only searched again once more of it was received.
+                let mut received = true;
+                loop {
+                    if let Some(captures) = received.then(|| pattern.captures(&self.pending)).flatten() {
+                        for name in pattern.capture_names().flatten() {
+                            if let Some(value) = captures.name(name) {
+                                self.variables.insert(name.to_owned(), value.as_str().to_owned());
+                            }
+                        }
+                        let end = captures.get(0).unwrap().end();
+                        self.pending.drain(..end);
+                        return Ok(());
+                    }
+                    let now = Instant::now();
+                    if self.eof {
+                        return Err((String::from("The program exited"), Some(description.clone())));
+                    }
+                    if now >= deadline {
+                        let message = format!("Timed out after {:?}", timeout);
+                        return Err((message, Some(description.clone())));
+                    }
+                    received = self.read(deadline - now).map_err(error)?;
+                }
+            }
+            Command::AssertExit { code, timeout } => {
+                let deadline = Instant::now() + *timeout;
+                loop {
+                    if let Some(status) = self.pty.as_ref().unwrap().get_exitstatus().map_err(error)? {
+                        if status != *code {
+                            return Err((format!("Expected exit code {}, got {}", code, status), None));
+                        }
+                        return Ok(());
+                    }
+                    let now = Instant::now();
+                    if now >= deadline {
+                        let message = format!("The program did not exit after {:?}", timeout);
+                        return Err((message, Some(format!("exit code {}", code))));
+                    }
+                    if self.eof {
+                        thread::sleep(POLL_INTERVAL.min(deadline - now));
+                    } else {
+                        self.read(deadline - now).map_err(error)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the output of the program, waiting at most `timeout`.
+    ///
+    /// # Returns
+    /// Whether any text was added to the pending output.
+    fn read(&mut self, timeout: Duration) -> Result<bool, OsString> {
+        let pty = self.pty.as_ref().unwrap();
+        let output = match pty.read_timeout(timeout.min(POLL_INTERVAL)) {
+            Ok(output) => output,
+            Err(err) => {
+                if pty.is_eof()? {
+                    self.eof = true;
+                    return Ok(false);
+                }
+                return Err(err);
+            }
+        };
+
+        let mut text = String::new();
+        push_text(&mut self.tokenizer, &output.to_string_lossy(), &mut text);
+        self.pending.push_str(&text);
+        self.recent.push_str(&text);
+        truncate_front(&mut self.pending, MAX_PENDING_OUTPUT);
+        let recent_bytes = self.recent.char_indices().rev().nth(RECENT_OUTPUT - 1).map_or(0, |(i, _)| i);
+        self.recent.drain(..recent_bytes);
+        Ok(!text.is_empty())
+    }
+
+    /// Recent output, with carriage returns turned into new lines.
+    fn recent_output(&self) -> String {
+        self.recent.replace("\r\n", "\n").replace('\r', "\n")
+    }
+}
+
+/// Compile a regular expression, describing its syntax errors in a single line.
+pub(crate) fn compile_pattern(source: &str) -> Result<Regex, String> {
+    Regex::new(source).map_err(|err| {
+        let message = err.to_string();
+        let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ");
+        format!("Invalid pattern /{}/: {}", source, reason)
+    })
+}
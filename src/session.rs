@@ -0,0 +1,164 @@
+//! Run commands one at a time in a long-lived interactive shell.
+//!
+//! Every command is wrapped between two unique sentinel markers that are printed by the
+//! shell itself, which allows to separate the command output from the echo of the command
+//! line and from the prompt, as well as to retrieve its exit code.
+
+use std::ffi::OsString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::pty::{Signal, PTY};
+use crate::shell_integration::ShellKind;
+use crate::vt::{push_text, Tokenizer};
+
+/// Maximum amount of time to wait for output on each read, so that cancellation
+/// requests and timeouts are honored promptly.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Result of a command executed by a [`ShellSession`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Output of the command, stripped of escape sequences, of the command echo and of the prompt.
+    pub text: String,
+    /// Exit code of the command, if the shell reported a valid one.
+    pub exit_code: Option<i32>,
+    /// Time elapsed between the command was written and its end marker was read.
+    pub duration: Duration,
+}
+
+/// Handle used to cancel the command that a [`ShellSession`] is running from another thread.
+#[derive(Clone, Debug)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Request the cancellation of the running command, which is interrupted by sending Ctrl+C to the shell.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Wrapper over a [`PTY`] running an interactive shell, used to run commands and retrieve their output.
+pub struct ShellSession {
+    pty: PTY,
+    kind: ShellKind,
+    counter: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ShellSession {
+    /// Create a new session.
+    ///
+    /// # Arguments
+    /// * `pty` - PTY where the shell was already spawned.
+    /// * `kind` - Kind of the spawned shell, used to build the sentinel markers.
+    pub fn new(pty: PTY, kind: ShellKind) -> ShellSession {
+        ShellSession {
+            pty,
+            kind,
+            counter: 0,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Retrieve the PTY where the shell runs.
+    pub fn pty(&self) -> &PTY {
+        &self.pty
+    }
+
+    /// Destroy the session and retrieve the PTY where the shell runs.
+    pub fn into_inner(self) -> PTY {
+        self.pty
+    }
+
+    /// Retrieve a handle that allows to cancel the running command from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle { cancelled: self.cancelled.clone() }
+    }
+
+    /// Run a command in the shell and wait for it to finish.
+    ///
+    /// # Arguments
+    /// * `command` - Command line to run, it must fit in a single line.
+    /// * `timeout` - Maximum amount of time to wait for the command to finish. The command is
+    ///   interrupted by sending Ctrl+C to the shell once it elapses.
+    ///
+    /// # Returns
+    /// The [`CommandOutput`] of the command, or an error if the command timed out, was
+    /// cancelled through a [`CancelHandle`] or the shell exited.
+    pub fn run(&mut self, command: &str, timeout: Option<Duration>) -> Result<CommandOutput, OsString> {
+        self.counter += 1;
+        let id = format!("{}_{}", self.pty.get_pid(), self.counter);
+        let begin = format!("__WINPTY_BEGIN_{}__", id);
+        let end = format!("__WINPTY_END_{}__", id);
+        self.cancelled.store(false, Ordering::SeqCst);
+
+        let start = Instant::now();
+        self.pty.write(OsString::from(wrap_command(self.kind, command, &id)))?;
+
+        let mut tokenizer = Tokenizer::default();
+        let mut text = String::new();
+        loop {
+            if let Some(output) = parse_output(&text, &begin, &end) {
+                return Ok(CommandOutput { duration: start.elapsed(), ..output });
+            }
+
+            if self.cancelled.swap(false, Ordering::SeqCst) {
+                self.pty.signal(Signal::Interrupt)?;
+                return Err(OsString::from("The command was cancelled"));
+            }
+            if let Some(timeout) = timeout {
+                if start.elapsed() >= timeout {
+                    self.pty.signal(Signal::Interrupt)?;
+                    return Err(OsString::from(format!("The command timed out after {:?}", timeout)));
+                }
+            }
+
+            let output = self.pty.read_timeout(POLL_INTERVAL)?;
+            if output.is_empty() && self.pty.is_eof()? {
+                return Err(OsString::from("The shell exited before the command finished"));
+            }
+            push_text(&mut tokenizer, &output.to_string_lossy(), &mut text);
+        }
+    }
+}
+
+/// Build the command line that runs `command` between the sentinel markers. The markers
+/// are split in the command line, so that its echo cannot be mistaken for them.
+fn wrap_command(kind: ShellKind, command: &str, id: &str) -> String {
+    match kind {
+        ShellKind::Cmd => format!(
+            "(echo __WINPTY_^BEGIN_{id}__)& {command} & call echo __WINPTY_^END_{id}__%^errorlevel%\r\n"
+        ),
+        ShellKind::PowerShell => format!(
+            "Write-Host ('__WINPTY_' + 'BEGIN_{id}__'); {command}; Write-Host ('__WINPTY_' + 'END_{id}__' + \
+             $(if ($?) {{ 0 }} elseif ($LASTEXITCODE) {{ $LASTEXITCODE }} else {{ 1 }}))\r\n"
+        ),
+        ShellKind::Bash | ShellKind::Zsh => format!(
+            "echo __WINPTY_\"\"BEGIN_{id}__; {command}; echo __WINPTY_\"\"END_{id}__$?\r"
+        ),
+    }
+}
+
+/// Extract the output of a command once both sentinel markers and the exit code were printed.
+fn parse_output(text: &str, begin: &str, end: &str) -> Option<CommandOutput> {
+    let begin_idx = text.find(begin)? + begin.len();
+    let rest = &text[begin_idx..];
+    let output_start = rest.find('\n')? + 1;
+    let rest = &rest[output_start..];
+
+    let end_idx = rest.find(end)?;
+    let after_end = &rest[end_idx + end.len()..];
+    let code_len = after_end.find(['\r', '\n'])?;
+
+    let output = rest[..end_idx].strip_suffix('\n').unwrap_or(&rest[..end_idx]);
+    let output = output.strip_suffix('\r').unwrap_or(output);
+    Some(CommandOutput {
+        text: output.to_owned(),
+        exit_code: after_end[..code_len].trim().parse().ok(),
+        duration: Duration::ZERO,
+    })
+}
@@ -0,0 +1,263 @@
+//! Shell integration marks (`OSC 133`, also known as FinalTerm marks).
+//!
+//! Shells that have the integration enabled report the boundaries of the prompt,
+//! the command line and the command output using the following sequences:
+//! * `OSC 133 ; A` - Start of the prompt.
+//! * `OSC 133 ; B` - End of the prompt, start of the command line typed by the user.
+//! * `OSC 133 ; C` - Start of the command output.
+//! * `OSC 133 ; D [; <exit code>]` - End of the command output.
+//!
+//! Those marks are turned into a timeline of [`CommandRecord`]s by [`ShellIntegration`].
+
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::vt::{Token, Tokenizer, DEFAULT_MAX_OSC_PAYLOAD};
+
+/// Maximum number of finished commands kept in the timeline.
+pub const MAX_COMMANDS: usize = 1000;
+
+/// Shells for which an integration snippet is available.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShellKind {
+    /// `cmd.exe`, the marks are emitted by the `PROMPT`. It does not report exit codes.
+    Cmd,
+    /// Windows PowerShell (`powershell.exe`) or PowerShell Core (`pwsh.exe`).
+    PowerShell,
+    /// GNU Bash (e.g., Git Bash, MSYS2 or WSL). Requires Bash 4.4+ to report the start of the output.
+    Bash,
+    /// Z shell.
+    Zsh,
+}
+
+impl ShellKind {
+    /// Guess the kind of a shell given the path or name of its executable.
+    pub fn from_program<S: AsRef<OsStr>>(program: S) -> Option<ShellKind> {
+        let program = program.as_ref().to_string_lossy().to_lowercase();
+        let name = program.rsplit(['\\', '/']).next()?;
+        let stem = Path::new(name).file_stem()?.to_str()?;
+        match stem {
+            "cmd" => Some(ShellKind::Cmd),
+            "powershell" | "pwsh" => Some(ShellKind::PowerShell),
+            "bash" | "sh" => Some(ShellKind::Bash),
+            "zsh" => Some(ShellKind::Zsh),
+            _ => None,
+        }
+    }
+
+    /// Command line that enables the shell integration marks when typed into the shell.
+    pub fn enable_command(&self) -> &'static str {
+        match self {
+            ShellKind::Cmd => "prompt $e]133;D$e\\$e]133;A$e\\$P$G$e]133;B$e\\\r\n",
+            ShellKind::PowerShell => concat!(
+                "$Global:__WinptyPrompt = $function:prompt; $Global:__WinptyLastId = -1; ",
+                "function Global:prompt { ",
+                "$c = if ($?) { 0 } elseif ($LASTEXITCODE) { $LASTEXITCODE } else { 1 }; ",
+                "$e = [char]27; $a = [char]7; $h = Get-History -Count 1; $d = ''; ",
+                "if ($h -and $h.Id -ne $Global:__WinptyLastId) { $Global:__WinptyLastId = $h.Id; $d = \"$e]133;D;$c$a\" }; ",
+                "\"$d$e]133;A$a\" + (& $Global:__WinptyPrompt) + \"$e]133;B$a\" }; ",
+                "if (Get-Module PSReadLine) { Set-PSReadLineKeyHandler -Chord Enter -ScriptBlock { ",
+                "[Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine(); ",
+                "[Console]::Write(\"$([char]27)]133;C$([char]7)\") } }\r\n"
+            ),
+            ShellKind::Bash => concat!(
+                "PS0='\\e]133;C\\a'; ",
+                "PS1='\\[\\e]133;D;$?\\a\\e]133;A\\a\\]'\"$PS1\"'\\[\\e]133;B\\a\\]'\n"
+            ),
+            ShellKind::Zsh => concat!(
+                "autoload -Uz add-zsh-hook; ",
+                "__winpty_precmd() { local c=$?; print -n \"\\e]133;D;$c\\a\\e]133;A\\a\" }; ",
+                "__winpty_preexec() { print -n \"\\e]133;C\\a\" }; ",
+                "add-zsh-hook precmd __winpty_precmd; add-zsh-hook preexec __winpty_preexec; ",
+                "PS1=\"$PS1%{\"$'\\e]133;B\\a'\"%}\"\n"
+            ),
+        }
+    }
+}
+
+/// Command that was executed in a shell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandRecord {
+    /// Command line typed by the user.
+    pub command: String,
+    /// Output of the command, stripped of escape sequences.
+    pub output: String,
+    /// Location of the output in the text (stripped of escape sequences) that was
+    /// processed by the [`ShellIntegration`], in characters.
+    pub output_range: Range<usize>,
+    /// Exit code reported by the shell, if any.
+    pub exit_code: Option<i32>,
+    /// Time elapsed between the start and the end of the command output, as observed
+    /// when the output was processed.
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Prompt,
+    Input,
+    Running,
+}
+
+/// Builds a timeline of commands out of the shell integration marks found in the output of a shell.
+#[derive(Debug)]
+pub struct ShellIntegration {
+    tokenizer: Tokenizer,
+    state: State,
+    /// Number of characters (stripped of escape sequences) processed so far.
+    offset: usize,
+    input: String,
+    /// Offset and time at which the first line of the command input ended.
+    submitted: Option<(usize, Instant)>,
+    command: String,
+    output: String,
+    output_start: usize,
+    started_at: Option<Instant>,
+    commands: VecDeque<CommandRecord>,
+}
+
+impl Default for ShellIntegration {
+    fn default() -> Self {
+        ShellIntegration::new()
+    }
+}
+
+impl ShellIntegration {
+    /// Create an empty timeline.
+    pub fn new() -> ShellIntegration {
+        ShellIntegration {
+            tokenizer: Tokenizer::new(DEFAULT_MAX_OSC_PAYLOAD),
+            state: State::Idle,
+            offset: 0,
+            input: String::new(),
+            submitted: None,
+            command: String::new(),
+            output: String::new(),
+            output_start: 0,
+            started_at: None,
+            commands: VecDeque::new(),
+        }
+    }
+
+    /// Finished commands, oldest first. At most [`MAX_COMMANDS`] are kept.
+    pub fn commands(&self) -> Vec<CommandRecord> {
+        self.commands.iter().cloned().collect()
+    }
+
+    /// Command line of the command that is currently running, if any.
+    pub fn running_command(&self) -> Option<String> {
+        match self.state {
+            State::Running => Some(self.command.clone()),
+            _ => None,
+        }
+    }
+
+    /// Process a chunk of the shell output.
+    pub fn feed(&mut self, output: &str) {
+        for token in self.tokenizer.feed(output) {
+            match token {
+                Token::Text(text) => self.push_text(&text),
+                Token::Control(c @ ('\r' | '\n' | '\t')) => {
+                    if c == '\n' && self.state == State::Input && self.submitted.is_none() {
+                        self.submitted = Some((self.offset + 1, Instant::now()));
+                    }
+                    self.push_char(c);
+                },
+                Token::Control('\x08') if self.state == State::Input => {
+                    self.input.pop();
+                },
+                Token::Osc(payload) => {
+                    if let Some(mark) = payload.strip_prefix("133;") {
+                        self.process_mark(mark);
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.push_char(c);
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.offset += 1;
+        match self.state {
+            State::Input => self.input.push(c),
+            State::Running => self.output.push(c),
+            _ => (),
+        }
+    }
+
+    fn process_mark(&mut self, mark: &str) {
+        let mut parts = mark.split(';');
+        match parts.next() {
+            Some("A") => {
+                if self.state == State::Running {
+                    // The previous command did not report its end.
+                    self.finish(None);
+                }
+                self.state = State::Prompt;
+            },
+            Some("B") => {
+                self.state = State::Input;
+                self.input.clear();
+                self.submitted = None;
+            },
+            Some("C") => {
+                self.command = self.input.trim().to_owned();
+                self.output.clear();
+                self.output_start = self.offset;
+                self.started_at = Some(Instant::now());
+                self.state = State::Running;
+            },
+            Some("D") => {
+                let exit_code = parts.next().and_then(|code| code.trim().parse().ok());
+                match self.state {
+                    State::Running => self.finish(exit_code),
+                    State::Input => {
+                        // Shells such as cmd do not report the start of the output, it
+                        // is assumed to start after the first line of the input.
+                        if let Some((offset, submitted)) = self.submitted {
+                            let input = std::mem::take(&mut self.input);
+                            let (command, output) = input.split_once('\n').unwrap_or((&input, ""));
+                            self.command = command.trim().to_owned();
+                            self.output = output.to_owned();
+                            self.output_start = offset;
+                            self.started_at = Some(submitted);
+                            self.finish(exit_code);
+                        }
+                    },
+                    _ => (),
+                }
+                self.state = State::Idle;
+            },
+            _ => (),
+        }
+    }
+
+    fn finish(&mut self, exit_code: Option<i32>) {
+        let duration = self.started_at.take().map(|t| t.elapsed()).unwrap_or_default();
+        let record = CommandRecord {
+            command: std::mem::take(&mut self.command),
+            output: std::mem::take(&mut self.output),
+            output_range: self.output_start..self.offset,
+            exit_code,
+            duration,
+        };
+
+        if self.commands.len() == MAX_COMMANDS {
+            self.commands.pop_front();
+        }
+        self.commands.push_back(record);
+        self.input.clear();
+        self.submitted = None;
+        self.state = State::Idle;
+    }
+}
@@ -0,0 +1,316 @@
+//! Discovery of the shells installed in the system and launch profiles to spawn them.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::resolver::environment_block;
+use crate::shell_integration::ShellKind;
+
+/// Quoting rules used by a shell to parse its command lines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// Rules of the Microsoft C runtime, used by most programs to split the command line
+    /// given to `CreateProcess`: arguments are surrounded by double quotes and inner double
+    /// quotes are escaped with a backslash.
+    Msvcrt,
+    /// Rules of `cmd.exe`: arguments are quoted as [`QuotingStyle::Msvcrt`] does for the
+    /// program that receives them, and the characters interpreted by `cmd.exe`
+    /// (`^ & | < > ( ) % ! "`) are escaped with a caret.
+    Cmd,
+    /// Rules of PowerShell: arguments are surrounded by single quotes, which are escaped by doubling them.
+    PowerShell,
+    /// Rules of POSIX shells: arguments are surrounded by single quotes, which cannot
+    /// be escaped inside a quoted string.
+    Posix,
+}
+
+impl QuotingStyle {
+    /// Quote an argument, if required, so that the shell reads it as a single word.
+    pub fn quote(&self, arg: &str) -> String {
+        match self {
+            QuotingStyle::Cmd => {
+                let quoted = QuotingStyle::Msvcrt.quote(arg);
+                let mut escaped = String::with_capacity(quoted.len());
+                for c in quoted.chars() {
+                    if "^&|<>()%!\"".contains(c) {
+                        escaped.push('^');
+                    }
+                    escaped.push(c);
+                }
+                escaped
+            },
+            QuotingStyle::Msvcrt => {
+                if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '"']) {
+                    return arg.to_owned();
+                }
+                let mut quoted = String::from("\"");
+                let mut backslashes = 0;
+                for c in arg.chars() {
+                    match c {
+                        '\\' => backslashes += 1,
+                        '"' => {
+                            // Backslashes that precede a quote must be escaped, as well as the quote.
+                            quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                            backslashes = 0;
+                        },
+                        _ => {
+                            quoted.push_str(&"\\".repeat(backslashes));
+                            backslashes = 0;
+                        }
+                    }
+                    if c != '\\' {
+                        quoted.push(c);
+                    }
+                }
+                // Backslashes that precede the closing quote must be escaped.
+                quoted.push_str(&"\\".repeat(backslashes * 2));
+                quoted.push('"');
+                quoted
+            },
+            QuotingStyle::PowerShell => {
+                let safe = |c: char| c.is_alphanumeric() || "-_./\\:=+,".contains(c);
+                if !arg.is_empty() && arg.chars().all(safe) {
+                    return arg.to_owned();
+                }
+                format!("'{}'", arg.replace('\'', "''"))
+            },
+            QuotingStyle::Posix => {
+                let safe = |c: char| c.is_alphanumeric() || "-_./:=+,@%".contains(c);
+                if !arg.is_empty() && arg.chars().all(safe) {
+                    return arg.to_owned();
+                }
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        }
+    }
+}
+
+/// Mode in which a shell is launched.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// Interactive shell.
+    Interactive,
+    /// Interactive login shell, which loads the profile of the user.
+    Login,
+}
+
+/// Arguments of [`PTY::spawn`](crate::PTY::spawn), see [`PTY::spawn_request`](crate::PTY::spawn_request).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpawnRequest {
+    /// Full path to the executable binary to spawn.
+    pub appname: OsString,
+    /// Optional space-delimited arguments to provide to the executable.
+    pub cmdline: Option<OsString>,
+    /// Optional path from where the executable should be spawned.
+    pub cwd: Option<OsString>,
+    /// Optional environment variables to provide to the process, declared as `VAR=VALUE`
+    /// and terminated by a NUL (0) character.
+    pub env: Option<OsString>,
+}
+
+/// Launch profile of an installed shell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShellProfile {
+    /// Human-readable name of the shell, e.g., `Windows PowerShell`.
+    pub name: String,
+    /// Kind of the shell, if it is known.
+    pub kind: Option<ShellKind>,
+    /// Full path to the executable of the shell.
+    pub executable: PathBuf,
+    /// Arguments used to start an interactive shell.
+    pub interactive_args: Vec<String>,
+    /// Arguments used to start an interactive login shell.
+    pub login_args: Vec<String>,
+    /// Environment variables recommended to run the shell inside a PTY, which are
+    /// added to the environment of the current process.
+    pub env: Vec<(String, String)>,
+    /// Quoting rules used by the shell.
+    pub quoting: QuotingStyle,
+}
+
+impl ShellProfile {
+    fn new(name: &str, kind: Option<ShellKind>, executable: PathBuf, quoting: QuotingStyle) -> ShellProfile {
+        ShellProfile {
+            name: name.to_owned(),
+            kind,
+            executable,
+            interactive_args: Vec::new(),
+            login_args: Vec::new(),
+            env: Vec::new(),
+            quoting,
+        }
+    }
+
+    fn with_args(mut self, interactive_args: &[&str], login_args: &[&str]) -> ShellProfile {
+        self.interactive_args = interactive_args.iter().map(|a| a.to_string()).collect();
+        self.login_args = login_args.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    fn with_env(mut self, env: &[(&str, &str)]) -> ShellProfile {
+        self.env = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        self
+    }
+
+    /// Build the arguments to spawn the shell inside a PTY.
+    ///
+    /// # Arguments
+    /// * `mode` - Whether to start an interactive or a login shell.
+    /// * `cwd` - Optional path from where the shell should be spawned.
+    pub fn spawn_request(&self, mode: LaunchMode, cwd: Option<OsString>) -> SpawnRequest {
+        let args = match mode {
+            LaunchMode::Interactive => &self.interactive_args,
+            LaunchMode::Login => &self.login_args,
+        };
+
+        // The command line is parsed by the C runtime of the executable, not by the shell itself.
+        let cmdline = match args.is_empty() {
+            true => None,
+            false => {
+                let quoted: Vec<String> = args.iter().map(|a| QuotingStyle::Msvcrt.quote(a)).collect();
+                Some(OsString::from(quoted.join(" ")))
+            }
+        };
+
+        let env = match self.env.is_empty() {
+            true => None,
+            false => {
+                let vars: Vec<(OsString, OsString)> =
+                    self.env.iter().map(|(k, v)| (OsString::from(k), OsString::from(v))).collect();
+                Some(environment_block(&vars))
+            }
+        };
+
+        SpawnRequest {
+            appname: self.executable.clone().into_os_string(),
+            cmdline,
+            cwd,
+            env,
+        }
+    }
+}
+
+fn env_path(var: &str) -> Option<PathBuf> {
+    env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).map(|dir| dir.join(name)).find(|p| p.is_file())
+}
+
+/// Retrieve the default shell of the system, i.e., `%ComSpec%` on Windows and `$SHELL` on Unix.
+pub fn default_shell() -> Option<ShellProfile> {
+    let variable = if cfg!(windows) { "ComSpec" } else { "SHELL" };
+    let executable = env_path(variable)?;
+    discover_shells()
+        .into_iter()
+        .find(|p| same_file(&p.executable, &executable))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.as_os_str().eq_ignore_ascii_case(b.as_os_str()),
+    }
+}
+
+/// Find the shells that are installed in the system.
+///
+/// On Windows, these are `cmd`, Windows PowerShell, PowerShell (`pwsh`), Git Bash and WSL.
+/// On Unix, these are the shells listed in `/etc/shells` and `$SHELL`.
+pub fn discover_shells() -> Vec<ShellProfile> {
+    let mut profiles = discover_platform_shells();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    profiles.retain(|p| {
+        if !p.executable.is_file() || seen.iter().any(|s| same_file(s, &p.executable)) {
+            return false;
+        }
+        seen.push(p.executable.clone());
+        true
+    });
+    profiles
+}
+
+#[cfg(windows)]
+fn discover_platform_shells() -> Vec<ShellProfile> {
+    let system_root = env_path("SystemRoot").unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+    let system32 = system_root.join("System32");
+    let program_dirs: Vec<PathBuf> = ["ProgramW6432", "ProgramFiles", "ProgramFiles(x86)"]
+        .iter()
+        .filter_map(|v| env_path(v))
+        .collect();
+
+    let mut profiles = Vec::new();
+    let cmd = env_path("ComSpec").unwrap_or_else(|| system32.join("cmd.exe"));
+    profiles.push(ShellProfile::new("Command Prompt", Some(ShellKind::Cmd), cmd, QuotingStyle::Cmd));
+
+    let powershell = system32.join("WindowsPowerShell").join("v1.0").join("powershell.exe");
+    profiles.push(
+        ShellProfile::new("Windows PowerShell", Some(ShellKind::PowerShell), powershell, QuotingStyle::PowerShell)
+            .with_args(&["-NoLogo"], &["-NoLogo"]),
+    );
+
+    // PowerShell 7+ installs each major version in its own directory, newest first.
+    let mut pwsh: Vec<(u32, PathBuf)> = program_dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir.join("PowerShell")).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let version = entry.file_name().to_string_lossy().split(['.', '-']).next()?.parse().ok()?;
+            Some((version, entry.path().join("pwsh.exe")))
+        })
+        .collect();
+    pwsh.sort_by(|a, b| b.0.cmp(&a.0));
+    let pwsh = pwsh.into_iter().map(|(_, path)| path).chain(find_in_path("pwsh.exe"));
+    for path in pwsh {
+        profiles.push(
+            ShellProfile::new("PowerShell", Some(ShellKind::PowerShell), path, QuotingStyle::PowerShell)
+                .with_args(&["-NoLogo"], &["-NoLogo"]),
+        );
+    }
+
+    let git_dirs = program_dirs
+        .iter()
+        .map(|dir| dir.join("Git"))
+        .chain(env_path("LOCALAPPDATA").map(|dir| dir.join("Programs").join("Git")));
+    for git in git_dirs {
+        profiles.push(
+            ShellProfile::new("Git Bash", Some(ShellKind::Bash), git.join("bin").join("bash.exe"), QuotingStyle::Posix)
+                .with_args(&["-i"], &["--login", "-i"])
+                .with_env(&[("CHERE_INVOKING", "1"), ("TERM", "xterm-256color")]),
+        );
+    }
+
+    profiles.push(
+        ShellProfile::new("WSL", None, system32.join("wsl.exe"), QuotingStyle::Posix)
+            .with_args(&[], &["--cd", "~"]),
+    );
+    profiles
+}
+
+#[cfg(not(windows))]
+fn discover_platform_shells() -> Vec<ShellProfile> {
+    let listed = std::fs::read_to_string("/etc/shells").unwrap_or_default();
+    let shells = env_path("SHELL")
+        .into_iter()
+        .chain(
+            listed
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(PathBuf::from),
+        );
+
+    shells
+        .map(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            ShellProfile::new(&name, ShellKind::from_program(&path), path, QuotingStyle::Posix)
+                .with_args(&["-i"], &["-l", "-i"])
+                .with_env(&[("TERM", "xterm-256color")])
+        })
+        .collect()
+}
@@ -0,0 +1,262 @@
+//! Triggers that react to the output of a PTY, e.g., to answer prompts automatically or to
+//! highlight errors.
+//!
+//! A [`Trigger`] is a regular expression, with the syntax of the [`regex`] crate, matched over the output of a [`crate::PTY`], once stripped
+//! of escape sequences (see [`crate::vt::Tokenizer`]). When it matches, the trigger calls its
+//! callback and writes its response into the PTY. Matches may span several reads: each
+//! trigger keeps the output that it did not match yet, up to a bounded lookback that is the
+//! only part of the output searched, and the output up to the end of a match is not matched
+//! again by the same trigger.
+//!
+//! Triggers are evaluated when the output is read, see [`crate::PTY::add_trigger`].
+
+use std::ffi::OsString;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::vt::{push_text, truncate_front, Tokenizer};
+
+/// Identifier of a trigger registered in a [`crate::PTY`].
+pub type TriggerId = u64;
+
+/// Default amount of output (in bytes) kept by a trigger to match across reads.
+pub const DEFAULT_LOOKBACK: usize = 4096;
+
+type Callback = Arc<dyn Fn(&TriggerMatch) + Send + Sync>;
+
+/// Output matched by a [`Trigger`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TriggerMatch {
+    /// Identifier of the trigger that matched.
+    pub trigger: TriggerId,
+    /// Text of the whole match.
+    pub text: String,
+    groups: Vec<Option<String>>,
+    names: Vec<(usize, String)>,
+}
+
+impl TriggerMatch {
+    /// Text of the group `index`, where group 0 is the whole match, if it participated in the match.
+    pub fn group(&self, index: usize) -> Option<&str> {
+        self.groups.get(index)?.as_deref()
+    }
+
+    /// Text of the group called `name`, if it participated in the match.
+    pub fn name(&self, name: &str) -> Option<&str> {
+        let index = self.names.iter().find(|(_, other)| other == name)?.0;
+        self.group(index)
+    }
+
+    /// Replace the `${N}` and `${name}` references of `template` by the text of the groups.
+    fn expand(&self, template: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("${") {
+            expanded.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let reference = &rest[start + 2..start + end];
+            let group = match reference.parse::<usize>() {
+                Ok(index) => self.group(index),
+                Err(_) => self.name(reference),
+            };
+            expanded.push_str(group.unwrap_or(""));
+            rest = &rest[start + end + 1..];
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+/// Pattern over the output of a PTY, alongside the reaction to its matches.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use winptyrs::Trigger;
+///
+/// // Answer a prompt once.
+/// let answer = Trigger::new(r"\(Y/N\)\?").unwrap().respond("Y\r\n").once();
+///
+/// // Report errors, at most once per second.
+/// let errors = Trigger::new(r"(?i)error: (?<message>[^\r\n]*)\r?\n")
+///     .unwrap()
+///     .on_match(|m| eprintln!("error: {}", m.name("message").unwrap()))
+///     .rate_limit(Duration::from_secs(1));
+/// ```
+pub struct Trigger {
+    pattern: Regex,
+    response: Option<String>,
+    callback: Option<Callback>,
+    once: bool,
+    min_interval: Option<Duration>,
+    lookback: usize,
+}
+
+impl Trigger {
+    /// Create a trigger that matches `pattern`, see [`regex`] for the supported syntax.
+    pub fn new(pattern: &str) -> Result<Trigger, OsString> {
+        Ok(Trigger {
+            pattern: Regex::new(pattern).map_err(|err| OsString::from(err.to_string()))?,
+            response: None,
+            callback: None,
+            once: false,
+            min_interval: None,
+            lookback: DEFAULT_LOOKBACK,
+        })
+    }
+
+    /// Write `response` into the PTY on every match. The references `${N}` and `${name}`
+    /// are replaced by the text of the groups of the match.
+    pub fn respond(mut self, response: &str) -> Trigger {
+        self.response = Some(response.to_owned());
+        self
+    }
+
+    /// Call `callback` on every match. It is called from the thread that reads the output,
+    /// so it must not block for long.
+    pub fn on_match<F: Fn(&TriggerMatch) + Send + Sync + 'static>(mut self, callback: F) -> Trigger {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Remove the trigger after its first match.
+    pub fn once(mut self) -> Trigger {
+        self.once = true;
+        self
+    }
+
+    /// Ignore the matches that happen less than `interval` after the last one that fired.
+    pub fn rate_limit(mut self, interval: Duration) -> Trigger {
+        self.min_interval = Some(interval);
+        self
+    }
+
+    /// Keep at most `bytes` of output that did not match yet, see [`DEFAULT_LOOKBACK`]. Only
+    /// the last `bytes` of the output are searched, so longer matches may be missed.
+    pub fn lookback(mut self, bytes: usize) -> Trigger {
+        self.lookback = bytes.max(1);
+        self
+    }
+
+    /// Source of the pattern of the trigger.
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+}
+
+impl fmt::Debug for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trigger")
+            .field("pattern", &self.pattern.as_str())
+            .field("response", &self.response)
+            .field("once", &self.once)
+            .field("min_interval", &self.min_interval)
+            .field("lookback", &self.lookback)
+            .finish()
+    }
+}
+
+/// Reaction to a match, to be run once the triggers are released.
+pub(crate) struct Firing {
+    pub matched: TriggerMatch,
+    pub callback: Option<Callback>,
+    pub response: Option<String>,
+}
+
+/// Trigger registered in a [`Triggers`] set.
+struct Registered {
+    id: TriggerId,
+    trigger: Trigger,
+    /// Output that was not matched yet.
+    pending: String,
+    last_fired: Option<Instant>,
+    done: bool,
+}
+
+/// Set of the triggers of a PTY.
+pub(crate) struct Triggers {
+    tokenizer: Tokenizer,
+    triggers: Vec<Registered>,
+    next_id: TriggerId,
+}
+
+impl Triggers {
+    pub fn new(max_osc_payload: usize) -> Triggers {
+        Triggers { tokenizer: Tokenizer::new(max_osc_payload), triggers: Vec::new(), next_id: 1 }
+    }
+
+    pub fn add(&mut self, trigger: Trigger) -> TriggerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.triggers.push(Registered { id, trigger, pending: String::new(), last_fired: None, done: false });
+        id
+    }
+
+    pub fn remove(&mut self, id: TriggerId) -> bool {
+        let count = self.triggers.len();
+        self.triggers.retain(|registered| registered.id != id);
+        self.triggers.len() < count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    /// Match the output against every trigger, returning the ones that fired.
+    pub fn feed(&mut self, output: &str) -> Vec<Firing> {
+        let mut text = String::new();
+        push_text(&mut self.tokenizer, output, &mut text);
+        if text.is_empty() {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        let mut fired = Vec::new();
+        for registered in &mut self.triggers {
+            registered.pending.push_str(&text);
+            truncate_front(&mut registered.pending, registered.trigger.lookback);
+            while let Some(captures) = registered.trigger.pattern.captures(&registered.pending) {
+                let range = captures.get(0).map_or(0..0, |whole| whole.range());
+                let limited = match (registered.last_fired, registered.trigger.min_interval) {
+                    (Some(last), Some(interval)) => now.duration_since(last) < interval,
+                    _ => false,
+                };
+                if !limited {
+                    let pattern = &registered.trigger.pattern;
+                    let groups = captures.iter().map(|group| group.map(|group| group.as_str().to_owned())).collect();
+                    let names = pattern.capture_names().enumerate();
+                    let matched = TriggerMatch {
+                        trigger: registered.id,
+                        text: registered.pending[range.clone()].to_owned(),
+                        groups,
+                        names: names.filter_map(|(index, name)| Some((index, name?.to_owned()))).collect(),
+                    };
+                    let response = registered.trigger.response.as_deref().map(|response| matched.expand(response));
+                    fired.push(Firing { matched, callback: registered.trigger.callback.clone(), response });
+                    registered.last_fired = Some(now);
+                }
+
+                // Skip past the match, or past one character if the match is empty.
+                let end = match range.end > range.start {
+                    true => range.end,
+                    false => registered.pending[range.end..].chars().next().map_or(range.end, |c| range.end + c.len_utf8()),
+                };
+                registered.pending.drain(..end);
+                if registered.trigger.once && !limited {
+                    registered.done = true;
+                    break;
+                }
+                if registered.pending.is_empty() {
+                    break;
+                }
+            }
+        }
+        self.triggers.retain(|registered| !registered.done);
+        fired
+    }
+}
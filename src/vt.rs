@@ -0,0 +1,274 @@
+//! Streaming tokenizer for the VT sequences produced by a process running inside a PTY.
+//!
+//! The tokenizer keeps its state between calls to [`Tokenizer::feed`], hence an escape
+//! sequence can be split across several reads of the PTY output.
+
+mod lines;
+mod screen;
+
+pub use lines::{render_lines, LineRenderer, MAX_LINE_LENGTH};
+pub use screen::{char_width, Cell, Color, Screen, Style};
+
+/// Default maximum length (in characters) of the payload of an OSC sequence.
+pub const DEFAULT_MAX_OSC_PAYLOAD: usize = 4096;
+
+/// Maximum length of the parameters and intermediates of a CSI or ESC sequence.
+const MAX_SEQUENCE_LEN: usize = 256;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+const CSI_C1: char = '\u{9b}';
+const ST_C1: char = '\u{9c}';
+const OSC_C1: char = '\u{9d}';
+
+/// Token produced by the [`Tokenizer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// Printable text.
+    Text(String),
+    /// C0 control character (e.g., `\r`, `\n` or `\x07`).
+    Control(char),
+    /// Control Sequence Introducer, e.g., `ESC [ 1 ; 2 H`.
+    Csi {
+        /// Parameter bytes (`0x30`-`0x3f`), e.g., `1;2` or `?25`.
+        params: String,
+        /// Intermediate bytes (`0x20`-`0x2f`).
+        intermediates: String,
+        /// Final byte of the sequence (`0x40`-`0x7e`).
+        action: char,
+    },
+    /// Operating System Command, without its introducer and terminator, e.g., `0;title`.
+    Osc(String),
+    /// Escape sequence that is neither a CSI nor an OSC, e.g., `ESC 7` or `ESC ( B`.
+    Esc {
+        /// Intermediate bytes (`0x20`-`0x2f`).
+        intermediates: String,
+        /// Final byte of the sequence.
+        action: char,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+/// Incremental VT tokenizer.
+#[derive(Clone, Debug)]
+pub struct Tokenizer {
+    state: State,
+    /// Parameters of the current CSI sequence or payload of the current OSC sequence.
+    params: String,
+    /// Length (in characters) of `params`.
+    params_len: usize,
+    /// Intermediates of the current CSI or ESC sequence.
+    intermediates: String,
+    /// Set when the current sequence exceeds its maximum length and must be discarded.
+    overflow: bool,
+    max_osc_payload: usize,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer::new(DEFAULT_MAX_OSC_PAYLOAD)
+    }
+}
+
+impl Tokenizer {
+    /// Create a new tokenizer.
+    ///
+    /// # Arguments
+    /// * `max_osc_payload` - Maximum length (in characters) of the payload of an OSC sequence.
+    ///   Longer sequences are consumed but discarded.
+    pub fn new(max_osc_payload: usize) -> Tokenizer {
+        Tokenizer {
+            state: State::Ground,
+            params: String::new(),
+            params_len: 0,
+            intermediates: String::new(),
+            overflow: false,
+            max_osc_payload,
+        }
+    }
+
+    /// Maximum length (in characters) of the payload of an OSC sequence.
+    pub fn max_osc_payload(&self) -> usize {
+        self.max_osc_payload
+    }
+
+    /// Tokenize a chunk of output. Incomplete sequences at the end of the chunk are kept
+    /// until the following call.
+    pub fn feed(&mut self, input: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+
+        for c in input.chars() {
+            if self.state == State::Ground {
+                match c {
+                    ESC | CSI_C1 | OSC_C1 => {
+                        if !text.is_empty() {
+                            tokens.push(Token::Text(std::mem::take(&mut text)));
+                        }
+                    },
+                    c if is_control(c) => {
+                        if !text.is_empty() {
+                            tokens.push(Token::Text(std::mem::take(&mut text)));
+                        }
+                    },
+                    c => {
+                        text.push(c);
+                        continue;
+                    }
+                }
+            }
+            self.advance(c, &mut tokens);
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        tokens
+    }
+
+    fn begin(&mut self, state: State) {
+        self.state = state;
+        self.params.clear();
+        self.params_len = 0;
+        self.intermediates.clear();
+        self.overflow = false;
+    }
+
+    fn push_param(&mut self, c: char, limit: usize) {
+        if self.params_len < limit {
+            self.params.push(c);
+            self.params_len += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn push_intermediate(&mut self, c: char) {
+        if self.intermediates.len() < MAX_SEQUENCE_LEN {
+            self.intermediates.push(c);
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn finish_osc(&mut self, tokens: &mut Vec<Token>) {
+        if !self.overflow {
+            tokens.push(Token::Osc(std::mem::take(&mut self.params)));
+        }
+        self.begin(State::Ground);
+    }
+
+    fn advance(&mut self, c: char, tokens: &mut Vec<Token>) {
+        match self.state {
+            State::Ground => match c {
+                ESC => self.begin(State::Escape),
+                CSI_C1 => self.begin(State::Csi),
+                OSC_C1 => self.begin(State::Osc),
+                c => tokens.push(Token::Control(c)),
+            },
+            State::Escape => match c {
+                ESC => self.begin(State::Escape),
+                '[' if self.intermediates.is_empty() => self.begin(State::Csi),
+                ']' if self.intermediates.is_empty() => self.begin(State::Osc),
+                '\x20'..='\x2f' => self.push_intermediate(c),
+                '\x30'..='\x7e' => {
+                    if !self.overflow {
+                        tokens.push(Token::Esc {
+                            intermediates: std::mem::take(&mut self.intermediates),
+                            action: c,
+                        });
+                    }
+                    self.begin(State::Ground);
+                },
+                '\x18' | '\x1a' => self.begin(State::Ground),
+                c if is_control(c) => tokens.push(Token::Control(c)),
+                _ => self.begin(State::Ground),
+            },
+            State::Csi => match c {
+                ESC => self.begin(State::Escape),
+                '\x30'..='\x3f' if self.intermediates.is_empty() => {
+                    self.push_param(c, MAX_SEQUENCE_LEN)
+                },
+                '\x30'..='\x3f' => self.overflow = true,
+                '\x20'..='\x2f' => self.push_intermediate(c),
+                '\x40'..='\x7e' => {
+                    if !self.overflow {
+                        tokens.push(Token::Csi {
+                            params: std::mem::take(&mut self.params),
+                            intermediates: std::mem::take(&mut self.intermediates),
+                            action: c,
+                        });
+                    }
+                    self.begin(State::Ground);
+                },
+                '\x18' | '\x1a' => self.begin(State::Ground),
+                c if is_control(c) => tokens.push(Token::Control(c)),
+                _ => self.begin(State::Ground),
+            },
+            State::Osc => match c {
+                BEL | ST_C1 => self.finish_osc(tokens),
+                ESC => self.state = State::OscEscape,
+                '\x18' | '\x1a' => self.begin(State::Ground),
+                c if is_control(c) => (),
+                c => {
+                    let limit = self.max_osc_payload;
+                    self.push_param(c, limit)
+                }
+            },
+            State::OscEscape => {
+                if c == '\\' {
+                    self.finish_osc(tokens);
+                } else {
+                    // Any other escape sequence aborts the OSC sequence.
+                    self.begin(State::Escape);
+                    self.advance(c, tokens);
+                }
+            }
+        }
+    }
+}
+
+/// Numeric parameters of a CSI sequence, e.g., `[1, 0, 2]` for `1;;2`. Missing or invalid
+/// parameters are 0, and a leading private marker (e.g., `?`) is ignored.
+pub(crate) fn csi_params(params: &str) -> Vec<u32> {
+    let params = params.trim_start_matches(['<', '=', '>', '?']);
+    if params.is_empty() {
+        return Vec::new();
+    }
+    params.split(';').map(|param| param.parse().unwrap_or(0)).collect()
+}
+
+/// Tokenize a chunk of output and append its printable text to `text`, keeping only the
+/// carriage returns, new lines and tabs among the control characters.
+pub(crate) fn push_text(tokenizer: &mut Tokenizer, output: &str, text: &mut String) {
+    for token in tokenizer.feed(output) {
+        match token {
+            Token::Text(t) => text.push_str(&t),
+            Token::Control(c @ ('\r' | '\n' | '\t')) => text.push(c),
+            _ => (),
+        }
+    }
+}
+
+/// Discard the oldest part of `text`, so that it is at most `max` bytes long.
+pub(crate) fn truncate_front(text: &mut String, max: usize) {
+    if text.len() > max {
+        let mut start = text.len() - max;
+        while !text.is_char_boundary(start) {
+            start += 1;
+        }
+        text.drain(..start);
+    }
+}
+
+fn is_control(c: char) -> bool {
+    c < '\x20' || c == '\x7f'
+}
@@ -0,0 +1,146 @@
+//! Rendering of the output of a PTY into plain-text lines, e.g., for build logs.
+//!
+//! Programs that draw progress bars rewrite the current line with carriage returns,
+//! backspaces, cursor motions and erasures. A [`LineRenderer`] interprets them within the
+//! current line and only emits the final state of each line, without any escape sequence.
+//! Motions to other lines are ignored. Only the current line is kept in memory, hence
+//! outputs of any size can be rendered.
+
+use super::{csi_params, Token, Tokenizer};
+
+/// Maximum length (in characters) of a line, beyond which it is wrapped.
+pub const MAX_LINE_LENGTH: usize = 65536;
+
+/// Width of the tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// Streaming renderer of output into plain-text lines.
+///
+/// # Examples
+/// ```
+/// use winptyrs::vt::LineRenderer;
+///
+/// let mut renderer = LineRenderer::new();
+/// assert_eq!(renderer.feed("Downloading  10%\rDownloading 1"), "");
+/// assert_eq!(renderer.feed("00%\x1b[K\r\n\x1b[32mdone\x1b[0m"), "Downloading 100%\n");
+/// assert_eq!(renderer.finish(), "done\n");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LineRenderer {
+    tokenizer: Tokenizer,
+    line: Vec<char>,
+    cursor: usize,
+}
+
+impl LineRenderer {
+    /// Create a renderer at the beginning of an empty line.
+    pub fn new() -> LineRenderer {
+        LineRenderer::default()
+    }
+
+    /// Render a chunk of output, returning the lines that it completed, each one terminated
+    /// by `\n`. Trailing spaces are removed from every line.
+    pub fn feed(&mut self, output: &str) -> String {
+        let mut lines = String::new();
+        for token in self.tokenizer.feed(output) {
+            match token {
+                Token::Text(text) => {
+                    for c in text.chars() {
+                        self.put(c, &mut lines);
+                    }
+                }
+                Token::Control('\n' | '\x0b' | '\x0c') => self.end_line(&mut lines),
+                Token::Control('\r') => self.cursor = 0,
+                Token::Control('\x08') => self.cursor = self.cursor.saturating_sub(1),
+                Token::Control('\t') => self.move_to((self.cursor / TAB_WIDTH + 1) * TAB_WIDTH),
+                Token::Csi { params, intermediates, action } if intermediates.is_empty() => {
+                    self.csi(&params, action, &mut lines)
+                }
+                Token::Esc { intermediates, action: 'E' } if intermediates.is_empty() => self.end_line(&mut lines),
+                _ => (),
+            }
+        }
+        lines
+    }
+
+    /// Flush the current line, unless it is blank, e.g., once the process exited.
+    pub fn finish(&mut self) -> String {
+        let mut lines = String::new();
+        if self.line.iter().any(|&c| c != ' ') {
+            self.end_line(&mut lines);
+        }
+        self.line.clear();
+        self.cursor = 0;
+        lines
+    }
+
+    fn put(&mut self, c: char, lines: &mut String) {
+        if self.cursor >= MAX_LINE_LENGTH {
+            self.end_line(lines);
+        }
+        if self.cursor < self.line.len() {
+            self.line[self.cursor] = c;
+        } else {
+            self.line.resize(self.cursor, ' ');
+            self.line.push(c);
+        }
+        self.cursor += 1;
+    }
+
+    fn move_to(&mut self, column: usize) {
+        self.cursor = column.min(MAX_LINE_LENGTH - 1);
+    }
+
+    /// Replace the characters from `start` to `end` (excluded) by spaces.
+    fn blank(&mut self, start: usize, end: usize) {
+        let end = end.min(self.line.len());
+        if start < end {
+            self.line[start..end].fill(' ');
+        }
+    }
+
+    fn csi(&mut self, params: &str, action: char, lines: &mut String) {
+        let params = csi_params(params);
+        let mode = params.first().copied().unwrap_or(0);
+        let count = (mode as usize).max(1);
+        match action {
+            'C' => self.move_to(self.cursor.saturating_add(count)),
+            'D' => self.cursor = self.cursor.saturating_sub(count),
+            'G' | '`' => self.move_to(count - 1),
+            'E' => self.end_line(lines),
+            // Erasures beyond the current line (ED) only affect the current line.
+            'K' | 'J' => match mode {
+                0 => self.line.truncate(self.cursor),
+                1 => self.blank(0, self.cursor.saturating_add(1)),
+                _ => self.line.clear(),
+            },
+            'X' => self.blank(self.cursor, self.cursor.saturating_add(count)),
+            'P' if self.cursor < self.line.len() => {
+                let end = self.cursor.saturating_add(count).min(self.line.len());
+                self.line.drain(self.cursor..end);
+            }
+            '@' if self.cursor < self.line.len() => {
+                let count = count.min(MAX_LINE_LENGTH);
+                self.line.splice(self.cursor..self.cursor, std::iter::repeat_n(' ', count));
+                self.line.truncate(MAX_LINE_LENGTH);
+            }
+            _ => (),
+        }
+    }
+
+    fn end_line(&mut self, lines: &mut String) {
+        let end = self.line.iter().rposition(|&c| c != ' ').map_or(0, |i| i + 1);
+        lines.extend(&self.line[..end]);
+        lines.push('\n');
+        self.line.clear();
+        self.cursor = 0;
+    }
+}
+
+/// Render a whole output into plain-text lines, see [`LineRenderer`].
+pub fn render_lines(output: &str) -> String {
+    let mut renderer = LineRenderer::new();
+    let mut lines = renderer.feed(output);
+    lines.push_str(&renderer.finish());
+    lines
+}
@@ -0,0 +1,627 @@
+//! Virtual screen that interprets the output of a PTY into a grid of styled cells.
+//!
+//! The [`Screen`] supports the sequences commonly used by shells and full-screen programs:
+//! cursor motions, erasures, insertions and deletions, scrolling regions, the alternate
+//! screen, SGR colors (16, 256 and 24-bit) and attributes, and OSC 8 hyperlinks. Lines that
+//! scroll off the top of the screen are kept in a bounded scrollback, so that a whole
+//! transcript can be rendered too.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::{csi_params, Token, Tokenizer};
+
+/// Width of the tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// Color of a cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// Default foreground or background color of the terminal.
+    #[default]
+    Default,
+    /// Color of the 256-color palette, where the first 16 are the ANSI colors.
+    Indexed(u8),
+    /// 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+/// Colors and attributes of a cell, as set by SGR sequences.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Swap the foreground and background colors.
+    pub inverse: bool,
+}
+
+/// Character cell of a [`Screen`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    /// Character of the cell, or `'\0'` if the cell is covered by the wide character that
+    /// precedes it.
+    pub c: char,
+    pub style: Style,
+    /// Target of the OSC 8 hyperlink that covers the cell.
+    pub link: Option<Arc<str>>,
+}
+
+impl Cell {
+    fn blank(style: Style) -> Cell {
+        Cell { c: ' ', style: Style { bg: style.bg, ..Style::default() }, link: None }
+    }
+
+    /// Check if the cell is covered by the wide character that precedes it.
+    pub fn is_continuation(&self) -> bool {
+        self.c == '\0'
+    }
+
+    /// Number of columns taken by the character of the cell.
+    pub fn width(&self) -> usize {
+        match self.is_continuation() {
+            true => 0,
+            false => char_width(self.c),
+        }
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::blank(Style::default())
+    }
+}
+
+/// Number of columns taken by `c`: 0 for combining and zero-width characters, 2 for wide
+/// East Asian characters and emoji, and 1 otherwise.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x0483..=0x0489 | 0x0591..=0x05bd | 0x0610..=0x061a | 0x064b..=0x065f
+        | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x200b..=0x200f | 0x2028..=0x202e | 0x2060..=0x2064
+        | 0x20d0..=0x20ff | 0xfe00..=0xfe0f | 0xfe20..=0xfe2f | 0xfeff | 0xe0100..=0xe01ef => 0,
+        0x1100..=0x115f | 0x231a..=0x231b | 0x2329..=0x232a | 0x23e9..=0x23ec | 0x23f0 | 0x23f3
+        | 0x25fd..=0x25fe | 0x2614..=0x2615 | 0x2648..=0x2653 | 0x267f | 0x2693 | 0x26a1
+        | 0x26aa..=0x26ab | 0x26bd..=0x26be | 0x26c4..=0x26c5 | 0x26ce | 0x26d4 | 0x26ea
+        | 0x26f2..=0x26f3 | 0x26f5 | 0x26fa | 0x26fd | 0x2705 | 0x270a..=0x270b | 0x2728 | 0x274c
+        | 0x274e | 0x2753..=0x2755 | 0x2757 | 0x2795..=0x2797 | 0x27b0 | 0x27bf | 0x2b1b..=0x2b1c
+        | 0x2b50 | 0x2b55 | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf | 0xa960..=0xa97f | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe10..=0xfe19
+        | 0xfe30..=0xfe6f | 0xff00..=0xff60 | 0xffe0..=0xffe6 | 0x16fe0..=0x18d08 | 0x1b000..=0x1b2ff
+        | 0x1f004 | 0x1f0cf | 0x1f18e | 0x1f191..=0x1f19a | 0x1f200..=0x1f251 | 0x1f300..=0x1f320
+        | 0x1f32d..=0x1f335 | 0x1f337..=0x1f37c | 0x1f37e..=0x1f393 | 0x1f3a0..=0x1f3ca
+        | 0x1f3cf..=0x1f3d3 | 0x1f3e0..=0x1f3f0 | 0x1f3f4 | 0x1f3f8..=0x1f43e | 0x1f440
+        | 0x1f442..=0x1f4fc | 0x1f4ff..=0x1f53d | 0x1f54b..=0x1f54e | 0x1f550..=0x1f567 | 0x1f57a
+        | 0x1f595..=0x1f596 | 0x1f5a4 | 0x1f5fb..=0x1f64f | 0x1f680..=0x1f6c5 | 0x1f6cc
+        | 0x1f6d0..=0x1f6d2 | 0x1f6d5..=0x1f6d7 | 0x1f6eb..=0x1f6ec | 0x1f6f4..=0x1f6fc
+        | 0x1f7e0..=0x1f7eb | 0x1f90c..=0x1f93a | 0x1f93c..=0x1f945 | 0x1f947..=0x1f9ff
+        | 0x1fa70..=0x1faff | 0x20000..=0x2fffd | 0x30000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// Position and pen saved by `ESC 7` or `CSI s`.
+#[derive(Clone, Debug)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    style: Style,
+}
+
+/// Virtual screen of `cols` × `rows` cells.
+///
+/// # Examples
+/// ```
+/// use winptyrs::vt::{Color, Screen};
+///
+/// let mut screen = Screen::new(20, 3);
+/// screen.feed("\x1b[31mred\x1b[0m\r\n\x1b[2;5Hmoved");
+/// assert_eq!(screen.text(), "red\n    moved\n");
+/// assert_eq!(screen.row(0)[0].style.fg, Color::Indexed(1));
+/// assert_eq!(screen.cursor(), (1, 9));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Screen {
+    tokenizer: Tokenizer,
+    cols: usize,
+    rows: usize,
+    grid: Vec<Vec<Cell>>,
+    scrollback: VecDeque<Vec<Cell>>,
+    max_scrollback: usize,
+    /// Grid of the main screen while the alternate screen is active.
+    main: Option<Vec<Vec<Cell>>>,
+    /// Cursor of the main screen, saved when entering the alternate screen.
+    main_cursor: Option<SavedCursor>,
+    row: usize,
+    col: usize,
+    /// Set when a character was written at the last column, so that the next one wraps.
+    pending_wrap: bool,
+    saved: Option<SavedCursor>,
+    style: Style,
+    link: Option<Arc<str>>,
+    /// First and last rows of the scrolling region.
+    top: usize,
+    bottom: usize,
+    cursor_visible: bool,
+}
+
+impl Screen {
+    /// Create a blank screen of `cols` × `rows` cells, without scrollback.
+    pub fn new(cols: usize, rows: usize) -> Screen {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        Screen {
+            tokenizer: Tokenizer::default(),
+            cols,
+            rows,
+            grid: vec![vec![Cell::default(); cols]; rows],
+            scrollback: VecDeque::new(),
+            max_scrollback: 0,
+            main: None,
+            main_cursor: None,
+            row: 0,
+            col: 0,
+            pending_wrap: false,
+            saved: None,
+            style: Style::default(),
+            link: None,
+            top: 0,
+            bottom: rows - 1,
+            cursor_visible: true,
+        }
+    }
+
+    /// Keep up to `lines` lines that scroll off the top of the screen, e.g., `usize::MAX`
+    /// to render a whole transcript.
+    pub fn with_scrollback(mut self, lines: usize) -> Screen {
+        self.max_scrollback = lines;
+        self
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Position of the cursor, as `(row, col)` starting from 0.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Check if the cursor was not hidden with `CSI ? 25 l`.
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Check if the alternate screen is active, e.g., while a full-screen program runs.
+    pub fn is_alternate(&self) -> bool {
+        self.main.is_some()
+    }
+
+    /// Cells of the row `index` of the screen.
+    ///
+    /// # Panics
+    /// If `index` is not lower than [`Screen::rows`].
+    pub fn row(&self, index: usize) -> &[Cell] {
+        &self.grid[index]
+    }
+
+    /// Lines that scrolled off the top of the screen, from the oldest one.
+    pub fn scrollback(&self) -> impl Iterator<Item = &[Cell]> {
+        self.scrollback.iter().map(Vec::as_slice)
+    }
+
+    /// Lines of the scrollback followed by the rows of the screen.
+    pub fn lines(&self) -> impl Iterator<Item = &[Cell]> {
+        self.scrollback().chain(self.grid.iter().map(Vec::as_slice))
+    }
+
+    /// Text of the rows of the screen, each one without trailing spaces and terminated by
+    /// `\n`. The blank rows at the bottom are omitted.
+    pub fn text(&self) -> String {
+        let mut rows: Vec<String> = self.grid.iter().map(|row| line_text(row)).collect();
+        while rows.last().is_some_and(String::is_empty) {
+            rows.pop();
+        }
+        rows.into_iter().map(|row| row + "\n").collect()
+    }
+
+    /// Change the size of the screen. Rows and columns are added or removed at the bottom
+    /// and at the right, and the cursor is kept within the screen.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        for grid in std::iter::once(&mut self.grid).chain(self.main.as_mut()) {
+            grid.resize(rows, vec![Cell::default(); cols]);
+            for row in grid.iter_mut() {
+                row.resize(cols, Cell::default());
+                if row.last().is_some_and(|cell| char_width(cell.c) == 2) {
+                    *row.last_mut().unwrap() = Cell::default();
+                }
+            }
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.top = 0;
+        self.bottom = rows - 1;
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+        self.pending_wrap = false;
+    }
+
+    /// Interpret a chunk of output. Incomplete sequences at the end of the chunk are kept
+    /// until the following call.
+    pub fn feed(&mut self, output: &str) {
+        for token in self.tokenizer.feed(output) {
+            match token {
+                Token::Text(text) => {
+                    for c in text.chars() {
+                        self.put(c);
+                    }
+                }
+                Token::Control(c) => self.control(c),
+                Token::Csi { params, intermediates, action } if intermediates.is_empty() => self.csi(&params, action),
+                Token::Osc(payload) => self.osc(&payload),
+                Token::Esc { intermediates, action } if intermediates.is_empty() => self.esc(action),
+                _ => (),
+            }
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        let width = char_width(c);
+        if width == 0 || (width == 2 && self.cols < 2) {
+            return;
+        }
+        if self.pending_wrap {
+            self.col = 0;
+            self.linefeed();
+        }
+        if self.col + width > self.cols {
+            self.erase(self.row, self.col, self.cols);
+            self.col = 0;
+            self.linefeed();
+        }
+        self.split_wide(self.row, self.col);
+        self.split_wide(self.row, self.col + width - 1);
+
+        let cell = Cell { c, style: self.style, link: self.link.clone() };
+        if width == 2 {
+            self.grid[self.row][self.col + 1] = Cell { c: '\0', ..cell.clone() };
+        }
+        self.grid[self.row][self.col] = cell;
+        self.col += width;
+        self.pending_wrap = self.col == self.cols;
+        if self.pending_wrap {
+            self.col -= 1;
+        }
+    }
+
+    /// Blank the other half of the wide character that covers the cell at `col`, if any.
+    fn split_wide(&mut self, row: usize, col: usize) {
+        let Some(cell) = self.grid[row].get(col) else {
+            return;
+        };
+        if cell.is_continuation() && col > 0 {
+            self.grid[row][col - 1] = Cell::default();
+        } else if char_width(cell.c) == 2 && col + 1 < self.cols {
+            self.grid[row][col + 1] = Cell::default();
+        }
+    }
+
+    /// Blank the cells of `row` from `start` to `end` (excluded).
+    fn erase(&mut self, row: usize, start: usize, end: usize) {
+        let end = end.min(self.cols);
+        if start >= end {
+            return;
+        }
+        self.split_wide(row, start);
+        self.split_wide(row, end - 1);
+        let blank = Cell::blank(self.style);
+        self.grid[row][start..end].fill(blank);
+    }
+
+    fn blank_row(&self) -> Vec<Cell> {
+        vec![Cell::blank(self.style); self.cols]
+    }
+
+    /// Scroll the rows of the scrolling region up by `count`. If `save` is set, the rows that
+    /// leave the top of the main screen are saved into the scrollback.
+    fn scroll_up(&mut self, count: usize, save: bool) {
+        for _ in 0..count.min(self.bottom - self.top + 1) {
+            let row = self.grid.remove(self.top);
+            if save && self.top == 0 && self.main.is_none() && self.max_scrollback > 0 {
+                if self.scrollback.len() == self.max_scrollback {
+                    self.scrollback.pop_front();
+                }
+                self.scrollback.push_back(row);
+            }
+            self.grid.insert(self.bottom, self.blank_row());
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        for _ in 0..count.min(self.bottom - self.top + 1) {
+            self.grid.remove(self.bottom);
+            self.grid.insert(self.top, self.blank_row());
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.pending_wrap = false;
+        if self.row == self.bottom {
+            self.scroll_up(1, true);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        if self.row == self.top {
+            self.scroll_down(1);
+        } else {
+            self.row = self.row.saturating_sub(1);
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.pending_wrap = false;
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\r' => self.move_to(self.row, 0),
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+            '\x08' => self.move_to(self.row, self.col.saturating_sub(1)),
+            '\t' => self.move_to(self.row, (self.col / TAB_WIDTH + 1) * TAB_WIDTH),
+            _ => (),
+        }
+    }
+
+    fn esc(&mut self, action: char) {
+        match action {
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.linefeed(),
+            'E' => {
+                self.linefeed();
+                self.col = 0;
+            }
+            'M' => self.reverse_index(),
+            'c' => {
+                let max_scrollback = self.max_scrollback;
+                *self = Screen::new(self.cols, self.rows).with_scrollback(max_scrollback);
+            }
+            _ => (),
+        }
+    }
+
+    fn osc(&mut self, payload: &str) {
+        // OSC 8 ; params ; URI, where an empty URI ends the hyperlink.
+        if let Some(link) = payload.strip_prefix("8;") {
+            let uri = link.split_once(';').map_or("", |(_, uri)| uri);
+            self.link = (!uri.is_empty()).then(|| Arc::from(uri));
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = Some(SavedCursor { row: self.row, col: self.col, style: self.style });
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved.clone().unwrap_or(SavedCursor { row: 0, col: 0, style: Style::default() });
+        self.style = saved.style;
+        self.move_to(saved.row, saved.col);
+    }
+
+    fn csi(&mut self, raw: &str, action: char) {
+        if action == 'm' {
+            return self.sgr(raw);
+        }
+        let private = raw.starts_with('?');
+        let params = csi_params(raw);
+        let param = |index: usize| params.get(index).copied().unwrap_or(0) as usize;
+        let count = param(0).max(1);
+        let (row, col) = (self.row, self.col);
+        match (private, action) {
+            (false, 'A') => {
+                let top = if row >= self.top { self.top } else { 0 };
+                self.move_to(row.saturating_sub(count).max(top), col)
+            }
+            (false, 'B' | 'e') => {
+                let bottom = if row <= self.bottom { self.bottom } else { self.rows - 1 };
+                self.move_to(row.saturating_add(count).min(bottom), col)
+            }
+            (false, 'C' | 'a') => self.move_to(row, col.saturating_add(count)),
+            (false, 'D') => self.move_to(row, col.saturating_sub(count)),
+            (false, 'E') => self.move_to(row.saturating_add(count), 0),
+            (false, 'F') => self.move_to(row.saturating_sub(count), 0),
+            (false, 'G' | '`') => self.move_to(row, count - 1),
+            (false, 'd') => self.move_to(count - 1, col),
+            (false, 'H' | 'f') => self.move_to(param(0).max(1) - 1, param(1).max(1) - 1),
+            (_, 'J') => {
+                let rows = match param(0) {
+                    0 => {
+                        self.erase(row, col, self.cols);
+                        row + 1..self.rows
+                    }
+                    1 => {
+                        self.erase(row, 0, col + 1);
+                        0..row
+                    }
+                    mode => {
+                        if mode == 3 {
+                            self.scrollback.clear();
+                        }
+                        0..self.rows
+                    }
+                };
+                for row in rows {
+                    self.erase(row, 0, self.cols);
+                }
+            }
+            (_, 'K') => match param(0) {
+                0 => self.erase(row, col, self.cols),
+                1 => self.erase(row, 0, col + 1),
+                _ => self.erase(row, 0, self.cols),
+            },
+            (false, 'X') => self.erase(row, col, col.saturating_add(count)),
+            (false, 'P') => {
+                self.split_wide(row, col);
+                let end = col.saturating_add(count).min(self.cols);
+                self.split_wide(row, end - 1);
+                self.grid[row].drain(col..end);
+                let blank = Cell::blank(self.style);
+                self.grid[row].resize(self.cols, blank);
+            }
+            (false, '@') => {
+                self.split_wide(row, col);
+                let count = count.min(self.cols - col);
+                let blank = Cell::blank(self.style);
+                self.grid[row].splice(col..col, std::iter::repeat_n(blank, count));
+                self.grid[row].truncate(self.cols);
+                self.split_wide(row, self.cols - 1);
+            }
+            (false, 'L' | 'M') if (self.top..=self.bottom).contains(&row) => {
+                let top = std::mem::replace(&mut self.top, row);
+                match action {
+                    'L' => self.scroll_down(count),
+                    _ => self.scroll_up(count, false),
+                }
+                self.top = top;
+                self.move_to(row, 0);
+            }
+            (false, 'S') => self.scroll_up(count, false),
+            (false, 'T') => self.scroll_down(count),
+            (false, 'r') => {
+                let top = param(0).max(1) - 1;
+                let bottom = match param(1) {
+                    0 => self.rows,
+                    bottom => bottom.min(self.rows),
+                } - 1;
+                if top < bottom {
+                    (self.top, self.bottom) = (top, bottom);
+                    self.move_to(0, 0);
+                }
+            }
+            (false, 's') => self.save_cursor(),
+            (false, 'u') => self.restore_cursor(),
+            (true, 'h' | 'l') => {
+                let set = action == 'h';
+                for mode in params {
+                    match mode {
+                        25 => self.cursor_visible = set,
+                        47 | 1047 | 1049 => self.set_alternate(set, mode == 1049),
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn set_alternate(&mut self, alternate: bool, save_cursor: bool) {
+        if alternate == self.is_alternate() {
+            return;
+        }
+        // The cursor saved by mode 1049 is kept apart from the one saved by `ESC 7`.
+        if alternate {
+            if save_cursor {
+                self.main_cursor = Some(SavedCursor { row: self.row, col: self.col, style: self.style });
+            }
+            let blank = vec![vec![Cell::default(); self.cols]; self.rows];
+            self.main = Some(std::mem::replace(&mut self.grid, blank));
+        } else {
+            self.grid = self.main.take().unwrap_or_default();
+            if let Some(saved) = self.main_cursor.take().filter(|_| save_cursor) {
+                self.style = saved.style;
+                self.move_to(saved.row, saved.col);
+            }
+        }
+        self.pending_wrap = false;
+    }
+
+    fn sgr(&mut self, params: &str) {
+        let groups: Vec<&str> = params.split(';').collect();
+        let mut i = 0;
+        while i < groups.len() {
+            let group = groups[i];
+            i += 1;
+            // Sub-parameters, e.g., `38:2::255:0:0` or `4:3`.
+            if group.contains(':') {
+                let sub: Vec<u32> = group.split(':').map(|n| n.parse().unwrap_or(0)).collect();
+                match sub[0] {
+                    4 => self.style.underline = sub[1] != 0,
+                    38 | 48 => {
+                        let color = match sub.get(1) {
+                            Some(5) => sub.get(2).map(|&n| Color::Indexed(n as u8)),
+                            // The color space identifier is optional.
+                            Some(2) if sub.len() >= 5 => {
+                                let rgb = &sub[sub.len() - 3..];
+                                Some(Color::Rgb(rgb[0] as u8, rgb[1] as u8, rgb[2] as u8))
+                            }
+                            _ => None,
+                        };
+                        if let Some(color) = color {
+                            self.set_color(sub[0], color);
+                        }
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+
+            let code: u32 = group.parse().unwrap_or(0);
+            match code {
+                0 => self.style = Style::default(),
+                1 => self.style.bold = true,
+                3 => self.style.italic = true,
+                4 => self.style.underline = true,
+                7 => self.style.inverse = true,
+                22 => self.style.bold = false,
+                23 => self.style.italic = false,
+                24 => self.style.underline = false,
+                27 => self.style.inverse = false,
+                30..=37 => self.style.fg = Color::Indexed((code - 30) as u8),
+                39 => self.style.fg = Color::Default,
+                40..=47 => self.style.bg = Color::Indexed((code - 40) as u8),
+                49 => self.style.bg = Color::Default,
+                90..=97 => self.style.fg = Color::Indexed((code - 90 + 8) as u8),
+                100..=107 => self.style.bg = Color::Indexed((code - 100 + 8) as u8),
+                38 | 48 => {
+                    let number = |index: usize| groups.get(index).and_then(|n| n.parse::<u32>().ok()).unwrap_or(0) as u8;
+                    match groups.get(i).copied() {
+                        Some("5") => {
+                            self.set_color(code, Color::Indexed(number(i + 1)));
+                            i += 2;
+                        }
+                        Some("2") => {
+                            self.set_color(code, Color::Rgb(number(i + 1), number(i + 2), number(i + 3)));
+                            i += 4;
+                        }
+                        _ => i = groups.len(),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn set_color(&mut self, code: u32, color: Color) {
+        match code {
+            38 => self.style.fg = color,
+            _ => self.style.bg = color,
+        }
+    }
+}
+
+/// Text of a line, without trailing spaces.
+pub(crate) fn line_text(line: &[Cell]) -> String {
+    let text: String = line.iter().filter(|cell| !cell.is_continuation()).map(|cell| cell.c).collect();
+    text.trim_end_matches(' ').to_owned()
+}
@@ -0,0 +1,99 @@
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use winptyrs::{BatchCommand, BatchOptions, BatchProgress, BatchRunner, BatchStatus, PTYBackend};
+
+mod common;
+use common::{command_cassette, replay_args};
+
+/// Options that replay a command that prints `done` and exits with `code` after `millis`.
+fn replay_options(name: &str, millis: u64, code: u32) -> BatchOptions {
+    let args = replay_args(&command_cassette(name, "done\r\n", millis, code));
+    BatchOptions { args, backend: PTYBackend::Replay, ..BatchOptions::default() }
+}
+
+#[test]
+fn run_batches() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let options = BatchOptions { concurrency: 2, ..replay_options("batch", 300, 0) };
+    let runner = BatchRunner::new(options).on_progress(move |progress| {
+        let event = match progress {
+            BatchProgress::Started(index, _) => format!("started {}", index),
+            BatchProgress::Output(index, output) => format!("output {} {:?}", index, output),
+            BatchProgress::Finished(result) => format!("finished {} {}", result.index, result.status),
+        };
+        recorded.lock().unwrap().push(event);
+    });
+    let commands: Vec<BatchCommand> = (0..4).map(|i| BatchCommand::new("tool.exe", Some(&i.to_string()))).collect();
+    let report = runner.run(commands.clone());
+
+    assert!(report.success());
+    assert!(report.duration >= Duration::from_millis(600), "{:?}", report.duration);
+    assert!(report.duration < Duration::from_millis(1200), "{:?}", report.duration);
+    for (i, result) in report.results.iter().enumerate() {
+        assert_eq!((result.index, &result.command, &result.status), (i, &commands[i], &BatchStatus::Exited(0)));
+        assert_eq!(result.transcript, "done\r\n");
+    }
+    let summary = report.to_string();
+    assert!(summary.starts_with("[ok] tool.exe 0 (0.3s)\n"), "{}", summary);
+    assert!(summary.contains("\n4 commands, 4 succeeded, 0 failed in "), "{}", summary);
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 12, "{:?}", events);
+    for i in 0..4 {
+        let position = |event: String| events.iter().position(|e| *e == event).unwrap();
+        assert!(position(format!("started {}", i)) < position(format!("output {} \"done\\r\\n\"", i)));
+        assert!(position(format!("output {} \"done\\r\\n\"", i)) < position(format!("finished {} ok", i)));
+    }
+}
+
+#[test]
+fn time_out_commands() {
+    let options = BatchOptions { timeout: Some(Duration::from_millis(200)), ..replay_options("timeout", 5000, 0) };
+    let report = BatchRunner::new(options).run(vec![BatchCommand::new("tool.exe", None)]);
+    assert_eq!(report.results[0].status, BatchStatus::TimedOut);
+    assert_eq!(report.results[0].transcript, "done\r\n");
+    assert!(report.duration < Duration::from_secs(2));
+    assert!(!report.success());
+
+    // The timeout of a command takes precedence over the one of the batch.
+    let options = BatchOptions { timeout: Some(Duration::from_millis(100)), ..replay_options("override", 300, 0) };
+    let command = BatchCommand { timeout: Some(Duration::from_secs(10)), ..BatchCommand::new("tool.exe", None) };
+    let report = BatchRunner::new(options).run(vec![command]);
+    assert_eq!(report.results[0].status, BatchStatus::Exited(0));
+}
+
+#[test]
+fn fail_fast() {
+    let options = BatchOptions { concurrency: 1, fail_fast: true, ..replay_options("failing", 100, 2) };
+    let commands = vec![BatchCommand::new("tool.exe", None); 3];
+    let report = BatchRunner::new(options.clone()).run(commands.clone());
+    let statuses: Vec<&BatchStatus> = report.results.iter().map(|result| &result.status).collect();
+    assert_eq!(statuses, [&BatchStatus::Exited(2), &BatchStatus::Skipped, &BatchStatus::Skipped]);
+    assert_eq!(report.failures().count(), 3);
+    assert!(report.to_string().starts_with("[exit 2] tool.exe (0.1s)\n[skipped] tool.exe (0.0s)\n"));
+
+    let report = BatchRunner::new(BatchOptions { fail_fast: false, ..options }).run(commands);
+    assert!(report.results.iter().all(|result| result.status == BatchStatus::Exited(2)));
+
+    // A command that times out cancels the ones that are still running.
+    let options = BatchOptions { concurrency: 2, fail_fast: true, ..replay_options("cancelled", 1000, 0) };
+    let slow = BatchCommand { timeout: Some(Duration::from_millis(100)), ..BatchCommand::new("tool.exe", None) };
+    let report = BatchRunner::new(options).run(vec![slow, BatchCommand::new("tool.exe", None)]);
+    assert_eq!(report.results[0].status, BatchStatus::TimedOut);
+    assert_eq!(report.results[1].status, BatchStatus::Cancelled);
+    assert!(report.duration < Duration::from_millis(900));
+}
+
+#[test]
+fn report_spawn_failures() {
+    let options = BatchOptions { backend: PTYBackend::Replay, ..BatchOptions::default() };
+    let report = BatchRunner::new(options).run(vec![BatchCommand::new("tool.exe", None)]);
+    let error = OsString::from("The Replay backend requires PTYArgs::cassette");
+    assert_eq!(report.results[0].status, BatchStatus::Failed(error));
+    assert!(report.to_string().starts_with("[failed: The Replay backend requires PTYArgs::cassette] tool.exe"));
+
+    assert!(BatchRunner::new(BatchOptions::default()).run(Vec::new()).results.is_empty());
+}
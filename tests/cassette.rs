@@ -0,0 +1,161 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use winptyrs::{Cassette, CassetteEvent, PTYArgs, PTYBackend, PTY};
+
+mod common;
+use common::{entry, replay_args, save_cassette};
+
+fn session() -> Cassette {
+    Cassette {
+        cols: 80,
+        rows: 24,
+        entries: vec![
+            entry(0, CassetteEvent::Spawn { appname: String::from("cmd.exe"), cmdline: None }),
+            entry(10, CassetteEvent::Output(String::from("C:\\> "))),
+            entry(1000, CassetteEvent::Input(String::from("echo \"hi\"\r\n"))),
+            entry(1020, CassetteEvent::Output(String::from("hi\r\n"))),
+            entry(1500, CassetteEvent::Resize { cols: 120, rows: 40 }),
+            entry(2000, CassetteEvent::Input(String::from("exit\r\n"))),
+            entry(2300, CassetteEvent::Exit(3)),
+        ],
+    }
+}
+
+fn replay(path: &Path, strict: bool) -> PTY {
+    let args = replay_args(path).strict_replay(strict);
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Replay).unwrap();
+    pty.spawn(OsString::from("cmd.exe"), None, None, None).unwrap();
+    pty
+}
+
+/// Read until `expected` was received.
+fn read_until(pty: &PTY, expected: &str) -> String {
+    let mut output = String::new();
+    let start = Instant::now();
+    while !output.contains(expected) {
+        assert!(start.elapsed() < Duration::from_secs(5), "{:?} was not received in {:?}", expected, output);
+        output.push_str(&pty.read_timeout(Duration::from_millis(100)).unwrap().to_string_lossy());
+    }
+    output
+}
+
+#[test]
+fn serialize_cassettes() {
+    let cassette = session();
+    let text = cassette.to_string();
+    assert!(text.starts_with("{\"cassette\":1,\"cols\":80,\"rows\":24}\n"));
+    assert!(text.contains("{\"time\":1000,\"type\":\"input\",\"data\":\"echo \\\"hi\\\"\\r\\n\"}\n"));
+    assert_eq!(Cassette::parse(&text).unwrap(), cassette);
+
+    for (text, error) in [
+        ("", "The cassette is empty"),
+        ("{\"cassette\":2,\"cols\":80,\"rows\":24}", "Unsupported cassette version 2"),
+        ("{\"cols\":80,\"rows\":24}", "Invalid cassette at line 1: missing header"),
+        ("{\"cassette\":1,\"cols\":80,\"rows\":24}\n{\"time\":5,\"type\":\"bell\"}", "Invalid cassette at line 2: unknown event bell"),
+        ("{\"cassette\":1,\"cols\":80,\"rows\":24}\n\n{\"type\":\"exit\",\"code\":0}", "Invalid cassette at line 3: missing time"),
+    ] {
+        assert_eq!(Cassette::parse(text).unwrap_err(), error);
+    }
+}
+
+#[test]
+fn replay_sessions() {
+    let path = save_cassette("replay", session().entries);
+    let pty = replay(&path, false);
+    assert_eq!(read_until(&pty, "C:\\> "), "C:\\> ");
+
+    // The output that follows an input is not released until the input is written, and
+    // unexpected input is ignored.
+    assert_eq!(pty.read_timeout(Duration::from_millis(200)).unwrap(), "");
+    pty.write(OsString::from("\x1b[0;0R")).unwrap();
+    pty.write(OsString::from("echo \"h")).unwrap();
+    assert_eq!(pty.read_timeout(Duration::from_millis(200)).unwrap(), "");
+    pty.write(OsString::from("i\"\r\n")).unwrap();
+    assert_eq!(read_until(&pty, "hi\r\n"), "hi\r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), None);
+
+    // Resizes are not waited for, and the exit is released with its recorded delay.
+    let start = Instant::now();
+    pty.write(OsString::from("exit\r\n")).unwrap();
+    assert!(pty.wait_for_exit().unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(250));
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3));
+    assert!(pty.is_eof().unwrap());
+    assert!(pty.read_timeout(Duration::from_millis(10)).is_err());
+    assert!(pty.write(OsString::from("dir\r\n")).is_err());
+}
+
+#[test]
+fn replay_strictly() {
+    let path = save_cassette("strict", session().entries);
+    let pty = replay(&path, true);
+    read_until(&pty, "C:\\> ");
+    pty.write(OsString::from("echo \"hi\"\r\n")).unwrap();
+    read_until(&pty, "hi\r\n");
+
+    // The exit is not released before the recorded resize.
+    pty.write(OsString::from("exit\r\n")).unwrap();
+    assert_eq!(pty.read_timeout(Duration::from_millis(300)).unwrap(), "");
+    assert_eq!(pty.get_exitstatus().unwrap(), None);
+    let err = pty.set_size(100, 30).unwrap_err();
+    assert_eq!(err, "The resize diverged from the cassette: expected 120x40, got 100x30");
+    assert_eq!(pty.read_timeout(Duration::from_millis(10)).unwrap_err(), err);
+
+    let pty = replay(&path, true);
+    read_until(&pty, "C:\\> ");
+    let err = pty.write(OsString::from("echo \"ho\"\r\n")).unwrap_err();
+    assert_eq!(err, "The input diverged from the cassette: expected \"echo \\\"hi\\\"\\r\\n\", got \"echo \\\"ho\\\"\\r\\n\"");
+
+    let args = PTYArgs::default().cassette(path.into_os_string()).strict_replay(true);
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Replay).unwrap();
+    assert!(pty.spawn(OsString::from("powershell.exe"), None, None, None).is_err());
+}
+
+#[test]
+fn reject_missing_cassettes() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("missing").join("replay.cassette");
+    let args = PTYArgs::default().cassette(path.into_os_string());
+    let err = PTY::new_with_backend(&args, PTYBackend::Replay).err().unwrap();
+    assert!(err.to_string_lossy().starts_with("Cannot read the cassette"));
+    let err = PTY::new_with_backend(&PTYArgs::default(), PTYBackend::Replay).err().unwrap();
+    assert_eq!(err, "The Replay backend requires PTYArgs::cassette");
+}
+
+#[test]
+fn wait_until_idle() {
+    let path = save_cassette(
+        "idle",
+        vec![
+            entry(0, CassetteEvent::Spawn { appname: String::from("cmd.exe"), cmdline: None }),
+            entry(0, CassetteEvent::Output(String::from("a"))),
+            entry(50, CassetteEvent::Output(String::from("b"))),
+            entry(400, CassetteEvent::Output(String::from("c"))),
+            entry(500, CassetteEvent::Input(String::from("x"))),
+            entry(510, CassetteEvent::Output(String::from("d"))),
+            entry(1000, CassetteEvent::Exit(0)),
+        ],
+    );
+    let pty = replay(&path, false);
+
+    // The output settles once nothing is released for the quiet period.
+    let start = Instant::now();
+    assert_eq!(pty.wait_idle(Duration::from_millis(200), Duration::from_secs(5)).unwrap(), "ab");
+    assert!(start.elapsed() >= Duration::from_millis(250) && start.elapsed() < Duration::from_millis(400));
+    assert_eq!(pty.wait_idle(Duration::from_millis(500), Duration::from_millis(10)).unwrap(), "");
+
+    // The quiet period is measured from the last output, not from the call.
+    let start = Instant::now();
+    assert_eq!(pty.wait_idle(Duration::from_millis(100), Duration::from_secs(5)).unwrap(), "");
+    assert!(start.elapsed() < Duration::from_millis(50));
+    assert_eq!(pty.read_timeout(Duration::from_secs(1)).unwrap(), "c");
+
+    // The wait ends as soon as the process exits.
+    pty.write(OsString::from("x")).unwrap();
+    let start = Instant::now();
+    assert_eq!(pty.wait_idle(Duration::from_secs(5), Duration::from_secs(10)).unwrap(), "d");
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(0));
+    assert!(pty.wait_idle(Duration::from_millis(100), Duration::from_secs(1)).is_err());
+}
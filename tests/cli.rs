@@ -0,0 +1,52 @@
+use std::process::{Command, Output};
+
+fn winpty_rs(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_winpty-rs")).args(args).output().unwrap()
+}
+
+#[test]
+fn reject_invalid_commands() {
+    let output = winpty_rs(&[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage: winpty-rs"));
+
+    let output = winpty_rs(&["spawn"]);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Unknown command spawn"));
+}
+
+#[test]
+fn reject_invalid_run_options() {
+    for (args, error) in [
+        (&["run"][..], "Missing command"),
+        (&["run", "--"], "Missing command"),
+        (&["run", "--backend", "vt100", "--", "cmd"], "Invalid value of --backend"),
+        (&["run", "--cols", "wide", "--", "cmd"], "Invalid value of --cols: wide"),
+        (&["run", "--env", "=1", "--", "cmd"], "Invalid value of --env: =1"),
+        (&["run", "--rows", "0", "--", "cmd"], "Invalid size 80x0"),
+        (&["run", "--shell", "--", "cmd"], "Unknown option --shell"),
+        (&["run", "cmd"], "Unexpected argument cmd"),
+    ] {
+        let output = winpty_rs(args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with(error), "{:?} printed {}", args, stderr);
+    }
+
+    let output = winpty_rs(&["run", "--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage: winpty-rs run"));
+}
+
+#[test]
+fn reject_invalid_scripts() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("invalid.pty");
+    std::fs::write(&path, "spawn cmd.exe\n\nexpect /(unclosed/\n").unwrap();
+
+    let output = winpty_rs(&["script", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with("invalid.pty: line 3: Invalid pattern /(unclosed/: unclosed group\n"), "{}", stderr);
+
+    let output = winpty_rs(&["script"]);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Missing script"));
+}
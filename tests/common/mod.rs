@@ -0,0 +1,37 @@
+//! Cassette fixtures shared by the tests that use the Replay backend.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use winptyrs::{Cassette, CassetteEntry, CassetteEvent, PTYArgs};
+
+/// Entry of a cassette that happens `millis` after the start of the session.
+pub fn entry(millis: u64, event: CassetteEvent) -> CassetteEntry {
+    CassetteEntry { time: Duration::from_millis(millis), event }
+}
+
+/// Save an 80x24 cassette made of `entries` as `<name>.cassette` in the temporary directory
+/// of the tests, returning its path.
+pub fn save_cassette(name: &str, entries: Vec<CassetteEntry>) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.cassette", name));
+    Cassette { cols: 80, rows: 24, entries }.save(path.as_os_str()).unwrap();
+    path
+}
+
+/// Save a cassette where `tool.exe` prints `output` and exits with `code` after `millis`.
+pub fn command_cassette(name: &str, output: &str, millis: u64, code: u32) -> PathBuf {
+    save_cassette(
+        name,
+        vec![
+            entry(0, CassetteEvent::Spawn { appname: String::from("tool.exe"), cmdline: None }),
+            entry(10, CassetteEvent::Output(String::from(output))),
+            entry(millis, CassetteEvent::Exit(code)),
+        ],
+    )
+}
+
+/// Arguments that replay the cassette at `path`.
+pub fn replay_args(path: &Path) -> PTYArgs {
+    PTYArgs::default().cassette(path.as_os_str())
+}
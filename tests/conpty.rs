@@ -2,41 +2,34 @@
 
 use std::ffi::OsString;
 use std::thread::sleep;
-use std::time::Duration;
-use std::{thread, time};
+use std::time::{Duration, Instant};
+use std::thread;
 use regex::Regex;
 
-use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig};
+use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig, ShutdownPolicy, Signal, OscEvent, ShellKind, ShellSession, LaunchMode, default_shell, PtyManager, PtyEvent, PtyServer, RemotePTY};
+use winptyrs::jsonrpc::{read_message, write_message, Value};
 
 #[test]
 #[ignore]
 fn spawn_conpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
     pty.spawn(appname, None, None, None).unwrap();
     pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
-
-    let ten_millis = time::Duration::from_millis(10);
-    thread::sleep(ten_millis);
+    pty.wait_idle(Duration::from_millis(100), Duration::from_secs(5)).unwrap();
 }
 
 #[test]
 fn read_write_conpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -49,7 +42,8 @@ fn read_write_conpty() {
     let mut out: OsString;
     let mut tries = 0;
 
-    sleep(Duration::from_millis(5000));
+    out = pty.wait_idle(Duration::from_millis(500), Duration::from_secs(10)).unwrap();
+    output_str = out.to_str().unwrap();
 
     pty.write("\r\n".into()).unwrap();
 
@@ -91,13 +85,10 @@ fn read_write_conpty() {
 
 #[test]
 fn set_size_conpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -189,13 +180,10 @@ fn set_size_conpty() {
 
 #[test]
 fn is_alive_exitstatus_conpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -218,13 +206,10 @@ fn is_alive_exitstatus_conpty() {
 
 #[test]
 fn wait_for_exit() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -246,13 +231,10 @@ fn wait_for_exit() {
 
 #[test]
 fn check_eof_output() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("python.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
@@ -295,13 +277,10 @@ fn drop_timing_conpty() {
     use std::thread;
     use std::time::{ Duration, Instant };
 
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
     let (tx, rx) = channel::<Instant>();
@@ -331,3 +310,840 @@ fn drop_timing_conpty() {
         }
     }
 }
+
+#[test]
+fn signal_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    // Interrupting an interactive shell must not terminate it.
+    pty.signal(Signal::Interrupt).unwrap();
+    assert!(pty.is_alive().unwrap());
+
+    pty.signal(Signal::Kill).unwrap();
+    let _ = pty.wait_for_exit();
+
+    assert!(!pty.is_alive().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(1))
+}
+
+#[test]
+fn kill_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    assert!(pty.kill().is_err());
+
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    assert!(pty.is_alive().unwrap());
+
+    pty.kill().unwrap();
+    assert!(!pty.is_alive().unwrap());
+    assert_ne!(pty.get_exitstatus().unwrap(), None);
+}
+
+#[test]
+fn kill_tree_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+        .contain_tree(true);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    // Start a nested shell, which must be contained as well.
+    pty.write("cmd.exe\r\n".into()).unwrap();
+    let mut tries = 0;
+    while pty.get_tree_pids().unwrap().len() < 2 && tries < 100 {
+        sleep(Duration::from_millis(100));
+        tries += 1;
+    }
+
+    let pids = pty.get_tree_pids().unwrap();
+    assert!(pids.contains(&pty.get_pid()));
+    assert!(pids.len() >= 2);
+
+    pty.kill_tree().unwrap();
+    let _ = pty.wait_for_exit();
+    assert!(!pty.is_alive().unwrap());
+
+    let mut tries = 0;
+    while !pty.get_tree_pids().unwrap().is_empty() && tries < 100 {
+        sleep(Duration::from_millis(100));
+        tries += 1;
+    }
+    assert!(pty.get_tree_pids().unwrap().is_empty());
+}
+
+#[test]
+fn kill_tree_not_contained_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    assert!(pty.kill_tree().is_err());
+    assert!(pty.get_tree_pids().is_err());
+}
+
+#[test]
+fn close_terminate_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+        .shutdown_policy(ShutdownPolicy::Terminate);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.write("echo closing\r\n".into()).unwrap();
+
+    let status = pty.close().unwrap().unwrap();
+    assert!(status.terminated);
+}
+
+#[test]
+fn close_exited_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+        .shutdown_policy(ShutdownPolicy::WaitWithTimeout(Duration::from_secs(10)));
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, Some("/c echo leftover && exit 3".into()), None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let status = pty.close().unwrap().unwrap();
+    assert!(!status.terminated);
+    assert_eq!(status.code, 3);
+    assert!(status.output.to_str().unwrap().contains("leftover"));
+}
+
+#[test]
+fn close_detach_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let start = Instant::now();
+    assert_eq!(pty.close().unwrap(), None);
+    assert!(start.elapsed() < Duration::from_millis(pty_args.drain_timeout as u64 * 2));
+}
+
+#[test]
+fn resource_usage_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    assert!(pty.resource_usage().is_err());
+
+    pty.spawn(appname, Some("/c for /L %i in (1,1,20000) do @rem".into()), None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let running = pty.resource_usage().unwrap();
+    assert!(running.peak_memory > 0);
+
+    let _ = pty.wait_for_exit();
+    let exited = pty.resource_usage().unwrap();
+    assert!(exited.peak_memory >= running.peak_memory);
+    assert!(exited.user_time + exited.kernel_time >= running.user_time + running.kernel_time);
+    assert!(exited.wall_time > Duration::ZERO);
+
+    // The wall time is frozen once the process exits.
+    sleep(Duration::from_millis(100));
+    assert_eq!(pty.resource_usage().unwrap().wall_time, exited.wall_time);
+}
+
+#[test]
+fn process_tree_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    assert!(pty.process_tree().is_err());
+
+    pty.spawn(appname, None, Some("C:\\Windows".into()), None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let tree = pty.process_tree().unwrap();
+    assert_eq!(tree[0].pid, pty.get_pid());
+    assert_eq!(tree[0].parent_pid, std::process::id());
+    assert_eq!(tree[0].name.to_str().unwrap().to_lowercase(), "cmd.exe");
+
+    let foreground = pty.foreground_process().unwrap().unwrap();
+    assert_eq!(foreground.pid, pty.get_pid());
+    assert_eq!(foreground.cwd.unwrap().to_str().unwrap().trim_end_matches('\\'), "C:\\Windows");
+
+    pty.write("ping -n 10 127.0.0.1\r\n".into()).unwrap();
+    let start = Instant::now();
+    let mut foreground = pty.foreground_process().unwrap().unwrap();
+    while foreground.pid == pty.get_pid() && start.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(100));
+        foreground = pty.foreground_process().unwrap().unwrap();
+    }
+
+    assert_eq!(foreground.name.to_str().unwrap().to_lowercase(), "ping.exe");
+    assert_eq!(foreground.parent_pid, pty.get_pid());
+    assert!(foreground.cmdline.unwrap().to_str().unwrap().contains("127.0.0.1"));
+    assert!(pty.process_tree().unwrap().iter().any(|p| p.pid == foreground.pid));
+}
+
+#[test]
+fn title_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    let events = pty.osc_events();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.write("title winpty-rs title\r\n".into()).unwrap();
+
+    let start = Instant::now();
+    while pty.title().as_deref() != Some("winpty-rs title") && start.elapsed() < Duration::from_secs(5) {
+        let _ = pty.wait_idle(Duration::from_millis(50), Duration::from_secs(1));
+    }
+
+    assert_eq!(pty.title().as_deref(), Some("winpty-rs title"));
+    assert!(events.try_iter().any(|e| e == OscEvent::TitleChanged("winpty-rs title".into())));
+}
+
+#[test]
+fn shell_integration_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname.clone(), None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    pty.enable_shell_integration(ShellKind::from_program(&appname).unwrap()).unwrap();
+    pty.write("echo marked\r\n".into()).unwrap();
+
+    let start = Instant::now();
+    while pty.commands().is_empty() && start.elapsed() < Duration::from_secs(10) {
+        let _ = pty.wait_idle(Duration::from_millis(50), Duration::from_secs(1));
+    }
+
+    let commands = pty.commands();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].command, "echo marked");
+    assert!(commands[0].output.contains("marked"));
+    assert_eq!(commands[0].exit_code, None);
+}
+
+#[test]
+fn shell_session_conpty() {
+    let pty_args = PTYArgs::default();
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let mut session = ShellSession::new(pty, ShellKind::Cmd);
+    let output = session.run("echo first& echo second", Some(Duration::from_secs(10))).unwrap();
+    assert_eq!(output.text.trim_end(), "first\r\nsecond");
+    assert_eq!(output.exit_code, Some(0));
+
+    let output = session.run("cmd /c exit 3", Some(Duration::from_secs(10))).unwrap();
+    assert_eq!(output.text.trim(), "");
+    assert_eq!(output.exit_code, Some(3));
+
+    let result = session.run("ping -n 30 127.0.0.1", Some(Duration::from_secs(1)));
+    assert!(result.is_err());
+
+    let cancel = session.cancel_handle();
+    let canceller = thread::spawn(move || {
+        sleep(Duration::from_millis(500));
+        cancel.cancel();
+    });
+    assert!(session.run("ping -n 30 127.0.0.1", None).is_err());
+    canceller.join().unwrap();
+
+    let output = session.run("echo after", Some(Duration::from_secs(10))).unwrap();
+    assert_eq!(output.text.trim_end(), "after");
+}
+
+#[test]
+fn spawn_default_shell_conpty() {
+    let pty_args = PTYArgs::default();
+    let profile = default_shell().unwrap();
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn_request(profile.spawn_request(LaunchMode::Interactive, None)).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    assert!(pty.is_alive().unwrap());
+
+    let foreground = pty.foreground_process().unwrap().unwrap();
+    assert_eq!(foreground.pid, pty.get_pid());
+}
+
+#[test]
+fn spawn_resolve_conpty() {
+    let pty_args = PTYArgs::default();
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    pty.spawn("cmd".into(), Some("/c exit 7".into()), None, None).unwrap();
+    let _ = pty.wait_for_exit();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(7));
+
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+    let err = pty.spawn("winptyrs-missing".into(), None, None, None).unwrap_err();
+    let err = err.to_str().unwrap().to_lowercase();
+    assert!(err.contains("winptyrs-missing.exe"));
+    assert!(err.contains("system32\\winptyrs-missing.exe"));
+}
+
+#[test]
+fn manager_conpty() {
+    let pty_args = PTYArgs::default();
+    let manager = PtyManager::new();
+    let events = manager.events();
+
+    let first = manager.create(&pty_args, PTYBackend::ConPTY).unwrap();
+    let second = manager.create(&pty_args, PTYBackend::ConPTY).unwrap();
+    assert_ne!(first, second);
+    assert_eq!(manager.ids(), vec![first, second]);
+
+    manager.spawn(first, "cmd".into(), Some("/c title first& exit 2".into()), None, None).unwrap();
+    manager.spawn(second, "cmd".into(), None, None, None).unwrap();
+    manager.write(second, OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    manager.resize(second, 100, 30).unwrap();
+    assert!(manager.spawn(second, "cmd".into(), None, None, None).is_err());
+
+    let mut exit = None;
+    let mut output = false;
+    let start = Instant::now();
+    while exit.is_none() && start.elapsed() < Duration::from_secs(10) {
+        match events.recv_timeout(Duration::from_millis(100)) {
+            Ok(PtyEvent::Output(id, _)) => output |= id == second,
+            Ok(PtyEvent::Exit(id, code)) => {
+                assert_eq!(id, first);
+                exit = Some(code);
+            },
+            _ => ()
+        }
+    }
+
+    assert_eq!(exit, Some(Some(2)));
+    assert!(manager.close(first).is_ok());
+    assert_eq!(manager.ids(), vec![second]);
+    assert!(manager.write(first, "echo\r\n".into()).is_err());
+    assert!(output || events.recv_timeout(Duration::from_secs(5)).is_ok());
+}
+
+#[test]
+fn shared_reactor_conpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+        .shared_reactor(true);
+
+    let ptys: Vec<PTY> = (0..4).map(|i| {
+        let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+        let cmdline = format!("/c echo reactor_{}& exit {}", i, i);
+        pty.spawn("cmd".into(), Some(cmdline.into()), None, None).unwrap();
+        pty
+    }).collect();
+
+    for (i, pty) in ptys.iter().enumerate() {
+        let mut output = String::new();
+        let start = Instant::now();
+        while !output.contains(&format!("reactor_{}", i)) && start.elapsed() < Duration::from_secs(10) {
+            match pty.read_timeout(Duration::from_millis(100)) {
+                Ok(out) => output.push_str(&out.to_string_lossy()),
+                Err(_) => break
+            }
+        }
+        assert!(output.contains(&format!("reactor_{}", i)));
+        pty.wait_for_exit().unwrap();
+        assert_eq!(pty.get_exitstatus().unwrap(), Some(i as u32));
+    }
+}
+
+/// Number of threads that belong to the current process.
+fn thread_count() -> usize {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+
+    let pid = std::process::id();
+    let mut count = 0;
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0).unwrap();
+        let mut entry = THREADENTRY32 {
+            dwSize: std::mem::size_of::<THREADENTRY32>() as u32,
+            ..Default::default()
+        };
+        let mut next = Thread32First(snapshot, &mut entry).is_ok();
+        while next {
+            if entry.th32OwnerProcessID == pid {
+                count += 1;
+            }
+            next = Thread32Next(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    count
+}
+
+/// Compare the thread count and the echo latency of the dedicated reader threads
+/// against the shared reactor. Run with `--ignored --nocapture` to see the results.
+#[test]
+#[ignore]
+fn reactor_benchmark_conpty() {
+    const PTYS: usize = 50;
+    for shared_reactor in [false, true] {
+        let pty_args = PTYArgs::new(80, 25)
+            .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+            .timeout(10000)
+            .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+            .shutdown_policy(ShutdownPolicy::Terminate)
+            .shared_reactor(shared_reactor);
+
+        let before = thread_count();
+        let ptys: Vec<PTY> = (0..PTYS).map(|_| {
+            let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+            pty.spawn("cmd".into(), None, None, None).unwrap();
+            pty
+        }).collect();
+        let threads = thread_count() - before;
+
+        let mut latencies = Vec::new();
+        for (i, pty) in ptys.iter().enumerate() {
+            // Skip the banner and the first prompt.
+            let start = Instant::now();
+            let mut output = String::new();
+            while !output.contains('>') && start.elapsed() < Duration::from_secs(10) {
+                output.push_str(&pty.read_timeout(Duration::from_millis(100)).unwrap().to_string_lossy());
+            }
+
+            // The marker is split in the command line, so that its echo does not match.
+            let marker = format!("latency_{}", i);
+            pty.write(format!("echo late^ncy_{}\r\n", i).into()).unwrap();
+            let start = Instant::now();
+            let mut output = String::new();
+            while !output.contains(&marker) && start.elapsed() < Duration::from_secs(10) {
+                output.push_str(&pty.read_timeout(Duration::from_millis(1)).unwrap().to_string_lossy());
+            }
+            assert!(output.contains(&marker));
+            latencies.push(start.elapsed());
+        }
+
+        latencies.sort();
+        let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        println!(
+            "shared_reactor={}: {} threads for {} PTYs, echo latency mean {:?}, p50 {:?}, max {:?}",
+            shared_reactor, threads, PTYS, mean, latencies[PTYS / 2], latencies[PTYS - 1]
+        );
+    }
+}
+
+#[test]
+fn remote_conpty() {
+    let name = OsString::from(format!("winptyrs_remote_conpty_{}", std::process::id()));
+    let server = PtyServer::bind(&name).unwrap();
+
+    let mut pty = RemotePTY::connect(&name, &PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    pty.spawn("cmd".into(), None, None, None).unwrap();
+    assert_ne!(pty.get_pid(), 0);
+    assert!(pty.is_alive().unwrap());
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.set_size(100, 30).unwrap();
+
+    // A second client attached to the same session receives its output too.
+    let other = RemotePTY::attach(&name, pty.name(), None).unwrap();
+    assert_eq!(other.get_pid(), pty.get_pid());
+    other.write(OsString::from("echo re^mote\r\n")).unwrap();
+    for client in [&pty, &other] {
+        let mut output = String::new();
+        let start = Instant::now();
+        while !output.contains("remote") && start.elapsed() < Duration::from_secs(10) {
+            output.push_str(&client.read_timeout(Duration::from_millis(100)).unwrap().to_string_lossy());
+        }
+        assert!(output.contains("remote"));
+    }
+    drop(other);
+
+    pty.write(OsString::from("exit 3\r\n")).unwrap();
+    assert!(pty.wait_for_exit().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(3));
+    assert!(!pty.is_alive().unwrap());
+
+    let session = pty.name().to_owned();
+    assert!(pty.close().is_ok());
+    assert!(server.manager().ids().is_empty());
+    assert!(RemotePTY::attach(&name, &session, None).is_err());
+}
+
+#[test]
+fn detach_conpty() {
+    let name = OsString::from(format!("winptyrs_detach_conpty_{}", std::process::id()));
+    PtyServer::launch(&name, &OsString::from(env!("CARGO_BIN_EXE_winpty-rs"))).unwrap();
+
+    let mut pty = RemotePTY::create(&name, "shell", &PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    pty.spawn("cmd".into(), None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.write(OsString::from("echo be^fore\r\n")).unwrap();
+    let mut output = String::new();
+    let start = Instant::now();
+    while !output.contains("before") && start.elapsed() < Duration::from_secs(10) {
+        output.push_str(&pty.read_timeout(Duration::from_millis(100)).unwrap().to_string_lossy());
+    }
+    let pid = pty.get_pid();
+    pty.detach();
+
+    // The shell keeps running in the host process, which retained its output.
+    let sessions = RemotePTY::list(&name).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!((sessions[0].name.as_str(), sessions[0].pid, sessions[0].clients), ("shell", pid, 0));
+
+    let pty = RemotePTY::attach(&name, "shell", Some((100, 30))).unwrap();
+    assert_eq!(pty.get_pid(), pid);
+    assert!(pty.is_alive().unwrap());
+    assert!(pty.read(false).unwrap().to_string_lossy().contains("before"));
+    assert_eq!(pty.size(), (100, 30));
+
+    // The session takes the smallest size preferred by its clients.
+    let other = RemotePTY::attach(&name, "shell", Some((90, 40))).unwrap();
+    assert_eq!(other.size(), (90, 30));
+    drop(other);
+    let start = Instant::now();
+    while pty.size() != (100, 30) && start.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(pty.size(), (100, 30));
+
+    assert!(pty.close().is_ok());
+    assert!(RemotePTY::attach(&name, "shell", None).is_err());
+}
+
+#[test]
+fn jsonrpc_conpty() {
+    use std::io::{BufReader, Write};
+    use std::process::{Command, Stdio};
+
+    let mut host = Command::new(env!("CARGO_BIN_EXE_winpty-rs"))
+        .args(["host", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = host.stdin.take().unwrap();
+    let mut stdout = BufReader::new(host.stdout.take().unwrap());
+    let (messages_tx, messages_rx) = crossbeam_channel::unbounded();
+    let reader = thread::spawn(move || {
+        while let Ok(Some(content)) = read_message(&mut stdout) {
+            messages_tx.send(serde_json::from_str::<Value>(&content).unwrap()).unwrap();
+        }
+    });
+    let mut send = move |content: String| {
+        let mut message = Vec::new();
+        write_message(&mut message, &content).unwrap();
+        stdin.write_all(&message).unwrap();
+    };
+    let next = || messages_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+
+    send(String::from(r#"{"jsonrpc":"2.0","id":1,"method":"spawn","params":{"command":"cmd","cols":100,"rows":30,"backend":"conpty","env":{"WINPTY_RS":"json"}}}"#));
+    let response = next();
+    assert_eq!(response.get("id").and_then(Value::as_i64), Some(1));
+    let result = response.get("result").unwrap();
+    let id = result.get("id").and_then(Value::as_u64).unwrap();
+    assert!(result.get("pid").and_then(Value::as_u64).unwrap() > 0);
+
+    send(format!(r#"{{"jsonrpc":"2.0","id":2,"method":"write","params":{{"id":{},"data":"\u001b[?1;0c\u001b[0;0R"}}}}"#, id));
+    send(format!(r#"{{"jsonrpc":"2.0","id":3,"method":"write","params":{{"id":{},"data":"ZWNobyAlV0lOUFRZX1JTJQ0K","encoding":"base64"}}}}"#, id));
+    send(format!(r#"{{"jsonrpc":"2.0","id":4,"method":"resize","params":{{"id":{},"cols":120,"rows":40}}}}"#, id));
+
+    // Responses and notifications are interleaved, but every request gets a response.
+    let mut output = String::new();
+    let mut responses = 0;
+    while responses < 3 || !output.contains("json") {
+        let message = next();
+        match message.get("method").and_then(Value::as_str) {
+            Some("output") => output.push_str(message.get("params").unwrap().get("data").unwrap().as_str().unwrap()),
+            Some(_) => (),
+            None => {
+                assert!(message.get("error").is_none(), "Unexpected error {}", message);
+                responses += 1;
+            }
+        }
+    }
+
+    send(format!(r#"{{"jsonrpc":"2.0","id":5,"method":"kill","params":{{"id":{}}}}}"#, id));
+    let mut exited = false;
+    while !exited {
+        let message = next();
+        exited = message.get("method").and_then(Value::as_str) == Some("exit");
+    }
+
+    drop(send);
+    assert!(host.wait().unwrap().success());
+    reader.join().unwrap();
+}
+
+#[test]
+#[ignore = "requires a C compiler, the CI runs it from a developer prompt"]
+fn ffi_conpty() {
+    use std::path::Path;
+    use std::process::Command;
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = Path::new(env!("CARGO_BIN_EXE_winpty-rs")).parent().unwrap();
+    let source = root.join("tests").join("ffi").join("test_ffi.c");
+    let include = root.join("include");
+    let program = target.join("test_ffi.exe");
+
+    // Build the C test program with the first compiler that is available, linked against the cdylib.
+    let mut msvc = Command::new("cl");
+    msvc.arg("/nologo").arg("/I").arg(&include).arg(&source).arg(target.join("winptyrs.dll.lib"))
+        .arg(format!("/Fe{}", program.display())).arg(format!("/Fo{}\\", target.display()));
+    let mut gcc = Command::new("gcc");
+    gcc.arg("-I").arg(&include).arg(&source).arg(target.join("winptyrs.dll")).arg("-o").arg(&program);
+    let mut clang = Command::new("clang");
+    clang.arg("-I").arg(&include).arg(&source).arg(target.join("winptyrs.dll.lib")).arg("-o").arg(&program);
+
+    let built = [msvc, gcc, clang].iter_mut().find_map(|compiler| compiler.output().ok());
+    let built = built.expect("No C compiler available (cl, gcc or clang)");
+    assert!(built.status.success(), "{}{}", String::from_utf8_lossy(&built.stdout), String::from_utf8_lossy(&built.stderr));
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("exit code: 0"));
+}
+
+#[test]
+fn run_conpty() {
+    use std::process::{Command, Stdio};
+
+    let output = Command::new(env!("CARGO_BIN_EXE_winpty-rs"))
+        .args(["run", "--backend", "conpty", "--cols", "100", "--env", "WINPTY_RS=run", "--", "cmd", "/c", "echo %WINPTY_RS%-ok & exit 3"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("run-ok"));
+}
+
+#[test]
+fn script_conpty() {
+    use winptyrs::Script;
+
+    let script = Script::parse(
+        r#"
+        spawn cmd.exe /q
+        sendline "set VALUE=42"
+        sendline "echo value=%VALUE%"
+        expect /value=(?<value>\d+)/
+        resize 100 30
+        sendline "exit ${value}"
+        assert-exit 42
+        "#,
+    )
+    .unwrap();
+    let variables = script.run(&PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    assert_eq!(variables["value"], "42");
+
+    let script = Script::parse("spawn cmd.exe /q\nsendline \"echo waiting\"\nexpect /never/ timeout=1s").unwrap();
+    let err = script.run(&PTYArgs::default(), PTYBackend::ConPTY).unwrap_err();
+    assert_eq!((err.line, err.expectation.as_deref()), (3, Some("/never/")));
+    assert!(err.output.contains("waiting"), "{}", err);
+}
+
+#[test]
+fn cassette_conpty() {
+    use winptyrs::{Cassette, CassetteEvent};
+
+    let path = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("conpty.cassette");
+    let args = PTYArgs::default().cassette(path.clone().into_os_string());
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::ConPTY).unwrap();
+    pty.spawn(OsString::from("cmd.exe"), Some(OsString::from("/q")), None, None).unwrap();
+    pty.write(OsString::from("echo recorded\r\n")).unwrap();
+    let mut output = String::new();
+    while !output.contains("recorded\r\n") {
+        output.push_str(&pty.read_timeout(Duration::from_millis(500)).unwrap().to_string_lossy());
+    }
+    pty.write(OsString::from("exit 5\r\n")).unwrap();
+    pty.wait_for_exit().unwrap();
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(5));
+    drop(pty);
+
+    let cassette = Cassette::load(path.as_os_str()).unwrap();
+    assert!(matches!(cassette.entries.first().map(|entry| &entry.event), Some(CassetteEvent::Spawn { .. })));
+    assert!(matches!(cassette.entries.last().map(|entry| &entry.event), Some(CassetteEvent::Exit(5))));
+
+    let args = PTYArgs::default().cassette(path.into_os_string()).strict_replay(true);
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Replay).unwrap();
+    pty.spawn(OsString::from("cmd.exe"), Some(OsString::from("/q")), None, None).unwrap();
+    pty.write(OsString::from("echo recorded\r\n")).unwrap();
+    let mut output = String::new();
+    while !output.contains("recorded\r\n") {
+        output.push_str(&pty.read_timeout(Duration::from_millis(500)).unwrap().to_string_lossy());
+    }
+    pty.write(OsString::from("exit 5\r\n")).unwrap();
+    assert!(pty.wait_for_exit().unwrap());
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(5));
+}
+
+#[test]
+fn batch_conpty() {
+    use winptyrs::{BatchCommand, BatchOptions, BatchRunner, BatchStatus};
+
+    let options = BatchOptions { backend: PTYBackend::ConPTY, concurrency: 2, ..BatchOptions::default() };
+    let env = vec![(OsString::from("WINPTY_RS"), OsString::from("batch"))];
+    let commands = vec![
+        BatchCommand { env, ..BatchCommand::new("cmd.exe", Some("/c echo %WINPTY_RS%-ok & exit 3")) },
+        BatchCommand::new("cmd.exe", Some("/c echo second")),
+        BatchCommand { timeout: Some(Duration::from_millis(500)), ..BatchCommand::new("cmd.exe", None) },
+    ];
+    let report = BatchRunner::new(options).run(commands);
+    assert_eq!(report.results[0].status, BatchStatus::Exited(3));
+    assert!(report.results[0].transcript.to_string_lossy().contains("batch-ok"));
+    assert_eq!(report.results[1].status, BatchStatus::Exited(0));
+    assert!(report.results[1].transcript.to_string_lossy().contains("second"));
+    assert_eq!(report.results[2].status, BatchStatus::TimedOut);
+    assert_eq!(report.failures().count(), 2);
+}
+
+#[test]
+fn batch_timeout_kills_tree_conpty() {
+    use std::process::Command;
+    use winptyrs::{BatchCommand, BatchOptions, BatchRunner, BatchStatus};
+
+    // The default options do not contain the tree, the runner has to kill it anyway.
+    let options = BatchOptions { backend: PTYBackend::ConPTY, ..BatchOptions::default() };
+    let script = "$child = Start-Process -FilePath ping.exe -ArgumentList '-n 120 127.0.0.1' -NoNewWindow -PassThru; \
+                  Write-Output ('pids=' + $PID + ',' + $child.Id); Wait-Process -Id $child.Id";
+    let cmdline = format!("-NoProfile -Command \"{}\"", script);
+    let command = BatchCommand {
+        timeout: Some(Duration::from_secs(5)),
+        ..BatchCommand::new("powershell.exe", Some(&cmdline))
+    };
+    let report = BatchRunner::new(options).run(vec![command]);
+    assert_eq!(report.results[0].status, BatchStatus::TimedOut);
+
+    let transcript = report.results[0].transcript.to_string_lossy().into_owned();
+    let captures = Regex::new(r"pids=(\d+),(\d+)").unwrap().captures(&transcript).expect(&transcript);
+    for pid in [&captures[1], &captures[2]] {
+        let running = || {
+            let output = Command::new("tasklist").args(["/FI", &format!("PID eq {}", pid), "/NH"]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).lines().any(|line| line.split_whitespace().nth(1) == Some(pid))
+        };
+        let start = Instant::now();
+        while running() && start.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(100));
+        }
+        assert!(!running(), "process {} is still running", pid);
+    }
+}
+
+#[test]
+fn wait_idle_conpty() {
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    let banner = pty.wait_idle(Duration::from_millis(500), Duration::from_secs(10)).unwrap();
+    assert!(banner.to_string_lossy().contains("Microsoft Windows"), "{:?}", banner);
+
+    pty.write("echo settled\r\n".into()).unwrap();
+    let output = pty.wait_idle(Duration::from_millis(500), Duration::from_secs(10)).unwrap();
+    assert!(output.to_string_lossy().contains("settled"), "{:?}", output);
+
+    // Output that was already received does not extend the wait.
+    pty.write("echo buffered& exit 4\r\n".into()).unwrap();
+    pty.wait_for_exit().unwrap();
+    let start = Instant::now();
+    let output = pty.wait_idle(Duration::from_secs(5), Duration::from_secs(10)).unwrap();
+    assert!(output.to_string_lossy().contains("buffered"), "{:?}", output);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(pty.wait_idle(Duration::from_millis(100), Duration::from_secs(1)).is_err());
+    assert!(pty.read(false).is_err());
+}
+
+#[test]
+fn triggers_conpty() {
+    use std::sync::{Arc, Mutex};
+    use winptyrs::Trigger;
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    let answer = pty.add_trigger(Trigger::new(r"Continue \(Y/N\)\?").unwrap().respond("echo answered\r\n").once());
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let recorded = paths.clone();
+    pty.add_trigger(
+        Trigger::new(r"(?<path>[A-Z]:\\[^>\r\n]*)>").unwrap().on_match(move |m| recorded.lock().unwrap().push(m.name("path").unwrap().to_owned())),
+    );
+    pty.spawn(appname, None, None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+
+    pty.write(OsString::from("echo Continue (Y/N)?\r\n")).unwrap();
+    let mut output = String::new();
+    let start = Instant::now();
+    while !output.contains("answered\r\n") {
+        assert!(start.elapsed() < Duration::from_secs(10), "{:?}", output);
+        output.push_str(&pty.read_timeout(Duration::from_millis(500)).unwrap().to_string_lossy());
+    }
+    assert!(!pty.remove_trigger(answer));
+    assert!(!paths.lock().unwrap().is_empty());
+}
+
+#[test]
+fn export_conpty() {
+    use winptyrs::export::{html, svg, ExportOptions};
+    use winptyrs::vt::Screen;
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, Some(OsString::from("/q")), None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.write(OsString::from("echo \x1b[31m^<b^>red^</b^>\x1b[0m\r\n")).unwrap();
+
+    let mut screen = Screen::new(80, 25).with_scrollback(usize::MAX);
+    screen.feed(&pty.wait_idle(Duration::from_millis(500), Duration::from_secs(10)).unwrap().to_string_lossy());
+    let options = ExportOptions { trim: true, ..ExportOptions::default() };
+    let document = html(&screen, &options);
+    assert!(document.contains("&lt;b&gt;red&lt;/b&gt;"), "{}", document);
+    assert!(!document.contains("<b>"));
+    assert!(svg(&screen, &options).contains("&lt;b&gt;red&lt;/b&gt;"));
+}
+
+#[test]
+fn screenshot_conpty() {
+    use winptyrs::export::{png, screenshot, Palette, PngOptions, CELL_HEIGHT, CELL_WIDTH};
+    use winptyrs::vt::Screen;
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&PTYArgs::default(), PTYBackend::ConPTY).unwrap();
+    pty.spawn(appname, Some(OsString::from("/q")), None, None).unwrap();
+    pty.write(OsString::from("\x1b[?1;0c\x1b[0;0R")).unwrap();
+    pty.write(OsString::from("echo \x1b[41mred\x1b[0m\r\n")).unwrap();
+
+    let mut screen = Screen::new(80, 25);
+    screen.feed(&pty.wait_idle(Duration::from_millis(500), Duration::from_secs(10)).unwrap().to_string_lossy());
+    let options = PngOptions { cursor: true, ..PngOptions::default() };
+    let image = screenshot(&screen, &options);
+    assert_eq!((image.width, image.height), (80 * CELL_WIDTH * 2, 25 * CELL_HEIGHT * 2));
+    assert!(image.pixels.contains(&Palette::default().ansi[1]), "{}", screen.text());
+    assert!(png(&screen, &options).starts_with(b"\x89PNG"));
+}
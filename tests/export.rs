@@ -0,0 +1,239 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use winptyrs::export::{escape, html, png, screenshot, svg, ExportOptions, Image, Palette, PngOptions, CELL_HEIGHT, CELL_WIDTH};
+use winptyrs::vt::{Color, Screen, Style};
+
+fn transcript(output: &str) -> Screen {
+    let mut screen = Screen::new(40, 5).with_scrollback(usize::MAX);
+    screen.feed(output);
+    screen
+}
+
+fn trimmed() -> ExportOptions {
+    ExportOptions { trim: true, ..ExportOptions::default() }
+}
+
+/// Content of the `pre` element of an HTML document.
+fn body(document: &str) -> &str {
+    let start = document.find("<pre class=\"terminal\">").unwrap() + "<pre class=\"terminal\">".len();
+    &document[start..document.rfind("</pre>").unwrap()]
+}
+
+#[test]
+fn export_colors_to_html() {
+    let screen = transcript("\x1b[31mred\x1b[0m \x1b[38;5;208morange\x1b[0m \x1b[38;2;1;2;3;48;5;231mrgb\x1b[0m\r\n\x1b[1;3;4mstyled\x1b[0m \x1b[7minverse\x1b[0m\r\n");
+    let document = html(&screen, &trimmed());
+    assert!(document.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n"));
+    assert_eq!(
+        body(&document),
+        "<span style=\"color:#c50f1f\">red</span> <span style=\"color:#ff8700\">orange</span> \
+         <span style=\"color:#010203;background-color:#ffffff\">rgb</span>\n\
+         <span style=\"font-weight:bold;font-style:italic;text-decoration:underline\">styled</span> \
+         <span style=\"color:#0c0c0c;background-color:#cccccc\">inverse</span>\n"
+    );
+
+    // The scrollback is exported before the rows of the screen.
+    let screen = transcript(&(0..8).map(|i| format!("line {}\r\n", i)).collect::<String>());
+    assert_eq!(body(&html(&screen, &trimmed())), (0..8).map(|i| format!("line {}\n", i)).collect::<String>());
+}
+
+#[test]
+fn export_with_options() {
+    let mut palette = Palette::default();
+    palette.ansi[1] = (0x12, 0x34, 0x56);
+    palette.foreground = (0xaa, 0xbb, 0xcc);
+    palette.background = (0xff, 0xff, 0xff);
+    let options = ExportOptions {
+        palette,
+        font_family: String::from("Fira Code, monospace"),
+        font_size: 20,
+        title: Some(String::from("Build & test")),
+        ..trimmed()
+    };
+    let screen = transcript("\x1b[31mred\x1b[39m default");
+    let document = html(&screen, &options);
+    assert!(document.contains("<title>Build &amp; test</title>"));
+    assert!(document.contains("color: #aabbcc; background-color: #ffffff; font-family: Fira Code, monospace; font-size: 20px;"));
+    assert_eq!(body(&document), "<span style=\"color:#123456\">red</span> default\n");
+
+    let image = svg(&screen, &options);
+    assert!(image.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"496\" height=\"40\" viewBox=\"0 0 496 40\" font-family=\"Fira Code, monospace\" font-size=\"20\">\n"));
+    assert!(image.contains("<title>Build &amp; test</title>\n<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n"));
+    assert!(image.contains("<tspan x=\"8\" textLength=\"36\" lengthAdjust=\"spacingAndGlyphs\" fill=\"#123456\">red</tspan>"));
+    assert!(image.contains("<tspan x=\"44\" textLength=\"96\" lengthAdjust=\"spacingAndGlyphs\" fill=\"#aabbcc\"> default</tspan>"));
+}
+
+#[test]
+fn export_screens_to_svg() {
+    let mut screen = Screen::new(10, 3);
+    screen.feed("\x1b[44mab\x1b[0m\x1b[1;4mc\x1b[0m界\x1b[2;1H\x1b]8;;https://example.com/?a=1&b=2\x07link\x1b]8;;\x07");
+    let image = svg(&screen, &ExportOptions { cursor: true, ..ExportOptions::default() });
+    assert!(image.contains("width=\"100\" height=\"66.4\" viewBox=\"0 0 100 66.4\""));
+    assert!(image.contains("<rect x=\"8\" y=\"8\" width=\"16.8\" height=\"16.8\" fill=\"#0037da\"/>"));
+    assert!(image.contains("fill=\"#cccccc\" font-weight=\"bold\" text-decoration=\"underline\">c</tspan>"));
+    // Wide characters take two cells.
+    assert!(image.contains("<tspan x=\"33.2\" textLength=\"16.8\" lengthAdjust=\"spacingAndGlyphs\" fill=\"#cccccc\">界</tspan>"));
+    assert!(image.contains("<a href=\"https://example.com/?a=1&amp;b=2\"><tspan x=\"8\" textLength=\"33.6\""));
+    // The cursor is drawn after the link.
+    assert!(image.contains("<rect x=\"41.6\" y=\"24.8\" width=\"8.4\" height=\"16.8\" fill=\"#ffffff\"/>"));
+
+    screen.feed("\x1b[?25l");
+    assert!(!svg(&screen, &ExportOptions { cursor: true, ..ExportOptions::default() }).contains("fill=\"#ffffff\""));
+}
+
+#[test]
+fn escape_hostile_output() {
+    let hostile = "<script>alert(1)</script> \"quoted\" 'single' & </pre><img src=x onerror=alert(1)> ]]>";
+    let escaped = "&lt;script&gt;alert(1)&lt;/script&gt; &quot;quoted&quot; &#39;single&#39; &amp; \
+                   &lt;/pre&gt;&lt;img src=x onerror=alert(1)&gt; ]]&gt;";
+    assert_eq!(escape(hostile), escaped);
+
+    let mut screen = Screen::new(200, 2);
+    screen.feed(hostile);
+    let document = html(&screen, &trimmed());
+    assert_eq!(body(&document), format!("{}\n", escaped));
+    assert!(!document.contains("<script>") && !document.contains("<img"));
+    let image = svg(&screen, &trimmed());
+    assert!(image.contains(&format!("\">{}</tspan>", escaped)));
+    assert!(!image.contains("<script>") && !image.contains("<img"));
+
+    // Hyperlinks are escaped, and the ones that could run scripts are dropped.
+    let mut screen = Screen::new(80, 3);
+    screen.feed("\x1b]8;;https://a.com/\"><script>x</script>\x07one\x1b]8;;\x07\r\n");
+    screen.feed("\x1b]8;;javascript:alert(1)\x07two\x1b]8;;\x07\r\n\x1b]8;;JavaScript:alert(1)\x07three");
+    let document = html(&screen, &trimmed());
+    assert_eq!(body(&document), "<a href=\"https://a.com/&quot;&gt;&lt;script&gt;x&lt;/script&gt;\">one</a>\ntwo\nthree\n");
+    let image = svg(&screen, &trimmed());
+    assert!(image.contains("<a href=\"https://a.com/&quot;&gt;&lt;script&gt;x&lt;/script&gt;\">"));
+    assert!(!image.contains("javascript") && !image.contains("JavaScript"));
+
+    // Options cannot inject markup either.
+    let options = ExportOptions {
+        font_family: String::from("x; } </style><script>alert(1)</script>"),
+        title: Some(String::from("</title><script>")),
+        ..trimmed()
+    };
+    let document = html(&screen, &options);
+    assert!(!document.contains("<script>") && !document.contains("</style><"));
+    assert!(document.contains("<title>&lt;/title&gt;&lt;script&gt;</title>"));
+    assert!(!svg(&screen, &options).contains("<script>"));
+}
+
+#[test]
+fn resolve_palettes() {
+    let palette = Palette::default();
+    assert_eq!(palette.indexed(9), (0xe7, 0x48, 0x56));
+    assert_eq!(palette.indexed(16), (0, 0, 0));
+    assert_eq!(palette.indexed(196), (255, 0, 0));
+    assert_eq!(palette.indexed(67), (95, 135, 175));
+    assert_eq!(palette.indexed(232), (8, 8, 8));
+    assert_eq!(palette.indexed(255), (238, 238, 238));
+
+    let style = Style { fg: Color::Rgb(1, 2, 3), inverse: true, ..Style::default() };
+    assert_eq!(palette.colors(&style), (palette.background, (1, 2, 3)));
+}
+
+/// Screenshot with one pixel per pixel of the font.
+fn shot(output: &str) -> Image {
+    let mut screen = Screen::new(4, 2);
+    screen.feed(output);
+    screenshot(&screen, &PngOptions { scale: 1, ..PngOptions::default() })
+}
+
+/// Pixels of the row `y` of an image, as `#` for the `fg` color and `.` for the others.
+fn pixels(image: &Image, y: usize, fg: (u8, u8, u8)) -> String {
+    (0..image.width).map(|x| if image.pixel(x, y) == fg { '#' } else { '.' }).collect()
+}
+
+#[test]
+fn draw_screenshots() {
+    let palette = Palette::default();
+    let image = shot("");
+    assert_eq!((image.width, image.height), (4 * CELL_WIDTH, 2 * CELL_HEIGHT));
+    assert!(image.pixels.iter().all(|&pixel| pixel == palette.background));
+    let mut screen = Screen::new(4, 2);
+    screen.feed("I");
+    assert_eq!(screenshot(&screen, &PngOptions::default()).width, 4 * CELL_WIDTH * 2);
+
+    // Glyphs start on the second row of the cells, and bold glyphs are drawn twice.
+    let image = shot("I\x1b[1mI\x1b[22;4mI");
+    assert_eq!(pixels(&image, 0, palette.foreground), "........................");
+    assert_eq!(pixels(&image, 1, palette.foreground), ".###...####..###........");
+    assert_eq!(pixels(&image, 2, palette.foreground), "..#.....##....#.........");
+    assert_eq!(pixels(&image, 10, palette.foreground), "............######......");
+
+    // Colors, inverse, wide characters and box drawing.
+    let image = shot("\x1b[31;42mI\x1b[0;7m \x1b[0m界\r\n──");
+    let red = palette.ansi[1];
+    assert_eq!(pixels(&image, 1, red), ".###....................");
+    assert_eq!(image.pixel(0, 0), palette.ansi[2]);
+    assert_eq!(pixels(&image, 0, palette.foreground), "......######............");
+    assert_eq!(pixels(&image, 1, palette.foreground), "......#################.");
+    assert_eq!(pixels(&image, 2, palette.foreground), "......#######.........#.");
+    assert_eq!(pixels(&image, CELL_HEIGHT + 5, palette.foreground), "############............");
+
+    // The cursor is drawn as a block, unless it is hidden.
+    let mut screen = Screen::new(4, 2);
+    screen.feed("a\r\nb");
+    let options = PngOptions { scale: 1, cursor: true, ..PngOptions::default() };
+    let image = screenshot(&screen, &options);
+    assert_eq!(pixels(&image, CELL_HEIGHT, palette.cursor), "......######............");
+    screen.feed("\x1b[?25l");
+    assert!(!screenshot(&screen, &options).pixels.contains(&palette.cursor));
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[test]
+fn encode_pngs() {
+    let mut screen = Screen::new(80, 24);
+    screen.feed("\x1b[1;32mPASS\x1b[0m tests/export.rs\r\n");
+    let image = png(&screen, &PngOptions::default());
+    assert!(image.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+    assert_eq!(&image[16..29], &[0, 0, 3, 192, 0, 0, 2, 64, 8, 2, 0, 0, 0]);
+    assert!(image.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+    // The pixels are compressed, the screen being mostly blank.
+    assert!(image.len() < 960 * 576 * 3 / 100);
+
+    let mut chunks = Vec::new();
+    let mut idat = Vec::new();
+    let mut rest = &image[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+        assert_eq!(crc32(&rest[4..8 + length]), crc);
+        chunks.push(String::from_utf8_lossy(&rest[4..8]).into_owned());
+        if &rest[4..8] == b"IDAT" {
+            idat.extend_from_slice(&rest[8..8 + length]);
+        }
+        rest = &rest[12 + length..];
+    }
+    assert_eq!(chunks, ["IHDR", "IDAT", "IEND"]);
+
+    // The decompressed rows, once unfiltered, hold the pixels of the screenshot.
+    let mut raw = Vec::new();
+    ZlibDecoder::new(&idat[..]).read_to_end(&mut raw).unwrap();
+    let expected = screenshot(&screen, &PngOptions::default());
+    assert_eq!(raw.len(), (3 * expected.width + 1) * expected.height);
+    let mut pixels = Vec::new();
+    for row in raw.chunks(3 * expected.width + 1) {
+        // Sub filter: every byte is the difference with the same channel of the pixel to its left.
+        assert_eq!(row[0], 1);
+        let mut left = (0u8, 0u8, 0u8);
+        for delta in row[1..].chunks(3) {
+            left = (left.0.wrapping_add(delta[0]), left.1.wrapping_add(delta[1]), left.2.wrapping_add(delta[2]));
+            pixels.push(left);
+        }
+    }
+    assert!(pixels == expected.pixels, "the decoded pixels differ from the screenshot");
+}
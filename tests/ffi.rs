@@ -0,0 +1,104 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use winptyrs::ffi::*;
+use winptyrs::{PTYArgs, PTYBackend};
+
+mod common;
+use common::command_cassette;
+
+fn last_error() -> String {
+    let error = winptyrs_last_error();
+    assert!(!error.is_null());
+    unsafe { CStr::from_ptr(error) }.to_str().unwrap().to_owned()
+}
+
+#[test]
+fn default_args() {
+    let mut args = unsafe { std::mem::zeroed::<WinptyrsArgs>() };
+    assert_eq!(unsafe { winptyrs_args_default(&mut args) }, WINPTYRS_OK);
+    let defaults = PTYArgs::default();
+    assert_eq!((args.cols, args.rows, args.timeout), (defaults.cols, defaults.rows, defaults.timeout));
+    assert_eq!(args.agent_config, defaults.agent_config.bits());
+    assert_eq!((args.shutdown_policy, args.drain_timeout), (WINPTYRS_SHUTDOWN_CLOSE_CONSOLE, defaults.drain_timeout));
+    assert_eq!(args.osc_max_payload, defaults.osc_max_payload);
+    assert!(args.cassette.is_null() && !args.strict_replay);
+
+    assert_eq!(WINPTYRS_BACKEND_CONPTY, PTYBackend::ConPTY as u32);
+    assert_eq!(WINPTYRS_BACKEND_WINPTY, PTYBackend::WinPTY as u32);
+    assert_eq!(WINPTYRS_BACKEND_AUTO, PTYBackend::Auto as u32);
+    assert_eq!(WINPTYRS_BACKEND_REPLAY, PTYBackend::Replay as u32);
+}
+
+#[test]
+fn reject_invalid_arguments() {
+    assert_eq!(unsafe { winptyrs_args_default(ptr::null_mut()) }, WINPTYRS_ERROR_NULL_ARGUMENT);
+    assert_eq!(last_error(), "args must not be null");
+
+    let mut args = unsafe { std::mem::zeroed::<WinptyrsArgs>() };
+    unsafe { winptyrs_args_default(&mut args) };
+    let mut pty = ptr::NonNull::<WinptyrsPty>::dangling().as_ptr();
+    args.mouse_mode = 7;
+    assert_eq!(unsafe { winptyrs_pty_new(&args, WINPTYRS_BACKEND_CONPTY, &mut pty) }, WINPTYRS_ERROR_INVALID_ARGUMENT);
+    assert_eq!(last_error(), "Invalid mouse mode 7");
+    assert!(pty.is_null());
+
+    args.mouse_mode = 0;
+    args.shutdown_policy = 9;
+    assert_eq!(unsafe { winptyrs_pty_new(&args, WINPTYRS_BACKEND_CONPTY, &mut pty) }, WINPTYRS_ERROR_INVALID_ARGUMENT);
+    assert_eq!(last_error(), "Invalid shutdown policy 9");
+    assert_eq!(unsafe { winptyrs_pty_new(ptr::null(), 3, &mut pty) }, WINPTYRS_ERROR_INVALID_ARGUMENT);
+    assert_eq!(last_error(), "Invalid backend 3");
+    assert_eq!(unsafe { winptyrs_pty_new(ptr::null(), WINPTYRS_BACKEND_AUTO, ptr::null_mut()) }, WINPTYRS_ERROR_NULL_ARGUMENT);
+
+    let mut read = 0;
+    let mut buf = [0u8; 8];
+    let mut exited = false;
+    unsafe {
+        assert_eq!(winptyrs_pty_read(ptr::null(), buf.as_mut_ptr(), buf.len(), 0, &mut read), WINPTYRS_ERROR_NULL_ARGUMENT);
+        assert_eq!(last_error(), "pty must not be null");
+        assert_eq!(winptyrs_pty_write(ptr::null(), b"ls".as_ptr(), 2, ptr::null_mut()), WINPTYRS_ERROR_NULL_ARGUMENT);
+        assert_eq!(winptyrs_pty_resize(ptr::null(), 80, 24), WINPTYRS_ERROR_NULL_ARGUMENT);
+        assert_eq!(winptyrs_pty_exit_status(ptr::null(), &mut exited, ptr::null_mut()), WINPTYRS_ERROR_NULL_ARGUMENT);
+        assert_eq!(winptyrs_pty_spawn(ptr::null_mut(), c"cmd".as_ptr(), ptr::null(), ptr::null(), ptr::null()), WINPTYRS_ERROR_NULL_ARGUMENT);
+        winptyrs_pty_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn replay_through_c_api() {
+    let path = command_cassette("ffi", "hello\r\n", 50, 7);
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+
+    let mut args = unsafe { std::mem::zeroed::<WinptyrsArgs>() };
+    let mut pty = ptr::null_mut();
+    unsafe {
+        assert_eq!(winptyrs_args_default(&mut args), WINPTYRS_OK);
+        args.cassette = path.as_ptr();
+        assert_eq!(winptyrs_pty_new(&args, WINPTYRS_BACKEND_REPLAY, &mut pty), WINPTYRS_OK, "{}", last_error());
+        assert!(!pty.is_null());
+        assert_eq!(winptyrs_pty_spawn(pty, c"tool.exe".as_ptr(), ptr::null(), ptr::null(), ptr::null()), WINPTYRS_OK);
+        assert_eq!(winptyrs_pty_resize(pty, 100, 30), WINPTYRS_OK);
+        assert_eq!(winptyrs_pty_resize(pty, 0, 30), WINPTYRS_ERROR_INVALID_ARGUMENT);
+
+        // Small reads return the output in pieces until the end of the output.
+        let mut output = Vec::new();
+        let mut buf = [0u8; 3];
+        let mut read = 0;
+        let status = loop {
+            match winptyrs_pty_read(pty, buf.as_mut_ptr(), buf.len(), 1000, &mut read) {
+                WINPTYRS_OK => output.extend_from_slice(&buf[..read]),
+                status => break status,
+            }
+        };
+        assert_eq!(status, WINPTYRS_ERROR_EOF, "{}", last_error());
+        assert_eq!(String::from_utf8(output).unwrap(), "hello\r\n");
+
+        let mut exited = false;
+        let mut code = 0;
+        assert_eq!(winptyrs_pty_exit_status(pty, &mut exited, &mut code), WINPTYRS_OK);
+        assert_eq!((exited, code), (true, 7));
+        assert_eq!(winptyrs_pty_write(pty, [0xffu8].as_ptr(), 1, ptr::null_mut()), WINPTYRS_ERROR_INVALID_UTF8);
+        winptyrs_pty_free(pty);
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Exercises the C API of winptyrs through include/winptyrs.h.
+ *
+ * Build it against the cdylib produced by `cargo build`, e.g. with MSVC:
+ *     cl /I include tests\ffi\test_ffi.c target\debug\winptyrs.dll.lib
+ * The tests of the conpty feature build and run it when a C compiler is available.
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "winptyrs.h"
+
+#define CHECK(expr)                                                                 \
+    do {                                                                            \
+        if (!(expr)) {                                                              \
+            const char *error = winptyrs_last_error();                              \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n", __FILE__, \
+                    __LINE__, #expr, error ? error : "none");                       \
+            return 1;                                                               \
+        }                                                                           \
+    } while (0)
+
+static int read_until(WinptyrsPty *pty, char *output, size_t capacity, const char *needle) {
+    size_t length = strlen(output);
+    for (int attempt = 0; attempt < 100 && strstr(output, needle) == NULL; attempt++) {
+        size_t read = 0;
+        /* A small buffer exercises the output retained between reads. */
+        size_t chunk = capacity - 1 - length < 7 ? capacity - 1 - length : 7;
+        WinptyrsStatus status = winptyrs_pty_read(pty, (uint8_t *)output + length, chunk, 100, &read);
+        if (status == WINPTYRS_ERROR_EOF) {
+            break;
+        }
+        if (status != WINPTYRS_OK) {
+            return status;
+        }
+        length += read;
+        output[length] = '\0';
+    }
+    return strstr(output, needle) != NULL ? WINPTYRS_OK : WINPTYRS_ERROR_EOF;
+}
+
+int main(void) {
+    WinptyrsArgs args;
+    WinptyrsPty *pty = NULL;
+    char output[16384] = {0};
+
+    /* Invalid arguments are reported with a status and a message. */
+    CHECK(winptyrs_args_default(NULL) == WINPTYRS_ERROR_NULL_ARGUMENT);
+    CHECK(winptyrs_last_error() != NULL && strstr(winptyrs_last_error(), "args") != NULL);
+    CHECK(winptyrs_args_default(&args) == WINPTYRS_OK);
+    CHECK(args.cols == 80 && args.rows == 24 && args.shutdown_policy == WINPTYRS_SHUTDOWN_CLOSE_CONSOLE);
+    args.mouse_mode = 42;
+    CHECK(winptyrs_pty_new(&args, WINPTYRS_BACKEND_CONPTY, &pty) == WINPTYRS_ERROR_INVALID_ARGUMENT);
+    CHECK(pty == NULL);
+    CHECK(winptyrs_pty_new(NULL, 42, &pty) == WINPTYRS_ERROR_INVALID_ARGUMENT);
+    CHECK(winptyrs_pty_resize(NULL, 80, 24) == WINPTYRS_ERROR_NULL_ARGUMENT);
+
+    CHECK(winptyrs_args_default(&args) == WINPTYRS_OK);
+    args.cols = 100;
+    args.rows = 30;
+    args.shutdown_policy = WINPTYRS_SHUTDOWN_TERMINATE;
+    CHECK(winptyrs_pty_new(&args, WINPTYRS_BACKEND_CONPTY, &pty) == WINPTYRS_OK);
+    CHECK(pty != NULL);
+
+    const char *env[] = {"WINPTYRS_FFI=ffi", "SystemRoot=C:\\Windows", NULL};
+    CHECK(winptyrs_pty_spawn(pty, NULL, NULL, NULL, NULL) == WINPTYRS_ERROR_NULL_ARGUMENT);
+    CHECK(winptyrs_pty_spawn(pty, "C:\\Windows\\System32\\cmd.exe", NULL, NULL, env) == WINPTYRS_OK);
+
+    uint32_t pid = 0;
+    CHECK(winptyrs_pty_pid(pty, &pid) == WINPTYRS_OK && pid != 0);
+    bool exited = true;
+    CHECK(winptyrs_pty_exit_status(pty, &exited, NULL) == WINPTYRS_OK && !exited);
+    CHECK(winptyrs_pty_resize(pty, 120, 40) == WINPTYRS_OK);
+    CHECK(winptyrs_pty_resize(pty, 0, 40) == WINPTYRS_ERROR_INVALID_ARGUMENT);
+
+    const char *command = "\x1b[?1;0c\x1b[0;0Recho %WINPTYRS_FFI%-ok\r\n";
+    uint32_t written = 0;
+    CHECK(winptyrs_pty_write(pty, (const uint8_t *)command, strlen(command), &written) == WINPTYRS_OK);
+    CHECK(written > 0);
+    CHECK(winptyrs_pty_write(pty, (const uint8_t *)"\xff", 1, NULL) == WINPTYRS_ERROR_INVALID_UTF8);
+    CHECK(read_until(pty, output, sizeof(output), "ffi-ok") == WINPTYRS_OK);
+
+    const char *exit_command = "exit\r\n";
+    CHECK(winptyrs_pty_write(pty, (const uint8_t *)exit_command, strlen(exit_command), NULL) == WINPTYRS_OK);
+    WinptyrsStatus status = WINPTYRS_OK;
+    for (int attempt = 0; attempt < 100 && status == WINPTYRS_OK; attempt++) {
+        size_t read = 0;
+        status = winptyrs_pty_read(pty, (uint8_t *)output, sizeof(output) - 1, 100, &read);
+    }
+    CHECK(status == WINPTYRS_ERROR_EOF);
+
+    uint32_t code = 0;
+    CHECK(winptyrs_pty_exit_status(pty, &exited, &code) == WINPTYRS_OK && exited);
+    printf("exit code: %u\n", code);
+
+    winptyrs_pty_free(pty);
+    winptyrs_pty_free(NULL);
+    return 0;
+}
@@ -0,0 +1,127 @@
+use std::io::{BufReader, Cursor, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use winptyrs::jsonrpc::{
+    read_message, write_message, Value, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR,
+    PTY_ERROR,
+};
+use winptyrs::JsonRpcHost;
+
+/// Output of a host that remains readable after the host is dropped.
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn frame(messages: &[&str]) -> Vec<u8> {
+    let mut framed = Vec::new();
+    for message in messages {
+        write_message(&mut framed, message).unwrap();
+    }
+    framed
+}
+
+fn read_all(data: &[u8]) -> Vec<Value> {
+    let mut reader = Cursor::new(data);
+    let mut messages = Vec::new();
+    while let Some(content) = read_message(&mut reader).unwrap() {
+        messages.push(serde_json::from_str(&content).unwrap());
+    }
+    messages
+}
+
+fn error_code(response: &Value) -> Option<i64> {
+    response.get("error")?.get("code")?.as_i64()
+}
+
+#[test]
+fn frame_messages() {
+    let mut data = frame(&["{}", "[1,2]"]);
+    data.extend_from_slice(b"\r\ncontent-length: 4\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\nnull");
+    let mut reader = Cursor::new(data);
+    assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("{}"));
+    assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("[1,2]"));
+    assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("null"));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+
+    for invalid in [&b"Content-Type: json\r\n\r\n{}"[..], b"Content-Length: x\r\n\r\n", b"Content-Length: 10\r\n\r\n{}", b"Content-Length: 2\r\n", b"{}\r\n\r\n"] {
+        assert!(read_message(&mut Cursor::new(invalid)).is_err(), "{:?} was accepted", invalid);
+    }
+}
+
+#[test]
+fn reject_invalid_requests() {
+    let sink = Sink::default();
+    let input = frame(&[
+        "{\"jsonrpc\":",
+        r#"{"jsonrpc":"1.0","id":1,"method":"kill"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"attach","params":{}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"write","params":[1]}"#,
+        r#"{"jsonrpc":"2.0","id":"w","method":"write","params":{"id":9,"data":"ls"}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"write","params":{"id":9,"data":"!","encoding":"base64"}}"#,
+        r#"{"jsonrpc":"2.0","id":6,"method":"resize","params":{"id":9,"cols":0,"rows":24}}"#,
+        r#"{"jsonrpc":"2.0","id":7,"method":"signal","params":{"id":9,"signal":"hangup"}}"#,
+        r#"{"jsonrpc":"2.0","id":8,"method":"spawn","params":{"command":"cmd","backend":"vt100"}}"#,
+        r#"{"jsonrpc":"2.0","method":"kill","params":{"id":9}}"#,
+        r#"[]"#,
+        r#"[{"jsonrpc":"2.0","id":10,"method":"kill","params":{"id":9}},{"jsonrpc":"2.0","method":"kill"},7]"#,
+    ]);
+    {
+        let host = JsonRpcHost::new(sink.clone());
+        host.serve(BufReader::new(Cursor::new(input))).unwrap();
+    }
+
+    let responses = read_all(&sink.0.lock().unwrap());
+    let codes: Vec<(String, Option<i64>)> = responses
+        .iter()
+        .flat_map(|response| response.as_array().cloned().unwrap_or_else(|| vec![response.clone()]))
+        .map(|response| (response.get("id").unwrap().to_string(), error_code(&response)))
+        .collect();
+    let expected = [
+        ("null", PARSE_ERROR),
+        ("1", INVALID_REQUEST),
+        ("2", METHOD_NOT_FOUND),
+        ("3", INVALID_PARAMS),
+        ("\"w\"", PTY_ERROR),
+        ("5", INVALID_PARAMS),
+        ("6", INVALID_PARAMS),
+        ("7", INVALID_PARAMS),
+        ("8", INVALID_PARAMS),
+        ("null", INVALID_REQUEST),
+        ("10", PTY_ERROR),
+        ("null", INVALID_REQUEST),
+    ];
+    let expected: Vec<(String, Option<i64>)> = expected.iter().map(|(id, code)| (id.to_string(), Some(*code))).collect();
+    assert_eq!(codes, expected);
+    assert!(responses.iter().all(|response| response.get("jsonrpc").is_some() || response.as_array().is_some()));
+}
+
+#[test]
+fn host_stdio() {
+    let mut host = Command::new(env!("CARGO_BIN_EXE_winpty-rs"))
+        .args(["host", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = host.stdin.take().unwrap();
+    stdin.write_all(&frame(&[r#"{"jsonrpc":"2.0","id":1,"method":"resize","params":{"id":1,"cols":80,"rows":24}}"#])).unwrap();
+    drop(stdin);
+
+    let output = host.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let responses = read_all(&output.stdout);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get("id").and_then(Value::as_i64), Some(1));
+    assert_eq!(error_code(&responses[0]), Some(PTY_ERROR));
+}
@@ -0,0 +1,35 @@
+use std::ffi::OsString;
+use std::time::Duration;
+
+use winptyrs::{PTYBackend, PtyEvent, PtyManager};
+
+mod common;
+use common::{command_cassette, replay_args};
+
+
+#[test]
+fn manage_sessions() {
+    let manager = PtyManager::new();
+    let events = manager.events();
+    let id = manager.create(&replay_args(&command_cassette("manager", "done\r\n", 200, 3)), PTYBackend::Replay).unwrap();
+    manager.spawn(id, OsString::from("tool.exe"), None, None, None).unwrap();
+    assert!(manager.spawn(id, OsString::from("tool.exe"), None, None, None).is_err());
+
+    // A session that is still in use is left open.
+    manager
+        .with_session(id, |_| {
+            assert!(manager.close(id).is_err());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(manager.ids(), vec![id]);
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(events.recv_timeout(timeout).unwrap(), PtyEvent::Output(id, OsString::from("done\r\n")));
+    assert_eq!(events.recv_timeout(timeout).unwrap(), PtyEvent::Exit(id, Some(3)));
+
+    manager.close(id).unwrap();
+    assert!(manager.ids().is_empty());
+    assert!(manager.close(id).is_err());
+    assert!(manager.write(id, OsString::from("x")).is_err());
+}
@@ -0,0 +1,188 @@
+use std::ffi::OsString;
+use std::io::Cursor;
+use std::time::Duration;
+
+use winptyrs::remote::{Frame, LocalStream, SessionInfo, MAX_FRAME_LENGTH, MAX_OSC_PAYLOAD, PROTOCOL_VERSION};
+use winptyrs::{AgentConfig, ExitStatus, MouseMode, PTYArgs, PTYBackend, PtyServer, RemotePTY, ShutdownPolicy, Signal};
+
+fn server_name(name: &str) -> OsString {
+    let name = format!("winptyrs_remote_{}_{}", name, std::process::id());
+    if cfg!(windows) {
+        OsString::from(name)
+    } else {
+        std::env::temp_dir().join(name).into_os_string()
+    }
+}
+
+fn round_trip(frame: &Frame) -> Frame {
+    let encoded = frame.encode();
+    assert_eq!(u32::from_le_bytes(encoded[..4].try_into().unwrap()) as usize, encoded.len() - 4);
+    let decoded = Frame::read_from(&mut Cursor::new(&encoded)).unwrap().unwrap();
+    assert_eq!(decoded.encode(), encoded);
+    decoded
+}
+
+#[test]
+fn encode_frames() {
+    let args = PTYArgs::new(132, 43)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_FORCE)
+        .timeout(2500)
+        .agent_config(AgentConfig::WINPTY_FLAG_CONERR | AgentConfig::WINPTY_FLAG_PLAIN_OUTPUT)
+        .contain_tree(true)
+        .shutdown_policy(ShutdownPolicy::WaitWithTimeout(Duration::from_millis(1500)))
+        .drain_timeout(100)
+        .osc_max_payload(512)
+        .shared_reactor(true);
+    match round_trip(&Frame::Create { args, backend: PTYBackend::WinPTY, name: Some("build".into()) }) {
+        Frame::Create { args, backend, name } => {
+            assert_eq!((args.cols, args.rows, args.timeout, args.drain_timeout), (132, 43, 2500, 100));
+            assert!(matches!(args.mouse_mode, MouseMode::WINPTY_MOUSE_MODE_FORCE));
+            assert_eq!(args.agent_config.bits(), 0b11);
+            assert!(args.contain_tree && args.shared_reactor);
+            assert_eq!(args.shutdown_policy, ShutdownPolicy::WaitWithTimeout(Duration::from_millis(1500)));
+            assert_eq!(args.osc_max_payload, 512);
+            assert!(matches!(backend, PTYBackend::WinPTY));
+            assert_eq!(name.as_deref(), Some("build"));
+        },
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    // The server picks its own files and bounds the memory used by each session.
+    let args = PTYArgs::default().cassette("session.cassette").strict_replay(true).osc_max_payload(usize::MAX);
+    let encoded = Frame::Create { args, backend: PTYBackend::Replay, name: None }.encode();
+    match Frame::read_from(&mut Cursor::new(&encoded)).unwrap().unwrap() {
+        Frame::Create { args, .. } => {
+            assert!(args.cassette.is_none() && !args.strict_replay);
+            assert_eq!(args.osc_max_payload, MAX_OSC_PAYLOAD);
+        },
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    match round_trip(&Frame::Spawn {
+        session: 3, appname: "cmd".into(), cmdline: Some("/c echo ü".into()), cwd: None, env: Some("A=1\0".into())
+    }) {
+        Frame::Spawn { session, appname, cmdline, cwd, env } => {
+            assert_eq!(session, 3);
+            assert_eq!(appname, "cmd");
+            assert_eq!(cmdline.as_deref(), Some("/c echo ü"));
+            assert_eq!(cwd, None);
+            assert_eq!(env.as_deref(), Some("A=1\0"));
+        },
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    let status = ExitStatus { code: 2, terminated: true, output: "bye".into() };
+    match round_trip(&Frame::Closed { session: 7, status: Some(status.clone()) }) {
+        Frame::Closed { session, status: decoded } => assert_eq!((session, decoded), (7, Some(status))),
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    match round_trip(&Frame::Attached {
+        session: 4, name: "build".into(), pid: 10, cols: 90, rows: 20, scrollback: "C:\\>".into(), exit: Some(None)
+    }) {
+        Frame::Attached { session, name, pid, cols, rows, scrollback, exit } => {
+            assert_eq!((session, name.as_str(), pid, cols, rows), (4, "build", 10, 90, 20));
+            assert_eq!(scrollback, "C:\\>");
+            assert_eq!(exit, Some(None));
+        },
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    let sessions = vec![
+        SessionInfo { session: 1, name: "1".into(), pid: 0, cols: 80, rows: 24, clients: 0, exit: None },
+        SessionInfo { session: 2, name: "build".into(), pid: 12, cols: 100, rows: 30, clients: 2, exit: Some(Some(1)) },
+    ];
+    match round_trip(&Frame::Listed { sessions: sessions.clone() }) {
+        Frame::Listed { sessions: decoded } => assert_eq!(decoded, sessions),
+        other => panic!("Unexpected frame {:?}", other)
+    }
+
+    for frame in [
+        Frame::Hello { version: PROTOCOL_VERSION },
+        Frame::Create { args: PTYArgs::default(), backend: PTYBackend::ConPTY, name: None },
+        Frame::Attach { name: "build".into(), size: None },
+        Frame::Attach { name: "1".into(), size: Some((120, 40)) },
+        Frame::List,
+        Frame::Listed { sessions: Vec::new() },
+        Frame::Input { session: 1, data: "echo\r\n".into() },
+        Frame::Resize { session: 1, cols: 100, rows: 30 },
+        Frame::Signal { session: 1, signal: Signal::Break },
+        Frame::Close { session: 1 },
+        Frame::Created { session: 1, name: "1".into() },
+        Frame::Spawned { session: 1, pid: 1234 },
+        Frame::Written { session: 1, count: 6 },
+        Frame::Ack { session: 1 },
+        Frame::Closed { session: 1, status: None },
+        Frame::Error { message: "failed".into() },
+        Frame::Output { session: 1, data: "\x1b[0mok".into() },
+        Frame::Exit { session: 1, code: None },
+        Frame::Exit { session: 1, code: Some(0) },
+        Frame::Resized { session: 1, cols: 80, rows: 24 },
+    ] {
+        round_trip(&frame);
+    }
+}
+
+#[test]
+fn reject_invalid_frames() {
+    assert!(Frame::read_from(&mut Cursor::new(Vec::new())).unwrap().is_none());
+    assert!(Frame::decode(&[0x7f]).is_err());
+    assert!(Frame::decode(&[]).is_err());
+
+    // Truncated and trailing data.
+    let encoded = Frame::Ack { session: 1 }.encode();
+    assert!(Frame::decode(&encoded[4..encoded.len() - 1]).is_err());
+    let mut trailing = encoded[4..].to_vec();
+    trailing.push(0);
+    assert!(Frame::decode(&trailing).is_err());
+    assert!(Frame::read_from(&mut Cursor::new(&encoded[..encoded.len() - 1])).is_err());
+
+    // Invalid values.
+    assert!(Frame::decode(&[0x07, 1, 0, 0, 0, 0, 0, 0, 0, 9]).is_err());
+    let too_long = (MAX_FRAME_LENGTH + 1).to_le_bytes();
+    assert!(Frame::read_from(&mut Cursor::new(too_long)).is_err());
+}
+
+#[test]
+fn reject_protocol_version() {
+    let name = server_name("version");
+    let server = PtyServer::bind(&name).unwrap();
+    assert_eq!(server.name(), name.as_os_str());
+
+    let mut stream = LocalStream::connect(&name).unwrap();
+    Frame::Hello { version: PROTOCOL_VERSION + 1 }.write_to(&mut stream).unwrap();
+    assert!(matches!(Frame::read_from(&mut stream).unwrap(), Some(Frame::Hello { version: PROTOCOL_VERSION })));
+    assert!(matches!(Frame::read_from(&mut stream).unwrap(), Some(Frame::Error { .. })));
+    assert!(Frame::read_from(&mut stream).unwrap().is_none());
+}
+
+#[test]
+fn reject_unknown_session() {
+    let name = server_name("unknown");
+    let _server = PtyServer::bind(&name).unwrap();
+    assert!(RemotePTY::attach(&name, "42", None).is_err());
+    assert!(RemotePTY::attach(&name, "build", None).is_err());
+    assert!(RemotePTY::list(&name).unwrap().is_empty());
+}
+
+#[test]
+fn reject_session_names() {
+    let name = server_name("names");
+    let server = PtyServer::bind(&name).unwrap();
+    let args = PTYArgs::default();
+    assert!(RemotePTY::create(&name, "", &args, PTYBackend::ConPTY).is_err());
+    assert!(RemotePTY::create(&name, "12", &args, PTYBackend::ConPTY).is_err());
+
+    let pty = RemotePTY::create(&name, "build", &args, PTYBackend::ConPTY).unwrap();
+    assert_eq!(pty.name(), "build");
+    assert!(RemotePTY::create(&name, "build", &args, PTYBackend::ConPTY).is_err());
+
+    let other = RemotePTY::connect(&name, &args, PTYBackend::ConPTY).unwrap();
+    assert_eq!(other.name(), other.session().to_string());
+
+    let sessions = RemotePTY::list(&name).unwrap();
+    assert_eq!(sessions, server.sessions());
+    let names: Vec<&str> = sessions.iter().map(|info| info.name.as_str()).collect();
+    assert_eq!(names, vec!["build", other.name()]);
+    assert!(sessions.iter().all(|info| info.clients == 1 && info.pid == 0 && info.exit.is_none()));
+}
@@ -0,0 +1,96 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use winptyrs::resolver::{candidates, environment_block, launch_command, resolve_executable};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("winptyrs_resolver_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    dir
+}
+
+#[cfg(windows)]
+#[test]
+fn resolve_with_pathext() {
+    let dir = scratch_dir("pathext");
+    fs::write(dir.join("bin").join("tool.cmd"), "@echo off").unwrap();
+    // Scripts opened through file associations cannot be spawned, even if they are in PATHEXT.
+    fs::write(dir.join("tool.js"), "").unwrap();
+    let env = OsString::from(format!("=C:=C:\\\0Path={}\0PATHEXT=.JS;.EXE;.CMD\0", dir.join("bin").display()));
+    let cwd = OsString::from(dir.as_os_str());
+
+    let found = resolve_executable(&OsString::from("tool"), Some(&cwd), Some(&env));
+    assert_eq!(found, dir.join("bin").join("tool.cmd"));
+
+    let found = resolve_executable(&OsString::from("bin\\tool.cmd"), Some(&cwd), Some(&env));
+    assert_eq!(found, dir.join("bin").join("tool.cmd"));
+
+    let checked = candidates(&OsString::from("missing"), Some(&cwd), Some(&env));
+    assert_eq!(checked, vec![
+        dir.join("missing.exe"), dir.join("missing.cmd"),
+        dir.join("bin").join("missing.exe"), dir.join("bin").join("missing.cmd"),
+    ]);
+
+    let err = resolve_executable(&OsString::from("missing"), Some(&cwd), Some(&env)).unwrap_err();
+    let err = err.to_string_lossy();
+    for candidate in checked {
+        assert!(err.contains(&*candidate.to_string_lossy()));
+    }
+}
+
+#[cfg(not(windows))]
+#[test]
+fn resolve_with_exec_bits() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = scratch_dir("exec");
+    let tool = dir.join("bin").join("tool");
+    fs::write(&tool, "#!/bin/sh").unwrap();
+    fs::write(dir.join("bin").join("data"), "").unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    let env = OsString::from(format!("PATH={}\0", dir.join("bin").display()));
+    let cwd = OsString::from(dir.as_os_str());
+
+    assert_eq!(candidates(&OsString::from("tool"), Some(&cwd), Some(&env)), vec![tool.clone()]);
+    assert_eq!(resolve_executable(&OsString::from("tool"), Some(&cwd), Some(&env)).unwrap(), tool);
+    assert_eq!(resolve_executable(&OsString::from("./bin/tool"), Some(&cwd), Some(&env)).unwrap(), dir.join("./bin/tool"));
+
+    let err = resolve_executable(&OsString::from("data"), Some(&cwd), Some(&env)).unwrap_err();
+    assert!(err.to_string_lossy().contains(&*dir.join("bin").join("data").to_string_lossy()));
+}
+
+#[test]
+fn build_launch_commands() {
+    let env = OsString::from("ComSpec=C:\\cmd.exe\0");
+    let program = PathBuf::from("C:\\tools\\tool.EXE");
+    let (appname, cmdline) = launch_command(&program, Some(OsString::from("-v")), Some(&env));
+    assert_eq!((appname, cmdline), (program.into_os_string(), Some(OsString::from("-v"))));
+
+    let program = PathBuf::from("C:\\my tools\\build.cmd");
+    let (appname, cmdline) = launch_command(&program, Some(OsString::from("\"a b\" c")), Some(&env));
+    assert_eq!(appname, OsString::from("C:\\cmd.exe"));
+    assert_eq!(cmdline, Some(OsString::from("/d /s /c \"\"C:\\my tools\\build.cmd\" \"a b\" c\"")));
+    let (_, cmdline) = launch_command(&PathBuf::from("run.bat"), None, Some(&env));
+    assert_eq!(cmdline, Some(OsString::from("/d /s /c \"\"run.bat\"\"")));
+
+    // Other extensions are left to CreateProcess, which runs any executable image.
+    for program in ["tool", "tool.js", "tool.1.2"] {
+        let (appname, cmdline) = launch_command(&PathBuf::from(program), None, Some(&env));
+        assert_eq!((appname, cmdline), (OsString::from(program), None));
+    }
+}
+
+#[test]
+fn build_environment_blocks() {
+    let vars = [
+        (OsString::from("WINPTYRS_BLOCK"), OsString::from("a=b")),
+        (OsString::from("WINPTYRS_BLOCK"), OsString::from("c")),
+    ];
+    let block = environment_block(&vars).into_string().unwrap();
+    assert!(block.ends_with("\0WINPTYRS_BLOCK=c\0"));
+    assert_eq!(block.matches("WINPTYRS_BLOCK=").count(), 1);
+    if let Some(path) = std::env::var_os("PATH") {
+        assert!(block.contains(&format!("PATH={}\0", path.to_string_lossy())));
+    }
+}
@@ -0,0 +1,77 @@
+use winptyrs::script::Script;
+use winptyrs::{CassetteEvent, PTYBackend};
+
+mod common;
+use common::{entry, replay_args, save_cassette};
+
+#[test]
+fn parse_scripts() {
+    let script = r#"
+        # Start a shell and wait for its prompt.
+        spawn cmd.exe /q /k "echo ready"
+        expect /ready/ timeout=1.5s
+        sendline "echo \"value=42\""
+        expect /value=(?<value>\d+)/
+        key up enter ctrl-c alt-x f12
+        resize 120 40
+        sleep 250ms
+        send "exit ${value}\r\n"
+        expect "a/b" timeout=2
+        assert-exit 42 timeout=1m
+    "#;
+    Script::parse(script).unwrap();
+}
+
+#[test]
+fn reject_invalid_scripts() {
+    for (script, line, error) in [
+        ("spawn cmd\nfrobnicate", 2, "Unknown command frobnicate"),
+        ("\n# comment\nsendline \"dir\"", 3, "sendline requires a previous spawn"),
+        ("spawn cmd\nspawn cmd", 2, "The program was already spawned"),
+        ("spawn", 1, "Missing program of spawn"),
+        ("spawn cmd\nsend dir", 2, "send expects a quoted text"),
+        ("spawn cmd\nsend \"dir", 2, "Unterminated text"),
+        ("spawn cmd\nsend \"\\q\"", 2, "Invalid escape \\q"),
+        ("spawn cmd\nsend \"${prompt}\"", 2, "Undefined variable prompt"),
+        ("spawn cmd\nexpect /(a/", 2, "Invalid pattern /(a/: unclosed group"),
+        ("spawn cmd\nexpect /a{3,1}/", 2, "Invalid pattern /a{3,1}/: invalid repetition count range, the start must be <= the end"),
+        ("spawn cmd\nexpect /a", 2, "Unterminated pattern"),
+        ("spawn cmd\nexpect /a/ timeout=soon", 2, "Invalid duration soon"),
+        ("spawn cmd\nexpect /a/ after=1", 2, "Unknown option after of expect"),
+        ("spawn cmd\nsleep 1s 2s", 2, "Too many arguments of sleep"),
+        ("spawn cmd\nresize 80", 2, "Missing size of resize"),
+        ("spawn cmd\nresize 0 24", 2, "Invalid size 0"),
+        ("spawn cmd\nkey hyper-x", 2, "Unknown key hyper-x"),
+        ("spawn cmd\nkey", 2, "Missing key of key"),
+        ("spawn cmd\nassert-exit -1", 2, "Invalid exit code -1"),
+    ] {
+        let err = Script::parse(script).unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (line, error), "{:?}", script);
+        assert_eq!(err.to_string(), format!("line {}: {}", line, error));
+    }
+}
+
+#[test]
+fn run_scripts() {
+    let path = save_cassette(
+        "script",
+        vec![
+            entry(0, CassetteEvent::Spawn { appname: String::from("tool.exe"), cmdline: None }),
+            entry(10, CassetteEvent::Output(String::from("\x1b[1mwait\x1b[0m\r\n"))),
+            entry(100, CassetteEvent::Output(String::from("value=4"))),
+            entry(200, CassetteEvent::Output(String::from("2\r\n"))),
+            entry(300, CassetteEvent::Exit(3)),
+        ],
+    );
+    let args = replay_args(&path);
+
+    // The match spans two reads, and its named groups become variables.
+    let script = Script::parse("spawn tool.exe\nexpect /value=(?<value>\\d+)\\r\\n/\nassert-exit 3").unwrap();
+    let variables = script.run(&args, PTYBackend::Replay).unwrap();
+    assert_eq!(variables["value"], "42");
+
+    let script = Script::parse("spawn tool.exe\nexpect \"wait\"\nexpect /wait/").unwrap();
+    let err = script.run(&args, PTYBackend::Replay).unwrap_err();
+    assert_eq!((err.line, err.message.as_str(), err.expectation.as_deref()), (3, "The program exited", Some("/wait/")));
+    assert!(err.output.contains("value=42"));
+}
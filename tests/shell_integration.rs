@@ -0,0 +1,45 @@
+use winptyrs::shell_integration::{ShellIntegration, ShellKind};
+
+#[test]
+fn shell_kind_from_program() {
+    assert_eq!(ShellKind::from_program("C:\\Windows\\System32\\cmd.exe"), Some(ShellKind::Cmd));
+    assert_eq!(ShellKind::from_program("pwsh.exe"), Some(ShellKind::PowerShell));
+    assert_eq!(ShellKind::from_program("C:\\Program Files\\Git\\bin\\bash.exe"), Some(ShellKind::Bash));
+    assert_eq!(ShellKind::from_program("/usr/bin/zsh"), Some(ShellKind::Zsh));
+    assert_eq!(ShellKind::from_program("python.exe"), None);
+}
+
+#[test]
+fn command_timeline() {
+    let mut shell = ShellIntegration::new();
+    shell.feed("\x1b]133;A\x07PS C:\\> \x1b]133;B\x07");
+    shell.feed("dir\x08r\r\n\x1b]133;C\x07");
+    assert_eq!(shell.running_command(), Some("dir".into()));
+
+    shell.feed("file1\r\n\x1b[32mfile2\x1b[0m\r\n\x1b]1");
+    shell.feed("33;D;0\x07\x1b]133;A\x07PS C:\\> \x1b]133;B\x07exit 3\r\n\x1b]133;C\x07\x1b]133;D;3\x07");
+
+    let commands = shell.commands();
+    assert_eq!(shell.running_command(), None);
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0].command, "dir");
+    assert_eq!(commands[0].output, "file1\r\nfile2\r\n");
+    assert_eq!(commands[0].output_range.len(), commands[0].output.chars().count());
+    assert_eq!(commands[0].exit_code, Some(0));
+    assert_eq!(commands[1].command, "exit 3");
+    assert_eq!(commands[1].output, "");
+    assert_eq!(commands[1].exit_code, Some(3));
+}
+
+#[test]
+fn command_timeline_without_output_mark() {
+    let mut shell = ShellIntegration::new();
+    shell.feed("\x1b]133;D\x1b\\\x1b]133;A\x1b\\C:\\>\x1b]133;B\x1b\\echo hi\r\nhi\r\n\r\n");
+    shell.feed("\x1b]133;D\x1b\\\x1b]133;A\x1b\\C:\\>\x1b]133;B\x1b\\");
+
+    let commands = shell.commands();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].command, "echo hi");
+    assert_eq!(commands[0].output, "hi\r\n\r\n");
+    assert_eq!(commands[0].exit_code, None);
+}
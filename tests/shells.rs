@@ -0,0 +1,51 @@
+use std::ffi::OsString;
+use winptyrs::shells::{discover_shells, LaunchMode, QuotingStyle};
+
+#[test]
+fn quote_arguments() {
+    assert_eq!(QuotingStyle::Msvcrt.quote("plain"), "plain");
+    assert_eq!(QuotingStyle::Msvcrt.quote(""), "\"\"");
+    assert_eq!(QuotingStyle::Msvcrt.quote("C:\\Program Files\\"), "\"C:\\Program Files\\\\\"");
+    assert_eq!(QuotingStyle::Msvcrt.quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    assert_eq!(QuotingStyle::Msvcrt.quote("a\\\"b"), "\"a\\\\\\\"b\"");
+
+    assert_eq!(QuotingStyle::Cmd.quote("plain"), "plain");
+    assert_eq!(QuotingStyle::Cmd.quote(""), "^\"^\"");
+    assert_eq!(QuotingStyle::Cmd.quote("a&b|c>d"), "a^&b^|c^>d");
+    assert_eq!(QuotingStyle::Cmd.quote("100% (done)"), "^\"100^% ^(done^)^\"");
+    assert_eq!(QuotingStyle::Cmd.quote("say \"hi\" ^"), "^\"say \\^\"hi\\^\" ^^^\"");
+
+    assert_eq!(QuotingStyle::PowerShell.quote("C:\\Windows"), "C:\\Windows");
+    assert_eq!(QuotingStyle::PowerShell.quote("it's $HOME"), "'it''s $HOME'");
+
+    assert_eq!(QuotingStyle::Posix.quote("/usr/bin"), "/usr/bin");
+    assert_eq!(QuotingStyle::Posix.quote("it's $HOME"), "'it'\\''s $HOME'");
+}
+
+#[test]
+fn discover_installed_shells() {
+    let shells = discover_shells();
+    for shell in &shells {
+        assert!(shell.executable.is_file());
+    }
+
+    if let Some(shell) = shells.iter().find(|s| !s.env.is_empty()) {
+        let request = shell.spawn_request(LaunchMode::Login, Some(OsString::from(".")));
+        assert_eq!(request.appname, shell.executable.clone().into_os_string());
+        assert_eq!(request.cwd, Some(OsString::from(".")));
+
+        let env = request.env.unwrap().into_string().unwrap();
+        for (key, value) in &shell.env {
+            assert!(env.split('\0').any(|v| v == format!("{}={}", key, value)));
+        }
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn discover_cmd() {
+    let shells = discover_shells();
+    let cmd = shells.iter().find(|s| s.name == "Command Prompt").unwrap();
+    assert_eq!(cmd.quoting, QuotingStyle::Cmd);
+    assert_eq!(winptyrs::default_shell().unwrap().executable, cmd.executable);
+}
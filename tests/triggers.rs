@@ -0,0 +1,145 @@
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use winptyrs::{CassetteEvent, PTYBackend, Trigger, TriggerMatch, PTY};
+
+mod common;
+use common::{entry, replay_args, save_cassette};
+
+/// Replay a session made of the given events, after spawning `cmd.exe`.
+fn replay(name: &str, events: Vec<(u64, CassetteEvent)>) -> PTY {
+    let mut entries = vec![entry(0, CassetteEvent::Spawn { appname: String::from("cmd.exe"), cmdline: None })];
+    entries.extend(events.into_iter().map(|(time, event)| entry(time, event)));
+    let args = replay_args(&save_cassette(name, entries));
+    let mut pty = PTY::new_with_backend(&args, PTYBackend::Replay).unwrap();
+    pty.spawn(OsString::from("cmd.exe"), None, None, None).unwrap();
+    pty
+}
+
+fn output(text: &str) -> CassetteEvent {
+    CassetteEvent::Output(String::from(text))
+}
+
+/// Read until the process exits, returning all of its output.
+fn read_to_end(pty: &PTY) -> String {
+    let mut transcript = String::new();
+    let start = Instant::now();
+    while let Ok(output) = pty.read_timeout(Duration::from_millis(100)) {
+        assert!(start.elapsed() < Duration::from_secs(5), "The process did not exit: {:?}", transcript);
+        transcript.push_str(&output.to_string_lossy());
+    }
+    transcript
+}
+
+/// Trigger that records its matches into `matches`.
+fn recording(pattern: &str, matches: &Arc<Mutex<Vec<TriggerMatch>>>) -> Trigger {
+    let matches = matches.clone();
+    Trigger::new(pattern).unwrap().on_match(move |m| matches.lock().unwrap().push(m.clone()))
+}
+
+#[test]
+fn answer_prompts() {
+    let pty = replay(
+        "prompt",
+        vec![
+            (10, output("Continue (Y/N)? ")),
+            (500, CassetteEvent::Input(String::from("Y\r\n"))),
+            (510, output("done\r\n")),
+            (600, CassetteEvent::Exit(0)),
+        ],
+    );
+    pty.add_trigger(Trigger::new(r"\(Y/N\)\? $").unwrap().respond("Y\r\n").once());
+    assert_eq!(read_to_end(&pty), "Continue (Y/N)? done\r\n");
+    assert_eq!(pty.get_exitstatus().unwrap(), Some(0));
+}
+
+#[test]
+fn expand_responses() {
+    let pty = replay(
+        "password",
+        vec![
+            (10, output("Password for alice: ")),
+            (500, CassetteEvent::Input(String::from("alice-secret\r\n"))),
+            (510, output("\r\nwelcome\r\n")),
+            (600, CassetteEvent::Exit(0)),
+        ],
+    );
+    pty.add_trigger(Trigger::new(r"Password for (?<user>\w+): ").unwrap().respond("${user}-secret\r\n"));
+    assert!(read_to_end(&pty).ends_with("welcome\r\n"));
+}
+
+#[test]
+fn match_across_reads() {
+    let matches = Arc::new(Mutex::new(Vec::new()));
+    let pty = replay(
+        "split",
+        vec![
+            (10, output("\x1b[31mEr")),
+            (100, output("ror\x1b[0m: disk ")),
+            (200, output("full\r\nerror: quota exceeded\r\n")),
+            (300, CassetteEvent::Exit(1)),
+        ],
+    );
+    pty.add_trigger(recording(r"(?i)error: (?<message>[^\r\n]*)\r\n", &matches));
+    read_to_end(&pty);
+
+    // The escape sequences are stripped before matching, and each match is reported once.
+    let matches = matches.lock().unwrap();
+    let messages: Vec<&str> = matches.iter().map(|m| m.name("message").unwrap()).collect();
+    assert_eq!(messages, ["disk full", "quota exceeded"]);
+    assert_eq!(matches[0].text, "Error: disk full\r\n");
+    assert_eq!(matches[0].group(1), Some("disk full"));
+    assert_eq!(matches[0].group(2), None);
+}
+
+#[test]
+fn limit_firings() {
+    let events = (0..5).map(|i| (10 + 20 * i, output("ping\r\n"))).chain([(200, CassetteEvent::Exit(0))]).collect();
+    let pty = replay("limits", events);
+    let (every, once, limited) = (Arc::default(), Arc::default(), Arc::default());
+    pty.add_trigger(recording("ping", &every));
+    let id = pty.add_trigger(recording("ping", &once).once());
+    pty.add_trigger(recording("ping", &limited).rate_limit(Duration::from_secs(10)));
+    read_to_end(&pty);
+
+    assert_eq!(every.lock().unwrap().len(), 5);
+    assert_eq!(once.lock().unwrap().len(), 1);
+    assert_eq!(limited.lock().unwrap().len(), 1);
+    // A one-shot trigger is removed once it fires.
+    assert!(!pty.remove_trigger(id));
+}
+
+#[test]
+fn bound_lookback() {
+    let pty = replay("lookback", vec![(10, output("abc")), (100, output("def")), (200, CassetteEvent::Exit(0))]);
+    let (long, short) = (Arc::default(), Arc::default());
+    pty.add_trigger(recording("abcdef", &long));
+    pty.add_trigger(recording("abcdef", &short).lookback(2));
+    read_to_end(&pty);
+    assert_eq!(long.lock().unwrap().len(), 1);
+    assert_eq!(short.lock().unwrap().len(), 0);
+
+    // Only the tail of a read longer than the lookback is searched.
+    let pty = replay("lookback-tail", vec![(10, output("abc-xyz")), (200, CassetteEvent::Exit(0))]);
+    let (head, tail) = (Arc::default(), Arc::default());
+    pty.add_trigger(recording("abc", &head).lookback(3));
+    pty.add_trigger(recording("xyz", &tail).lookback(3));
+    read_to_end(&pty);
+    assert_eq!(head.lock().unwrap().len(), 0);
+    assert_eq!(tail.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn remove_triggers() {
+    let matches = Arc::new(Mutex::new(Vec::new()));
+    let pty = replay("remove", vec![(10, output("ping\r\n")), (200, CassetteEvent::Exit(0))]);
+    let id = pty.add_trigger(recording("ping", &matches));
+    assert!(pty.remove_trigger(id));
+    assert!(!pty.remove_trigger(id));
+    read_to_end(&pty);
+    assert!(matches.lock().unwrap().is_empty());
+
+    assert!(Trigger::new("(unclosed").is_err());
+    assert_eq!(Trigger::new(r"\d+").unwrap().pattern(), r"\d+");
+}
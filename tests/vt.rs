@@ -0,0 +1,183 @@
+use winptyrs::vt::{render_lines, Cell, Color, LineRenderer, Screen, Style, Token, Tokenizer};
+use winptyrs::osc::{OscEvent, OscTracker};
+
+#[test]
+fn tokenize_sequences() {
+    let mut tokenizer = Tokenizer::default();
+    let tokens = tokenizer.feed("ab\r\n\x1b[1;2H\x1b]0;title\x07\x1b(B\x1b7c");
+    assert_eq!(tokens, vec![
+        Token::Text("ab".into()),
+        Token::Control('\r'),
+        Token::Control('\n'),
+        Token::Csi { params: "1;2".into(), intermediates: "".into(), action: 'H' },
+        Token::Osc("0;title".into()),
+        Token::Esc { intermediates: "(".into(), action: 'B' },
+        Token::Esc { intermediates: "".into(), action: '7' },
+        Token::Text("c".into()),
+    ]);
+}
+
+#[test]
+fn tokenize_split_sequences() {
+    let mut tokenizer = Tokenizer::default();
+    assert_eq!(tokenizer.feed("a\x1b"), vec![Token::Text("a".into())]);
+    assert_eq!(tokenizer.feed("[?25"), vec![]);
+    assert_eq!(tokenizer.feed("l\x1b]2;ti"), vec![
+        Token::Csi { params: "?25".into(), intermediates: "".into(), action: 'l' }
+    ]);
+    assert_eq!(tokenizer.feed("tle\x1b"), vec![]);
+    assert_eq!(tokenizer.feed("\\b"), vec![Token::Osc("2;title".into()), Token::Text("b".into())]);
+}
+
+#[test]
+fn tokenize_osc_max_payload() {
+    let mut tokenizer = Tokenizer::new(8);
+    assert_eq!(tokenizer.feed("\x1b]0;12345678\x07\x1b]0;123456\x07"), vec![
+        Token::Osc("0;123456".into())
+    ]);
+}
+
+#[test]
+fn tokenize_aborted_osc() {
+    let mut tokenizer = Tokenizer::default();
+    assert_eq!(tokenizer.feed("\x1b]0;abc\x1b[m"), vec![
+        Token::Csi { params: "".into(), intermediates: "".into(), action: 'm' }
+    ]);
+}
+
+#[test]
+fn track_title_and_cwd() {
+    let mut tracker = OscTracker::default();
+    let events = tracker.subscribe();
+    assert_eq!(tracker.title(), None);
+    assert_eq!(tracker.cwd(), None);
+
+    tracker.feed("\x1b]0;cmd\x07\x1b]0;cmd\x07\x1b]2;pwsh\x1b\\");
+    assert_eq!(tracker.title(), Some("pwsh".into()));
+
+    tracker.feed("\x1b]7;file://host/C:/Program%20Files\x07");
+    assert_eq!(tracker.cwd(), Some("C:\\Program Files".into()));
+    tracker.feed("\x1b]9;9;\"C:\\Windows\"\x07");
+    assert_eq!(tracker.cwd(), Some("C:\\Windows".into()));
+
+    let received: Vec<OscEvent> = events.try_iter().collect();
+    assert_eq!(received, vec![
+        OscEvent::TitleChanged("cmd".into()),
+        OscEvent::TitleChanged("pwsh".into()),
+        OscEvent::CwdChanged("C:\\Program Files".into()),
+        OscEvent::CwdChanged("C:\\Windows".into()),
+    ]);
+}
+
+#[test]
+fn render_progress_lines() {
+    let output = "Building\r\n[=>  ] 25%\r[==> ] 50%\r[====] 100%\r\n\x1b[1;32mFinished\x1b[0m\x1b]0;title\x07 release\r\n";
+    assert_eq!(render_lines(output), "Building\n[====] 100%\nFinished release\n");
+
+    // Backspaces, erasures and motions within the line.
+    assert_eq!(render_lines("spinner |\x08/\x08-\x08\\\x08 \r\n"), "spinner\n");
+    assert_eq!(render_lines("abcdef\r\x1b[3C\x1b[K!\n"), "abc!\n");
+    assert_eq!(render_lines("abcdef\x1b[3D\x1b[1K\n"), "    ef\n");
+    assert_eq!(render_lines("abcdef\x1b[2K\rxy\n"), "xy\n");
+    assert_eq!(render_lines("abcdef\x1b[3G\x1b[2X\x1b[10Gz\n"), "ab  ef   z\n");
+    assert_eq!(render_lines("abcdef\r\x1b[2P\x1b[2C\x1b[@\n"), "cd ef\n");
+    assert_eq!(render_lines("a\tb\r\x1b[Jc\x1bEd"), "c\nd\n");
+
+    // Motions to other lines and other sequences are stripped.
+    assert_eq!(render_lines("one\x1b[1A\x1b[?25l\x1b[5;1Htwo\x07\n"), "onetwo\n");
+    assert_eq!(render_lines("   \r"), "");
+}
+
+#[test]
+fn render_lines_incrementally() {
+    let mut renderer = LineRenderer::new();
+    assert_eq!(renderer.feed("50% \x1b["), "");
+    assert_eq!(renderer.feed("K\rdone\r"), "");
+    assert_eq!(renderer.feed("\nnext"), "done\n");
+    assert_eq!(renderer.finish(), "next\n");
+    assert_eq!(renderer.finish(), "");
+
+    // Huge lines are wrapped and cursor motions are bounded.
+    let mut renderer = LineRenderer::new();
+    assert_eq!(renderer.feed(&"x".repeat(winptyrs::vt::MAX_LINE_LENGTH + 1)).len(), winptyrs::vt::MAX_LINE_LENGTH + 1);
+    assert_eq!(renderer.finish(), "x\n");
+    assert_eq!(renderer.feed("\x1b[4294967295C!\n").len(), winptyrs::vt::MAX_LINE_LENGTH + 1);
+}
+
+fn texts<'a>(lines: impl Iterator<Item = &'a [Cell]>) -> Vec<String> {
+    lines.map(|line| line.iter().map(|cell| cell.c).collect::<String>().trim_end().to_owned()).collect()
+}
+
+#[test]
+fn render_screens() {
+    let mut screen = Screen::new(10, 4);
+    screen.feed("hello\r\nworld\x1b[1;3H\x1b[KY\x1b[2;2H\x1b[2P\x1b[3;4Hx\x1b[3;1H\x1b[2@\x1b[4;1H\x1b[7Xz\tt");
+    assert_eq!(screen.text(), "heY\nwld\n     x\nz       t\n");
+    assert_eq!(screen.cursor(), (3, 9));
+
+    // Wrapping, scrolling and the scrollback.
+    let mut screen = Screen::new(4, 2).with_scrollback(2);
+    screen.feed("abcdefgh\r\nij\r\nkl");
+    assert_eq!(screen.text(), "ij\nkl\n");
+    assert_eq!(texts(screen.scrollback()), ["abcd", "efgh"]);
+    screen.feed("\x1b[2J\x1b[Hm");
+    assert_eq!(texts(screen.lines()), ["abcd", "efgh", "m", ""]);
+    screen.feed("\x1b[3J");
+    assert_eq!(screen.scrollback().count(), 0);
+
+    // Scrolling regions, insertions and deletions of lines.
+    let mut screen = Screen::new(5, 4);
+    screen.feed("1\r\n2\r\n3\r\n4\x1b[2;3r\x1b[3;1H\n");
+    assert_eq!(screen.text(), "1\n3\n\n4\n");
+    screen.feed("\x1b[2;1H\x1bM\x1b[1;1H\x1bM");
+    assert_eq!(screen.text(), "1\n\n3\n4\n");
+    screen.feed("\x1b[r\x1b[2;1H\x1b[L");
+    assert_eq!(screen.text(), "1\n\n\n3\n");
+    screen.feed("\x1b[1;1H\x1b[2M");
+    assert_eq!(screen.text(), "\n3\n");
+
+    // Cursor saving and the alternate screen.
+    let mut screen = Screen::new(10, 3);
+    screen.feed("shell$ \x1b[?1049h\x1b[?25l\x1b[2;2Hfull\x1b7\x1b[H\x1b8!");
+    assert!(screen.is_alternate() && !screen.cursor_visible());
+    assert_eq!(screen.text(), "\n full!\n");
+    screen.feed("\x1b[?1049l\x1b[?25h");
+    assert_eq!((screen.text().as_str(), screen.cursor()), ("shell$\n", (0, 7)));
+
+    screen.feed("\x1bc");
+    assert_eq!((screen.text().as_str(), screen.cursor()), ("", (0, 0)));
+}
+
+#[test]
+fn render_screen_styles() {
+    let mut screen = Screen::new(20, 2);
+    screen.feed("\x1b[1;3;4;7;31;42ma\x1b[22;23;24;27;39;49mb\x1b[38;5;208;48;2;1;2;3mc\x1b[38:2::4:5:6;48:5:9;4:0md\x1b[0;93;104me");
+    let styles: Vec<Style> = screen.row(0)[..5].iter().map(|cell| cell.style).collect();
+    assert_eq!(styles[0], Style { fg: Color::Indexed(1), bg: Color::Indexed(2), bold: true, italic: true, underline: true, inverse: true });
+    assert_eq!(styles[1], Style::default());
+    assert_eq!((styles[2].fg, styles[2].bg), (Color::Indexed(208), Color::Rgb(1, 2, 3)));
+    assert_eq!((styles[3].fg, styles[3].bg, styles[3].underline), (Color::Rgb(4, 5, 6), Color::Indexed(9), false));
+    assert_eq!((styles[4].fg, styles[4].bg), (Color::Indexed(11), Color::Indexed(12)));
+
+    // Erasures keep the background color.
+    screen.feed("\x1b[44m\x1b[2;1H\x1b[K");
+    assert!(screen.row(1).iter().all(|cell| cell.style == Style { bg: Color::Indexed(4), ..Style::default() }));
+
+    // Hyperlinks.
+    screen.feed("\x1b[0m\x1b[H\x1b]8;id=1;https://example.com\x1b\\link\x1b]8;;\x1b\\ no");
+    let links: Vec<Option<&str>> = screen.row(0)[..6].iter().map(|cell| cell.link.as_deref()).collect();
+    assert_eq!(links, [Some("https://example.com"); 4].into_iter().chain([None, None]).collect::<Vec<_>>());
+}
+
+#[test]
+fn render_wide_characters() {
+    let mut screen = Screen::new(5, 3);
+    screen.feed("a界b\u{301}c\r\n1234界");
+    assert_eq!(screen.text(), "a界bc\n1234\n界\n");
+    assert!(screen.row(0)[2].is_continuation());
+    assert_eq!((screen.row(0)[1].width(), screen.row(0)[2].width(), screen.row(0)[3].width()), (2, 0, 1));
+
+    // Overwriting half of a wide character blanks the other half.
+    screen.feed("\x1b[1;3Hx\x1b[3;1Hy");
+    assert_eq!(screen.text(), "a xbc\n1234\ny\n");
+}
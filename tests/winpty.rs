@@ -4,17 +4,14 @@ use std::ffi::OsString;
 use std::env;
 use regex::Regex;
 
-use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig};
+use winptyrs::{PTY, PTYArgs, PTYBackend, MouseMode, AgentConfig, ShutdownPolicy, Signal};
 
 #[test]
 fn spawn_winpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
@@ -23,13 +20,10 @@ fn spawn_winpty() {
 
 #[test]
 fn read_write_winpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
@@ -72,13 +66,10 @@ fn read_write_winpty() {
 
 #[test]
 fn set_size_winpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
@@ -148,13 +139,10 @@ fn set_size_winpty() {
 
 #[test]
 fn is_alive_exitstatus_winpty() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
@@ -175,13 +163,10 @@ fn is_alive_exitstatus_winpty() {
 
 #[test]
 fn wait_for_exit() {
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
     let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
@@ -204,13 +189,10 @@ fn drop_timing_winpty() {
     use std::thread;
     use std::time::{ Duration, Instant };
 
-    let pty_args = PTYArgs {
-        cols: 80,
-        rows: 25,
-        mouse_mode: MouseMode::WINPTY_MOUSE_MODE_NONE,
-        timeout: 10000,
-        agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
-    };
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
 
     let pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
     let (tx, rx) = channel::<Instant>();
@@ -240,3 +222,54 @@ fn drop_timing_winpty() {
         }
     }
 }
+
+#[test]
+fn signal_winpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    pty.signal(Signal::Interrupt).unwrap();
+    assert!(pty.is_alive().unwrap());
+    assert!(pty.signal(Signal::Break).is_err());
+    assert!(pty.signal(Signal::Terminate).is_err());
+    assert!(pty.is_alive().unwrap());
+
+    pty.kill().unwrap();
+    assert!(!pty.is_alive().unwrap());
+    assert_ne!(pty.get_exitstatus().unwrap(), None);
+}
+
+#[test]
+fn close_terminate_winpty() {
+    let pty_args = PTYArgs::new(80, 25)
+        .mouse_mode(MouseMode::WINPTY_MOUSE_MODE_NONE)
+        .timeout(10000)
+        .agent_config(AgentConfig::WINPTY_FLAG_COLOR_ESCAPES)
+        .shutdown_policy(ShutdownPolicy::Terminate);
+
+    let appname = OsString::from("C:\\Windows\\System32\\cmd.exe");
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
+    pty.spawn(appname, None, None, None).unwrap();
+
+    let status = pty.close().unwrap().unwrap();
+    assert!(status.terminated);
+    assert_eq!(status.code, 1);
+}
+
+#[test]
+fn spawn_resolve_winpty() {
+    let pty_args = PTYArgs::default();
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
+    pty.spawn("cmd".into(), None, None, None).unwrap();
+    assert!(pty.is_alive().unwrap());
+
+    let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::WinPTY).unwrap();
+    let err = pty.spawn("winptyrs-missing".into(), None, None, None).unwrap_err();
+    assert!(err.to_str().unwrap().to_lowercase().contains("winptyrs-missing.exe"));
+}